@@ -0,0 +1,164 @@
+//! Move a populated cache between machines as a plain tar archive.
+//!
+//! The archive mirrors the on-disk layout: `pkgs/<name>/<version>/package/...` for the download
+//! cache and, when the store is included, `store/packages/<entry>/...`. Imports merge entry by
+//! entry and never replace one that already exists locally, since a `name@version` (or store
+//! key) always holds the same contents.
+
+use super::{copy_tree, unique_suffix, CasStore};
+use crate::config::Config;
+use crate::fsutil::cache_root;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// What an export wrote or an import merged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    pub packages: usize,
+    pub store_entries: usize,
+    /// Entries an import left alone because they were already present.
+    pub skipped: usize,
+}
+
+/// Write the download cache, and the store `config` points at when `include_store` is set, to
+/// `dest`.
+pub fn export_archive(dest: &Path, include_store: bool, config: &Config) -> Result<ArchiveSummary> {
+    let file = fs::File::create(dest).with_context(|| format!("create {}", dest.display()))?;
+    let mut builder = tar::Builder::new(file);
+    // Packages may ship internal symlinks; archive them as links rather than their targets.
+    builder.follow_symlinks(false);
+    let mut summary = ArchiveSummary::default();
+
+    let pkgs = cache_root().join("pkgs");
+    for dir in cached_version_dirs(&pkgs) {
+        let rel = dir.strip_prefix(&pkgs).expect("walked below pkgs");
+        builder
+            .append_dir_all(Path::new("pkgs").join(rel), &dir)
+            .with_context(|| format!("archive {}", dir.display()))?;
+        summary.packages += 1;
+    }
+    if include_store {
+        let store = CasStore::open_with(config)?;
+        for dir in store_entry_dirs(&store.packages_dir) {
+            let rel = dir.strip_prefix(&store.packages_dir).expect("walked below packages");
+            builder
+                .append_dir_all(Path::new("store/packages").join(rel), &dir)
+                .with_context(|| format!("archive {}", dir.display()))?;
+            summary.store_entries += 1;
+        }
+    }
+    builder.into_inner()?.sync_all()?;
+    Ok(summary)
+}
+
+/// Merge an archive written by [`export_archive`] into the local cache and the store `config`
+/// points at.
+pub fn import_archive(src: &Path, config: &Config) -> Result<ArchiveSummary> {
+    let file = fs::File::open(src).with_context(|| format!("open {}", src.display()))?;
+    let store = CasStore::open_with(config)?;
+    // Unpack inside the cache so downloads can be renamed into place once complete. The store
+    // may live on another filesystem; `merge_dir` copies entries across when a rename cannot.
+    let staging = cache_root().join(format!(".import-{}", unique_suffix()));
+    let result = (|| {
+        tar::Archive::new(file)
+            .unpack(&staging)
+            .with_context(|| format!("unpack {}", src.display()))?;
+        let staged_pkgs = staging.join("pkgs");
+        let staged_store = staging.join("store").join("packages");
+        if !staged_pkgs.exists() && !staged_store.exists() {
+            bail!("{} is not a pacm cache archive", src.display());
+        }
+        let mut summary = ArchiveSummary::default();
+        let pkgs = cache_root().join("pkgs");
+        for dir in cached_version_dirs(&staged_pkgs) {
+            let target = pkgs.join(dir.strip_prefix(&staged_pkgs).expect("walked below pkgs"));
+            if merge_dir(&dir, &target, &staging)? {
+                summary.packages += 1;
+            } else {
+                summary.skipped += 1;
+            }
+        }
+        for dir in store_entry_dirs(&staged_store) {
+            let rel = dir.strip_prefix(&staged_store).expect("walked below packages");
+            if merge_dir(&dir, &store.packages_dir.join(rel), &store.tmp_dir)? {
+                summary.store_entries += 1;
+            } else {
+                summary.skipped += 1;
+            }
+        }
+        Ok(summary)
+    })();
+    fs::remove_dir_all(&staging).ok();
+    result
+}
+
+/// Move `staged` to `target` unless `target` already exists. Returns whether it was moved.
+///
+/// When `staged` cannot be renamed (e.g. it sits on another filesystem), it is copied into
+/// `scratch`, which must share a filesystem with `target`, and renamed from there.
+fn merge_dir(staged: &Path, target: &Path, scratch: &Path) -> Result<bool> {
+    if target.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(staged, target).is_ok() {
+        return Ok(true);
+    }
+    // Another process cached the same entry in the meantime.
+    if target.exists() {
+        return Ok(false);
+    }
+    let copy = scratch.join(format!("import-copy-{}", unique_suffix()));
+    let moved = copy_tree(staged, &copy)
+        .with_context(|| format!("copy {}", staged.display()))
+        .and_then(|()| match fs::rename(&copy, target) {
+            Ok(()) => Ok(true),
+            Err(_) if target.exists() => Ok(false),
+            Err(err) => Err(err).with_context(|| format!("move into {}", target.display())),
+        });
+    fs::remove_dir_all(&copy).ok();
+    moved
+}
+
+/// `pkgs/<name>/<version>` directories that hold a complete `package/`, skipping in-progress
+/// extractions (dot-prefixed temporaries).
+pub(super) fn cached_version_dirs(pkgs: &Path) -> Vec<PathBuf> {
+    WalkDir::new(pkgs)
+        .min_depth(3)
+        .max_depth(4)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_dir() && e.file_name() == "package")
+        .filter(|e| e.depth() == if is_scoped(pkgs, e.path()) { 4 } else { 3 })
+        .filter_map(|e| {
+            let version_dir = e.path().parent()?;
+            let hidden = version_dir.file_name()?.to_string_lossy().starts_with('.');
+            (!hidden).then(|| version_dir.to_path_buf())
+        })
+        .collect()
+}
+
+/// Store entry directories (those with a `metadata.json`) below `packages_dir`, one level
+/// deeper for scoped names.
+pub(super) fn store_entry_dirs(packages_dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(packages_dir)
+        .min_depth(2)
+        .max_depth(3)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file() && e.file_name() == "metadata.json")
+        .filter(|e| e.depth() == 2 || is_scoped(packages_dir, e.path()))
+        .filter_map(|e| e.path().parent().map(Path::to_path_buf))
+        .collect()
+}
+
+fn is_scoped(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root)
+        .ok()
+        .and_then(|rel| rel.components().next())
+        .is_some_and(|first| first.as_os_str().to_string_lossy().starts_with('@'))
+}
@@ -0,0 +1,46 @@
+//! Store entries kept as a single `package.tar.zst` instead of an expanded `package/` tree.
+//!
+//! Enabled with `PACM_STORE_COMPRESS=1`, mainly to shrink store directories that CI systems
+//! cache between runs. Compressed entries cannot be linked into `node_modules`, so installs
+//! always extract them. The setting only affects entries created while it is on; existing
+//! entries keep whichever layout they were written with.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// File name of the archive inside a compressed store entry.
+pub(super) const COMPRESSED_PACKAGE: &str = "package.tar.zst";
+
+/// zstd's default level: most of the size win for a fraction of the CPU of higher levels.
+const COMPRESSION_LEVEL: i32 = 3;
+
+pub(super) fn compression_enabled() -> bool {
+    std::env::var("PACM_STORE_COMPRESS")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Write the contents of `package_dir` to `archive` as a zstd-compressed tar.
+pub(super) fn compress_package(package_dir: &Path, archive: &Path) -> Result<()> {
+    let file =
+        fs::File::create(archive).with_context(|| format!("create {}", archive.display()))?;
+    let encoder = zstd::Encoder::new(file, COMPRESSION_LEVEL)?;
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+    builder
+        .append_dir_all(".", package_dir)
+        .with_context(|| format!("archive {}", package_dir.display()))?;
+    builder.into_inner()?.finish()?.sync_all()?;
+    Ok(())
+}
+
+/// Stream-extract a compressed package archive into `dest`, creating it if needed.
+pub fn unpack_compressed(archive: &Path, dest: &Path) -> Result<()> {
+    let file = fs::File::open(archive).with_context(|| format!("open {}", archive.display()))?;
+    fs::create_dir_all(dest)?;
+    let mut tar = tar::Archive::new(zstd::Decoder::new(file)?);
+    tar.set_preserve_permissions(true);
+    tar.unpack(dest)
+        .with_context(|| format!("extract {} into {}", archive.display(), dest.display()))
+}
@@ -1,4 +1,4 @@
-use crate::fsutil::{cache_root, store_root};
+use crate::fsutil::{cache_root, copy_symlink, dir_size, store_root};
 use anyhow::{Context, Result};
 use base64::{engine::general_purpose::STANDARD, Engine};
 use data_encoding::BASE32_NOPAD;
@@ -10,10 +10,18 @@ use std::ffi::OsStr;
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Once;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tar::Archive;
 use walkdir::WalkDir;
 
+mod archive;
+mod compressed;
+
+pub use archive::{export_archive, import_archive, ArchiveSummary};
+pub use compressed::unpack_compressed;
+
 fn cache_dir_for(name: &str, version: &str) -> PathBuf {
     let mut root = cache_root();
     root.push("pkgs");
@@ -36,7 +44,179 @@ pub fn ensure_cached_package(
     bytes: &[u8],
     integrity_hint: Option<&str>,
 ) -> Result<String> {
-    // Hash bytes for integrity verification only
+    store_cached_package(name, version, bytes, integrity_hint, false)
+}
+
+/// Cache `bytes` for `name@version` like [`ensure_cached_package`], but also when a copy is
+/// already cached (`pacm install --force`). The old copy stays in place until the new one has
+/// passed its integrity check and been extracted.
+pub fn replace_cached_package(
+    name: &str,
+    version: &str,
+    bytes: &[u8],
+    integrity_hint: Option<&str>,
+) -> Result<String> {
+    store_cached_package(name, version, bytes, integrity_hint, true)
+}
+
+fn store_cached_package(
+    name: &str,
+    version: &str,
+    bytes: &[u8],
+    integrity_hint: Option<&str>,
+    replace: bool,
+) -> Result<String> {
+    let dir = cache_dir_for(name, version);
+    let marker = cache_package_path(name, version);
+    if marker.exists() && !replace {
+        return verify_integrity(bytes, integrity_hint);
+    }
+    sweep_cache_tmp_once();
+    let parent = dir.parent().expect("cache dir has a parent");
+    fs::create_dir_all(parent)?;
+    // Extract next to the final directory under a name no other process or thread will pick,
+    // then rename it into place.
+    let scratch = || parent.join(format!(".{}.tmp-{}", version.replace('/', "_"), unique_suffix()));
+    let tmp = scratch();
+    let integrity = match extract_tarball_to(bytes, &tmp.join("package"), integrity_hint) {
+        Ok(integrity) => integrity,
+        Err(err) => {
+            fs::remove_dir_all(&tmp).ok();
+            return Err(err);
+        }
+    };
+    // What the tarball actually hashed to, for checks that cannot trust a lockfile entry.
+    let computed = verify_integrity(bytes, None)?;
+    let _ = fs::write(tmp.join(CACHED_INTEGRITY), computed);
+    // Move the old copy aside under a scratch name (swept like any other leftover) only now that
+    // its replacement is ready.
+    let previous = (replace && dir.exists()).then(scratch);
+    if let Some(previous) = &previous {
+        if let Err(err) = fs::rename(&dir, previous) {
+            fs::remove_dir_all(&tmp).ok();
+            return Err(err).with_context(|| format!("replace cached {name}@{version}"));
+        }
+    }
+    if let Err(err) = fs::rename(&tmp, &dir) {
+        fs::remove_dir_all(&tmp).ok();
+        if let Some(previous) = &previous {
+            let _ = fs::rename(previous, &dir);
+        }
+        // Another install finished the same package first; its copy is just as good.
+        if !marker.exists() {
+            return Err(err)
+                .with_context(|| format!("move {name}@{version} into {}", dir.display()));
+        }
+    }
+    if let Some(previous) = previous {
+        fs::remove_dir_all(previous).ok();
+    }
+    Ok(integrity)
+}
+
+/// File next to a cached `package/` holding the `sha512-` integrity of the tarball it came from.
+const CACHED_INTEGRITY: &str = "integrity";
+
+/// The integrity computed from the tarball `name@version` was extracted from, when the cache
+/// recorded one.
+pub fn cached_integrity(name: &str, version: &str) -> Option<String> {
+    let text = fs::read_to_string(cache_dir_for(name, version).join(CACHED_INTEGRITY)).ok()?;
+    Some(text.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+/// Scratch directories untouched for this long belong to an install that died mid-way; a live
+/// install renames or removes its own long before then.
+pub const STALE_TMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// A project that links a store entry keeps it through `pacm cache gc` for this long, even when
+/// its lockfile does not list the entry yet: the install that recorded the reference may still be
+/// running.
+pub const REFERENCE_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// An entry lock older than this was left by a process that died while holding it; live holders
+/// only keep it for a metadata update or a rename.
+const ENTRY_LOCK_STALE: Duration = Duration::from_secs(60);
+
+static CACHE_SWEEP: Once = Once::new();
+
+/// Clear stale extraction leftovers from the cache before the first extraction of a process.
+fn sweep_cache_tmp_once() {
+    CACHE_SWEEP.call_once(|| {
+        remove_stale_cache_tmp(STALE_TMP_AGE);
+    });
+}
+
+/// Remove `.<version>.tmp-*` extraction directories in the cache that are older than `max_age`,
+/// returning how many were removed.
+pub fn remove_stale_cache_tmp(max_age: Duration) -> usize {
+    let pkgs = cache_root().join("pkgs");
+    let mut walker = WalkDir::new(&pkgs).min_depth(2).max_depth(3).into_iter();
+    let mut removed = 0;
+    while let Some(Ok(entry)) = walker.next() {
+        let name = entry.file_name().to_string_lossy();
+        if !entry.file_type().is_dir() || !name.starts_with('.') || !name.contains(".tmp-") {
+            continue;
+        }
+        walker.skip_current_dir();
+        if is_stale(entry.path(), max_age) && fs::remove_dir_all(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+fn is_stale(path: &Path, max_age: Duration) -> bool {
+    fs::symlink_metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age >= max_age)
+}
+
+/// Downloaded bytes did not hash to the integrity the registry or lockfile promised.
+#[derive(Debug, thiserror::Error)]
+#[error("integrity mismatch: expected {expected}, got {actual}")]
+pub struct IntegrityMismatch {
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Attach remediation advice to an [`IntegrityMismatch`] for `name@version`; other errors pass
+/// through unchanged.
+pub fn explain_integrity_error(err: anyhow::Error, name: &str, version: &str) -> anyhow::Error {
+    if !err.is::<IntegrityMismatch>() {
+        return err;
+    }
+    err.context(format!(
+        "{name}@{version} failed integrity verification. This can mean a corrupted download or a \
+         tampered registry response; run `pacm cache clean --package {name}@{version}` and retry"
+    ))
+}
+
+/// Remove one cached version of `name`, or every cached version when `version` is `None`.
+/// Returns whether anything was deleted.
+pub fn remove_cached_package(name: &str, version: Option<&str>) -> Result<bool> {
+    let dir = match version {
+        Some(v) => cache_dir_for(name, v),
+        None => {
+            let mut root = cache_root();
+            root.push("pkgs");
+            for part in name.split('/') {
+                root.push(part);
+            }
+            root
+        }
+    };
+    if !dir.exists() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(&dir).with_context(|| format!("remove {}", dir.display()))?;
+    Ok(true)
+}
+
+/// Verify `bytes` against an optional `sha512-` integrity string, returning the integrity to
+/// record (the hint when present, otherwise the computed digest).
+pub fn verify_integrity(bytes: &[u8], integrity_hint: Option<&str>) -> Result<String> {
     let mut hasher = Sha512::new();
     hasher.update(bytes);
     let digest = hasher.finalize();
@@ -45,19 +225,29 @@ pub fn ensure_cached_package(
         if let Some(b64) = integrity.strip_prefix("sha512-") {
             let raw = STANDARD.decode(b64).with_context(|| "decode integrity base64")?;
             if raw != digest[..] {
-                anyhow::bail!("integrity mismatch: expected {integrity}, got {computed_integrity}");
+                return Err(IntegrityMismatch {
+                    expected: integrity.to_string(),
+                    actual: computed_integrity,
+                }
+                .into());
             }
         }
     }
-    let dir = cache_dir_for(name, version);
-    let marker = cache_package_path(name, version);
-    if marker.exists() {
-        return Ok(integrity_hint.unwrap_or(&computed_integrity).to_string());
-    }
-    let tmp = dir.with_extension("tmp");
-    fs::create_dir_all(&tmp)?;
-    let extract_root = tmp.join("package");
-    fs::create_dir_all(&extract_root)?;
+    Ok(integrity_hint.unwrap_or(&computed_integrity).to_string())
+}
+
+/// Verify and unpack a gzipped package tarball into `dest`.
+///
+/// The npm `package/` prefix is stripped, entries escaping the root are skipped, and a single
+/// top-level directory holding a `package.json` (GitHub/codeload archives) is promoted so that
+/// `dest` always contains the package root. Returns the integrity to record.
+pub fn extract_tarball_to(
+    bytes: &[u8],
+    dest: &Path,
+    integrity_hint: Option<&str>,
+) -> Result<String> {
+    let integrity = verify_integrity(bytes, integrity_hint)?;
+    fs::create_dir_all(dest)?;
     let gz = GzDecoder::new(bytes);
     let mut ar = Archive::new(gz);
     for entry in ar.entries()? {
@@ -76,14 +266,14 @@ pub fn ensure_cached_package(
         if stripped.as_os_str().is_empty() {
             continue;
         }
-        let dest_path = extract_root.join(&stripped);
+        let dest_path = dest.join(&stripped);
         if let Some(parent) = dest_path.parent() {
             fs::create_dir_all(parent)?;
         }
         e.unpack(&dest_path)?;
     }
     let mut entries = Vec::new();
-    for d in fs::read_dir(&extract_root)? {
+    for d in fs::read_dir(dest)? {
         entries.push(d?);
     }
     if entries.len() == 1 {
@@ -93,15 +283,31 @@ pub fn ensure_cached_package(
             for child in fs::read_dir(&only_path)? {
                 let child = child?;
                 let from = child.path();
-                let to = extract_root.join(child.file_name());
+                let to = dest.join(child.file_name());
                 fs::rename(&from, &to)?;
             }
             fs::remove_dir(&only_path)?;
         }
     }
-    fs::create_dir_all(dir.parent().unwrap())?;
-    fs::rename(&tmp, &dir)?;
-    Ok(integrity_hint.unwrap_or(&computed_integrity).to_string())
+    Ok(integrity)
+}
+
+/// Disk used by the download cache and the store, as reported by `pacm cache size`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheSize {
+    pub cache_bytes: u64,
+    pub cached_packages: usize,
+    pub store_bytes: u64,
+    pub store_entries: usize,
+}
+
+pub fn size(config: &crate::config::Config) -> Result<CacheSize> {
+    let root = cache_root();
+    let pkgs = root.join("pkgs");
+    let cached_packages = archive::cached_version_dirs(&pkgs).len();
+    let (store_entries, store_bytes) = CasStore::open_with(config)?.size();
+    Ok(CacheSize { cache_bytes: dir_size(&root), cached_packages, store_bytes, store_entries })
 }
 
 /// Return all cached semantic versions for a given package, sorted descending.
@@ -151,6 +357,8 @@ pub struct CachedManifest {
     pub os: Vec<String>,
     #[serde(default, rename = "cpu")]
     pub cpu_arch: Vec<String>,
+    #[serde(default)]
+    pub libc: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -179,6 +387,8 @@ where
 pub enum BinField {
     Single(String),
     Map(std::collections::BTreeMap<String, String>),
+    /// Non-standard list of paths; each file's basename is used as the bin name.
+    Array(Vec<String>),
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -193,11 +403,45 @@ pub fn read_cached_manifest(name: &str, version: &str) -> Result<CachedManifest>
     p.push("package.json");
     let txt = fs::read_to_string(&p)
         .with_context(|| format!("read cached package.json at {}", p.display()))?;
-    let mf: CachedManifest = serde_json::from_str(&txt)
+    let raw: serde_json::Value = serde_json::from_str(&txt)
+        .with_context(|| format!("parse cached package.json at {}", p.display()))?;
+    for warning in dropped_dependency_fields(&raw) {
+        crate::logging::log(1, format!("warning: {name}@{version}: {warning}"));
+    }
+    let mf: CachedManifest = serde_json::from_value(raw)
         .with_context(|| format!("parse cached package.json at {}", p.display()))?;
     Ok(mf)
 }
 
+/// Dependency fields that [`CachedManifest`] reads as empty because they are not objects, e.g.
+/// a `null` or array `dependencies`. Surfaced with `-v` to explain missing transitive installs.
+pub fn dropped_dependency_fields(raw: &serde_json::Value) -> Vec<String> {
+    ["dependencies", "devDependencies", "optionalDependencies", "peerDependencies"]
+        .into_iter()
+        .filter_map(|field| {
+            let kind = match raw.get(field)? {
+                serde_json::Value::Null => "null",
+                serde_json::Value::Array(_) => "an array",
+                _ => return None,
+            };
+            Some(format!("`{field}` is {kind}; treating it as empty"))
+        })
+        .collect()
+}
+
+/// Whether `name@version` is cached with a `package.json` that still parses. A cached directory
+/// whose manifest is missing or corrupt is removed so the next resolution downloads it again.
+pub fn validate_cached_package(name: &str, version: &str) -> bool {
+    if !cache_package_path(name, version).exists() {
+        return false;
+    }
+    if read_cached_manifest(name, version).is_ok() {
+        return true;
+    }
+    let _ = remove_cached_package(name, Some(version));
+    false
+}
+
 #[derive(Debug, Clone)]
 pub struct DependencyFingerprint {
     pub name: String,
@@ -226,6 +470,10 @@ struct StoreMetadata {
     dependencies: Vec<StoredDependency>,
     #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
     pub scripts: std::collections::BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    compressed: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    referencing_projects: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -243,12 +491,22 @@ pub struct StoreEntry {
     pub root_dir: PathBuf,
     pub package_dir: PathBuf,
     pub metadata_path: PathBuf,
+    /// The contents are kept in [`StoreEntry::archive_path`] and `package_dir` does not exist
+    /// (`PACM_STORE_COMPRESS`).
+    pub compressed: bool,
+    /// Project directories whose installs linked this entry. `pacm cache gc` keeps an entry
+    /// while one of them still lists it in its lockfile.
+    pub referencing_projects: Vec<String>,
 }
 
 impl StoreEntry {
     pub fn package_dir(&self) -> &Path {
         &self.package_dir
     }
+
+    pub fn archive_path(&self) -> PathBuf {
+        self.root_dir.join(compressed::COMPRESSED_PACKAGE)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -259,6 +517,9 @@ pub struct EnsureParams<'a> {
     pub source_dir: &'a Path,
     pub integrity: Option<&'a str>,
     pub resolved: Option<&'a str>,
+    /// Project that will link the entry. It is recorded as a reference under the entry's lock,
+    /// so `pacm cache gc` cannot delete the entry between here and the link.
+    pub project: Option<&'a Path>,
 }
 
 #[derive(Debug, Clone)]
@@ -266,32 +527,130 @@ pub struct CasStore {
     root: PathBuf,
     packages_dir: PathBuf,
     tmp_dir: PathBuf,
+    locks_dir: PathBuf,
+}
+
+/// Exclusive hold on one store entry, released on drop. Installs take it to record a reference
+/// and gc takes it to decide whether to delete, so neither sees the other half-done.
+#[derive(Debug)]
+pub struct EntryLock {
+    path: PathBuf,
+}
+
+impl Drop for EntryLock {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
 }
 
 impl CasStore {
+    /// Open the store configured for the current directory.
     pub fn open() -> Result<Self> {
-        let root = store_root();
+        Self::open_with(&crate::config::Config::load())
+    }
+
+    /// Open the store `config` points at, for callers that already loaded it.
+    pub fn open_with(config: &crate::config::Config) -> Result<Self> {
+        let root = store_root(config);
         let packages_dir = root.join("packages");
         let tmp_dir = root.join("tmp");
+        let locks_dir = root.join("locks");
         fs::create_dir_all(&packages_dir)
             .with_context(|| format!("create store packages dir at {}", packages_dir.display()))?;
         fs::create_dir_all(&tmp_dir)
             .with_context(|| format!("create store tmp dir at {}", tmp_dir.display()))?;
-        Ok(Self { root, packages_dir, tmp_dir })
+        fs::create_dir_all(&locks_dir)
+            .with_context(|| format!("create store locks dir at {}", locks_dir.display()))?;
+        let store = Self { root, packages_dir, tmp_dir, locks_dir };
+        store.remove_stale_tmp(STALE_TMP_AGE);
+        Ok(store)
+    }
+
+    /// Remove anything in the store's `tmp` directory older than `max_age`: staged entries,
+    /// metadata swaps and imports left behind by an install that died mid-way. Returns how many
+    /// were removed.
+    pub fn remove_stale_tmp(&self, max_age: Duration) -> usize {
+        let Ok(children) = fs::read_dir(&self.tmp_dir) else {
+            return 0;
+        };
+        let mut removed = 0;
+        for child in children.flatten() {
+            let path = child.path();
+            if !is_stale(&path, max_age) {
+                continue;
+            }
+            let result = match child.file_type() {
+                Ok(kind) if kind.is_dir() => fs::remove_dir_all(&path),
+                _ => fs::remove_file(&path),
+            };
+            if result.is_ok() {
+                removed += 1;
+            }
+        }
+        removed
     }
 
     pub fn root(&self) -> &Path {
         &self.root
     }
 
+    /// Number of entries and their total size. Sizes come from each entry's metadata, falling
+    /// back to walking the entry when the metadata is unreadable.
+    pub fn size(&self) -> (usize, u64) {
+        let mut entries = 0;
+        let mut bytes = 0;
+        for meta in WalkDir::new(&self.packages_dir)
+            .min_depth(2)
+            .max_depth(3)
+            .into_iter()
+            .flatten()
+            .filter(|e| e.file_type().is_file() && e.file_name() == "metadata.json")
+        {
+            entries += 1;
+            bytes += match read_metadata(meta.path()) {
+                Ok(metadata) => metadata.size,
+                Err(_) => meta.path().parent().map(dir_size).unwrap_or(0),
+            };
+        }
+        (entries, bytes)
+    }
+
+    /// Every entry in the store, ordered by store key.
+    pub fn list_entries(&self) -> Result<Vec<StoreEntry>> {
+        let mut entries = archive::store_entry_dirs(&self.packages_dir)
+            .into_iter()
+            .map(|dir| {
+                let metadata_path = dir.join("metadata.json");
+                let metadata = read_metadata(&metadata_path)
+                    .with_context(|| format!("read {}", metadata_path.display()))?;
+                Ok(build_store_entry(dir, metadata))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort_by(|a, b| a.store_key.cmp(&b.store_key));
+        Ok(entries)
+    }
+
     pub fn ensure_entry(&self, params: &EnsureParams) -> Result<StoreEntry> {
+        self.build_entry(params, false)
+    }
+
+    /// Like [`ensure_entry`](Self::ensure_entry), but rebuilds the entry from `source_dir` even
+    /// when it already exists. The old entry stays in place until the new one is complete.
+    pub fn rebuild_entry(&self, params: &EnsureParams) -> Result<StoreEntry> {
+        self.build_entry(params, true)
+    }
+
+    fn build_entry(&self, params: &EnsureParams, replace: bool) -> Result<StoreEntry> {
         let (graph_hash, store_key) =
             compute_graph_hash(params.name, params.version, params.dependencies)?;
         let final_dir = self.store_dir_for(params.name, params.version, &graph_hash);
         let metadata_path = final_dir.join("metadata.json");
-        if metadata_path.exists() {
-            let metadata = read_metadata(&metadata_path)?;
-            return Ok(build_store_entry(final_dir, metadata));
+        if !replace {
+            let _lock = self.lock_entry(&store_key)?;
+            if metadata_path.exists() {
+                let metadata = self.add_reference(&metadata_path, params.project)?;
+                return Ok(build_store_entry(final_dir, metadata));
+            }
         }
 
         let tmp_target = self.tmp_dir.join(format!(
@@ -300,18 +659,120 @@ impl CasStore {
             params.version.replace('/', "_"),
             unique_suffix()
         ));
+        let metadata = match self.stage_entry(params, &tmp_target, &store_key, &graph_hash) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                fs::remove_dir_all(&tmp_target).ok();
+                return Err(err);
+            }
+        };
+        let _lock = match self.lock_entry(&store_key) {
+            Ok(lock) => lock,
+            Err(err) => {
+                fs::remove_dir_all(&tmp_target).ok();
+                return Err(err);
+            }
+        };
+        match self.install_staged(&tmp_target, &final_dir, metadata, replace) {
+            Ok(Some(metadata)) => Ok(build_store_entry(final_dir, metadata)),
+            // Another install finished the same entry first; its copy is just as good.
+            Ok(None) => {
+                let metadata = self.add_reference(&metadata_path, params.project)?;
+                Ok(build_store_entry(final_dir, metadata))
+            }
+            Err(err) => {
+                fs::remove_dir_all(&tmp_target).ok();
+                Err(err)
+            }
+        }
+    }
+
+    /// Rename the staged entry at `tmp_target` into `final_dir`; the caller holds the entry lock.
+    /// A rebuilt entry keeps the references of the one it replaces. Returns `None` when another
+    /// install created the entry first, in which case the staged copy is discarded.
+    fn install_staged(
+        &self,
+        tmp_target: &Path,
+        final_dir: &Path,
+        mut metadata: StoreMetadata,
+        replace: bool,
+    ) -> Result<Option<StoreMetadata>> {
+        let metadata_path = final_dir.join("metadata.json");
+        if let Some(parent) = final_dir.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if replace {
+            if let Ok(old) = read_metadata(&metadata_path) {
+                for project in old.referencing_projects {
+                    if !metadata.referencing_projects.contains(&project) {
+                        metadata.referencing_projects.push(project);
+                    }
+                }
+                metadata.referencing_projects.sort();
+                write_metadata(&tmp_target.join("metadata.json"), &metadata)?;
+            }
+        }
+
+        // Move the old entry aside rather than deleting it first, so a failure never leaves the
+        // store without this package.
+        let displaced = if replace && final_dir.exists() {
+            let mut aside = tmp_target.to_path_buf().into_os_string();
+            aside.push(".old");
+            let aside = PathBuf::from(aside);
+            fs::rename(final_dir, &aside)?;
+            Some(aside)
+        } else {
+            None
+        };
+        if let Err(rename_err) = fs::rename(tmp_target, final_dir) {
+            if metadata_path.exists() {
+                fs::remove_dir_all(tmp_target).ok();
+                if let Some(aside) = displaced {
+                    fs::remove_dir_all(aside).ok();
+                }
+                return Ok(None);
+            }
+            if let Some(aside) = &displaced {
+                fs::rename(aside, final_dir).ok();
+            }
+            return Err(rename_err.into());
+        }
+        if let Some(aside) = displaced {
+            fs::remove_dir_all(aside).ok();
+        }
+        Ok(Some(metadata))
+    }
+
+    /// Copy (and compress) `params.source_dir` into `tmp_target` and write its metadata there,
+    /// ready to be renamed into the store.
+    fn stage_entry(
+        &self,
+        params: &EnsureParams,
+        tmp_target: &Path,
+        store_key: &str,
+        graph_hash: &str,
+    ) -> Result<StoreMetadata> {
         let tmp_package_dir = tmp_target.join("package");
         fs::create_dir_all(&tmp_package_dir)?;
         copy_tree(params.source_dir, &tmp_package_dir).with_context(|| {
             format!("copy package contents for {}@{} into store", params.name, params.version)
         })?;
         let (content_hash, total_size) = compute_tree_content_hash(&tmp_package_dir)?;
+        let compressed = compressed::compression_enabled();
+        if compressed {
+            compressed::compress_package(
+                &tmp_package_dir,
+                &tmp_target.join(compressed::COMPRESSED_PACKAGE),
+            )
+            .with_context(|| format!("compress {}@{}", params.name, params.version))?;
+            fs::remove_dir_all(&tmp_package_dir)?;
+        }
         let metadata = StoreMetadata {
-            store_key: store_key.clone(),
+            store_key: store_key.to_string(),
             name: params.name.to_string(),
             version: params.version.to_string(),
-            graph_hash: graph_hash.clone(),
-            content_hash: content_hash.clone(),
+            graph_hash: graph_hash.to_string(),
+            content_hash,
             size: total_size,
             created_at: unix_timestamp()?,
             integrity: params.integrity.map(|s| s.to_string()),
@@ -340,41 +801,101 @@ impl CasStore {
                 }
                 scripts_map
             },
+            compressed,
+            referencing_projects: params.project.map(project_key).into_iter().collect(),
         };
         let metadata_tmp_path = tmp_target.join("metadata.json");
         write_metadata(&metadata_tmp_path, &metadata)?;
+        Ok(metadata)
+    }
 
-        if let Some(parent) = final_dir.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        match fs::rename(&tmp_target, &final_dir) {
-            Ok(()) => {}
-            Err(rename_err) => {
-                if metadata_path.exists() {
-                    fs::remove_dir_all(&tmp_target).ok();
-                    let metadata = read_metadata(&metadata_path)?;
-                    return Ok(build_store_entry(final_dir, metadata));
+    /// Take the lock for `store_key`, waiting for any other holder. A lock abandoned by a process
+    /// that died is broken once it is [`ENTRY_LOCK_STALE`].
+    pub fn lock_entry(&self, store_key: &str) -> Result<EntryLock> {
+        let path = self.locks_dir.join(format!("{}.lock", sanitize_for_fs(store_key)));
+        loop {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(EntryLock { path }),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if is_stale(&path, ENTRY_LOCK_STALE) {
+                        fs::remove_file(&path).ok();
+                    } else {
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| format!("lock {}", path.display()));
                 }
-                return Err(rename_err.into());
             }
         }
+    }
 
-        Ok(StoreEntry {
-            store_key,
-            name: metadata.name.clone(),
-            version: metadata.version.clone(),
-            graph_hash,
-            content_hash,
-            size: metadata.size,
-            integrity: metadata.integrity.clone(),
-            resolved: metadata.resolved.clone(),
-            created_at: metadata.created_at,
-            dependencies: metadata.dependencies.clone(),
-            root_dir: final_dir.clone(),
-            package_dir: final_dir.join("package"),
-            metadata_path,
-        })
+    /// Load the entry for `store_key` and record `project` as linking it, or `None` when the
+    /// store has no such entry. The lockfile fast path uses this in place of
+    /// [`ensure_entry`](Self::ensure_entry).
+    pub fn reference_entry(&self, store_key: &str, project: &Path) -> Result<Option<StoreEntry>> {
+        let Some((name, version, graph_hash)) = split_store_key(store_key) else {
+            return Ok(None);
+        };
+        let dir = self.store_dir_for(&name, &version, &graph_hash);
+        let metadata_path = dir.join("metadata.json");
+        let _lock = self.lock_entry(store_key)?;
+        if !metadata_path.exists() {
+            return Ok(None);
+        }
+        let metadata = self.add_reference(&metadata_path, Some(project))?;
+        Ok(Some(build_store_entry(dir, metadata)))
+    }
+
+    /// Record that `project` links the entry at `metadata_path`; the caller holds its lock. The
+    /// metadata is rewritten even when the reference exists, which restarts the entry's
+    /// [`REFERENCE_GRACE`].
+    fn add_reference(&self, metadata_path: &Path, project: Option<&Path>) -> Result<StoreMetadata> {
+        let mut metadata = read_metadata(metadata_path)?;
+        let Some(project) = project.map(project_key) else {
+            return Ok(metadata);
+        };
+        if !metadata.referencing_projects.contains(&project) {
+            metadata.referencing_projects.push(project);
+            metadata.referencing_projects.sort();
+        }
+        self.replace_metadata(metadata_path, &metadata)?;
+        Ok(metadata)
+    }
+
+    /// Replace the projects recorded as referencing `entry`; the caller holds its lock.
+    pub fn set_references(&self, entry: &StoreEntry, projects: Vec<String>) -> Result<()> {
+        let mut metadata = read_metadata(&entry.metadata_path)?;
+        metadata.referencing_projects = projects;
+        self.replace_metadata(&entry.metadata_path, &metadata)
+    }
+
+    /// Whether a project recorded a reference to `entry` within [`REFERENCE_GRACE`].
+    pub fn recently_referenced(&self, entry: &StoreEntry) -> bool {
+        !is_stale(&entry.metadata_path, REFERENCE_GRACE)
+    }
+
+    /// Swap in new metadata with a rename, so concurrent readers never see a partial file.
+    fn replace_metadata(&self, path: &Path, metadata: &StoreMetadata) -> Result<()> {
+        let tmp = self.tmp_dir.join(format!("metadata-{}.json", unique_suffix()));
+        write_metadata(&tmp, metadata)?;
+        fs::rename(&tmp, path).with_context(|| format!("update {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Recompute the content hash of `entry`'s package directory and compare it with the hash
+    /// recorded when the entry was created. Hardlinked installs share files with the store, so
+    /// edits inside `node_modules` show up here.
+    pub fn verify_entry(&self, entry: &StoreEntry) -> Result<bool> {
+        if !entry.compressed {
+            let (content_hash, _) = compute_tree_content_hash(&entry.package_dir)?;
+            return Ok(content_hash == entry.content_hash);
+        }
+        let scratch = self.tmp_dir.join(format!("verify-{}", unique_suffix()));
+        let result = unpack_compressed(&entry.archive_path(), &scratch)
+            .and_then(|()| compute_tree_content_hash(&scratch));
+        fs::remove_dir_all(&scratch).ok();
+        Ok(result?.0 == entry.content_hash)
     }
 
     pub fn load_entry(&self, store_key: &str) -> Result<Option<StoreEntry>> {
@@ -390,6 +911,29 @@ impl CasStore {
         Ok(Some(build_store_entry(dir, metadata)))
     }
 
+    /// Delete the entry for `store_key`, returning the bytes it held, or `None` when the store
+    /// has no such entry.
+    pub fn remove_entry(&self, store_key: &str) -> Result<Option<u64>> {
+        let Some((name, version, graph_hash)) = split_store_key(store_key) else {
+            return Ok(None);
+        };
+        let dir = self.store_dir_for(&name, &version, &graph_hash);
+        if !dir.exists() {
+            return Ok(None);
+        }
+        let size = match read_metadata(&dir.join("metadata.json")) {
+            Ok(metadata) => metadata.size,
+            Err(_) => dir_size(&dir),
+        };
+        fs::remove_dir_all(&dir)
+            .with_context(|| format!("remove store entry {}", dir.display()))?;
+        // Drop the scope directory once its last entry is gone; fails harmlessly otherwise.
+        if let Some(parent) = dir.parent().filter(|p| *p != self.packages_dir) {
+            fs::remove_dir(parent).ok();
+        }
+        Ok(Some(size))
+    }
+
     fn store_dir_for(&self, name: &str, version: &str, graph_hash: &str) -> PathBuf {
         let mut dir = self.packages_dir.clone();
         let mut parts: Vec<&str> = name.split('/').collect();
@@ -420,9 +964,16 @@ fn build_store_entry(dir: PathBuf, metadata: StoreMetadata) -> StoreEntry {
         root_dir: dir.clone(),
         package_dir: dir.join("package"),
         metadata_path: dir.join("metadata.json"),
+        compressed: metadata.compressed,
+        referencing_projects: metadata.referencing_projects,
     }
 }
 
+/// How a project directory is recorded in `referencing_projects`.
+pub fn project_key(project: &Path) -> String {
+    project.canonicalize().unwrap_or_else(|_| project.to_path_buf()).display().to_string()
+}
+
 fn compute_graph_hash(
     name: &str,
     version: &str,
@@ -465,7 +1016,7 @@ fn write_metadata(path: &Path, metadata: &StoreMetadata) -> Result<()> {
     Ok(())
 }
 
-fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+pub(super) fn copy_tree(from: &Path, to: &Path) -> Result<()> {
     for entry in WalkDir::new(from).follow_links(false) {
         let entry = entry?;
         let rel = entry.path().strip_prefix(from)?;
@@ -480,6 +1031,10 @@ fn copy_tree(from: &Path, to: &Path) -> Result<()> {
         if let Some(parent) = dest.parent() {
             fs::create_dir_all(parent)?;
         }
+        if entry.file_type().is_symlink() {
+            copy_symlink(from, entry.path(), &dest)?;
+            continue;
+        }
         fs::copy(entry.path(), &dest)?;
         let perms = entry.metadata()?.permissions();
         fs::set_permissions(&dest, perms)?;
@@ -506,6 +1061,21 @@ fn compute_tree_content_hash(root: &Path) -> Result<(String, u64)> {
             continue;
         }
         let rel_str = rel.to_string_lossy().replace('\\', "/");
+        if entry.file_type().is_symlink() {
+            // Hash the link itself, not whatever it happens to point at.
+            let target = fs::read_link(entry.path())?;
+            let digest = Sha256::digest(target.to_string_lossy().as_bytes());
+            let mut digest_bytes = [0u8; 32];
+            digest_bytes.copy_from_slice(&digest);
+            entries.push(ContentEntry {
+                path: rel_str,
+                kind: b'l',
+                size: 0,
+                readonly: false,
+                digest: Some(digest_bytes),
+            });
+            continue;
+        }
         let meta = entry.metadata()?;
         if entry.file_type().is_dir() {
             entries.push(ContentEntry {
@@ -575,8 +1145,12 @@ fn sanitize_for_fs(name: &str) -> String {
     sanitized
 }
 
-fn unique_suffix() -> String {
+/// Timestamp, process id and a per-process counter, so concurrent writers in the same process
+/// get distinct names too.
+pub(crate) fn unique_suffix() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
     unix_timestamp()
-        .map(|ts| format!("{}-{:x}", ts, std::process::id()))
-        .unwrap_or_else(|_| format!("fallback-{:x}", std::process::id()))
+        .map(|ts| format!("{}-{:x}-{seq}", ts, std::process::id()))
+        .unwrap_or_else(|_| format!("fallback-{:x}-{seq}", std::process::id()))
 }
@@ -0,0 +1,91 @@
+//! Ctrl-C handling for installs.
+//!
+//! The signal handler only raises a flag; resolution and linking poll it between packages so an
+//! in-flight store rename always completes. Installs check it again before writing the lockfile
+//! and before lifecycle scripts, and the error says how far the install got. A second Ctrl-C
+//! exits immediately.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+static HANDLER: Once = Once::new();
+
+/// Returned once an install notices the abort flag, naming the stage it stopped at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Cancelled {
+    #[error("install cancelled; no changes written")]
+    BeforeLinking,
+    #[error(
+        "install cancelled while linking; node_modules was partly updated but pacm.lockb and \
+         package.json were left unchanged (run 'pacm install' to finish)"
+    )]
+    WhileLinking,
+    #[error(
+        "install cancelled after linking; node_modules was updated but pacm.lockb and \
+         package.json were left unchanged (run 'pacm install' to finish)"
+    )]
+    AfterLinking,
+    #[error(
+        "install cancelled before lifecycle scripts; node_modules and pacm.lockb were updated \
+         but no scripts ran"
+    )]
+    BeforeScripts,
+}
+
+impl Cancelled {
+    /// Whether the lockfile (and with it any package.json edit) was already written.
+    pub fn wrote_lockfile(self) -> bool {
+        matches!(self, Cancelled::BeforeScripts)
+    }
+}
+
+#[cfg(test)]
+static CANCEL_AT: std::sync::Mutex<Option<Cancelled>> = std::sync::Mutex::new(None);
+
+/// Raise the abort flag as soon as an install reaches `stage`, as if Ctrl-C arrived right then.
+#[cfg(test)]
+pub(crate) fn cancel_at(stage: Option<Cancelled>) {
+    *CANCEL_AT.lock().unwrap_or_else(|e| e.into_inner()) = stage;
+}
+
+/// Route Ctrl-C to the abort flag. Safe to call more than once.
+pub fn install_handler() {
+    HANDLER.call_once(|| {
+        let _ = ctrlc::set_handler(|| {
+            if CANCELLED.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
+            }
+        });
+    });
+}
+
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::SeqCst);
+}
+
+pub fn reset() {
+    CANCELLED.store(false, Ordering::SeqCst);
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Fail with [`Cancelled::BeforeLinking`] if Ctrl-C was pressed.
+pub fn check() -> Result<()> {
+    check_at(Cancelled::BeforeLinking)
+}
+
+/// Fail with `stage` if Ctrl-C was pressed.
+pub fn check_at(stage: Cancelled) -> Result<()> {
+    #[cfg(test)]
+    if *CANCEL_AT.lock().unwrap_or_else(|e| e.into_inner()) == Some(stage) {
+        cancel();
+    }
+    if is_cancelled() {
+        return Err(stage.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use crate::colors::*;
+use crate::config::Config;
+use crate::fetch::Fetcher;
+use crate::lockfile::{self, Lockfile, PackageSource};
+use crate::output::{print_json, OutputFormat};
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// npm advisory severities, lowest first so they compare naturally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Severity {
+    Info,
+    Low,
+    Moderate,
+    High,
+    Critical,
+}
+
+impl Severity {
+    const ALL: [Severity; 5] =
+        [Severity::Critical, Severity::High, Severity::Moderate, Severity::Low, Severity::Info];
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "info" => Some(Severity::Info),
+            "low" => Some(Severity::Low),
+            "moderate" => Some(Severity::Moderate),
+            "high" => Some(Severity::High),
+            "critical" => Some(Severity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Low => "low",
+            Severity::Moderate => "moderate",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+
+    fn color(self) -> Style {
+        match self {
+            Severity::Critical | Severity::High => C_RED,
+            Severity::Moderate => C_YELLOW,
+            Severity::Low | Severity::Info => C_DIM,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Advisory {
+    pub module: String,
+    pub severity: Severity,
+    pub title: String,
+    pub vulnerable_versions: String,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AuditReport {
+    pub advisories: Vec<Advisory>,
+}
+
+impl AuditReport {
+    /// Parse the `advisories` map of an npm audit response; unknown severities count as info.
+    pub fn from_response(response: &Value) -> Self {
+        let mut advisories: Vec<Advisory> = response
+            .get("advisories")
+            .and_then(Value::as_object)
+            .map(|map| {
+                map.values()
+                    .map(|adv| {
+                        let text = |key: &str| {
+                            adv.get(key).and_then(Value::as_str).unwrap_or_default().to_string()
+                        };
+                        Advisory {
+                            module: text("module_name"),
+                            severity: Severity::parse(&text("severity")).unwrap_or(Severity::Info),
+                            title: text("title"),
+                            vulnerable_versions: text("vulnerable_versions"),
+                            url: adv.get("url").and_then(Value::as_str).map(str::to_string),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        advisories
+            .sort_by(|a, b| b.severity.cmp(&a.severity).then_with(|| a.module.cmp(&b.module)));
+        Self { advisories }
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.advisories.iter().filter(|a| a.severity == severity).count()
+    }
+
+    pub fn at_or_above(&self, level: Severity) -> usize {
+        self.advisories.iter().filter(|a| a.severity >= level).count()
+    }
+}
+
+pub fn cmd_audit(format: OutputFormat, audit_level: String, config: &Config) -> Result<()> {
+    let level = Severity::parse(&audit_level).ok_or_else(|| {
+        anyhow!(
+            "invalid --audit-level '{audit_level}' (expected info, low, moderate, high or critical)"
+        )
+    })?;
+    let project_root = std::env::current_dir()?;
+    let lock_path = project_root.join("pacm.lockb");
+    if !lock_path.exists() {
+        bail!("no pacm.lockb found; run 'pacm install' first");
+    }
+    let lock = lockfile::load(&lock_path)?;
+    let manifest_path = project_root.join("package.json");
+    let project_name = crate::manifest::load(&manifest_path)
+        .map(|m| m.name)
+        .unwrap_or_else(|_| "pacm-project".to_string());
+
+    let payload = build_audit_payload(&project_name, &lock);
+    let fetcher = Fetcher::from_config(config)?;
+    let response = fetcher.security_audit(&payload)?;
+    let report = AuditReport::from_response(&response);
+
+    if format.is_json() {
+        print_json(&response)?;
+    } else {
+        print_report(&report);
+    }
+    let failing = report.at_or_above(level);
+    if failing > 0 {
+        bail!("{failing} vulnerable package(s) at or above {} severity", level.as_str());
+    }
+    Ok(())
+}
+
+/// Build the npm audit request body from the lockfile. Workspace, git and tarball packages are
+/// left out since the advisory database only knows registry versions.
+pub(crate) fn build_audit_payload(project_name: &str, lock: &Lockfile) -> Value {
+    let root = lock.packages.get("");
+    let mut requires = BTreeMap::new();
+    if let Some(root) = root {
+        for deps in [&root.dependencies, &root.dev_dependencies, &root.optional_dependencies] {
+            requires.extend(deps.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+    }
+
+    let mut dependencies = Map::new();
+    for (key, entry) in &lock.packages {
+        let (Some(name), Some(version)) = (key.strip_prefix("node_modules/"), &entry.version)
+        else {
+            continue;
+        };
+        let local = entry.source_kind() == Some(PackageSource::Workspace);
+        if local || version.contains('+') {
+            continue;
+        }
+        dependencies.insert(
+            name.to_string(),
+            json!({ "version": version, "requires": entry.dependencies }),
+        );
+    }
+
+    json!({
+        "name": project_name,
+        "version": root.and_then(|r| r.version.clone()).unwrap_or_else(|| "0.0.0".to_string()),
+        "requires": requires,
+        "dependencies": dependencies,
+        "install": [],
+        "remove": [],
+        "metadata": { "npm_version": "7.0.0", "node_version": "", "platform": "" },
+    })
+}
+
+fn print_report(report: &AuditReport) {
+    if report.advisories.is_empty() {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}no known vulnerabilities{C_RESET}");
+        return;
+    }
+    for adv in &report.advisories {
+        let color = adv.severity.color();
+        println!(
+            "  {color}{}{C_RESET} {} {C_DIM}{}{C_RESET} {}",
+            adv.severity.as_str(),
+            adv.module,
+            adv.vulnerable_versions,
+            adv.title
+        );
+        if let Some(url) = &adv.url {
+            println!("    {C_DIM}{url}{C_RESET}");
+        }
+    }
+    let breakdown: Vec<String> = Severity::ALL
+        .iter()
+        .filter_map(|sev| {
+            let n = report.count(*sev);
+            (n > 0).then(|| format!("{n} {}", sev.as_str()))
+        })
+        .collect();
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} found {C_YELLOW}{}{C_RESET} vulnerabilities ({})",
+        report.advisories.len(),
+        breakdown.join(", ")
+    );
+}
@@ -1,7 +1,11 @@
 use crate::colors::*;
+use crate::config::Config;
 use crate::fsutil;
+use crate::output::{print_json, OutputFormat};
+use crate::resolver::spec::parse_spec;
 use anyhow::Result;
 use std::fs;
+use std::path::Path;
 
 pub fn cmd_cache_path() -> Result<()> {
     let path = fsutil::cache_root();
@@ -14,7 +18,53 @@ pub fn cmd_cache_path() -> Result<()> {
     Ok(())
 }
 
-pub fn cmd_cache_clean() -> Result<()> {
+pub fn cmd_cache_size(format: OutputFormat, config: &Config) -> Result<()> {
+    let size = crate::cache::size(config)?;
+    if format.is_json() {
+        return print_json(&size);
+    }
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} cache: {C_GREEN}{}{C_RESET} in {} packages ({})",
+        fsutil::human_size(size.cache_bytes),
+        size.cached_packages,
+        fsutil::cache_root().display()
+    );
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} store: {C_GREEN}{}{C_RESET} in {} entries ({})",
+        fsutil::human_size(size.store_bytes),
+        size.store_entries,
+        fsutil::store_root(config).display()
+    );
+    Ok(())
+}
+
+pub fn cmd_cache_gc(format: OutputFormat, config: &Config) -> Result<()> {
+    let store = crate::cache::CasStore::open_with(config)?;
+    let summary = crate::cli::commands::install::collect_unreferenced_store_entries(&store)?;
+    if format.is_json() {
+        return print_json(&summary);
+    }
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} gc: removed {C_GREEN}{}{C_RESET} store entries ({}), kept {} still in use",
+        summary.removed,
+        fsutil::human_size(summary.freed_bytes),
+        summary.kept
+    );
+    Ok(())
+}
+
+pub fn cmd_cache_clean(package: Option<String>) -> Result<()> {
+    if let Some(spec) = package {
+        let (name, range) = parse_spec(&spec);
+        let version = (range != "*").then_some(range.as_str());
+        let label = version.map(|v| format!("{name}@{v}")).unwrap_or_else(|| name.clone());
+        if crate::cache::remove_cached_package(&name, version)? {
+            println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}removed{C_RESET} {label} from cache");
+        } else {
+            println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}{label} is not cached{C_RESET}");
+        }
+        return Ok(());
+    }
     let root = fsutil::cache_root();
     if root.exists() {
         fs::remove_dir_all(&root).ok();
@@ -29,3 +79,26 @@ pub fn cmd_cache_clean() -> Result<()> {
     );
     Ok(())
 }
+
+pub fn cmd_cache_export(file: &str, include_store: bool, config: &Config) -> Result<()> {
+    let summary = crate::cache::export_archive(Path::new(file), include_store, config)?;
+    let store = if include_store {
+        format!(" and {} store entries", summary.store_entries)
+    } else {
+        String::new()
+    };
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} {C_GREEN}exported{C_RESET} {} packages{store} to {file}",
+        summary.packages
+    );
+    Ok(())
+}
+
+pub fn cmd_cache_import(file: &str, config: &Config) -> Result<()> {
+    let summary = crate::cache::import_archive(Path::new(file), config)?;
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} {C_GREEN}imported{C_RESET} {} packages and {} store entries from {file} ({C_DIM}{} already present{C_RESET})",
+        summary.packages, summary.store_entries, summary.skipped
+    );
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use crate::cache::CasStore;
+use crate::cli::commands::install::lock_drift;
+use crate::colors::*;
+use crate::config::Config;
+use crate::fsutil::{cache_root, store_root};
+use crate::lockfile;
+use crate::workspaces::discover_workspaces;
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Check {
+    pub status: CheckStatus,
+    pub label: &'static str,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(status: CheckStatus, label: &'static str, detail: impl Into<String>) -> Self {
+        Self { status, label, detail: detail.into() }
+    }
+}
+
+pub fn cmd_doctor(config: &Config) -> Result<()> {
+    let project_root = std::env::current_dir()?;
+    let checks = run_checks(&project_root, config);
+    println!("{C_GRAY}[pacm]{C_RESET} doctor report for {}", project_root.display());
+    for check in &checks {
+        let (color, tag) = match check.status {
+            CheckStatus::Pass => (C_GREEN, "pass"),
+            CheckStatus::Warn => (C_YELLOW, "warn"),
+            CheckStatus::Fail => (C_RED, "fail"),
+        };
+        println!("  {color}{tag}{C_RESET} {}: {}", check.label, check.detail);
+    }
+    let failures = checks.iter().filter(|c| c.status == CheckStatus::Fail).count();
+    if failures > 0 {
+        bail!("doctor found {failures} problem(s)");
+    }
+    Ok(())
+}
+
+/// Run every diagnostic against `project_root` without printing anything.
+pub(crate) fn run_checks(project_root: &Path, config: &Config) -> Vec<Check> {
+    let mut checks = vec![
+        check_node(),
+        check_writable("store", &store_root(config)),
+        check_writable("cache", &cache_root()),
+    ];
+
+    let lock_path = project_root.join("pacm.lockb");
+    if !lock_path.exists() {
+        checks.push(Check::new(CheckStatus::Warn, "lockfile", "no pacm.lockb; run 'pacm install'"));
+        return checks;
+    }
+    let lock = match lockfile::load(&lock_path) {
+        Ok(lock) => lock,
+        Err(err) => {
+            checks.push(Check::new(CheckStatus::Fail, "lockfile", format!("{err:#}")));
+            return checks;
+        }
+    };
+    checks.push(Check::new(
+        CheckStatus::Pass,
+        "lockfile",
+        format!("{} entries", lock.packages.len()),
+    ));
+    if let Some(check) = check_lock_drift(project_root, &lock) {
+        checks.push(check);
+    }
+
+    let store = match CasStore::open_with(config) {
+        Ok(store) => store,
+        Err(err) => {
+            checks.push(Check::new(CheckStatus::Fail, "store entries", format!("{err:#}")));
+            return checks;
+        }
+    };
+    let mut missing = Vec::new();
+    let mut modified = Vec::new();
+    let mut checked = 0usize;
+    for (key, entry) in &lock.packages {
+        let Some(store_key) = &entry.store_key else { continue };
+        checked += 1;
+        match store.load_entry(store_key) {
+            Ok(Some(stored)) => {
+                if !store.verify_entry(&stored).unwrap_or(false) {
+                    modified.push(key.trim_start_matches("node_modules/").to_string());
+                }
+            }
+            _ => missing.push(key.trim_start_matches("node_modules/").to_string()),
+        }
+    }
+    checks.push(if !missing.is_empty() {
+        Check::new(
+            CheckStatus::Fail,
+            "store entries",
+            format!("missing from store: {}; run 'pacm install'", missing.join(", ")),
+        )
+    } else if !modified.is_empty() {
+        Check::new(
+            CheckStatus::Warn,
+            "store entries",
+            format!("contents changed since install: {}", modified.join(", ")),
+        )
+    } else {
+        Check::new(CheckStatus::Pass, "store entries", format!("{checked} verified"))
+    });
+
+    checks.push(check_node_modules(project_root, &lock));
+    checks
+}
+
+fn check_lock_drift(project_root: &Path, lock: &lockfile::Lockfile) -> Option<Check> {
+    let manifest = crate::manifest::load(&project_root.join("package.json")).ok()?;
+    let workspaces = discover_workspaces(project_root, &manifest).unwrap_or_default();
+    let drift = lock_drift(
+        std::iter::once(&manifest).chain(workspaces.iter().map(|ws| &ws.manifest)),
+        lock,
+    );
+    Some(if drift.is_empty() {
+        Check::new(CheckStatus::Pass, "lockfile ranges", "locked versions satisfy package.json")
+    } else {
+        Check::new(
+            CheckStatus::Warn,
+            "lockfile ranges",
+            format!("{}; run 'pacm install'", drift.join(", ")),
+        )
+    })
+}
+
+fn check_node() -> Check {
+    match Command::new("node").arg("--version").output() {
+        Ok(out) if out.status.success() => Check::new(
+            CheckStatus::Pass,
+            "node",
+            String::from_utf8_lossy(&out.stdout).trim().to_string(),
+        ),
+        _ => Check::new(
+            CheckStatus::Warn,
+            "node",
+            "not found on PATH; scripts and package binaries will not run",
+        ),
+    }
+}
+
+fn check_writable(label: &'static str, dir: &Path) -> Check {
+    let probe = dir.join(format!(".pacm-doctor-{}", std::process::id()));
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b"ok"))
+        .and_then(|_| std::fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check::new(CheckStatus::Pass, label, format!("{} is writable", dir.display())),
+        Err(err) => Check::new(
+            CheckStatus::Fail,
+            label,
+            format!("cannot write to {}: {err}", dir.display()),
+        ),
+    }
+}
+
+fn check_node_modules(project_root: &Path, lock: &lockfile::Lockfile) -> Check {
+    let pacm_dir = project_root.join("node_modules").join(".pacm");
+    let mut mismatched = Vec::new();
+    let mut total = 0usize;
+    for (key, entry) in &lock.packages {
+        let (Some(name), Some(version)) = (key.strip_prefix("node_modules/"), &entry.version)
+        else {
+            continue;
+        };
+        total += 1;
+        let manifest_path = pacm_dir.join(name).join("package.json");
+        let installed = std::fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|txt| serde_json::from_str::<serde_json::Value>(&txt).ok())
+            .and_then(|val| val.get("version").and_then(|v| v.as_str()).map(str::to_string));
+        // Remote and local tarballs carry a `+build` suffix that the package itself doesn't know;
+        // build metadata has no say in semver precedence, so compare by precedence.
+        let matches = installed.as_deref().is_some_and(|installed| {
+            match (semver::Version::parse(installed), semver::Version::parse(version)) {
+                (Ok(installed), Ok(locked)) => installed.cmp_precedence(&locked).is_eq(),
+                _ => installed == version,
+            }
+        });
+        if !matches {
+            mismatched.push(name.to_string());
+        }
+    }
+    if mismatched.is_empty() {
+        Check::new(
+            CheckStatus::Pass,
+            "node_modules",
+            format!("{total} packages match the lockfile"),
+        )
+    } else {
+        Check::new(
+            CheckStatus::Fail,
+            "node_modules",
+            format!("missing or out of date: {}; run 'pacm install'", mismatched.join(", ")),
+        )
+    }
+}
@@ -0,0 +1,24 @@
+use crate::colors::*;
+use crate::lockfile;
+use crate::lockfile_import::import_file;
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+pub fn cmd_import_lock(file: String) -> Result<()> {
+    let lock_path = PathBuf::from("pacm.lockb");
+    if lock_path.exists() {
+        bail!("pacm.lockb already exists; remove it before importing another lockfile");
+    }
+    let source = PathBuf::from(&file);
+    let imported = import_file(&source)?;
+    let count = imported.lockfile.packages.keys().filter(|k| !k.is_empty()).count();
+    lockfile::write(&imported.lockfile, lock_path)?;
+    println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}imported{C_RESET} {count} packages from {file}");
+    if imported.skipped > 0 {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}note{C_RESET}: skipped {} nested, linked or duplicate entries; 'pacm install' resolves them",
+            imported.skipped
+        );
+    }
+    Ok(())
+}
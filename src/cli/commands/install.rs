@@ -1,16 +1,22 @@
-pub(crate) use install_command::{cmd_install, InstallOptions};
+pub(crate) use ci::cmd_ci;
+pub(crate) use global::cmd_install_global;
+pub(crate) use install_command::cmd_install;
+pub use install_command::{install_project, InstallKind, InstallOptions, InstallReport};
 
-pub(crate) use fast::build_fast_instances;
+pub(crate) use fast::{build_fast_instances, lock_drift};
 pub(crate) use prune::{
-    cleanup_empty_node_modules_dir, lockfile_has_no_packages, prune_removed_from_lock,
+    cleanup_empty_node_modules_dir, collect_store_garbage, collect_unreferenced_store_entries,
+    dev_only_packages, lockfile_has_no_packages, production_packages, prune_removed_from_lock,
     prune_unreachable, remove_dirs,
 };
 
+mod ci;
 mod fast;
+mod global;
 mod install_command;
 pub mod manifest_updates;
 mod node_modules;
-mod platform;
 mod progress;
 mod prune;
+pub(crate) mod signatures;
 mod util;
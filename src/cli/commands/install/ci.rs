@@ -0,0 +1,25 @@
+use super::install_command::{cmd_install, InstallOptions};
+use crate::config::Config;
+use anyhow::{bail, Result};
+
+/// Clean install for CI: drop `node_modules` and reinstall exactly what `pacm.lockb` records,
+/// offline, from the store or the package cache. Never resolves, never touches package.json,
+/// and fails when the lockfile is missing or no longer satisfies the manifest. `node_modules`
+/// is only removed once all of that has been checked, so a failure leaves it as it was.
+pub(crate) fn cmd_ci(no_progress: bool, config: &Config) -> Result<()> {
+    let project_root = std::env::current_dir()?;
+    if !project_root.join("pacm.lockb").exists() {
+        bail!("pacm ci requires an existing pacm.lockb; run 'pacm install' first");
+    }
+    cmd_install(
+        Vec::new(),
+        InstallOptions {
+            offline: true,
+            no_progress,
+            prefer_frozen: true,
+            frozen_lockfile: true,
+            config: config.clone(),
+            ..InstallOptions::default()
+        },
+    )
+}
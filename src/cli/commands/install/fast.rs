@@ -1,8 +1,56 @@
+use super::prune::enqueue_entry;
 use crate::installer::PackageInstance;
 use crate::lockfile::Lockfile;
 use crate::manifest::Manifest;
+use crate::resolver::spec::PackageSpec;
+use crate::resolver::{looks_like_dist_tag, parse_range_to_reqs};
 use std::collections::BTreeMap;
 
+/// Dependencies of `manifests` whose locked version falls outside their range, formatted as
+/// `name@locked does not satisfy range`. Dist-tags, aliases, workspace, git and tarball specs
+/// are pinned by the lockfile itself and never count as drift.
+pub(crate) fn lock_drift<'a>(
+    manifests: impl IntoIterator<Item = &'a Manifest>,
+    lock: &Lockfile,
+) -> Vec<String> {
+    let mut drift = Vec::new();
+    for manifest in manifests {
+        let deps = manifest
+            .dependencies
+            .iter()
+            .chain(&manifest.dev_dependencies)
+            .chain(&manifest.optional_dependencies);
+        for (name, range) in deps {
+            let PackageSpec::Registry { range: spec } = PackageSpec::parse(range) else {
+                continue;
+            };
+            if range.trim_start().starts_with("npm:")
+                || spec.starts_with("workspace:")
+                || looks_like_dist_tag(&spec)
+            {
+                continue;
+            }
+            let Some(locked) = lock
+                .packages
+                .get(&format!("node_modules/{name}"))
+                .and_then(|entry| entry.version.as_deref())
+            else {
+                continue;
+            };
+            let (Ok(version), Ok(reqs)) =
+                (semver::Version::parse(locked), parse_range_to_reqs(&spec))
+            else {
+                continue;
+            };
+            let message = format!("{name}@{locked} does not satisfy {range}");
+            if !reqs.iter().any(|req| req.matches(&version)) && !drift.contains(&message) {
+                drift.push(message);
+            }
+        }
+    }
+    drift
+}
+
 pub(crate) fn build_fast_instances(
     manifest: &Manifest,
     lock: &Lockfile,
@@ -26,33 +74,14 @@ pub(crate) fn build_fast_instances(
         return Some(BTreeMap::new());
     }
 
-    let mut queue: VecDeque<String> = needed.iter().cloned().collect();
+    // Same closure as `prune_unreachable`: required peers are installed, optional ones are not.
+    let mut queue: VecDeque<String> = needed.drain().collect();
     while let Some(name) = queue.pop_front() {
-        let key = format!("node_modules/{name}");
-        if let Some(entry) = lock.packages.get(&key) {
-            for dep in entry.dependencies.keys() {
-                if needed.insert(dep.clone()) {
-                    queue.push_back(dep.clone());
-                }
-            }
-            for dep in entry.dev_dependencies.keys() {
-                if needed.insert(dep.clone()) {
-                    queue.push_back(dep.clone());
-                }
-            }
-            for dep in entry.optional_dependencies.keys() {
-                if needed.insert(dep.clone()) {
-                    queue.push_back(dep.clone());
-                }
-            }
-            for dep in entry.peer_dependencies.keys() {
-                if needed.insert(dep.clone()) {
-                    queue.push_back(dep.clone());
-                }
-            }
-        } else {
-            return None;
+        if !needed.insert(name.clone()) {
+            continue;
         }
+        let entry = lock.packages.get(&format!("node_modules/{name}"))?;
+        enqueue_entry(entry, &mut queue, lock.settings.legacy_peer_deps);
     }
 
     let mut instances: BTreeMap<String, PackageInstance> = BTreeMap::new();
@@ -60,7 +89,7 @@ pub(crate) fn build_fast_instances(
         let key = format!("node_modules/{name}");
         let entry = lock.packages.get(&key)?;
         let version = entry.version.clone()?;
-        if !crate::cache::cache_package_path(name, &version).exists() {
+        if !crate::cache::validate_cached_package(name, &version) {
             return None;
         }
         instances.insert(
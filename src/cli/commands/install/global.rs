@@ -0,0 +1,45 @@
+use super::install_command::{cmd_install, InstallOptions};
+use crate::colors::*;
+use crate::fsutil::global_root;
+use crate::manifest::{self, Manifest};
+use anyhow::{bail, Result};
+use std::path::Path;
+
+/// Install `specs` into the global prefix and print how to put its bin dir on PATH.
+pub(crate) fn cmd_install_global(specs: Vec<String>, options: InstallOptions) -> Result<()> {
+    if specs.is_empty() {
+        bail!("--global requires at least one package to install");
+    }
+    let prefix = global_root(&options.config);
+    std::fs::create_dir_all(&prefix)?;
+    let manifest_path = prefix.join("package.json");
+    if !manifest_path.exists() {
+        manifest::write(&Manifest::new("pacm-global".into(), "0.0.0".into()), &manifest_path)?;
+    }
+
+    // The install pipeline works relative to the current directory, so run it from the prefix.
+    let previous = std::env::current_dir()?;
+    std::env::set_current_dir(&prefix)?;
+    let result = cmd_install(
+        specs,
+        InstallOptions { dev: false, optional: false, no_save: false, ..options },
+    );
+    std::env::set_current_dir(&previous)?;
+    result?;
+
+    let bin_dir = prefix.join("node_modules").join(".bin");
+    println!("{C_GRAY}[pacm]{C_RESET} global bin dir: {}", bin_dir.display());
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} add it to your PATH: {C_DIM}{}{C_RESET}",
+        path_hint(&bin_dir)
+    );
+    Ok(())
+}
+
+fn path_hint(bin_dir: &Path) -> String {
+    if cfg!(windows) {
+        format!("setx PATH \"%PATH%;{}\"", bin_dir.display())
+    } else {
+        format!("export PATH=\"{}:$PATH\"", bin_dir.display())
+    }
+}
@@ -1,339 +1,364 @@
-use super::fast::build_fast_instances;
-use super::manifest_updates::{parse_spec, update_manifest_for_specs};
-use super::node_modules::node_modules_intact;
-use super::platform::platform_supported;
+use super::fast::{build_fast_instances, lock_drift};
+use super::manifest_updates::update_manifest_for_specs;
+use super::node_modules::{node_modules_intact, write_install_state};
 use super::progress::{format_status, ProgressRenderer};
 use super::prune::{
-    cleanup_empty_node_modules_dir, lockfile_has_no_packages, prune_removed_from_lock,
-    prune_unreachable, remove_dirs,
+    cleanup_empty_node_modules_dir, collect_store_garbage, lockfile_has_no_packages,
+    prune_removed_from_lock, prune_unreachable, remove_dirs,
 };
-use crate::cache::{CachedManifest, CasStore, DependencyFingerprint, EnsureParams, StoreEntry};
+use super::signatures::SignatureAudit;
+use super::util::{check_package_manager, validate_dependency_names};
+use crate::cache::{CasStore, DependencyFingerprint, EnsureParams, StoreEntry};
+use crate::cancel::Cancelled;
+use crate::cli::commands::scripts::run_install_scripts;
 use crate::colors::*;
+use crate::config::Config;
 use crate::fetch::Fetcher;
-use crate::installer::{InstallMode, InstallPlanEntry, Installer, PackageInstance};
-use crate::lockfile::{self, Lockfile, PackageEntry};
+use crate::installer::{
+    InstallMode, InstallPlanEntry, Installer, PackageInstance, ProgressCallback,
+};
+use crate::lockfile::{self, Lockfile, PackageSource, PackageVersion};
+use crate::logging::PhaseTimings;
 use crate::manifest;
-use crate::resolver::spec::PackageSpec;
-use crate::workspaces::{discover_workspaces, workspace_dep_satisfies, WorkspaceInfo};
+use crate::resolver::graph::{resolve_graph, write_lock_entry, ResolveOptions};
+use crate::resolver::platform::TargetPlatform;
+use crate::resolver::spec::{parse_spec, validate_package_name, PackageSpec};
+use crate::workspaces::{discover_workspaces, WorkspaceInfo};
 use anyhow::{anyhow, bail, Context, Result};
-use flate2::read::GzDecoder;
-use sha2::{Digest, Sha256};
-use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
-use std::io::Read;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tar::Archive;
 
 use rayon::prelude::*;
 
-fn ensure_lock_entry<'a>(lock: &'a mut Lockfile, name: &str) -> &'a mut PackageEntry {
-    let key = format!("node_modules/{name}");
-    lock.packages.entry(key).or_insert(PackageEntry {
-        version: None,
-        integrity: None,
-        resolved: None,
-        dependencies: BTreeMap::new(),
-        dev_dependencies: BTreeMap::new(),
-        optional_dependencies: BTreeMap::new(),
-        peer_dependencies: BTreeMap::new(),
-        peer_dependencies_meta: BTreeMap::new(),
-        os: Vec::new(),
-        cpu_arch: Vec::new(),
-        store_key: None,
-        content_hash: None,
-        link_mode: None,
-        store_path: None,
-    })
-}
-
-#[allow(clippy::too_many_arguments)]
-fn write_lock_entry(
-    lock: &mut Lockfile,
-    name: &str,
-    version: &str,
-    integrity: Option<&str>,
-    resolved: Option<&str>,
-    dependencies: &BTreeMap<String, String>,
-    dev_dependencies: &BTreeMap<String, String>,
-    optional_dependencies: &BTreeMap<String, String>,
-    peer_dependencies: &BTreeMap<String, String>,
-    peer_meta: &BTreeMap<String, crate::lockfile::PeerMeta>,
-    os: &[String],
-    cpu_arch: &[String],
-) {
-    let entry = ensure_lock_entry(lock, name);
-    entry.version = Some(version.to_string());
-    entry.integrity = integrity.map(|s| s.to_string());
-    entry.resolved = resolved.map(|s| s.to_string());
-    entry.dependencies = dependencies.clone();
-    entry.dev_dependencies = dev_dependencies.clone();
-    entry.optional_dependencies = optional_dependencies.clone();
-    entry.peer_dependencies = peer_dependencies.clone();
-    entry.peer_dependencies_meta = peer_meta.clone();
-    entry.os = os.to_vec();
-    entry.cpu_arch = cpu_arch.to_vec();
-    entry.store_key = None;
-    entry.content_hash = None;
-    entry.link_mode = None;
-    entry.store_path = None;
-}
-
-fn entry_to_instance(name: &str, entry: &PackageEntry) -> PackageInstance {
-    PackageInstance {
-        name: name.to_string(),
-        version: entry.version.clone().unwrap_or_default(),
-        dependencies: entry.dependencies.clone(),
-        optional_dependencies: entry.optional_dependencies.clone(),
-        peer_dependencies: entry.peer_dependencies.clone(),
-        dev_dependencies: entry.dev_dependencies.clone(),
-        source: None,
-    }
+#[derive(Clone, Debug)]
+pub struct InstallOptions {
+    pub dev: bool,
+    pub optional: bool,
+    pub no_save: bool,
+    pub exact: bool,
+    pub prefer_offline: bool,
+    pub offline: bool,
+    pub no_progress: bool,
+    pub link: bool,
+    pub copy: bool,
+    pub lockfile_only: bool,
+    /// `--ignore-scripts`: disable lifecycle scripts for this project from now on, recorded in
+    /// the lockfile.
+    pub ignore_scripts: bool,
+    /// Don't run lifecycle scripts this time without recording anything, for an `ignore-scripts`
+    /// default from `.pacmrc` or `PACM_IGNORE_SCRIPTS`. A per-user setting is not a project one.
+    pub skip_scripts: bool,
+    pub force: bool,
+    pub gc: bool,
+    /// Install straight from the lockfile when it still satisfies the manifest, resolving only
+    /// when it is out of date. On by default; turning it off always resolves.
+    pub prefer_frozen: bool,
+    /// Treat every registry spec as `name@latest`, replacing whatever range the manifest
+    /// already declares.
+    pub latest: bool,
+    /// Skip `node_modules/.bin` shims from now on; recorded in the lockfile like
+    /// `ignore_scripts`.
+    pub no_bin_links: bool,
+    /// Fail instead of warning when `packageManager` names another pacm version or tool.
+    pub strict: bool,
+    /// Run install lifecycle scripts once node_modules is in place instead of only noting them.
+    pub run_scripts: bool,
+    /// Install exactly what the existing lockfile records from the store, failing instead of
+    /// resolving when it is missing, out of date, or references uncached packages (`pacm ci`).
+    pub frozen_lockfile: bool,
+    /// Only follow dependencies this many levels below the manifest (`--depth`). The install is
+    /// incomplete by design, so it always resolves and leaves pacm.lockb untouched.
+    pub depth: Option<usize>,
+    /// Settle on one version that all of a package's dependents accept, even when a newer
+    /// version would match some of them (`--prefer-dedupe`).
+    pub prefer_dedupe: bool,
+    /// `Some(false)` symlinks workspace packages straight to their source directory instead of
+    /// materializing a copy from the store; `Some(true)` switches back. Recorded in the lockfile,
+    /// so `None` keeps whatever the project last chose (`--install-links`).
+    pub install_links: Option<bool>,
+    /// Save dist-tag specs to package.json as the tag (`"pkg": "beta"`) instead of the version
+    /// it resolves to; the lockfile still pins the version (`pacm add --tag`).
+    pub save_tag: bool,
+    /// Filter platform-specific packages for this target instead of the host (`--os`, `--cpu`,
+    /// `--libc`). pacm.lockb describes the host install, so it is left unchanged.
+    pub platform: TargetPlatform,
+    /// Rebuild pacm.lockb from package.json after a merge conflict: always resolve, and start
+    /// from an empty lockfile when the existing one cannot be read (`--merge-lockfile`).
+    pub merge_lockfile: bool,
+    /// Skip comparing downloads against their expected integrity, recording the computed one
+    /// instead (`--no-verify`). Dangerous; for debugging registries with broken metadata.
+    pub no_verify: bool,
+    /// Verify every registry package's signature against the registry's published keys and
+    /// fail on an invalid one (`--audit-signatures`). Always resolves, so the check sees
+    /// registry metadata.
+    pub audit_signatures: bool,
+    /// Settings the command loaded once; the install never reads rc files or the environment
+    /// for them again.
+    pub config: Config,
 }
 
-fn pick_cached_satisfying_manifest(
-    resolver: &crate::resolver::Resolver,
-    name: &str,
-    range: &str,
-) -> Option<(semver::Version, CachedManifest)> {
-    // Only attempt semver selection for registry specs; git/tarball/url ranges should be handled
-    // by the main resolution path.
-    if !matches!(
-        crate::resolver::spec::PackageSpec::parse(range),
-        crate::resolver::spec::PackageSpec::Registry { .. }
-    ) {
-        return None;
-    }
-    let cached_versions = crate::cache::cached_versions(name);
-    if cached_versions.is_empty() {
-        return None;
-    }
-    let mut map: BTreeMap<semver::Version, String> = BTreeMap::new();
-    for v in cached_versions {
-        map.insert(v, String::new());
-    }
-    let picked = resolver.pick_version(&map, range).ok()?;
-    let ver_str = picked.0.to_string();
-    if !crate::cache::cache_package_path(name, &ver_str).exists() {
-        return None;
+impl Default for InstallOptions {
+    fn default() -> Self {
+        Self {
+            dev: false,
+            optional: false,
+            no_save: false,
+            exact: false,
+            prefer_offline: false,
+            offline: false,
+            no_progress: false,
+            link: false,
+            copy: false,
+            lockfile_only: false,
+            ignore_scripts: false,
+            skip_scripts: false,
+            force: false,
+            gc: false,
+            prefer_frozen: true,
+            latest: false,
+            no_bin_links: false,
+            strict: false,
+            run_scripts: false,
+            frozen_lockfile: false,
+            depth: None,
+            prefer_dedupe: false,
+            install_links: None,
+            save_tag: false,
+            platform: TargetPlatform::host(),
+            merge_lockfile: false,
+            no_verify: false,
+            audit_signatures: false,
+            config: Config::default(),
+        }
     }
-    let manifest = crate::cache::read_cached_manifest(name, &ver_str).ok()?;
-    Some((picked.0, manifest))
 }
 
-fn retry_download_into_cache(
-    fetcher: &Fetcher,
-    pd: &PendingDownload,
-    progress: &Arc<Mutex<ProgressRenderer>>,
-    counter: &AtomicUsize,
-    total: usize,
-    no_progress: bool,
-) -> Result<String> {
-    let mut last_err: Option<anyhow::Error> = None;
-    let max_attempts = 3;
-    for attempt in 1..=max_attempts {
-        match download_into_cache(
-            fetcher,
-            &pd.name,
-            &pd.version,
-            &pd.url,
-            pd.integrity_hint.as_deref(),
-            pd.scripts.as_ref(),
-        ) {
-            Ok(integrity) => {
-                if !no_progress {
-                    let done = counter.fetch_add(1, Ordering::SeqCst) + 1;
-                    let mut pr = progress.lock().unwrap();
-                    pr.render(format_status(
-                        "downloading",
-                        &format!("{done}/{total} {name}@{ver}", name = pd.name, ver = pd.version),
-                    ));
-                }
-                return Ok(integrity);
-            }
-            Err(e) => {
-                last_err = Some(e);
-                if attempt < max_attempts {
-                    std::thread::sleep(Duration::from_millis(200 * attempt as u64));
-                    continue;
-                }
-            }
+fn install_deadline_from_env() -> Result<Option<Duration>> {
+    match std::env::var("PACM_INSTALL_DEADLINE") {
+        Ok(raw) if !raw.trim().is_empty() => {
+            let secs: u64 = raw.trim().parse().with_context(|| {
+                format!("invalid PACM_INSTALL_DEADLINE '{raw}' (expected seconds)")
+            })?;
+            Ok(Some(Duration::from_secs(secs)))
         }
+        _ => Ok(None),
     }
-    Err(last_err.unwrap())
 }
 
-#[derive(Clone)]
-struct PendingDownload {
-    name: String,
-    version: String,
-    url: String,
-    integrity_hint: Option<String>,
-    scripts: Option<std::collections::BTreeMap<String, String>>,
+/// Which route an install took; decides how its [`InstallReport`] is summarized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InstallKind {
+    /// Dependencies were resolved and node_modules was written.
+    #[default]
+    Resolved,
+    /// node_modules was written straight from the lockfile without resolving.
+    Frozen,
+    /// Only the lockfile (and store) were updated (`--lockfile-only`).
+    LockfileOnly,
+    /// Nothing changed; node_modules already matched the lockfile.
+    UpToDate,
 }
 
-#[derive(Debug, Clone)]
-struct GithubResolved {
-    tarball_url: String,
-    commit: String,
+/// What an install did, for callers that embed pacm instead of reading its output.
+#[derive(Debug, Clone, Default)]
+pub struct InstallReport {
+    pub kind: InstallKind,
+    /// Root dependencies that were added, with the version they resolved to.
+    pub added: Vec<PackageVersion>,
+    /// Root dependencies that were removed, with the version that was locked.
+    pub removed: Vec<PackageVersion>,
+    /// Packages in the install plan; with `--lockfile-only` they are locked but not written.
+    pub installed: usize,
+    /// Packages fetched from the registry rather than reused from the cache.
+    pub downloaded: usize,
+    /// Packages placed in node_modules by linking from the store.
+    pub linked: usize,
+    /// Packages placed in node_modules by copying.
+    pub copied: usize,
+    /// Every resolved package instance, sorted by name.
+    pub instances: Vec<PackageInstance>,
+    pub duration: Duration,
 }
 
-fn resolve_github_tarball(spec: &crate::resolver::spec::GithubSpec) -> Result<GithubResolved> {
-    #[derive(serde::Deserialize)]
-    struct RepoInfo {
-        default_branch: Option<String>,
+/// Print the usual `pacm install` summary for `report`.
+fn print_report(report: &InstallReport) {
+    let dur = report.duration;
+    let total = report.installed;
+    match report.kind {
+        InstallKind::UpToDate => {
+            println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}no dependency changes{C_RESET}");
+            println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}0 added, 0 removed{C_RESET}");
+            println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}already up to date{C_RESET}");
+            return;
+        }
+        InstallKind::LockfileOnly => {
+            println!(
+                "{C_GRAY}[pacm]{C_RESET} {C_GREEN}lockfile updated{C_RESET} {total} packages ({C_GREEN}{} downloaded{C_RESET}) in {dur:.2?}; node_modules left untouched",
+                report.downloaded
+            );
+            return;
+        }
+        InstallKind::Resolved | InstallKind::Frozen => {}
     }
 
-    #[derive(serde::Deserialize)]
-    struct CommitInfo {
-        sha: String,
+    if report.added.is_empty() && report.removed.is_empty() {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}no dependency changes{C_RESET}");
     }
-
-    let client = crate::fetch::http_client();
-    let base = format!("https://api.github.com/repos/{}/{}", spec.owner, spec.repo);
-    let reference = if let Some(r) = &spec.reference {
-        r.clone()
-    } else {
-        let resp = client.get(&base).send().with_context(|| format!("GET {base}"))?;
-        if resp.status().is_success() {
-            let info: RepoInfo = resp.json()?;
-            info.default_branch.unwrap_or_else(|| "main".to_string())
-        } else {
-            // Fall back to common defaults if API rate limits or errors
-            "main".to_string()
-        }
-    };
-
-    let commit_url = format!("{base}/commits/{reference}");
-    let resp = client.get(&commit_url).send().with_context(|| format!("GET {commit_url}"))?;
-    if !resp.status().is_success() {
-        // Last-resort fallback to master if main/default failed
-        if reference != "master" {
-            let fallback_url = format!("{base}/commits/master");
-            let resp_fb =
-                client.get(&fallback_url).send().with_context(|| format!("GET {fallback_url}"))?;
-            if resp_fb.status().is_success() {
-                let commit: CommitInfo = resp_fb.json()?;
-                let tarball_url = format!(
-                    "https://codeload.github.com/{}/{}/tar.gz/{}",
-                    spec.owner, spec.repo, commit.sha
-                );
-                return Ok(GithubResolved { tarball_url, commit: commit.sha });
+    for (sign, color, list) in [("+", C_GREEN, &report.added), ("-", C_RED, &report.removed)] {
+        for pkg in list {
+            match &pkg.version {
+                Some(ver) => {
+                    println!("{C_GRAY}[pacm]{C_RESET} {color}{sign}{C_RESET} {}@{ver}", pkg.name)
+                }
+                None => println!("{C_GRAY}[pacm]{C_RESET} {color}{sign}{C_RESET} {}", pkg.name),
             }
         }
-        // Try direct tarball for the provided ref even if commit lookup failed (e.g., repo uses
-        // non-main/master default branch or branch protection blocks commit API).
-        let tarball_url = format!(
-            "https://codeload.github.com/{}/{}/tar.gz/{}",
-            spec.owner, spec.repo, reference
+    }
+    println!(
+        "{gray}[pacm]{reset} summary: {green}{add} added{reset}, {red}{removed} removed{reset}",
+        gray = C_GRAY,
+        green = C_GREEN,
+        red = C_RED,
+        add = report.added.len(),
+        removed = report.removed.len(),
+        reset = C_RESET
+    );
+    let (linked_count, copied_count) = (report.linked, report.copied);
+    if report.kind == InstallKind::Frozen {
+        if copied_count == 0 {
+            println!(
+                "{C_GRAY}[pacm]{C_RESET} {C_GREEN}linked{C_RESET} {total} packages (all cached) in {dur:.2?}"
+            );
+        } else {
+            println!(
+                "{C_GRAY}[pacm]{C_RESET} linked {C_GREEN}{linked_count}{C_RESET} packages ({C_DIM}{copied_count}{C_RESET} copied fallback) in {dur:.2?}"
+            );
+        }
+        return;
+    }
+    if copied_count == 0 {
+        println!("{C_GRAY}[pacm]{C_RESET} linking: {C_GREEN}{linked_count}{C_RESET} linked");
+    } else {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} linking: {C_GREEN}{linked_count}{C_RESET} linked, {C_DIM}{copied_count}{C_RESET} copied"
         );
-        return Ok(GithubResolved { tarball_url, commit: reference });
     }
-
-    let commit: CommitInfo = resp.json()?;
-    let tarball_url =
-        format!("https://codeload.github.com/{}/{}/tar.gz/{}", spec.owner, spec.repo, commit.sha);
-    Ok(GithubResolved { tarball_url, commit: commit.sha })
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} {C_GREEN}installed{C_RESET} {total} packages ({C_GREEN}{} downloaded{C_RESET}, {C_DIM}{} reused{C_RESET}) in {dur:.2?}",
+        report.downloaded,
+        total.saturating_sub(report.downloaded)
+    );
 }
 
-fn read_manifest_from_tarball(bytes: &[u8]) -> Result<CachedManifest> {
-    let gz = GzDecoder::new(bytes);
-    let mut ar = Archive::new(gz);
-    for entry in ar.entries()? {
-        let mut entry = entry?;
-        let path = entry.path()?;
-        if path.file_name().map(|n| n == "package.json").unwrap_or(false) {
-            let mut buf = String::new();
-            entry.read_to_string(&mut buf)?;
-            let mf: CachedManifest = serde_json::from_str(&buf)?;
-            return Ok(mf);
-        }
+pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result<()> {
+    let project_root = std::env::current_dir()?;
+    if !project_root.join("package.json").exists() {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_RED}error{C_RESET} no package.json found. Run 'pacm init' first.");
+        return Ok(());
     }
-    anyhow::bail!("package.json not found in tarball")
+    crate::cancel::install_handler();
+    let report = install_project(&project_root, specs, options)?;
+    print_report(&report);
+    Ok(())
 }
 
-fn short_hash(input: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(input.as_bytes());
-    let digest = hasher.finalize();
-    let mut hex = String::with_capacity(64);
-    for byte in digest {
-        let _ = std::fmt::Write::write_fmt(&mut hex, format_args!("{:02x}", byte));
-    }
-    hex.chars().take(8).collect()
+/// Install `specs` (or just the manifest's dependencies) into the project at `project_root` and
+/// report what changed. Progress, warnings and notes are still printed; the final summary is
+/// left to the caller. The current directory is switched to `project_root` for the duration.
+pub fn install_project(
+    project_root: &Path,
+    specs: Vec<String>,
+    options: InstallOptions,
+) -> Result<InstallReport> {
+    let _cwd = CwdGuard::enter(project_root)?;
+    install_in_current_dir(specs, options)
 }
 
-fn append_build(base: &str, build_tag: &str) -> String {
-    if base.contains('+') {
-        format!("{base}.{build_tag}")
-    } else {
-        format!("{base}+{build_tag}")
-    }
+/// Switches the current directory for an install and switches back when dropped, so an error or
+/// a panic partway through never leaves the process in the project directory.
+struct CwdGuard {
+    previous: Option<PathBuf>,
 }
 
-fn write_scripts_sidecar(package: &str, version: &str, scripts: &BTreeMap<String, String>) {
-    if scripts.is_empty() {
-        return;
-    }
-    let sidecar = crate::cache::cache_package_path(package, version).join(".registry-scripts.json");
-    if let Ok(txt) = serde_json::to_string_pretty(scripts) {
-        let _ = std::fs::write(sidecar, txt);
+impl CwdGuard {
+    fn enter(dir: &Path) -> Result<Self> {
+        let previous = std::env::current_dir()?;
+        if previous == dir {
+            return Ok(Self { previous: None });
+        }
+        std::env::set_current_dir(dir).with_context(|| format!("enter {}", dir.display()))?;
+        Ok(Self { previous: Some(previous) })
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
-pub(crate) struct InstallOptions {
-    pub dev: bool,
-    pub optional: bool,
-    pub no_save: bool,
-    pub exact: bool,
-    pub prefer_offline: bool,
-    pub no_progress: bool,
-    pub link: bool,
-    pub copy: bool,
+impl Drop for CwdGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            let _ = std::env::set_current_dir(previous);
+        }
+    }
 }
 
-fn download_into_cache(
-    fetcher: &Fetcher,
-    name: &str,
-    version: &str,
-    url: &str,
-    integrity_hint: Option<&str>,
-    scripts: Option<&std::collections::BTreeMap<String, String>>,
-) -> Result<String> {
-    let bytes = fetcher
-        .download_tarball(url)
-        .with_context(|| format!("download tarball for {name}@{version}"))?;
-    let integrity = crate::cache::ensure_cached_package(name, version, &bytes, integrity_hint)?;
-    // write registry scripts sidecar if provided
-    if let Some(s) = scripts {
-        let cache_path = crate::cache::cache_package_path(name, version);
-        let sidecar = cache_path.join(".registry-scripts.json");
-        if let Ok(txt) = serde_json::to_string_pretty(s) {
-            let _ = std::fs::write(&sidecar, txt);
+/// Run an install, putting package.json back the way it was if Ctrl-C interrupts it after the
+/// manifest was updated for new specs but before the lockfile was written. The lockfile is only
+/// written once linking finishes.
+fn install_in_current_dir(specs: Vec<String>, options: InstallOptions) -> Result<InstallReport> {
+    let manifest_path = std::env::current_dir()?.join("package.json");
+    let original_manifest = std::fs::read(&manifest_path).ok();
+    let result = resolve_and_link(specs, options);
+    if let Err(err) = &result {
+        if err.downcast_ref::<Cancelled>().is_some_and(|c| !c.wrote_lockfile()) {
+            if let Some(bytes) = original_manifest {
+                std::fs::write(&manifest_path, bytes)
+                    .with_context(|| format!("restore {}", manifest_path.display()))?;
+            }
         }
     }
-    Ok(integrity)
+    result
 }
 
-pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result<()> {
+fn resolve_and_link(specs: Vec<String>, options: InstallOptions) -> Result<InstallReport> {
     let InstallOptions {
         dev,
         optional,
         no_save,
-        exact: _exact,
+        exact,
         prefer_offline,
+        offline,
         no_progress,
         link,
         copy,
+        lockfile_only,
+        ignore_scripts,
+        skip_scripts,
+        force,
+        gc,
+        prefer_frozen,
+        latest,
+        no_bin_links,
+        strict,
+        run_scripts,
+        frozen_lockfile,
+        depth,
+        prefer_dedupe,
+        install_links,
+        save_tag,
+        platform,
+        merge_lockfile,
+        no_verify,
+        audit_signatures,
+        config,
     } = options;
+    let deadline = install_deadline_from_env()?.map(|limit| Instant::now() + limit);
     let project_root = std::env::current_dir()?;
     let manifest_path = project_root.join("package.json");
     if !manifest_path.exists() {
-        println!("{C_GRAY}[pacm]{C_RESET} {C_RED}error{C_RESET} no package.json found. Run 'pacm init' first.");
-        return Ok(());
+        bail!("no package.json found. Run 'pacm init' first.");
     }
     let mut manifest = manifest::load(&manifest_path)?;
+    check_package_manager(&manifest, strict)?;
     let workspaces_vec = discover_workspaces(&project_root, &manifest)?;
     let mut workspace_map: BTreeMap<String, WorkspaceInfo> = BTreeMap::new();
     for ws in workspaces_vec {
@@ -341,11 +366,75 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
     }
     let workspace_names: Vec<String> = workspace_map.keys().cloned().collect();
 
-    update_manifest_for_specs(&specs, &mut manifest, &manifest_path, dev, optional, no_save)?;
+    for spec in &specs {
+        let (name, req) = parse_spec(spec);
+        if matches!(PackageSpec::parse(&req), PackageSpec::Registry { .. }) {
+            validate_package_name(&name)?;
+        }
+    }
+    validate_dependency_names(&manifest)?;
+    for ws in workspace_map.values() {
+        validate_dependency_names(&ws.manifest)?;
+    }
+
+    let specs: Vec<String> = if latest {
+        specs
+            .iter()
+            .map(|spec| {
+                let (name, req) = parse_spec(spec);
+                match PackageSpec::parse(&req) {
+                    PackageSpec::Registry { .. } => format!("{name}@latest"),
+                    _ => spec.clone(),
+                }
+            })
+            .collect()
+    } else {
+        specs
+    };
+    // --exact wins over a configured save-prefix.
+    let save_prefix =
+        if exact { String::new() } else { config.save_prefix.clone().unwrap_or_default() };
+    let specs = update_manifest_for_specs(
+        &specs,
+        &mut manifest,
+        &manifest_path,
+        dev,
+        optional,
+        no_save,
+        offline,
+        &save_prefix,
+        save_tag,
+        &config,
+    )?;
+    // package.json keeps its `catalog:` specs; the lockfile and resolver see the real ranges, so
+    // bumping a catalog entry re-resolves like editing the range in place.
+    let catalogs = manifest.clone();
+    catalogs.apply_catalogs(&mut manifest)?;
+    for ws in workspace_map.values_mut() {
+        catalogs.apply_catalogs(&mut ws.manifest)?;
+    }
 
     let lock_path = project_root.join("pacm.lockb");
-    let mut lock = if lock_path.exists() {
-        Lockfile::load_or_default(lock_path.clone())?
+    let mut lock = if frozen_lockfile {
+        if !lock_path.exists() {
+            bail!("no pacm.lockb found; run 'pacm install' to create one");
+        }
+        lockfile::load_strict(&lock_path)?
+    } else if lock_path.exists() {
+        match Lockfile::load_or_default(lock_path.clone()) {
+            Ok(lock) => lock,
+            Err(err) if merge_lockfile => {
+                println!(
+                    "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}note{C_RESET}: pacm.lockb is unreadable ({err}); regenerating it from package.json"
+                );
+                Lockfile::default()
+            }
+            Err(err) => {
+                return Err(err.context(
+                    "read pacm.lockb; if it conflicted in a merge, run 'pacm install --merge-lockfile' to regenerate it",
+                ))
+            }
+        }
     } else {
         let legacy = project_root.join("pacm-lock.json");
         if legacy.exists() {
@@ -358,12 +447,27 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
         }
     };
     let original_lock = lock.clone();
+    if ignore_scripts {
+        // Recorded after the snapshot so a newly set preference counts as a lockfile change.
+        lock.settings.ignore_scripts = true;
+    }
+    if no_bin_links {
+        lock.settings.no_bin_links = true;
+    }
+    if let Some(install_links) = install_links {
+        lock.settings.symlink_workspaces = !install_links;
+    }
+    // Recorded so the lockfile walkers and later installs agree on whether peers are installed;
+    // without a configured value the project keeps what it last resolved with.
+    if let Some(legacy) = config.legacy_peer_deps {
+        lock.settings.legacy_peer_deps = legacy;
+    }
 
     if link && copy {
         bail!("--link and --copy cannot be used together");
     }
     let install_mode = if copy { InstallMode::Copy } else { InstallMode::Link };
-    let store = CasStore::open()?;
+    let store = CasStore::open_with(&config)?;
 
     let old_root_deps: BTreeMap<String, String> = original_lock
         .packages
@@ -395,6 +499,15 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
     let added_root: Vec<String> = new_names.difference(&old_names).cloned().collect();
     let removed_root: Vec<String> = old_names.difference(&new_names).cloned().collect();
 
+    // Lock entries pin dist-tag dependencies, so switching a dependency to a different tag must
+    // drop the old pin or the previous tag's version would be reused.
+    for (name, range) in &new_root_deps {
+        let retagged = old_root_deps.get(name).is_some_and(|old| old != range);
+        if retagged && crate::resolver::looks_like_dist_tag(range) {
+            lock.packages.remove(&format!("node_modules/{name}"));
+        }
+    }
+
     // Determine which workspace packages are actually referenced from any manifest
     let workspace_pkg_names: std::collections::HashSet<String> =
         workspace_map.keys().cloned().collect();
@@ -433,19 +546,64 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
         workspace_folder_paths.insert(ws.relative_path.clone());
     }
 
-    if lock == original_lock
+    // A lockfile whose versions no longer satisfy the manifest ranges must be re-resolved, even
+    // when its root entry and node_modules look unchanged (hand edits, merges, imports).
+    let drift = lock_drift(
+        std::iter::once(&manifest).chain(workspace_map.values().map(|ws| &ws.manifest)),
+        &lock,
+    );
+    if frozen_lockfile && (!drift.is_empty() || !added_root.is_empty() || !removed_root.is_empty())
+    {
+        let mut reasons = drift.clone();
+        reasons.extend(added_root.iter().map(|name| format!("{name} is not locked")));
+        reasons.extend(removed_root.iter().map(|name| format!("{name} is no longer declared")));
+        bail!(
+            "pacm.lockb does not satisfy package.json: {}; run 'pacm install' to update it",
+            reasons.join(", ")
+        );
+    }
+    if !drift.is_empty() {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}warning{C_RESET} lockfile is out of date: {}; resolving",
+            drift.join(", ")
+        );
+    }
+
+    // Installing for another platform picks different packages than the lockfile records.
+    let cross_platform = !platform.is_host();
+    if !force
+        && !frozen_lockfile
+        && !cross_platform
+        && !merge_lockfile
+        && !audit_signatures
+        && drift.is_empty()
+        && lock == original_lock
         && added_root.is_empty()
         && removed_root.is_empty()
-        && node_modules_intact(&manifest, &workspace_names)
+        && node_modules_intact(&manifest, &lock, &workspace_names, store.root())
     {
-        println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}no dependency changes{C_RESET}");
-        println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}0 added, 0 removed{C_RESET}");
-        println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}already up to date{C_RESET}");
-        return Ok(());
+        let instances =
+            build_fast_instances(&manifest, &lock, &workspace_names).unwrap_or_default();
+        return Ok(InstallReport {
+            kind: InstallKind::UpToDate,
+            instances: instances.into_values().collect(),
+            ..InstallReport::default()
+        });
     }
 
-    if specs.is_empty() && added_root.is_empty() {
-        if let Some(instances) = build_fast_instances(&manifest, &lock, &workspace_names) {
+    let frozen_ok = prefer_frozen
+        && drift.is_empty()
+        && depth.is_none()
+        && !cross_platform
+        && !merge_lockfile
+        && !audit_signatures;
+    if frozen_ok && specs.is_empty() && added_root.is_empty() && !lockfile_only && !force {
+        if let Some(mut instances) = build_fast_instances(&manifest, &lock, &workspace_names) {
+            for (name, instance) in instances.iter_mut() {
+                if let Some(ws) = workspace_map.get(name) {
+                    instance.source = Some(ws.dir.clone());
+                }
+            }
             if !removed_root.is_empty() {
                 prune_removed_from_lock(&mut lock, &removed_root);
                 remove_dirs(&removed_root);
@@ -454,7 +612,35 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
                     remove_dirs(&trans_removed);
                 }
             }
-            if let Ok(plan) = build_plan_from_lock(&store, &lock, &instances) {
+            let mut plan = build_plan_from_lock(&store, &project_root, &lock, &instances);
+            if frozen_lockfile {
+                // Every instance is in the package cache, so entries missing from the store
+                // can be rebuilt without resolving.
+                if plan.is_err() {
+                    plan = ensure_store_plan(
+                        &store,
+                        &project_root,
+                        &mut lock,
+                        &instances,
+                        false,
+                        platform,
+                    );
+                }
+                if let Err(err) = &plan {
+                    bail!("{err}; cannot install from the lockfile without resolving");
+                }
+                // Only now is the lockfile known to install, so a clean install cannot fail
+                // after throwing away the existing tree.
+                let node_modules = project_root.join("node_modules");
+                if node_modules.exists() {
+                    std::fs::remove_dir_all(&node_modules)
+                        .with_context(|| format!("remove {}", node_modules.display()))?;
+                    println!(
+                        "{C_GRAY}[pacm]{C_RESET} {C_DIM}removed existing node_modules{C_RESET}"
+                    );
+                }
+            }
+            if let Ok(plan) = plan {
                 let start = Instant::now();
                 let progress = Arc::new(Mutex::new(ProgressRenderer::new()));
                 {
@@ -464,16 +650,10 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
                         "link: using cached store; skipping resolution",
                     ));
                 }
-                let installer = Installer::new(install_mode);
-                let cb = if no_progress {
-                    None
-                } else {
-                    let prog = Arc::clone(&progress);
-                    Some(Arc::new(move |done: usize, total: usize, pkg: &str| {
-                        let mut pr = prog.lock().unwrap();
-                        pr.render(format_status("linking", &format!("{}/{} {}", done, total, pkg)));
-                    }) as Arc<dyn Fn(usize, usize, &str) + Send + Sync>)
-                };
+                let installer = Installer::new(install_mode)
+                    .with_bin_links(!lock.settings.no_bin_links)
+                    .with_workspace_symlinks(lock.settings.symlink_workspaces);
+                let cb = linking_progress(&progress, no_progress);
                 let outcomes = installer.install_with_progress(
                     &project_root,
                     &plan,
@@ -486,1104 +666,106 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
                     let mut pr = progress.lock().unwrap();
                     pr.finish();
                 }
+                crate::cancel::check_at(Cancelled::AfterLinking)?;
                 lockfile::write(&lock, lock_path.clone())?;
                 if lockfile_has_no_packages(&lock) {
                     let _ = std::fs::remove_file(&lock_path);
                 }
                 cleanup_empty_node_modules_dir();
-                let dur = start.elapsed();
-                if added_root.is_empty() && removed_root.is_empty() {
-                    println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}no dependency changes{C_RESET}");
-                }
-                for r in &removed_root {
-                    if let Some(ver) = original_lock
-                        .packages
-                        .get(&format!("node_modules/{r}"))
-                        .and_then(|e| e.version.as_ref())
-                    {
-                        println!("{C_GRAY}[pacm]{C_RESET} {C_RED}-{C_RESET} {r}@{ver}");
-                    } else {
-                        println!("{C_GRAY}[pacm]{C_RESET} {C_RED}-{C_RESET} {r}");
-                    }
+                record_install_state(install_mode, &store)?;
+                if gc {
+                    collect_store_garbage(&store, &project_root, &original_lock, &lock)?;
                 }
-                let total = plan.len();
-                println!(
-                    "{gray}[pacm]{reset} summary: {green}0 added{reset}, {red}{removed} removed{reset}",
-                    gray = C_GRAY,
-                    green = C_GREEN,
-                    red = C_RED,
-                    removed = removed_root.len(),
-                    reset = C_RESET
-                );
-                let linked_count =
-                    outcomes.iter().filter(|o| o.link_mode == InstallMode::Link).count();
-                let copied_count = total.saturating_sub(linked_count);
-                if copied_count == 0 {
-                    println!(
-                        "{C_GRAY}[pacm]{C_RESET} {C_GREEN}linked{C_RESET} {total} packages (all cached) in {dur:.2?}"
-                    );
-                } else {
-                    println!(
-                        "{C_GRAY}[pacm]{C_RESET} linked {C_GREEN}{linked_count}{C_RESET} packages ({C_DIM}{copied_count}{C_RESET} copied fallback) in {dur:.2?}"
-                    );
+                let duration = start.elapsed();
+                let linked = outcomes.iter().filter(|o| o.link_mode == InstallMode::Link).count();
+                if run_scripts && !lock.settings.ignore_scripts && !skip_scripts {
+                    crate::cancel::check_at(Cancelled::BeforeScripts)?;
+                    run_install_scripts(&project_root, &lock, instances.keys().cloned().collect())?;
                 }
-                return Ok(());
+                return Ok(InstallReport {
+                    kind: InstallKind::Frozen,
+                    added: Vec::new(),
+                    removed: locked_versions(&original_lock, &removed_root),
+                    installed: plan.len(),
+                    downloaded: 0,
+                    linked,
+                    copied: plan.len().saturating_sub(linked),
+                    instances: instances.into_values().collect(),
+                    duration,
+                });
             }
         }
     }
 
-    let registry_override = std::env::var("PACM_REGISTRY").ok();
-    let fetcher = Fetcher::new(registry_override)?;
-    let resolver = crate::resolver::Resolver::new();
-
-    #[derive(Clone)]
-    struct Task {
-        name: String,
-        range: String,
-        optional_root: bool,
+    if frozen_lockfile {
+        bail!("pacm.lockb is incomplete for package.json; run 'pacm install' to update it");
     }
 
-    let mut queue: VecDeque<Task> = VecDeque::new();
-    for ws in workspace_map.values() {
-        queue.push_back(Task {
-            name: ws.name.clone(),
-            range: format!("workspace:{}", ws.version),
-            optional_root: false,
-        });
-        for (n, r) in &ws.manifest.dependencies {
-            queue.push_back(Task { name: n.clone(), range: r.clone(), optional_root: false });
-        }
-        for (n, r) in &ws.manifest.dev_dependencies {
-            queue.push_back(Task { name: n.clone(), range: r.clone(), optional_root: false });
-        }
-        for (n, r) in &ws.manifest.optional_dependencies {
-            queue.push_back(Task { name: n.clone(), range: r.clone(), optional_root: true });
-        }
-    }
-    if specs.is_empty() {
-        for (n, r) in &manifest.dependencies {
-            queue.push_back(Task { name: n.clone(), range: r.clone(), optional_root: false });
-        }
-        for (n, r) in &manifest.dev_dependencies {
-            queue.push_back(Task { name: n.clone(), range: r.clone(), optional_root: false });
-        }
-        for (n, r) in &manifest.optional_dependencies {
-            queue.push_back(Task { name: n.clone(), range: r.clone(), optional_root: true });
-        }
-    } else {
-        for spec in &specs {
-            let (name, req) = parse_spec(spec);
-            queue.push_back(Task { name, range: req, optional_root: optional });
-        }
+    if cross_platform {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}warning{C_RESET} installing for {platform} instead of this host ({host}); pacm.lockb is left unchanged",
+            host = TargetPlatform::host()
+        );
     }
-
-    let mut visited_name_version: HashSet<(String, String)> = HashSet::new();
-    let start = Instant::now();
-    let mut installed_count = 0usize;
-    let progress = Arc::new(Mutex::new(ProgressRenderer::new()));
-    let mut pending_downloads: Vec<PendingDownload> = Vec::new();
-    let mut pending_set: HashSet<(String, String)> = HashSet::new();
-
-    let mut instances: BTreeMap<String, PackageInstance> = BTreeMap::new();
-
-    while let Some(Task { name, range, optional_root }) = queue.pop_front() {
-        if let Some(ws) = workspace_map.get(&name) {
-            let ws_version = ws.manifest.version.clone();
-            if !workspace_dep_satisfies(&range, &ws_version) {
-                if optional_root {
-                    continue;
-                }
-                bail!("workspace {name}@{ws_version} does not satisfy range {range}");
-            }
-            if visited_name_version.contains(&(name.clone(), ws_version.clone())) {
-                continue;
-            }
-            let package_os = ws.manifest.os.clone();
-            let package_cpu = ws.manifest.cpu_arch.clone();
-            let platform_ok = platform_supported(&package_os, &package_cpu);
-            let resolved_hint = Some(format!("workspace:{}", ws.relative_path));
-            if !platform_ok {
-                if optional_root {
-                    write_lock_entry(
-                        &mut lock,
-                        &name,
-                        &ws_version,
-                        None,
-                        resolved_hint.as_deref(),
-                        &ws.manifest.dependencies,
-                        &ws.manifest.dev_dependencies,
-                        &ws.manifest.optional_dependencies,
-                        &ws.manifest.peer_dependencies,
-                        &BTreeMap::new(),
-                        &package_os,
-                        &package_cpu,
-                    );
-                    visited_name_version.insert((name.clone(), ws_version.clone()));
-                    continue;
-                } else {
-                    bail!("workspace {name}@{ws_version} is not supported on this platform");
-                }
-            }
-
-            write_lock_entry(
-                &mut lock,
-                &name,
-                &ws_version,
-                None,
-                resolved_hint.as_deref(),
-                &ws.manifest.dependencies,
-                &ws.manifest.dev_dependencies,
-                &ws.manifest.optional_dependencies,
-                &ws.manifest.peer_dependencies,
-                &BTreeMap::new(),
-                &package_os,
-                &package_cpu,
-            );
-            instances.insert(
-                name.clone(),
-                PackageInstance {
-                    name: name.clone(),
-                    version: ws_version.clone(),
-                    dependencies: ws.manifest.dependencies.clone(),
-                    optional_dependencies: ws.manifest.optional_dependencies.clone(),
-                    peer_dependencies: ws.manifest.peer_dependencies.clone(),
-                    dev_dependencies: ws.manifest.dev_dependencies.clone(),
-                    source: Some(ws.dir.clone()),
-                },
-            );
-            visited_name_version.insert((name.clone(), ws_version.clone()));
-
-            let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
-            for (dn, dr) in ws.manifest.dependencies.iter() {
-                to_enqueue.push((dn.clone(), dr.clone(), false));
-            }
-            for (dn, dr) in ws.manifest.dev_dependencies.iter() {
-                to_enqueue.push((dn.clone(), dr.clone(), false));
-            }
-            for (dn, dr) in ws.manifest.optional_dependencies.iter() {
-                to_enqueue.push((dn.clone(), dr.clone(), true));
-            }
-            for (dn, dr) in ws.manifest.peer_dependencies.iter() {
-                to_enqueue.push((dn.clone(), dr.clone(), false));
-            }
-            for (dn, dr, optflag) in to_enqueue {
-                queue.push_back(Task { name: dn, range: dr, optional_root: optflag });
-            }
-            continue;
-        }
-
-        // Fast path: reuse an existing lock entry if it still satisfies the requested range and the
-        // package is already cached (or has a resolved URL we can download without re-resolving).
-        if matches!(PackageSpec::parse(&range), PackageSpec::Registry { .. }) {
-            let lock_key = format!("node_modules/{name}");
-            if let Some(lock_entry) = lock.packages.get(&lock_key) {
-                if let Some(ver_str) = &lock_entry.version {
-                    if let Ok(ver) = semver::Version::parse(ver_str) {
-                        let matches_range = match crate::resolver::version_satisfies(&range, &ver) {
-                            Ok(m) => m,
-                            Err(e) => {
-                                if optional_root {
-                                    false
-                                } else {
-                                    return Err(e);
-                                }
-                            }
-                        };
-                        if matches_range {
-                            let platform_ok =
-                                platform_supported(&lock_entry.os, &lock_entry.cpu_arch);
-                            if !platform_ok {
-                                if optional_root {
-                                    visited_name_version.insert((name.clone(), ver_str.clone()));
-                                    continue;
-                                } else {
-                                    bail!("{name}@{ver_str} is not supported on this platform");
-                                }
-                            }
-
-                            let cache_exists =
-                                crate::cache::cache_package_path(&name, ver_str).exists();
-                            let mut queued_download = false;
-                            if !cache_exists {
-                                if prefer_offline {
-                                    if optional_root {
-                                        visited_name_version
-                                            .insert((name.clone(), ver_str.clone()));
-                                        continue;
-                                    }
-                                    bail!(
-                                        "{name}@{ver_str} not in cache and --prefer-offline is set"
-                                    );
-                                }
-                                if let Some(url) = &lock_entry.resolved {
-                                    if !no_progress {
-                                        let mut pr = progress.lock().unwrap();
-                                        pr.render(format_status(
-                                            "queued",
-                                            &format!("download {name}@{ver_str} (lock)"),
-                                        ));
-                                    }
-                                    let key = (name.clone(), ver_str.clone());
-                                    if pending_set.insert(key) {
-                                        pending_downloads.push(PendingDownload {
-                                            name: name.clone(),
-                                            version: ver_str.clone(),
-                                            url: url.clone(),
-                                            integrity_hint: lock_entry.integrity.clone(),
-                                            scripts: None,
-                                        });
-                                        queued_download = true;
-                                    } else {
-                                        queued_download = true;
-                                    }
-                                }
-                            }
-
-                            if cache_exists || queued_download {
-                                if !no_progress {
-                                    let mut pr = progress.lock().unwrap();
-                                    pr.render(format_status(
-                                        "fast",
-                                        &format!("reuse {name}@{ver_str}"),
-                                    ));
-                                }
-                                instances
-                                    .insert(name.clone(), entry_to_instance(&name, lock_entry));
-                                visited_name_version.insert((name.clone(), ver_str.clone()));
-
-                                let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
-                                for (dn, dr) in lock_entry.dependencies.iter() {
-                                    to_enqueue.push((dn.clone(), dr.clone(), optional_root));
-                                }
-                                for (dn, dr) in lock_entry.optional_dependencies.iter() {
-                                    to_enqueue.push((dn.clone(), dr.clone(), true));
-                                }
-                                for (dn, dr) in lock_entry.peer_dependencies.iter() {
-                                    let is_optional = lock_entry
-                                        .peer_dependencies_meta
-                                        .get(dn)
-                                        .map(|m| m.optional)
-                                        .unwrap_or(false);
-                                    if !is_optional {
-                                        to_enqueue.push((dn.clone(), dr.clone(), false));
-                                    }
-                                }
-                                for (dn, dr, optflag) in to_enqueue {
-                                    queue.push_back(Task {
-                                        name: dn,
-                                        range: dr,
-                                        optional_root: optflag,
-                                    });
-                                }
-                                continue;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        // Cached store fast path: if a cached package satisfies the range, reuse it without
-        // touching the network. Helps for optional deps and repeated installs.
-        if let Some((picked_ver, cached_mf)) =
-            pick_cached_satisfying_manifest(&resolver, &name, &range)
-        {
-            let picked_version = picked_ver.to_string();
-            if visited_name_version.contains(&(name.clone(), picked_version.clone())) {
-                continue;
-            }
-            let package_os = cached_mf.os.clone();
-            let package_cpu = cached_mf.cpu_arch.clone();
-            let peer_meta_map: BTreeMap<String, crate::lockfile::PeerMeta> = cached_mf
-                .peer_dependencies_meta
-                .iter()
-                .map(|(k, v)| (k.clone(), crate::lockfile::PeerMeta { optional: v.optional }))
-                .collect();
-            let platform_ok = platform_supported(&package_os, &package_cpu);
-            if !platform_ok {
-                if optional_root {
-                    // Record entry in lockfile even when optional package is not
-                    // supported on this platform so tests can see the declared
-                    // version and platform metadata (store_key will remain None).
-                    write_lock_entry(
-                        &mut lock,
-                        &name,
-                        &picked_version,
-                        None,
-                        None,
-                        &cached_mf.dependencies,
-                        &cached_mf.dev_dependencies,
-                        &cached_mf.optional_dependencies,
-                        &cached_mf.peer_dependencies,
-                        &peer_meta_map,
-                        &package_os,
-                        &package_cpu,
-                    );
-                    visited_name_version.insert((name.clone(), picked_version.clone()));
-                    continue;
-                } else {
-                    bail!("{name}@{picked_version} is not supported on this platform");
-                }
-            }
-
-            write_lock_entry(
-                &mut lock,
-                &name,
-                &picked_version,
-                None,
-                None,
-                &cached_mf.dependencies,
-                &cached_mf.dev_dependencies,
-                &cached_mf.optional_dependencies,
-                &cached_mf.peer_dependencies,
-                &peer_meta_map,
-                &package_os,
-                &package_cpu,
-            );
-
-            instances.insert(
-                name.clone(),
-                PackageInstance {
-                    name: name.clone(),
-                    version: picked_version.clone(),
-                    dependencies: cached_mf.dependencies.clone(),
-                    optional_dependencies: cached_mf.optional_dependencies.clone(),
-                    peer_dependencies: cached_mf.peer_dependencies.clone(),
-                    dev_dependencies: cached_mf.dev_dependencies.clone(),
-                    source: None,
-                },
-            );
-            visited_name_version.insert((name.clone(), picked_version.clone()));
-
-            let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
-            for (dn, dr) in cached_mf.dependencies.into_iter() {
-                to_enqueue.push((dn, dr, optional_root));
-            }
-            for (dn, dr) in cached_mf.optional_dependencies.into_iter() {
-                to_enqueue.push((dn, dr, true));
-            }
-            for (dn, dr) in cached_mf.peer_dependencies.into_iter() {
-                let is_optional_peer = peer_meta_map.get(&dn).map(|m| m.optional).unwrap_or(false);
-                if !is_optional_peer {
-                    to_enqueue.push((dn, dr, false));
-                }
-            }
-            for (dn, dr, optflag) in to_enqueue {
-                queue.push_back(Task { name: dn, range: dr, optional_root: optflag });
-            }
-            continue;
-        }
-
-        if !no_progress {
-            let mut pr = progress.lock().unwrap();
-            pr.render(format_status("resolving", &format!("{name}@{range}")));
-        }
-
-        let spec_kind = PackageSpec::parse(&range);
-
-        if let PackageSpec::Github(gh_spec) = &spec_kind {
-            if !no_progress {
-                let mut pr = progress.lock().unwrap();
-                pr.render(format_status("resolving", &format!("{name} (github)")));
-            }
-
-            let resolved = match resolve_github_tarball(gh_spec) {
-                Ok(r) => r,
-                Err(e) => {
-                    if optional_root {
-                        continue;
-                    }
-                    return Err(e);
-                }
-            };
-
-            let bytes = match fetcher.download_tarball(&resolved.tarball_url) {
-                Ok(b) => b,
-                Err(e) => {
-                    if optional_root {
-                        continue;
-                    }
-                    return Err(e);
-                }
-            };
-
-            let manifest_from_tar = match read_manifest_from_tarball(&bytes) {
-                Ok(mf) => mf,
-                Err(e) => {
-                    if optional_root {
-                        continue;
-                    }
-                    return Err(e);
-                }
-            };
-
-            let base_version = manifest_from_tar.version.clone().unwrap_or_else(|| "0.0.0".into());
-            let short = resolved.commit.chars().take(8).collect::<String>();
-            let picked_version = append_build(&base_version, &format!("git.{short}"));
-            let cache_exists = crate::cache::cache_package_path(&name, &picked_version).exists();
-            let integrity_for_entry_string =
-                match crate::cache::ensure_cached_package(&name, &picked_version, &bytes, None) {
-                    Ok(i) => Some(i),
-                    Err(e) => {
-                        if optional_root {
-                            continue;
-                        }
-                        return Err(e);
-                    }
-                };
-
-            write_scripts_sidecar(&name, &picked_version, &manifest_from_tar.scripts);
-
-            let package_os = manifest_from_tar.os.clone();
-            let package_cpu = manifest_from_tar.cpu_arch.clone();
-            let platform_ok = platform_supported(&package_os, &package_cpu);
-            if !platform_ok {
-                if optional_root {
-                    write_lock_entry(
-                        &mut lock,
-                        &name,
-                        &picked_version,
-                        integrity_for_entry_string.as_deref(),
-                        Some(resolved.tarball_url.as_str()),
-                        &manifest_from_tar.dependencies,
-                        &BTreeMap::new(),
-                        &manifest_from_tar.optional_dependencies,
-                        &manifest_from_tar.peer_dependencies,
-                        &manifest_from_tar
-                            .peer_dependencies_meta
-                            .into_iter()
-                            .map(|(k, v)| (k, crate::lockfile::PeerMeta { optional: v.optional }))
-                            .collect(),
-                        &package_os,
-                        &package_cpu,
-                    );
-                    visited_name_version.insert((name.clone(), picked_version.clone()));
-                    continue;
-                }
-                bail!("{}@{} is not supported on this platform", name, picked_version);
-            }
-
-            let peer_meta_map: BTreeMap<String, crate::lockfile::PeerMeta> = manifest_from_tar
-                .peer_dependencies_meta
-                .iter()
-                .map(|(k, v)| (k.clone(), crate::lockfile::PeerMeta { optional: v.optional }))
-                .collect();
-
-            write_lock_entry(
-                &mut lock,
-                &name,
-                &picked_version,
-                integrity_for_entry_string.as_deref(),
-                Some(resolved.tarball_url.as_str()),
-                &manifest_from_tar.dependencies,
-                &BTreeMap::new(),
-                &manifest_from_tar.optional_dependencies,
-                &manifest_from_tar.peer_dependencies,
-                &peer_meta_map,
-                &package_os,
-                &package_cpu,
-            );
-
-            instances.insert(
-                name.clone(),
-                PackageInstance {
-                    name: name.clone(),
-                    version: picked_version.clone(),
-                    dependencies: manifest_from_tar.dependencies.clone(),
-                    optional_dependencies: manifest_from_tar.optional_dependencies.clone(),
-                    peer_dependencies: manifest_from_tar.peer_dependencies.clone(),
-                    dev_dependencies: BTreeMap::new(),
-                    source: None,
-                },
-            );
-            visited_name_version.insert((name.clone(), picked_version.clone()));
-            if !cache_exists {
-                installed_count += 1;
-            }
-
-            let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
-            for (dn, dr) in manifest_from_tar.dependencies.into_iter() {
-                to_enqueue.push((dn, dr, optional_root));
-            }
-            for (dn, dr) in manifest_from_tar.optional_dependencies.into_iter() {
-                to_enqueue.push((dn, dr, true));
-            }
-            for (dn, dr) in manifest_from_tar.peer_dependencies.into_iter() {
-                let is_optional_peer = peer_meta_map.get(&dn).map(|m| m.optional).unwrap_or(false);
-                if !is_optional_peer {
-                    to_enqueue.push((dn, dr, false));
-                }
-            }
-            for (dn, dr, optflag) in to_enqueue {
-                queue.push_back(Task { name: dn, range: dr, optional_root: optflag });
-            }
-            continue;
-        }
-
-        if let PackageSpec::Tarball { url } = &spec_kind {
-            if !no_progress {
-                let mut pr = progress.lock().unwrap();
-                pr.render(format_status("resolving", &format!("{name} (tarball)")));
-            }
-
-            let bytes = match fetcher.download_tarball(url) {
-                Ok(b) => b,
-                Err(e) => {
-                    if optional_root {
-                        continue;
-                    }
-                    return Err(e);
-                }
-            };
-
-            let manifest_from_tar = match read_manifest_from_tarball(&bytes) {
-                Ok(mf) => mf,
-                Err(e) => {
-                    if optional_root {
-                        continue;
-                    }
-                    return Err(e);
-                }
-            };
-
-            let base_version = manifest_from_tar.version.clone().unwrap_or_else(|| "0.0.0".into());
-            let version_tag = append_build(&base_version, &format!("remote.{}", short_hash(url)));
-            let cache_exists = crate::cache::cache_package_path(&name, &version_tag).exists();
-            let integrity_for_entry_string =
-                match crate::cache::ensure_cached_package(&name, &version_tag, &bytes, None) {
-                    Ok(i) => Some(i),
-                    Err(e) => {
-                        if optional_root {
-                            continue;
-                        }
-                        return Err(e);
-                    }
-                };
-            write_scripts_sidecar(&name, &version_tag, &manifest_from_tar.scripts);
-
-            let package_os = manifest_from_tar.os.clone();
-            let package_cpu = manifest_from_tar.cpu_arch.clone();
-            let platform_ok = platform_supported(&package_os, &package_cpu);
-            if !platform_ok {
-                if optional_root {
-                    write_lock_entry(
-                        &mut lock,
-                        &name,
-                        &version_tag,
-                        integrity_for_entry_string.as_deref(),
-                        Some(url.as_str()),
-                        &manifest_from_tar.dependencies,
-                        &BTreeMap::new(),
-                        &manifest_from_tar.optional_dependencies,
-                        &manifest_from_tar.peer_dependencies,
-                        &manifest_from_tar
-                            .peer_dependencies_meta
-                            .into_iter()
-                            .map(|(k, v)| (k, crate::lockfile::PeerMeta { optional: v.optional }))
-                            .collect(),
-                        &package_os,
-                        &package_cpu,
-                    );
-                    visited_name_version.insert((name.clone(), version_tag.clone()));
-                    continue;
-                }
-                bail!("{}@{} is not supported on this platform", name, version_tag);
-            }
-
-            let peer_meta_map: BTreeMap<String, crate::lockfile::PeerMeta> = manifest_from_tar
-                .peer_dependencies_meta
-                .iter()
-                .map(|(k, v)| (k.clone(), crate::lockfile::PeerMeta { optional: v.optional }))
-                .collect();
-
-            write_lock_entry(
-                &mut lock,
-                &name,
-                &version_tag,
-                integrity_for_entry_string.as_deref(),
-                Some(url.as_str()),
-                &manifest_from_tar.dependencies,
-                &BTreeMap::new(),
-                &manifest_from_tar.optional_dependencies,
-                &manifest_from_tar.peer_dependencies,
-                &peer_meta_map,
-                &package_os,
-                &package_cpu,
-            );
-
-            instances.insert(
-                name.clone(),
-                PackageInstance {
-                    name: name.clone(),
-                    version: version_tag.clone(),
-                    dependencies: manifest_from_tar.dependencies.clone(),
-                    optional_dependencies: manifest_from_tar.optional_dependencies.clone(),
-                    peer_dependencies: manifest_from_tar.peer_dependencies.clone(),
-                    dev_dependencies: BTreeMap::new(),
-                    source: None,
-                },
-            );
-            visited_name_version.insert((name.clone(), version_tag.clone()));
-            if !cache_exists {
-                installed_count += 1;
-            }
-
-            let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
-            for (dn, dr) in manifest_from_tar.dependencies.into_iter() {
-                to_enqueue.push((dn, dr, optional_root));
-            }
-            for (dn, dr) in manifest_from_tar.optional_dependencies.into_iter() {
-                to_enqueue.push((dn, dr, true));
-            }
-            for (dn, dr) in manifest_from_tar.peer_dependencies.into_iter() {
-                let is_optional_peer = peer_meta_map.get(&dn).map(|m| m.optional).unwrap_or(false);
-                if !is_optional_peer {
-                    to_enqueue.push((dn, dr, false));
-                }
-            }
-            for (dn, dr, optflag) in to_enqueue {
-                queue.push_back(Task { name: dn, range: dr, optional_root: optflag });
-            }
-            continue;
-        }
-
-        let range = match spec_kind {
-            PackageSpec::Registry { range } => range,
-            _ => range,
-        };
-
-        let picked_result: anyhow::Result<(semver::Version, String)> = (|| {
-            let cached = crate::cache::cached_versions(&name);
-            let canon = crate::resolver::canonicalize_npm_range(&range);
-            let parsed_req = semver::VersionReq::parse(&canon).ok();
-            let looks_like_tag =
-                !range.contains(' ') && !range.contains("||") && !range.contains(',');
-            let is_tag_spec = parsed_req.is_none()
-                && canon != "*"
-                && !range.eq_ignore_ascii_case("latest")
-                && looks_like_tag;
-            if is_tag_spec {
-                if prefer_offline {
-                    bail!("cannot resolve dist-tag '{range}' for {name} offline");
-                }
-                let meta = fetcher
-                    .package_metadata(&name)
-                    .with_context(|| format!("fetch metadata for {name}"))?;
-                if let Some(tags) = &meta.dist_tags {
-                    if let Some(ver_s) = tags.get(&range) {
-                        let ver = semver::Version::parse(ver_s).with_context(|| {
-                            format!("invalid version '{ver_s}' for tag '{range}'")
-                        })?;
-                        let tar = meta
-                            .versions
-                            .get(ver_s)
-                            .map(|v| v.dist.tarball.clone())
-                            .unwrap_or_default();
-                        Ok((ver, tar))
-                    } else {
-                        bail!("unknown dist-tag '{range}' for {name}");
-                    }
-                } else {
-                    bail!("no dist-tags available for {name}");
-                }
-            } else {
-                if range.contains("||") || canon.contains("||") {
-                    let mut map: BTreeMap<semver::Version, String> = BTreeMap::new();
-                    for v in cached.into_iter() {
-                        map.insert(v, String::new());
-                    }
-                    if !map.is_empty() {
-                        if let Ok((ver, _)) = resolver.pick_version(&map, &range) {
-                            return Ok((ver, String::new()));
-                        }
-                    }
-                } else {
-                    let req = if canon == "*" {
-                        semver::VersionReq::STAR
-                    } else {
-                        parsed_req.unwrap_or(semver::VersionReq::STAR)
-                    };
-                    if let Some(ver) = crate::cache::cached_versions(&name)
-                        .into_iter()
-                        .find(|candidate| req.matches(candidate))
-                    {
-                        return Ok((ver.clone(), String::new()));
-                    }
-                }
-                let meta = fetcher
-                    .package_metadata(&name)
-                    .with_context(|| format!("fetch metadata for {name}"))?;
-                if range.eq_ignore_ascii_case("latest") {
-                    if let Some(tags) = &meta.dist_tags {
-                        if let Some(ver_s) = tags.get("latest") {
-                            let ver = semver::Version::parse(ver_s)?;
-                            let tar = meta
-                                .versions
-                                .get(ver_s)
-                                .map(|v| v.dist.tarball.clone())
-                                .unwrap_or_default();
-                            Ok((ver, tar))
-                        } else {
-                            let version_map = crate::resolver::map_versions(&meta);
-                            resolver.pick_version(&version_map, "*")
-                        }
-                    } else {
-                        let version_map = crate::resolver::map_versions(&meta);
-                        resolver.pick_version(&version_map, "*")
-                    }
-                } else {
-                    let version_map = crate::resolver::map_versions(&meta);
-                    resolver.pick_version(&version_map, &range)
-                }
-            }
-        })();
-
-        let (picked_ver, tarball_url) = match picked_result {
-            Ok(v) => v,
-            Err(e) => {
-                if optional_root {
-                    if !no_progress {
-                        let mut pr = progress.lock().unwrap();
-                        pr.render(format_status(
-                            "fast",
-                            &format!("skip optional {name} (resolve failed)"),
-                        ));
-                    }
-                    continue;
-                }
-                return Err(e);
-            }
-        };
-
-        let picked_version = picked_ver.to_string();
-        if visited_name_version.contains(&(name.clone(), picked_version.clone())) {
-            continue;
-        }
-
-        let mut package_os: Vec<String> = Vec::new();
-        let mut package_cpu: Vec<String> = Vec::new();
-        #[allow(clippy::type_complexity)]
-        let (integrity_owned, dep_map, opt_map, peer_map, peer_meta_map, resolved_url, scripts_map): (
-            Option<String>,
-            BTreeMap<String, String>,
-            BTreeMap<String, String>,
-            BTreeMap<String, String>,
-            BTreeMap<String, crate::lockfile::PeerMeta>,
-            Option<String>,
-            Option<std::collections::BTreeMap<String, String>>,
-        ) = if tarball_url.is_empty() {
-            match crate::cache::read_cached_manifest(&name, &picked_version) {
-                Ok(mut cached_mf) => {
-                    package_os = std::mem::take(&mut cached_mf.os);
-                    package_cpu = std::mem::take(&mut cached_mf.cpu_arch);
-                    // Try to fetch registry metadata for scripts if possible (don't if prefer_offline)
-                    let scripts = if !prefer_offline {
-                        match fetcher.package_version_metadata(&name, &picked_version) {
-                            Ok(vm) => vm.scripts.clone().into_iter().collect::<std::collections::BTreeMap<_, _>>(),
-                            Err(_) => std::collections::BTreeMap::new(),
-                        }
-                    } else {
-                        std::collections::BTreeMap::new()
-                    };
-                    (
-                        None,
-                        cached_mf.dependencies.into_iter().collect(),
-                        cached_mf
-                            .optional_dependencies
-                            .into_iter()
-                            .filter(|(n, _)| {
-                                if let Some(ver) = lock
-                                    .packages
-                                    .get(&format!("node_modules/{n}"))
-                                    .and_then(|e| e.version.clone())
-                                {
-                                    if let Ok(m) = crate::cache::read_cached_manifest(n, &ver) {
-                                        return platform_supported(&m.os, &m.cpu_arch);
-                                    }
-                                }
-                                true
-                            })
-                            .collect(),
-                        cached_mf.peer_dependencies.into_iter().collect(),
-                        cached_mf
-                            .peer_dependencies_meta
-                            .into_iter()
-                            .map(|(k, v)| (k, crate::lockfile::PeerMeta { optional: v.optional }))
-                            .collect(),
-                        None,
-                        Some(scripts),
-                    )
-                }
-                Err(e) => {
-                    if optional_root {
-                        (
-                            None,
-                            BTreeMap::new(),
-                            BTreeMap::new(),
-                            BTreeMap::new(),
-                            BTreeMap::new(),
-                            None,
-                            None,
-                        )
-                    } else {
-                        return Err(e);
-                    }
-                }
-            }
-        } else {
-            let meta2 = match fetcher
-                .package_metadata(&name)
-                .with_context(|| format!("fetch metadata for {name}"))
-            {
-                Ok(m) => m,
-                Err(e) => {
-                    if optional_root {
-                        continue;
-                    } else {
-                        return Err(e);
-                    }
-                }
-            };
-            let version_meta = match meta2.versions.get(&picked_version) {
-                Some(v) => v,
-                None => {
-                    if optional_root {
-                        continue;
-                    } else {
-                        anyhow::bail!("version metadata missing for {name}@{picked_ver}");
-                    }
-                }
-            };
-            package_os = version_meta.os.clone();
-            package_cpu = version_meta.cpu_arch.clone();
-            let integrity_owned = version_meta.dist.integrity.clone();
-            let mut dm = BTreeMap::new();
-            for (dn, dr) in &version_meta.dependencies {
-                dm.insert(dn.clone(), dr.clone());
-            }
-            let mut om = BTreeMap::new();
-            for (dn, dr) in &version_meta.optional_dependencies {
-                om.insert(dn.clone(), dr.clone());
-            }
-            let mut pm = BTreeMap::new();
-            for (dn, dr) in &version_meta.peer_dependencies {
-                pm.insert(dn.clone(), dr.clone());
-            }
-            let mut pmm = BTreeMap::new();
-            for (n, m) in &version_meta.peer_dependencies_meta {
-                pmm.insert(n.clone(), crate::lockfile::PeerMeta { optional: m.optional });
-            }
-            (integrity_owned, dm, om, pm, pmm, Some(version_meta.dist.tarball.clone()), Some(version_meta.scripts.clone()))
-        };
-
-        let resolved_for_lock = resolved_url.clone().or_else(|| {
-            if !tarball_url.is_empty() {
-                Some(tarball_url.clone())
-            } else {
-                None
-            }
-        });
-
-        let platform_ok = platform_supported(&package_os, &package_cpu);
-        if !platform_ok && optional_root {
-            if !no_progress {
-                let mut pr = progress.lock().unwrap();
-                pr.render(format_status(
-                    "fast",
-                    &format!("{name}@{picked_version} skipped (platform mismatch)"),
-                ));
-            }
-            write_lock_entry(
-                &mut lock,
-                &name,
-                &picked_version,
-                integrity_owned.as_deref(),
-                resolved_for_lock.as_deref(),
-                &dep_map,
-                &BTreeMap::new(),
-                &opt_map,
-                &peer_map,
-                &peer_meta_map,
-                &package_os,
-                &package_cpu,
-            );
-            visited_name_version.insert((name.clone(), picked_version.clone()));
-            continue;
-        }
-        let mut reused = false;
-        let cached = crate::cache::cache_package_path(&name, &picked_version).exists();
-        let integrity_for_entry_string: Option<String>;
-
-        if cached {
-            reused = true;
-            integrity_for_entry_string = integrity_owned.clone();
-        } else {
-            if prefer_offline {
-                if optional_root {
-                    continue;
-                }
-                bail!("{name}@{picked_ver} not in cache and --prefer-offline is set");
-            }
-            let url = resolved_url
-                .as_deref()
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| tarball_url.clone());
-
-            if optional_root {
-                if !no_progress {
-                    let mut pr = progress.lock().unwrap();
-                    pr.render(format_status("downloading", &format!("{name}@{picked_version}")));
-                }
-                let download_result = download_into_cache(
-                    &fetcher,
-                    &name,
-                    &picked_version,
-                    &url,
-                    integrity_owned.as_deref(),
-                    scripts_map.as_ref(),
-                );
-                match download_result {
-                    Ok(integrity) => {
-                        integrity_for_entry_string = Some(integrity);
-                    }
-                    Err(e) => {
-                        if optional_root {
-                            if !no_progress {
-                                let mut pr = progress.lock().unwrap();
-                                pr.render(format_status(
-                                    "fast",
-                                    &format!(
-                                        "skip optional {name}@{picked_version} (download failed)"
-                                    ),
-                                ));
-                            }
-                            continue;
-                        } else {
-                            return Err(e);
-                        }
-                    }
-                }
-            } else {
-                if !no_progress {
-                    let mut pr = progress.lock().unwrap();
-                    pr.render(format_status(
-                        "queued",
-                        &format!("download {name}@{picked_version}"),
-                    ));
-                }
-                let key = (name.clone(), picked_version.clone());
-                if pending_set.insert(key) {
-                    pending_downloads.push(PendingDownload {
-                        name: name.clone(),
-                        version: picked_version.clone(),
-                        url,
-                        integrity_hint: integrity_owned.clone(),
-                        scripts: scripts_map.clone(),
-                    });
-                }
-                integrity_for_entry_string = integrity_owned.clone();
-            }
-        }
-
-        let integrity_for_entry = integrity_for_entry_string.as_deref();
-        write_lock_entry(
-            &mut lock,
-            &name,
-            &picked_version,
-            integrity_for_entry,
-            resolved_for_lock.as_deref(),
-            &dep_map,
-            &BTreeMap::new(),
-            &opt_map,
-            &peer_map,
-            &peer_meta_map,
-            &package_os,
-            &package_cpu,
+    if no_verify {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_RED}WARNING{C_RESET} --no-verify: package integrity is NOT checked; corrupted or tampered downloads will be installed and their hashes written to pacm.lockb"
         );
-        instances.insert(
-            name.clone(),
-            PackageInstance {
-                name: name.clone(),
-                version: picked_version.clone(),
-                dependencies: dep_map.clone(),
-                optional_dependencies: opt_map.clone(),
-                peer_dependencies: peer_map.clone(),
-                dev_dependencies: BTreeMap::new(),
-                source: None,
-            },
+    }
+    if let Some(max) = depth {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}warning{C_RESET} --depth {max}: dependencies more than {max} level(s) below package.json are skipped; node_modules may be incomplete and pacm.lockb is left unchanged"
         );
-        visited_name_version.insert((name.clone(), picked_version.clone()));
-        if !reused {
-            installed_count += 1;
-        }
-
-        let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
-        for (dn, dr) in dep_map.into_iter() {
-            to_enqueue.push((dn, dr, optional_root));
-        }
-        for (dn, dr) in opt_map.into_iter() {
-            to_enqueue.push((dn, dr, true));
-        }
-        for (dn, dr) in peer_map.into_iter() {
-            let is_optional_peer = peer_meta_map.get(&dn).map(|m| m.optional).unwrap_or(false);
-            if !is_optional_peer {
-                to_enqueue.push((dn, dr, false));
-            }
-        }
-        for (dn, dr, optflag) in to_enqueue {
-            queue.push_back(Task { name: dn, range: dr, optional_root: optflag });
-        }
     }
 
-    if !pending_downloads.is_empty() {
-        if !no_progress {
-            let mut pr = progress.lock().unwrap();
-            pr.render(format_status(
-                "downloading",
-                &format!("{} packages in parallel", pending_downloads.len()),
-            ));
-        }
-        let total_downloads = pending_downloads.len();
-        let counter = AtomicUsize::new(0);
-        let progress_clone = progress.clone();
-
-        let download_results: Result<Vec<(String, String)>> = pending_downloads
-            .par_iter()
-            .map(|pd| -> Result<(String, String)> {
-                let integrity = retry_download_into_cache(
-                    &fetcher,
-                    pd,
-                    &progress_clone,
-                    &counter,
-                    total_downloads,
-                    no_progress,
-                )?;
-                Ok((pd.name.clone(), integrity))
-            })
-            .collect();
-
-        let download_results = download_results?;
-        for (pkg_name, integrity) in download_results {
-            if let Some(entry) = lock.packages.get_mut(&format!("node_modules/{pkg_name}")) {
-                entry.integrity = Some(integrity);
-            }
-        }
-
-        if !no_progress {
-            let mut pr = progress.lock().unwrap();
-            pr.render(format_status(
-                "cached",
-                &format!("downloaded {} packages", pending_downloads.len()),
-            ));
+    let legacy_peer_deps = lock.settings.legacy_peer_deps;
+    let fetcher = Fetcher::from_config(&config)?.with_offline(offline);
+    let start = Instant::now();
+    let progress = Arc::new(Mutex::new(ProgressRenderer::new()));
+    let render_status = |kind: &str, detail: &str| {
+        let mut pr = progress.lock().unwrap();
+        if kind == "warning" {
+            pr.clear_line();
+            println!("{C_GRAY}[pacm]{C_RESET} {C_YELLOW}warning{C_RESET} {detail}");
+        } else if !no_progress {
+            pr.render(format_status(kind, detail));
         }
-    }
+    };
+    let resolve_options = ResolveOptions {
+        workspaces: Some(&workspace_map),
+        project_root: Some(&project_root),
+        specs: &specs,
+        optional_specs: optional,
+        prefer_offline,
+        offline,
+        force,
+        on_status: Some(&render_status),
+        deadline,
+        max_depth: depth,
+        prefer_dedupe,
+        platform,
+        legacy_peer_deps,
+        no_verify,
+    };
+    let mut timings = PhaseTimings::new();
+    let resolve_started = Instant::now();
+    let graph = resolve_graph(&manifest, lock, &fetcher, &resolve_options)?;
+    timings.record("resolve", resolve_started.elapsed().saturating_sub(graph.download_time));
+    timings.record("download", graph.download_time);
+    timings.record_packages(graph.resolve_times);
+    let instances = graph.instances;
+    let mut lock = graph.lockfile;
+    let installed_count = graph.downloaded;
 
     if !no_progress {
         let mut pr = progress.lock().unwrap();
         pr.clear_line();
+        for (pkg, msg) in &graph.deprecated {
+            println!("{C_GRAY}[pacm]{C_RESET} {C_YELLOW}deprecated{C_RESET} {pkg}: {msg}");
+        }
     }
 
-    {
+    if !legacy_peer_deps {
         let installed: HashSet<String> = instances.keys().cloned().collect();
         for (k, entry) in lock.packages.iter() {
             if k.is_empty() {
@@ -1606,7 +788,7 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
 
     if specs.is_empty() {
         let trans_removed = prune_unreachable(&mut lock);
-        if !trans_removed.is_empty() {
+        if !trans_removed.is_empty() && !lockfile_only {
             remove_dirs(&trans_removed);
         }
     }
@@ -1622,6 +804,11 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
                 &inst.version,
                 None,
                 None,
+                if inst.source.is_some() {
+                    PackageSource::Workspace
+                } else {
+                    PackageSource::Registry
+                },
                 &inst.dependencies,
                 &inst.dev_dependencies,
                 &inst.optional_dependencies,
@@ -1629,21 +816,46 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
                 &BTreeMap::new(),
                 &[],
                 &[],
+                &[],
             );
         }
     }
 
-    let plan = ensure_store_plan(&store, &mut lock, &instances)?;
-    let installer = Installer::new(install_mode);
-    let cb = if no_progress {
-        None
-    } else {
-        let prog = Arc::clone(&progress);
-        Some(Arc::new(move |done: usize, total: usize, pkg: &str| {
-            let mut pr = prog.lock().unwrap();
-            pr.render(format_status("linking", &format!("{}/{} {}", done, total, pkg)));
-        }) as Arc<dyn Fn(usize, usize, &str) + Send + Sync>)
-    };
+    if audit_signatures {
+        let audit = super::signatures::audit_signatures(&fetcher, &lock)?;
+        report_signature_audit(&audit);
+    }
+
+    let store_started = Instant::now();
+    let plan = ensure_store_plan(&store, &project_root, &mut lock, &instances, force, platform)?;
+    timings.record("store", store_started.elapsed());
+    if lockfile_only {
+        // Store entries are populated above so a later install can take the fast path.
+        lockfile::write(&lock, lock_path.clone())?;
+        if lockfile_has_no_packages(&lock) {
+            let _ = std::fs::remove_file(&lock_path);
+        }
+        if !no_progress {
+            let mut pr = progress.lock().unwrap();
+            pr.finish();
+        }
+        timings.report();
+        return Ok(InstallReport {
+            kind: InstallKind::LockfileOnly,
+            added: added_versions(&added_root, &instances),
+            removed: locked_versions(&original_lock, &removed_root),
+            installed: plan.len(),
+            downloaded: installed_count,
+            instances: instances.into_values().collect(),
+            duration: start.elapsed(),
+            ..InstallReport::default()
+        });
+    }
+    let installer = Installer::new(install_mode)
+        .with_bin_links(!lock.settings.no_bin_links)
+        .with_workspace_symlinks(lock.settings.symlink_workspaces);
+    let cb = linking_progress(&progress, no_progress);
+    let link_started = Instant::now();
     let outcomes = installer.install_with_progress(
         &project_root,
         &plan,
@@ -1652,63 +864,40 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
         &workspace_folder_paths,
         cb,
     )?;
-    lockfile::write(&lock, lock_path.clone())?;
-    if lockfile_has_no_packages(&lock) {
-        let _ = std::fs::remove_file(&lock_path);
+    timings.record("link", link_started.elapsed());
+    crate::cancel::check_at(Cancelled::AfterLinking)?;
+    if depth.is_none() && !cross_platform {
+        lockfile::write(&lock, lock_path.clone())?;
+        if lockfile_has_no_packages(&lock) {
+            let _ = std::fs::remove_file(&lock_path);
+        }
     }
     cleanup_empty_node_modules_dir();
-    let dur = start.elapsed();
+    record_install_state(install_mode, &store)?;
+    if gc {
+        collect_store_garbage(&store, &project_root, &original_lock, &lock)?;
+    }
+    let duration = start.elapsed();
 
     if !no_progress {
         let mut pr = progress.lock().unwrap();
         pr.finish();
     }
 
-    let total = plan.len();
-    let reused = total.saturating_sub(installed_count);
-    let linked_count = outcomes.iter().filter(|o| o.link_mode == InstallMode::Link).count();
-    let copied_count = total.saturating_sub(linked_count);
+    let linked = outcomes.iter().filter(|o| o.link_mode == InstallMode::Link).count();
+    let report = InstallReport {
+        kind: InstallKind::Resolved,
+        added: added_versions(&added_root, &instances),
+        removed: locked_versions(&original_lock, &removed_root),
+        installed: plan.len(),
+        downloaded: installed_count,
+        linked,
+        copied: plan.len().saturating_sub(linked),
+        instances: instances.values().cloned().collect(),
+        duration,
+    };
 
-    if added_root.is_empty() && removed_root.is_empty() {
-        println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}no dependency changes{C_RESET}");
-    }
-    for a in &added_root {
-        if let Some(inst) = instances.get(a) {
-            println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}+{C_RESET} {}@{}", a, inst.version);
-        } else {
-            println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}+{C_RESET} {a}");
-        }
-    }
-    for r in &removed_root {
-        if let Some(ver) = original_lock
-            .packages
-            .get(&format!("node_modules/{r}"))
-            .and_then(|e| e.version.as_ref())
-        {
-            println!("{C_GRAY}[pacm]{C_RESET} {C_RED}-{C_RESET} {r}@{ver}");
-        } else {
-            println!("{C_GRAY}[pacm]{C_RESET} {C_RED}-{C_RESET} {r}");
-        }
-    }
-    println!(
-        "{gray}[pacm]{reset} summary: {green}{add} added{reset}, {red}{removed} removed{reset}",
-        gray = C_GRAY,
-        green = C_GREEN,
-        red = C_RED,
-        add = added_root.len(),
-        removed = removed_root.len(),
-        reset = C_RESET
-    );
-    if copied_count == 0 {
-        println!("{C_GRAY}[pacm]{C_RESET} linking: {C_GREEN}{linked_count}{C_RESET} linked");
-    } else {
-        println!(
-            "{C_GRAY}[pacm]{C_RESET} linking: {C_GREEN}{linked_count}{C_RESET} linked, {C_DIM}{copied_count}{C_RESET} copied"
-        );
-    }
-    println!(
-        "{C_GRAY}[pacm]{C_RESET} {C_GREEN}installed{C_RESET} {total} packages ({C_GREEN}{installed_count} downloaded{C_RESET}, {C_DIM}{reused} reused{C_RESET}) in {dur:.2?}"
-    );
+    timings.report();
     // Detect packages that declare lifecycle scripts (preinstall/install/postinstall) in parallel
     let pkgs_with_scripts: Vec<String> = plan
         .par_iter()
@@ -1747,7 +936,18 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
         }
     }
 
-    if !pkgs_with_scripts.is_empty() || root_has_scripts {
+    if run_scripts && !lock.settings.ignore_scripts && !skip_scripts {
+        crate::cancel::check_at(Cancelled::BeforeScripts)?;
+        run_install_scripts(&project_root, &lock, pkgs_with_scripts)?;
+    } else if (!pkgs_with_scripts.is_empty() || root_has_scripts) && lock.settings.ignore_scripts {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}note{C_RESET}: lifecycle scripts are disabled for this project (installed with --ignore-scripts)."
+        );
+    } else if (!pkgs_with_scripts.is_empty() || root_has_scripts) && skip_scripts {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}note{C_RESET}: lifecycle scripts skipped (ignore-scripts is set in .pacmrc or PACM_IGNORE_SCRIPTS)."
+        );
+    } else if !pkgs_with_scripts.is_empty() || root_has_scripts {
         println!(
             "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}note{C_RESET}: lifecycle scripts detected for some packages. pacm does not run them during 'install' by default."
         );
@@ -1767,11 +967,66 @@ pub(crate) fn cmd_install(specs: Vec<String>, options: InstallOptions) -> Result
         );
     }
 
-    Ok(())
+    Ok(report)
+}
+
+/// Progress callback that redraws `linking X/total <pkg>` as packages are materialized, or
+/// `None` when progress output is disabled.
+fn linking_progress(
+    progress: &Arc<Mutex<ProgressRenderer>>,
+    no_progress: bool,
+) -> Option<ProgressCallback> {
+    if no_progress {
+        return None;
+    }
+    let progress = Arc::clone(progress);
+    Some(Arc::new(move |done: usize, total: usize, pkg: &str| {
+        let mut pr = progress.lock().unwrap();
+        pr.render(format_status("linking", &format!("{done}/{total} {pkg}")));
+    }))
+}
+
+/// `name@version` of each newly added root dependency, as resolved.
+fn added_versions(
+    added: &[String],
+    instances: &BTreeMap<String, PackageInstance>,
+) -> Vec<PackageVersion> {
+    added
+        .iter()
+        .map(|name| PackageVersion {
+            name: name.clone(),
+            version: instances.get(name).map(|inst| inst.version.clone()),
+        })
+        .collect()
 }
 
+/// `name@version` of each removed root dependency, as it was locked before the install.
+fn locked_versions(lock: &Lockfile, removed: &[String]) -> Vec<PackageVersion> {
+    removed
+        .iter()
+        .map(|name| PackageVersion {
+            name: name.clone(),
+            version: lock
+                .packages
+                .get(&format!("node_modules/{name}"))
+                .and_then(|entry| entry.version.clone()),
+        })
+        .collect()
+}
+
+fn record_install_state(mode: InstallMode, store: &CasStore) -> Result<()> {
+    let node_modules = std::path::Path::new("node_modules");
+    if !node_modules.exists() {
+        return Ok(());
+    }
+    write_install_state(node_modules, mode, store.root())
+}
+
+/// Plan the install from the store keys in `lock`, recording `project_root` as a reference to
+/// each entry so gc keeps them while they are linked.
 fn build_plan_from_lock(
     store: &CasStore,
+    project_root: &Path,
     lock: &Lockfile,
     instances: &BTreeMap<String, PackageInstance>,
 ) -> Result<HashMap<String, InstallPlanEntry>> {
@@ -1785,7 +1040,7 @@ fn build_plan_from_lock(
             .as_ref()
             .ok_or_else(|| anyhow!("no storeKey recorded for {name}"))?;
         let store_entry = store
-            .load_entry(store_key)?
+            .reference_entry(store_key, project_root)?
             .ok_or_else(|| anyhow!("store entry {store_key} not found on disk"))?;
         plan.insert(name.clone(), InstallPlanEntry { package: instance.clone(), store_entry });
     }
@@ -1794,15 +1049,28 @@ fn build_plan_from_lock(
 
 fn ensure_store_plan(
     store: &CasStore,
+    project_root: &Path,
     lock: &mut Lockfile,
     instances: &BTreeMap<String, PackageInstance>,
+    force: bool,
+    platform: TargetPlatform,
 ) -> Result<HashMap<String, InstallPlanEntry>> {
     let mut memo: HashMap<String, StoreEntry> = HashMap::new();
     let mut visiting: HashSet<String> = HashSet::new();
 
     for name in instances.keys() {
-        let entry =
-            ensure_store_for_package(store, lock, instances, name, &mut memo, &mut visiting)?;
+        crate::cancel::check()?;
+        let entry = ensure_store_for_package(
+            store,
+            project_root,
+            lock,
+            instances,
+            name,
+            force,
+            platform,
+            &mut memo,
+            &mut visiting,
+        )?;
         if let Some(lock_entry) = lock.packages.get_mut(&format!("node_modules/{name}")) {
             lock_entry.store_key = Some(entry.store_key.clone());
             lock_entry.content_hash = Some(entry.content_hash.clone());
@@ -1823,11 +1091,15 @@ fn ensure_store_plan(
     Ok(plan)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn ensure_store_for_package(
     store: &CasStore,
+    project_root: &Path,
     lock: &Lockfile,
     instances: &BTreeMap<String, PackageInstance>,
     name: &str,
+    force: bool,
+    platform: TargetPlatform,
     memo: &mut HashMap<String, StoreEntry>,
     visiting: &mut HashSet<String>,
 ) -> Result<StoreEntry> {
@@ -1871,7 +1143,7 @@ fn ensure_store_for_package(
         // If this dependency is optional for the parent package and the package
         // declares an OS/CPU restriction that does not match this host, skip it.
         if lock_entry.optional_dependencies.contains_key(&dep)
-            && !platform_supported(&dep_entry.os, &dep_entry.cpu_arch)
+            && !platform.supports(&dep_entry.os, &dep_entry.cpu_arch, &dep_entry.libc)
         {
             // skip optional dependency incompatible with platform
             continue;
@@ -1885,8 +1157,17 @@ fn ensure_store_for_package(
             });
             continue;
         }
-        let dep_store_entry =
-            ensure_store_for_package(store, lock, instances, &dep, memo, visiting)?;
+        let dep_store_entry = ensure_store_for_package(
+            store,
+            project_root,
+            lock,
+            instances,
+            &dep,
+            force,
+            platform,
+            memo,
+            visiting,
+        )?;
         dep_fps.push(DependencyFingerprint {
             name: dep.clone(),
             version: dep_version.clone(),
@@ -1906,9 +1187,24 @@ fn ensure_store_for_package(
         source_dir: &source_dir,
         integrity: lock_entry.integrity.as_deref(),
         resolved: lock_entry.resolved.as_deref(),
+        project: Some(project_root),
     };
-    let store_entry = store.ensure_entry(&params)?;
+    let store_entry =
+        if force { store.rebuild_entry(&params)? } else { store.ensure_entry(&params)? };
     visiting.remove(name);
     memo.insert(name.to_string(), store_entry.clone());
     Ok(store_entry)
 }
+
+fn report_signature_audit(audit: &SignatureAudit) {
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} verified registry signatures of {} package(s)",
+        audit.verified
+    );
+    if audit.unsigned_registry > 0 {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}warning{C_RESET} {} package(s) come from a registry that publishes no signing keys and were not checked",
+            audit.unsigned_registry
+        );
+    }
+}
@@ -1,8 +1,16 @@
 use crate::fetch::Fetcher;
 use crate::manifest::{self, Manifest};
-use crate::resolver::spec::{guess_name_from_spec, PackageSpec};
-use anyhow::{Context, Result};
+use crate::resolver::looks_like_dist_tag;
+pub use crate::resolver::spec::parse_spec;
+use crate::resolver::spec::{guess_name_from_spec, validate_dependency_name, PackageSpec};
+use anyhow::{bail, Context, Result};
 
+/// Record `specs` in the manifest and return the specs the resolver should use. `latest`
+/// requests come back pinned to the registry's current `latest`, so neither the cache nor an
+/// older lock entry can stand in for it, and unnamed git, github and tarball specs come back
+/// as `name@<spec>` with the name their `package.json` declares. With `save_tag`, a dist-tag
+/// spec is saved as the tag itself rather than the version it currently points to.
+#[allow(clippy::too_many_arguments)]
 pub(super) fn update_manifest_for_specs(
     specs: &[String],
     manifest: &mut Manifest,
@@ -10,32 +18,50 @@ pub(super) fn update_manifest_for_specs(
     dev: bool,
     optional: bool,
     no_save: bool,
-) -> Result<()> {
+    offline: bool,
+    save_prefix: &str,
+    save_tag: bool,
+    config: &crate::config::Config,
+) -> Result<Vec<String>> {
     if specs.is_empty() {
-        return Ok(());
+        return Ok(Vec::new());
     }
 
-    let registry_override = std::env::var("PACM_REGISTRY").ok();
-    let fetcher = if no_save {
-        None
-    } else {
-        Some(Fetcher::new(registry_override).with_context(|| "create fetcher")?)
-    };
+    let fetcher =
+        Fetcher::from_config(config).with_context(|| "create fetcher")?.with_offline(offline);
 
+    let mut resolve_specs = Vec::with_capacity(specs.len());
     for spec in specs {
-        let (name, req) = parse_spec(spec);
-        let resolved_version = if no_save {
+        let (mut name, req) = parse_spec(spec);
+        let mut spec = spec.clone();
+        if guess_name_from_spec(&spec).is_some() {
+            if let Some(declared) = declared_source_name(&req, &fetcher, offline)? {
+                if declared != name {
+                    validate_dependency_name(&declared)?;
+                    spec = format!("{declared}@{req}");
+                    name = declared;
+                }
+            }
+        }
+        let fetcher = (!offline).then_some(&fetcher);
+        let wants_latest = req.trim().eq_ignore_ascii_case("latest");
+        let resolved_version = if no_save && !wants_latest {
             req.clone()
         } else {
-            resolve_version_for_manifest(&name, &req, fetcher.as_ref())?
+            resolve_version_for_manifest(&name, &req, fetcher, offline)?
         };
+        resolve_specs.push(if wants_latest { format!("{name}@{resolved_version}") } else { spec });
         if !no_save {
+            // Only concrete versions take the prefix; ranges, tags and URLs are saved as given.
+            let saved = if save_tag && looks_like_dist_tag(&req) {
+                req.trim().to_string()
+            } else if semver::Version::parse(&resolved_version).is_ok() {
+                format!("{save_prefix}{resolved_version}")
+            } else {
+                resolved_version.clone()
+            };
             crate::cli::commands::install::util::add_spec_with_version(
-                manifest,
-                &name,
-                &resolved_version,
-                dev,
-                optional,
+                manifest, &name, &saved, dev, optional,
             )?;
         }
     }
@@ -44,39 +70,49 @@ pub(super) fn update_manifest_for_specs(
         manifest::write(manifest, manifest_path)?;
     }
 
-    Ok(())
+    Ok(resolve_specs)
 }
 
-pub fn parse_spec(spec: &str) -> (String, String) {
-    if let Some(guessed) = guess_name_from_spec(spec) {
-        return (guessed, spec.to_string());
+/// `pacm add <package> --tag <tag>`: the spec to install, `package@tag`. A package that already
+/// names a version or range is rejected, as is anything that is not a dist-tag.
+pub fn tagged_spec(package: &str, tag: &str) -> Result<String> {
+    let (name, req) = parse_spec(package);
+    if req != "*" || package.ends_with('@') {
+        bail!("--tag cannot be combined with a version or range ({package})");
     }
-    if spec.starts_with('@') {
-        if let Some(idx) = spec.rfind('@') {
-            if idx == 0 {
-                return (spec.to_string(), "*".to_string());
-            }
-            let (name, range) = spec.split_at(idx);
-            return (name.to_string(), range[1..].to_string());
-        }
-    } else if let Some((name, range)) = spec.split_once('@') {
-        let range = if range.is_empty() { "*" } else { range };
-        return (name.to_string(), range.to_string());
+    if !looks_like_dist_tag(tag) {
+        bail!("--tag expects a dist-tag such as 'beta' or 'next', got '{tag}'");
     }
+    Ok(format!("{name}@{tag}"))
+}
 
-    (spec.to_string(), "*".to_string())
+/// Fetch a non-registry source to read its declared name. Remote sources are left to the
+/// resolver when offline, which reports them as not cached.
+fn declared_source_name(req: &str, fetcher: &Fetcher, offline: bool) -> Result<Option<String>> {
+    let source = PackageSpec::parse(req);
+    if offline && !matches!(source, PackageSpec::LocalTarball { .. }) {
+        return Ok(None);
+    }
+    crate::resolver::graph::source_package_name(&source, fetcher)
+        .with_context(|| format!("read package name from {req}"))
 }
 
 fn resolve_version_for_manifest(
     name: &str,
     req: &str,
     fetcher: Option<&Fetcher>,
+    offline: bool,
 ) -> Result<String> {
     let req_trimmed = req.trim();
     if !matches!(PackageSpec::parse(req_trimmed), PackageSpec::Registry { .. }) {
         return Ok(req_trimmed.to_string());
     }
-    let cached_versions = crate::cache::cached_versions(name);
+    // `latest` means the registry's tag, not the newest version that happens to be cached.
+    let cached_versions = if req_trimmed.eq_ignore_ascii_case("latest") && !offline {
+        Vec::new()
+    } else {
+        crate::cache::cached_versions(name)
+    };
 
     if req_trimmed == "*" {
         if let Some(version) = cached_versions.first() {
@@ -92,6 +128,9 @@ fn resolve_version_for_manifest(
         }
     }
 
+    if offline {
+        bail!("offline: {name} not cached");
+    }
     if let Some(fetcher) = fetcher {
         if req_trimmed.eq_ignore_ascii_case("latest") || req_trimmed == "*" {
             let meta = fetcher
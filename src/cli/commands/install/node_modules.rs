@@ -1,7 +1,80 @@
+use super::prune::reachable_packages;
+use crate::installer::InstallMode;
+use crate::lockfile::Lockfile;
 use crate::manifest::Manifest;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-pub(super) fn node_modules_intact(manifest: &Manifest, workspace_names: &[String]) -> bool {
+/// Written into `node_modules` after every install, similar to pnpm's `.modules.yaml`.
+pub(crate) const STATE_FILE: &str = ".pacm-state.json";
+
+/// Describes which store and layout produced a `node_modules` tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct InstallState {
+    pub pacm_version: String,
+    pub install_mode: String,
+    pub store_root: PathBuf,
+    /// Packages reachable as `node_modules/<name>`, sorted.
+    pub hoisted: Vec<String>,
+}
+
+pub(super) fn write_install_state(
+    node_modules: &Path,
+    mode: InstallMode,
+    store_root: &Path,
+) -> Result<()> {
+    let state = InstallState {
+        pacm_version: env!("CARGO_PKG_VERSION").to_string(),
+        install_mode: match mode {
+            InstallMode::Link => "link",
+            InstallMode::Copy => "copy",
+        }
+        .to_string(),
+        store_root: store_root.to_path_buf(),
+        hoisted: top_level_packages(node_modules),
+    };
+    let path = node_modules.join(STATE_FILE);
+    let data = serde_json::to_string_pretty(&state)?;
+    std::fs::write(&path, data).with_context(|| format!("write {}", path.display()))
+}
+
+pub(crate) fn read_install_state(node_modules: &Path) -> Option<InstallState> {
+    let text = std::fs::read_to_string(node_modules.join(STATE_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn top_level_packages(node_modules: &Path) -> Vec<String> {
+    let mut names = Vec::new();
+    let Ok(entries) = std::fs::read_dir(node_modules) else {
+        return names;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        if name.starts_with('@') {
+            if let Ok(scoped) = std::fs::read_dir(entry.path()) {
+                for pkg in scoped.flatten() {
+                    names.push(format!("{name}/{}", pkg.file_name().to_string_lossy()));
+                }
+            }
+        } else {
+            names.push(name);
+        }
+    }
+    names.sort();
+    names
+}
+
+pub(super) fn node_modules_intact(
+    manifest: &Manifest,
+    lock: &Lockfile,
+    workspace_names: &[String],
+    store_root: &Path,
+) -> bool {
     let node_modules = PathBuf::from("node_modules");
     if !node_modules.exists() {
         return false;
@@ -10,6 +83,11 @@ pub(super) fn node_modules_intact(manifest: &Manifest, workspace_names: &[String
     if !pacm_dir.exists() {
         return false;
     }
+    // A tree linked from a different store (or by a build without the state file) is relinked.
+    match read_install_state(&node_modules) {
+        Some(state) if state.store_root == store_root => {}
+        _ => return false,
+    }
 
     for name in manifest
         .dependencies
@@ -26,5 +104,14 @@ pub(super) fn node_modules_intact(manifest: &Manifest, workspace_names: &[String
             return false;
         }
     }
+    // Every locked package is materialized under `.pacm`, including transitive ones that are
+    // never hoisted, so a manually deleted dependency anywhere in the graph is noticed.
+    for name in reachable_packages(lock) {
+        if lock.packages.contains_key(&format!("node_modules/{name}"))
+            && !pacm_dir.join(&name).exists()
+        {
+            return false;
+        }
+    }
     true
 }
@@ -1,5 +1,11 @@
-use crate::lockfile::{Lockfile, PackageEntry};
-use std::path::PathBuf;
+use crate::cache::{project_key, CasStore};
+use crate::colors::*;
+use crate::fsutil::human_size;
+use crate::lockfile::{Lockfile, PackageEntry, PackageSource};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 
 pub(crate) fn prune_removed_from_lock(lock: &mut Lockfile, removed: &[String]) {
     for name in removed {
@@ -8,25 +14,47 @@ pub(crate) fn prune_removed_from_lock(lock: &mut Lockfile, removed: &[String]) {
     }
 }
 
-pub(crate) fn prune_unreachable(lock: &mut Lockfile) -> Vec<String> {
-    use std::collections::{HashSet, VecDeque};
-
-    let mut reachable: HashSet<String> = HashSet::new();
+/// Names reachable from the root entry. Names without a lock entry of their own (for example
+/// optional dependencies skipped on this platform) are included as well.
+pub(crate) fn reachable_packages(lock: &Lockfile) -> HashSet<String> {
     let mut queue: VecDeque<String> = VecDeque::new();
+    if let Some(root) = lock.packages.get("") {
+        enqueue_root(root, &mut queue, true);
+    }
+    walk_from(lock, queue)
+}
 
+/// Like [`reachable_packages`], but without following the root's `devDependencies`.
+pub(crate) fn production_packages(lock: &Lockfile) -> HashSet<String> {
+    let mut queue: VecDeque<String> = VecDeque::new();
     if let Some(root) = lock.packages.get("") {
-        enqueue_root(root, &mut queue);
+        enqueue_root(root, &mut queue, false);
     }
+    walk_from(lock, queue)
+}
+
+/// Packages that are only installed because of the root's `devDependencies`.
+pub(crate) fn dev_only_packages(lock: &Lockfile) -> HashSet<String> {
+    let production = production_packages(lock);
+    reachable_packages(lock).into_iter().filter(|name| !production.contains(name)).collect()
+}
 
+fn walk_from(lock: &Lockfile, mut queue: VecDeque<String>) -> HashSet<String> {
+    let mut reachable: HashSet<String> = HashSet::new();
     while let Some(name) = queue.pop_front() {
         if !reachable.insert(name.clone()) {
             continue;
         }
         let key = format!("node_modules/{name}");
         if let Some(entry) = lock.packages.get(&key) {
-            enqueue_entry(entry, &mut queue);
+            enqueue_entry(entry, &mut queue, lock.settings.legacy_peer_deps);
         }
     }
+    reachable
+}
+
+pub(crate) fn prune_unreachable(lock: &mut Lockfile) -> Vec<String> {
+    let reachable = reachable_packages(lock);
 
     let mut to_remove = Vec::new();
     let mut removed_names = Vec::new();
@@ -47,6 +75,156 @@ pub(crate) fn prune_unreachable(lock: &mut Lockfile) -> Vec<String> {
     removed_names
 }
 
+/// Store keys referenced by `before` but no longer by `after`.
+pub(crate) fn orphaned_store_keys(before: &Lockfile, after: &Lockfile) -> BTreeSet<String> {
+    let keys = |lock: &Lockfile| -> BTreeSet<String> {
+        lock.packages.values().filter_map(|entry| entry.store_key.clone()).collect()
+    };
+    let still_used = keys(after);
+    keys(before).into_iter().filter(|key| !still_used.contains(key)).collect()
+}
+
+/// What a project's pacm.lockb says about store keys, loaded once per project.
+enum ProjectKeys {
+    /// The project directory is gone, so none of its references are live.
+    Deleted,
+    /// No readable lockfile: the first install may still be running, or the file is corrupt.
+    /// Every reference is kept to be safe.
+    Unknown,
+    Listed(BTreeSet<String>),
+}
+
+type ProjectStoreKeys = HashMap<String, ProjectKeys>;
+
+/// The projects among `projects` that still use `store_key`: their pacm.lockb lists it, they
+/// have no readable lockfile yet, or `recent` is set because an install recorded a reference
+/// within the grace window and may not have written its lockfile. Deleted projects drop out.
+fn live_references(
+    projects: &[String],
+    store_key: &str,
+    recent: bool,
+    lockfiles: &mut ProjectStoreKeys,
+) -> Vec<String> {
+    projects
+        .iter()
+        .filter(|project| {
+            let keys = lockfiles.entry(project.to_string()).or_insert_with(|| {
+                let dir = Path::new(project.as_str());
+                if !dir.exists() {
+                    return ProjectKeys::Deleted;
+                }
+                let lock_path = dir.join("pacm.lockb");
+                if !lock_path.exists() {
+                    return ProjectKeys::Unknown;
+                }
+                match Lockfile::load_or_default(lock_path) {
+                    Ok(lock) => ProjectKeys::Listed(
+                        lock.packages.values().filter_map(|e| e.store_key.clone()).collect(),
+                    ),
+                    Err(_) => ProjectKeys::Unknown,
+                }
+            });
+            match keys {
+                ProjectKeys::Deleted => false,
+                ProjectKeys::Unknown => true,
+                ProjectKeys::Listed(keys) => recent || keys.contains(store_key),
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Delete the store entries this project stopped referencing (`--gc`). The store is shared, so
+/// only entries dropped from this lockfile are candidates, and those another project's
+/// lockfile still lists are kept.
+pub(crate) fn collect_store_garbage(
+    store: &CasStore,
+    project_root: &Path,
+    before: &Lockfile,
+    after: &Lockfile,
+) -> Result<()> {
+    let this_project = project_key(project_root);
+    let mut lockfiles = ProjectStoreKeys::new();
+    let mut removed = 0;
+    let mut kept = 0;
+    let mut freed = 0;
+    for key in orphaned_store_keys(before, after) {
+        let _lock = store.lock_entry(&key)?;
+        let Some(entry) = store.load_entry(&key)? else {
+            continue;
+        };
+        if entry.referencing_projects.is_empty() {
+            // Recorded before references existed: other projects may use it unseen.
+            kept += 1;
+            continue;
+        }
+        let others: Vec<String> =
+            entry.referencing_projects.iter().filter(|p| **p != this_project).cloned().collect();
+        let live =
+            live_references(&others, &key, store.recently_referenced(&entry), &mut lockfiles);
+        if live.is_empty() {
+            if let Some(bytes) = store.remove_entry(&key)? {
+                removed += 1;
+                freed += bytes;
+            }
+        } else {
+            store.set_references(&entry, live)?;
+            kept += 1;
+        }
+    }
+    let kept =
+        if kept > 0 { format!("; kept {kept} used by other projects") } else { String::new() };
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} gc: removed {removed} store entries ({}){kept}",
+        human_size(freed)
+    );
+    Ok(())
+}
+
+/// Outcome of [`collect_unreferenced_store_entries`].
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StoreGcSummary {
+    pub removed: usize,
+    pub freed_bytes: u64,
+    pub kept: usize,
+}
+
+/// `pacm cache gc`: delete every store entry that no project uses any more. References to
+/// deleted projects, and to projects that dropped the entry, are pruned along the way. Entries
+/// created before references were recorded have none; they are kept, since `pacm ci` may need
+/// them and there is no telling which projects link them.
+pub(crate) fn collect_unreferenced_store_entries(store: &CasStore) -> Result<StoreGcSummary> {
+    let mut lockfiles = ProjectStoreKeys::new();
+    let mut summary = StoreGcSummary::default();
+    for listed in store.list_entries()? {
+        let _lock = store.lock_entry(&listed.store_key)?;
+        // Re-read under the lock: an install may have added a reference since the listing.
+        let Some(entry) = store.load_entry(&listed.store_key)? else {
+            continue;
+        };
+        if entry.referencing_projects.is_empty() {
+            summary.kept += 1;
+            continue;
+        }
+        let recent = store.recently_referenced(&entry);
+        let live =
+            live_references(&entry.referencing_projects, &entry.store_key, recent, &mut lockfiles);
+        if live.is_empty() {
+            if let Some(bytes) = store.remove_entry(&entry.store_key)? {
+                summary.removed += 1;
+                summary.freed_bytes += bytes;
+            }
+            continue;
+        }
+        if live != entry.referencing_projects {
+            store.set_references(&entry, live)?;
+        }
+        summary.kept += 1;
+    }
+    Ok(summary)
+}
+
 pub(crate) fn remove_dirs(names: &[String]) {
     use std::fs;
     for name in names {
@@ -92,15 +270,15 @@ pub(crate) fn lockfile_has_no_packages(lock: &Lockfile) -> bool {
     }
 }
 
-fn enqueue_root(entry: &PackageEntry, queue: &mut std::collections::VecDeque<String>) {
-    for name in entry
-        .dependencies
-        .keys()
-        .chain(entry.dev_dependencies.keys())
-        .chain(entry.optional_dependencies.keys())
-    {
+fn enqueue_root(entry: &PackageEntry, queue: &mut VecDeque<String>, include_dev: bool) {
+    for name in entry.dependencies.keys().chain(entry.optional_dependencies.keys()) {
         queue.push_back(name.clone());
     }
+    if include_dev {
+        for name in entry.dev_dependencies.keys() {
+            queue.push_back(name.clone());
+        }
+    }
     for peer in entry.peer_dependencies.keys() {
         let is_optional =
             entry.peer_dependencies_meta.get(peer).map(|meta| meta.optional).unwrap_or(false);
@@ -110,16 +288,30 @@ fn enqueue_root(entry: &PackageEntry, queue: &mut std::collections::VecDeque<Str
     }
 }
 
-fn enqueue_entry(entry: &PackageEntry, queue: &mut std::collections::VecDeque<String>) {
+/// Queue the packages `entry` needs installed. The fast path walks the lockfile with this too,
+/// so both agree on what a lockfile reaches. Peers are skipped under `legacy_peer_deps`, which
+/// never gives them lock entries.
+pub(crate) fn enqueue_entry(
+    entry: &PackageEntry,
+    queue: &mut VecDeque<String>,
+    legacy_peer_deps: bool,
+) {
     for dep in entry.dependencies.keys() {
         queue.push_back(dep.clone());
     }
-    for dep in entry.dev_dependencies.keys() {
-        queue.push_back(dep.clone());
+    // Dev dependencies of published packages are never installed transitively, but workspace
+    // packages are developed in-tree and need theirs.
+    if entry.source_kind() == Some(PackageSource::Workspace) {
+        for dep in entry.dev_dependencies.keys() {
+            queue.push_back(dep.clone());
+        }
     }
     for dep in entry.optional_dependencies.keys() {
         queue.push_back(dep.clone());
     }
+    if legacy_peer_deps {
+        return;
+    }
     for peer in entry.peer_dependencies.keys() {
         let is_optional =
             entry.peer_dependencies_meta.get(peer).map(|meta| meta.optional).unwrap_or(false);
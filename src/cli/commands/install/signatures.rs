@@ -0,0 +1,91 @@
+use crate::colors::*;
+use crate::fetch::{verify_signatures, Fetcher, InvalidSignature, SignatureStatus};
+use crate::lockfile::{Lockfile, PackageSource};
+use anyhow::{Context, Result};
+
+/// Outcome of [`audit_signatures`] for a lockfile whose signatures all verified.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct SignatureAudit {
+    pub verified: usize,
+    /// Packages from registries that publish no signing keys.
+    pub unsigned_registry: usize,
+}
+
+/// Verify the registry signature of every registry package in `lock` (`--audit-signatures`).
+/// A registry that publishes signing keys must have signed every version. The signed integrity
+/// must also be the one pacm.lockb records, or, for entries without one, the integrity of the
+/// cached tarball, so a tampered lockfile cannot point a signed version at other content. Every
+/// failure is printed; the first is returned as an [`InvalidSignature`].
+pub(crate) fn audit_signatures(fetcher: &Fetcher, lock: &Lockfile) -> Result<SignatureAudit> {
+    let mut audit = SignatureAudit::default();
+    let mut failures = Vec::new();
+    for (key, entry) in &lock.packages {
+        let (Some(name), Some(version)) = (key.strip_prefix("node_modules/"), &entry.version)
+        else {
+            continue;
+        };
+        if entry.source_kind() != Some(PackageSource::Registry) {
+            continue;
+        }
+        let keys = fetcher.signing_keys(name)?;
+        if keys.is_empty() {
+            audit.unsigned_registry += 1;
+            continue;
+        }
+        let package = format!("{name}@{version}");
+        let meta = fetcher
+            .package_metadata(name)
+            .with_context(|| format!("fetch metadata to verify {package}"))?;
+        let Some(dist) = meta.versions.get(version).map(|v| &v.dist) else {
+            failures.push(InvalidSignature {
+                package,
+                reason: "version is not in the registry metadata".into(),
+            });
+            continue;
+        };
+        let Some(integrity) = dist.integrity.as_deref() else {
+            failures.push(InvalidSignature {
+                package,
+                reason: "the registry metadata carries no integrity to verify".into(),
+            });
+            continue;
+        };
+        let installed =
+            entry.integrity.clone().or_else(|| crate::cache::cached_integrity(name, version));
+        let Some(installed) = installed else {
+            failures.push(InvalidSignature {
+                package,
+                reason: "no integrity in pacm.lockb or the cache to compare with the signed one; \
+                         run 'pacm install --force'"
+                    .into(),
+            });
+            continue;
+        };
+        if installed != integrity {
+            let source =
+                if entry.integrity.is_some() { "pacm.lockb" } else { "the cached tarball" };
+            failures.push(InvalidSignature {
+                package,
+                reason: format!(
+                    "{source} integrity differs from the integrity the registry signed"
+                ),
+            });
+            continue;
+        }
+        match verify_signatures(&keys, name, version, integrity, &dist.signatures) {
+            SignatureStatus::Verified => audit.verified += 1,
+            SignatureStatus::Missing => failures.push(InvalidSignature {
+                package,
+                reason: "the registry publishes signing keys but this version is unsigned".into(),
+            }),
+            SignatureStatus::Invalid(reason) => failures.push(InvalidSignature { package, reason }),
+        }
+    }
+    for failure in &failures {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_RED}error{C_RESET} {failure}");
+    }
+    match failures.into_iter().next() {
+        Some(first) => Err(first.into()),
+        None => Ok(audit),
+    }
+}
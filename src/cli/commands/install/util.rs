@@ -1,5 +1,6 @@
-use crate::manifest::Manifest;
-use anyhow::Result;
+use crate::colors::*;
+use crate::manifest::{Manifest, PackageManagerStatus};
+use anyhow::{bail, Context, Result};
 
 pub(super) fn add_spec_with_version(
     manifest: &mut Manifest,
@@ -11,6 +12,25 @@ pub(super) fn add_spec_with_version(
     if name.is_empty() {
         anyhow::bail!("empty package name");
     }
+    if !dev && !optional {
+        // Without an explicit flag, a package that is already declared keeps its section.
+        let existing = if manifest.dev_dependencies.contains_key(name) {
+            Some(("devDependencies", &mut manifest.dev_dependencies))
+        } else if manifest.optional_dependencies.contains_key(name) {
+            Some(("optionalDependencies", &mut manifest.optional_dependencies))
+        } else {
+            None
+        };
+        if let Some((section, deps)) = existing {
+            deps.insert(name.to_string(), version.to_string());
+            println!("{C_GRAY}[pacm]{C_RESET} {name} is already in {section}; keeping it there");
+            return Ok(());
+        }
+    }
+    // An explicit --dev/--optional moves the package, so it never ends up in two sections.
+    manifest.dependencies.remove(name);
+    manifest.dev_dependencies.remove(name);
+    manifest.optional_dependencies.remove(name);
     if dev {
         manifest.dev_dependencies.insert(name.to_string(), version.to_string());
     } else if optional {
@@ -22,22 +42,40 @@ pub(super) fn add_spec_with_version(
 }
 
 pub(super) fn looks_like_dist_tag(spec: &str) -> bool {
-    let trimmed = spec.trim();
-    if trimmed.is_empty() || trimmed == "*" {
-        return false;
-    }
-    if trimmed.eq_ignore_ascii_case("latest") {
-        return false;
-    }
-    if trimmed.contains(' ') || trimmed.contains("||") || trimmed.contains(',') {
-        return false;
-    }
-    if !trimmed.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
-        return false;
+    crate::resolver::looks_like_dist_tag(spec)
+}
+
+/// Reject dependency names npm could never serve before any of them reach the registry.
+pub(super) fn validate_dependency_names(manifest: &Manifest) -> Result<()> {
+    for name in manifest
+        .dependencies
+        .keys()
+        .chain(manifest.dev_dependencies.keys())
+        .chain(manifest.optional_dependencies.keys())
+        .chain(manifest.peer_dependencies.keys())
+    {
+        crate::resolver::spec::validate_dependency_name(name)
+            .with_context(|| format!("in the dependencies of {}", manifest.name))?;
     }
-    let canon = crate::resolver::canonicalize_npm_range(trimmed);
-    if canon == "*" {
-        return false;
+    Ok(())
+}
+
+/// Warn when `packageManager` asks for another pacm version or another tool; `strict` turns
+/// the warning into an error.
+pub(super) fn check_package_manager(manifest: &Manifest, strict: bool) -> Result<()> {
+    let running = env!("CARGO_PKG_VERSION");
+    let problem = match manifest.package_manager_status(running) {
+        PackageManagerStatus::Unset | PackageManagerStatus::Matches => return Ok(()),
+        PackageManagerStatus::OtherVersion(wanted) => {
+            format!("this project pins pacm@{wanted} in packageManager, but this is pacm {running}")
+        }
+        PackageManagerStatus::OtherTool(tool) => {
+            format!("this project expects {tool} (packageManager), not pacm")
+        }
+    };
+    if strict {
+        bail!("{problem}");
     }
-    semver::Version::parse(trimmed).is_err() && semver::VersionReq::parse(&canon).is_err()
+    println!("{C_GRAY}[pacm]{C_RESET} {C_YELLOW}warning{C_RESET} {problem}");
+    Ok(())
 }
@@ -0,0 +1,119 @@
+//! `pacm link` / `pacm unlink`: develop a package against a consumer without publishing it.
+//!
+//! `pacm link` in a package registers it in the global link registry ([`links_root`]).
+//! `pacm link <name>` in a consumer then symlinks the registered directory into its
+//! node_modules. As with npm, a later `pacm install` puts the registry version back if the
+//! package is a declared dependency.
+
+use crate::colors::*;
+use crate::config::Config;
+use crate::fsutil::links_root;
+use crate::installer::{link_local_package, unlink_local_package};
+use crate::lockfile::Lockfile;
+use crate::manifest;
+use crate::resolver::spec::validate_dependency_name;
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn cmd_link(packages: Vec<String>, config: &Config) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let links = links_root(config);
+    if packages.is_empty() {
+        let name = register_link(&links, &cwd)?;
+        println!("{C_GRAY}[pacm]{C_RESET} registered {C_CYAN}{name}{C_RESET} -> {}", cwd.display());
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_DIM}run `pacm link {name}` in a project to use it{C_RESET}"
+        );
+        return Ok(());
+    }
+    // Honor the project's recorded `--no-bin-links` preference, as installs do.
+    let bin_links = !Lockfile::load_or_default(cwd.join("pacm.lockb"))?.settings.no_bin_links;
+    for name in &packages {
+        let target = registered_link(&links, name)?;
+        link_local_package(&cwd, name, &target, bin_links)?;
+        println!("{C_GRAY}[pacm]{C_RESET} linked {C_CYAN}{name}{C_RESET} -> {}", target.display());
+    }
+    Ok(())
+}
+
+pub fn cmd_unlink(packages: Vec<String>, config: &Config) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    if packages.is_empty() {
+        let name = package_name(&cwd)?;
+        if unregister_link(&links_root(config), &name)? {
+            println!("{C_GRAY}[pacm]{C_RESET} unregistered {C_CYAN}{name}{C_RESET}");
+        } else {
+            println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}{name} was not linked{C_RESET}");
+        }
+        return Ok(());
+    }
+    let declared = manifest::load(&cwd.join("package.json")).ok();
+    for name in &packages {
+        if !unlink_local_package(&cwd, name)? {
+            println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}{name} is not linked here{C_RESET}");
+            continue;
+        }
+        println!("{C_GRAY}[pacm]{C_RESET} unlinked {C_CYAN}{name}{C_RESET}");
+        let is_dependency = declared.as_ref().is_some_and(|m| {
+            m.dependencies.contains_key(name)
+                || m.dev_dependencies.contains_key(name)
+                || m.optional_dependencies.contains_key(name)
+        });
+        if is_dependency {
+            println!(
+                "{C_GRAY}[pacm]{C_RESET} {C_DIM}run `pacm install` to restore the registry version{C_RESET}"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Register the package in `dir` in `links` under its package.json name and return that name.
+fn register_link(links: &Path, dir: &Path) -> Result<String> {
+    let name = package_name(dir)?;
+    let target = dir.canonicalize().with_context(|| format!("resolve {}", dir.display()))?;
+    let entry = links.join(&name);
+    if let Some(parent) = entry.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _ = fs::remove_file(&entry);
+    let _ = fs::remove_dir(&entry);
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(&target, &entry);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_dir(&target, &entry);
+    linked.with_context(|| format!("register {name} in {}", links.display()))?;
+    Ok(name)
+}
+
+/// The directory registered for `name` by `pacm link`.
+fn registered_link(links: &Path, name: &str) -> Result<PathBuf> {
+    validate_dependency_name(name)?;
+    let target = fs::read_link(links.join(name))
+        .map_err(|_| anyhow!("{name} is not linked; run `pacm link` in its directory first"))?;
+    if !target.is_dir() {
+        bail!("{name} was linked from {}, which no longer exists", target.display());
+    }
+    Ok(target)
+}
+
+/// Drop `name` from the link registry. Returns false when it was not registered.
+fn unregister_link(links: &Path, name: &str) -> Result<bool> {
+    let entry = links.join(name);
+    if fs::symlink_metadata(&entry).is_err() {
+        return Ok(false);
+    }
+    fs::remove_file(&entry).or_else(|_| fs::remove_dir(&entry))?;
+    Ok(true)
+}
+
+fn package_name(dir: &Path) -> Result<String> {
+    let manifest_path = dir.join("package.json");
+    if !manifest_path.exists() {
+        bail!("no package.json found in {}", dir.display());
+    }
+    let name = manifest::load(&manifest_path)?.name;
+    validate_dependency_name(&name).context("package.json needs a valid name to be linked")?;
+    Ok(name)
+}
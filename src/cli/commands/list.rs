@@ -1,9 +1,59 @@
+use crate::cli::commands::install::{dev_only_packages, production_packages};
 use crate::colors::*;
-use crate::lockfile;
+use crate::config::Config;
+use crate::fsutil::global_root;
+use crate::lockfile::{self, Lockfile, PackageSource};
+use crate::output::{print_json, OutputFormat};
 use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-pub fn cmd_list() -> Result<()> {
+/// JSON shape of `pacm list --json`: installed packages keyed by name.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct ListOutput {
+    pub packages: BTreeMap<String, ListedPackage>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ListedPackage {
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<PackageSource>,
+}
+
+impl ListOutput {
+    /// Collect the non-root lock entries, optionally restricted to `names`.
+    pub(crate) fn from_lock(lock: &Lockfile, names: Option<&[&str]>) -> Self {
+        let packages = lock
+            .packages
+            .iter()
+            .filter_map(|(key, entry)| {
+                let name = key.strip_prefix("node_modules/")?;
+                if names.is_some_and(|names| !names.contains(&name)) {
+                    return None;
+                }
+                let listed =
+                    ListedPackage { version: entry.version.clone(), source: entry.source_kind() };
+                Some((name.to_string(), listed))
+            })
+            .collect();
+        Self { packages }
+    }
+}
+
+/// List lockfile entries; `prod` and `dev` restrict the output to packages reachable from the
+/// root's production dependencies or only through its `devDependencies`.
+pub fn cmd_list(
+    global: bool,
+    prod: bool,
+    dev: bool,
+    format: OutputFormat,
+    config: &Config,
+) -> Result<()> {
+    if global {
+        return list_global(format, config);
+    }
     let lock_path = PathBuf::from("pacm.lockb");
     let lock = if lock_path.exists() {
         lockfile::load(&lock_path)?
@@ -11,8 +61,10 @@ pub fn cmd_list() -> Result<()> {
         let legacy = PathBuf::from("pacm-lock.json");
         if legacy.exists() {
             let lf = lockfile::load_json_compat(&legacy)?;
-            println!("{C_GRAY}[pacm]{C_RESET} {C_YELLOW}note{C_RESET}: reading legacy pacm-lock.json (run 'pacm install' to migrate)");
+            eprintln!("{C_GRAY}[pacm]{C_RESET} {C_YELLOW}note{C_RESET}: reading legacy pacm-lock.json (run 'pacm install' to migrate)");
             lf
+        } else if format.is_json() {
+            return print_json(&ListOutput::default());
         } else {
             println!(
                 "{C_GRAY}[pacm]{C_RESET} {C_RED}error{C_RESET} no lockfile. Run 'pacm install'."
@@ -21,15 +73,43 @@ pub fn cmd_list() -> Result<()> {
         }
     };
 
+    let selected = if prod {
+        Some(production_packages(&lock))
+    } else if dev {
+        Some(dev_only_packages(&lock))
+    } else {
+        None
+    };
+    let entries: Vec<_> = lock
+        .packages
+        .iter()
+        .filter(|(key, _)| match &selected {
+            Some(names) => key.strip_prefix("node_modules/").is_some_and(|n| names.contains(n)),
+            None => true,
+        })
+        .collect();
+
+    if format.is_json() {
+        let names: Vec<&str> =
+            entries.iter().filter_map(|(key, _)| key.strip_prefix("node_modules/")).collect();
+        return print_json(&ListOutput::from_lock(&lock, Some(&names)));
+    }
     println!(
         "{gray}[pacm]{reset} packages ({count} entries):",
         gray = C_GRAY,
         reset = C_RESET,
-        count = lock.packages.len()
+        count = entries.len()
     );
-    for (key, entry) in &lock.packages {
+    for (key, entry) in entries {
+        // Registry packages are the common case; only call out the others.
+        let source = match entry.source_kind() {
+            Some(source) if source != PackageSource::Registry => {
+                format!(" {C_DIM}({source}){C_RESET}")
+            }
+            _ => String::new(),
+        };
         println!(
-            "{gray}[pacm]{reset}  {dim}-{reset} {name} => {version}",
+            "{gray}[pacm]{reset}  {dim}-{reset} {name} => {version}{source}",
             gray = C_GRAY,
             dim = C_DIM,
             reset = C_RESET,
@@ -39,3 +119,30 @@ pub fn cmd_list() -> Result<()> {
     }
     Ok(())
 }
+
+fn list_global(format: OutputFormat, config: &Config) -> Result<()> {
+    let prefix = global_root(config);
+    let lock_path = prefix.join("pacm.lockb");
+    let lock = if lock_path.exists() { lockfile::load(&lock_path)? } else { Default::default() };
+    let top_level: Vec<&String> =
+        lock.packages.get("").map(|root| root.dependencies.keys().collect()).unwrap_or_default();
+    if format.is_json() {
+        let names: Vec<&str> = top_level.iter().map(|name| name.as_str()).collect();
+        return print_json(&ListOutput::from_lock(&lock, Some(&names)));
+    }
+    if top_level.is_empty() {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}no global packages installed{C_RESET}");
+        return Ok(());
+    }
+
+    println!("{C_GRAY}[pacm]{C_RESET} global packages in {}:", prefix.display());
+    for name in top_level {
+        let version = lock
+            .packages
+            .get(&format!("node_modules/{name}"))
+            .and_then(|e| e.version.as_deref())
+            .unwrap_or("(unresolved)");
+        println!("{C_GRAY}[pacm]{C_RESET}  {C_DIM}-{C_RESET} {name} => {version}");
+    }
+    Ok(())
+}
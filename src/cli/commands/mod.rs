@@ -1,18 +1,31 @@
+pub mod audit;
+pub mod doctor;
 pub mod install;
+pub mod list;
+pub mod pm;
 pub mod run;
 pub mod scripts;
+pub mod view;
 
 mod cache;
+mod import_lock;
 mod init;
-mod list;
-mod pm;
+mod link;
 mod remove;
 
-pub(crate) use cache::{cmd_cache_clean, cmd_cache_path};
+pub(crate) use audit::cmd_audit;
+pub(crate) use cache::{
+    cmd_cache_clean, cmd_cache_export, cmd_cache_gc, cmd_cache_import, cmd_cache_path,
+    cmd_cache_size,
+};
+pub(crate) use doctor::cmd_doctor;
+pub(crate) use import_lock::cmd_import_lock;
 pub(crate) use init::cmd_init;
-pub(crate) use install::{cmd_install, InstallOptions};
+pub(crate) use install::{cmd_ci, cmd_install, cmd_install_global, InstallOptions};
+pub(crate) use link::{cmd_link, cmd_unlink};
 pub(crate) use list::cmd_list;
-pub(crate) use pm::{cmd_pm_lockfile, cmd_pm_prune};
+pub(crate) use pm::{cmd_pm_lockfile, cmd_pm_lockfile_diff, cmd_pm_prune};
 pub(crate) use remove::cmd_remove;
 pub(crate) use run::cmd_run;
-pub(crate) use scripts::cmd_scripts_run;
+pub(crate) use scripts::{cmd_rebuild, cmd_scripts_run};
+pub(crate) use view::cmd_view;
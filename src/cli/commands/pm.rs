@@ -1,11 +1,19 @@
+use crate::cache::CasStore;
 use crate::cli::commands::install::{
-    build_fast_instances, cleanup_empty_node_modules_dir, lockfile_has_no_packages,
-    prune_unreachable, remove_dirs,
+    build_fast_instances, cleanup_empty_node_modules_dir, collect_store_garbage,
+    lockfile_has_no_packages, prune_unreachable, remove_dirs,
 };
 use crate::colors::*;
-use crate::lockfile;
+use crate::config::Config;
+use crate::fetch::Fetcher;
+use crate::lockfile::{self, LockfileDiff};
+use crate::output::{print_json, OutputFormat};
+use crate::resolver::graph::{resolve_graph, ResolveOptions};
+use crate::resolver::platform::TargetPlatform;
+use crate::workspaces::discover_workspaces;
 use anyhow::{bail, Result};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 pub fn cmd_pm_lockfile(format: String, save: bool) -> Result<()> {
     let lock_path = PathBuf::from("pacm.lockb");
@@ -37,7 +45,86 @@ pub fn cmd_pm_lockfile(format: String, save: bool) -> Result<()> {
     Ok(())
 }
 
-pub fn cmd_pm_prune() -> Result<()> {
+/// Print how the lockfile would change if `pacm install` ran now, without writing anything.
+pub fn cmd_pm_lockfile_diff(format: OutputFormat, config: &Config) -> Result<()> {
+    let diff = lockfile_diff(&std::env::current_dir()?, config)?;
+    if format.is_json() {
+        return print_json(&diff);
+    }
+    if diff.is_empty() {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}lockfile is up to date{C_RESET}");
+        return Ok(());
+    }
+    let show = |version: &Option<String>| version.clone().unwrap_or_else(|| "?".into());
+    for pkg in &diff.added {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}+{C_RESET} {}@{}", pkg.name, show(&pkg.version));
+    }
+    for pkg in &diff.removed {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_RED}-{C_RESET} {}@{}", pkg.name, show(&pkg.version));
+    }
+    for change in &diff.changed {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}~{C_RESET} {} {} -> {}",
+            change.name,
+            show(&change.from),
+            show(&change.to)
+        );
+    }
+    println!(
+        "{C_GRAY}[pacm]{C_RESET} summary: {C_GREEN}{} added{C_RESET}, {C_RED}{} removed{C_RESET}, {C_YELLOW}{} changed{C_RESET}",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+    Ok(())
+}
+
+/// Resolve the project from its current lockfile the way an install would and compare the
+/// result with the lockfile on disk. Packages may be downloaded into the cache; the lockfile and
+/// `node_modules` are left alone.
+pub(crate) fn lockfile_diff(project_root: &Path, config: &Config) -> Result<LockfileDiff> {
+    let manifest_path = project_root.join("package.json");
+    if !manifest_path.exists() {
+        bail!("no package.json found");
+    }
+    let manifest = crate::manifest::load(&manifest_path)?;
+    let lock_path = project_root.join("pacm.lockb");
+    if !lock_path.exists() {
+        bail!("no lockfile found to diff against; run 'pacm install'");
+    }
+    let current = lockfile::load(&lock_path)?;
+
+    let workspaces: BTreeMap<_, _> = discover_workspaces(project_root, &manifest)?
+        .into_iter()
+        .map(|ws| (ws.name.clone(), ws))
+        .collect();
+    let mut seed = current.clone();
+    seed.sync_from_manifest(&manifest);
+    let fetcher = Fetcher::from_config(config)?;
+    let options = ResolveOptions {
+        workspaces: Some(&workspaces),
+        project_root: Some(project_root),
+        specs: &[],
+        optional_specs: false,
+        prefer_offline: false,
+        offline: false,
+        force: false,
+        on_status: None,
+        deadline: None,
+        max_depth: None,
+        prefer_dedupe: false,
+        platform: TargetPlatform::host(),
+        legacy_peer_deps: config.legacy_peer_deps.unwrap_or(current.settings.legacy_peer_deps),
+        no_verify: false,
+    };
+    let mut fresh = resolve_graph(&manifest, seed, &fetcher, &options)?.lockfile;
+    prune_unreachable(&mut fresh);
+    Ok(current.diff(&fresh))
+}
+
+/// Drop lock entries and `node_modules` directories nothing depends on any more. With `store`,
+/// the store entries they used are deleted too unless another project's lockfile lists them.
+pub fn cmd_pm_prune(store: bool, config: &Config) -> Result<()> {
     let manifest_path = PathBuf::from("package.json");
     if !manifest_path.exists() {
         bail!("no package.json found");
@@ -51,6 +138,7 @@ pub fn cmd_pm_prune() -> Result<()> {
     };
 
     if build_fast_instances(&manifest, &lock, &[]).is_some() {
+        let before_prune = lock.clone();
         let removed = prune_unreachable(&mut lock);
         if !removed.is_empty() {
             remove_dirs(&removed);
@@ -68,6 +156,15 @@ pub fn cmd_pm_prune() -> Result<()> {
         } else {
             println!("{C_GRAY}[pacm]{C_RESET} nothing to prune");
         }
+        if store {
+            let project_root = std::env::current_dir()?;
+            collect_store_garbage(
+                &CasStore::open_with(config)?,
+                &project_root,
+                &before_prune,
+                &lock,
+            )?;
+        }
     } else {
         println!("{C_GRAY}[pacm]{C_RESET} {C_YELLOW}note{C_RESET}: prune requires existing cached instances; run 'pacm install'");
     }
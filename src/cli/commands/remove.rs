@@ -1,15 +1,26 @@
+use crate::cache::CasStore;
 use crate::cli::commands::install::{
-    cleanup_empty_node_modules_dir, lockfile_has_no_packages, prune_removed_from_lock,
-    prune_unreachable, remove_dirs,
+    cleanup_empty_node_modules_dir, collect_store_garbage, lockfile_has_no_packages,
+    prune_removed_from_lock, prune_unreachable, remove_dirs,
 };
+use crate::cli::commands::scripts::{run_lifecycle_phases, store_scripts};
 use crate::colors::*;
+use crate::config::Config;
+use crate::installer::{materialize_tree, PARALLEL_FILE_THRESHOLD};
 use crate::lockfile::{self, Lockfile};
 use crate::manifest;
 use anyhow::{bail, Result};
 use std::path::PathBuf;
 use std::time::Instant;
 
-pub fn cmd_remove(packages: Vec<String>) -> Result<()> {
+const UNINSTALL_PHASES: [&str; 2] = ["preuninstall", "postuninstall"];
+
+pub fn cmd_remove(
+    packages: Vec<String>,
+    run_scripts: bool,
+    gc: bool,
+    config: &Config,
+) -> Result<()> {
     let start = Instant::now();
     if packages.is_empty() {
         bail!("no packages specified to remove");
@@ -37,26 +48,83 @@ pub fn cmd_remove(packages: Vec<String>) -> Result<()> {
         return Ok(());
     }
 
-    manifest::write(&manifest, &manifest_path)?;
-
     let lock_path = PathBuf::from("pacm.lockb");
     let mut lock =
         if lock_path.exists() { lockfile::load(&lock_path)? } else { Lockfile::default() };
 
+    let before_prune = lock.clone();
     prune_removed_from_lock(&mut lock, &actually_removed);
     let trans_removed = prune_unreachable(&mut lock);
     let mut to_delete = actually_removed.clone();
     to_delete.extend(trans_removed);
+
+    // Uninstall hooks come from the store metadata, which outlives the node_modules copy.
+    let hooks: Vec<(String, serde_json::Value)> = to_delete
+        .iter()
+        .filter_map(|name| {
+            let entry = before_prune.packages.get(&format!("node_modules/{name}"))?;
+            let scripts = store_scripts(entry.store_path.as_deref()?)?;
+            let has_hooks = UNINSTALL_PHASES.iter().any(|phase| scripts.get(phase).is_some());
+            has_hooks.then(|| (name.clone(), scripts))
+        })
+        .collect();
+    let scripts_blocked = lock.settings.ignore_scripts;
+    let run_hooks = run_scripts && !scripts_blocked && !hooks.is_empty();
+    let project_root = std::env::current_dir()?;
+    // postuninstall runs after its package is gone, so it gets a throwaway copy of the
+    // node_modules directory. It never runs in the store entry other projects link to.
+    let mut scratch_dirs = ScratchDirs::default();
+    let mut post_dirs: Vec<(&str, PathBuf, &serde_json::Value)> = Vec::new();
+    if run_hooks {
+        // A failing preuninstall aborts the removal before anything is written.
+        for (name, scripts) in &hooks {
+            let pkg_dir = PathBuf::from("node_modules").join(name);
+            if !pkg_dir.exists() {
+                continue;
+            }
+            run_lifecycle_phases(&project_root, name, &pkg_dir, scripts, &["preuninstall"], false)?;
+            if scripts.get("postuninstall").is_some() {
+                let scratch = std::env::temp_dir()
+                    .join(format!("pacm-postuninstall-{}", crate::cache::unique_suffix()));
+                scratch_dirs.0.push(scratch.clone());
+                materialize_tree(&pkg_dir, &scratch, false, PARALLEL_FILE_THRESHOLD)?;
+                post_dirs.push((name, scratch, scripts));
+            }
+        }
+    }
+
+    manifest::write(&manifest, &manifest_path)?;
     if !to_delete.is_empty() {
         remove_dirs(&to_delete);
     }
-
     lockfile::write(&lock, lock_path.clone())?;
     if lockfile_has_no_packages(&lock) {
         let _ = std::fs::remove_file(&lock_path);
     }
     cleanup_empty_node_modules_dir();
 
+    if run_hooks {
+        for (name, scratch, scripts) in &post_dirs {
+            run_lifecycle_phases(&project_root, name, scratch, scripts, &["postuninstall"], false)?;
+        }
+    } else if !hooks.is_empty() {
+        let names: Vec<&str> = hooks.iter().map(|(name, _)| name.as_str()).collect();
+        let hint = if scripts_blocked {
+            "scripts are disabled for this project (--ignore-scripts)"
+        } else {
+            "pass --run-scripts to run them"
+        };
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}note{C_RESET}: uninstall scripts not run for {}; {hint}",
+            names.join(", ")
+        );
+    }
+
+    drop(scratch_dirs);
+    if gc {
+        collect_store_garbage(&CasStore::open_with(config)?, &project_root, &before_prune, &lock)?;
+    }
+
     for name in &actually_removed {
         if let Some(version) = lock
             .packages
@@ -80,3 +148,15 @@ pub fn cmd_remove(packages: Vec<String>) -> Result<()> {
     );
     Ok(())
 }
+
+/// Scratch directories handed to postuninstall, removed however [`cmd_remove`] returns.
+#[derive(Default)]
+struct ScratchDirs(Vec<PathBuf>);
+
+impl Drop for ScratchDirs {
+    fn drop(&mut self) {
+        for dir in &self.0 {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
@@ -1,9 +1,14 @@
 use crate::colors::*;
 use anyhow::{Context, Result};
 use std::ffi::OsString;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wait_timeout::ChildExt;
 
-fn path_with_bin_prefix(bin_dir: &Path) -> Option<OsString> {
+pub(crate) fn path_with_bin_prefix(bin_dir: &Path) -> Option<OsString> {
     if !bin_dir.exists() {
         return None;
     }
@@ -27,6 +32,87 @@ fn path_with_bin_prefix(bin_dir: &Path) -> Option<OsString> {
     }
 }
 
+/// `PACM_SCRIPT_TIMEOUT`: seconds a script may run before it is killed. Unset or 0 means no limit.
+pub(crate) fn script_timeout() -> Option<Duration> {
+    std::env::var("PACM_SCRIPT_TIMEOUT")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+/// Run `cmd` to completion, killing it once [`script_timeout`] elapses. With `quiet`, stdout and
+/// stderr are captured and only replayed (on stderr) when the process fails or times out.
+pub(crate) fn run_script_process(cmd: Command, label: &str, quiet: bool) -> Result<ExitStatus> {
+    run_script_process_to(cmd, label, quiet, &mut std::io::stderr().lock())
+}
+
+/// [`run_script_process`], replaying captured output into `replay` instead of stderr.
+pub(crate) fn run_script_process_to(
+    mut cmd: Command,
+    label: &str,
+    quiet: bool,
+    replay: &mut dyn Write,
+) -> Result<ExitStatus> {
+    if quiet {
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    let mut child = cmd.spawn().with_context(|| format!("spawn {label}"))?;
+    let captured = quiet.then(|| [capture(child.stdout.take()), capture(child.stderr.take())]);
+
+    let timeout = script_timeout();
+    let status = match timeout {
+        Some(limit) => {
+            let status = child.wait_timeout(limit).with_context(|| format!("wait for {label}"))?;
+            if status.is_none() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            status
+        }
+        None => Some(child.wait().with_context(|| format!("wait for {label}"))?),
+    };
+
+    if let Some(readers) = captured.filter(|_| !status.is_some_and(|s| s.success())) {
+        for (buf, reader) in readers {
+            // Drain the pipe fully once the process exited. After a timeout the readers are left
+            // running: a grandchild that outlived the kill may still hold the pipes open.
+            if status.is_some() {
+                let _ = reader.join();
+            }
+            let _ = replay.write_all(&buf.lock().unwrap_or_else(|e| e.into_inner()));
+        }
+    }
+    match (status, timeout) {
+        (Some(status), _) => Ok(status),
+        (None, limit) => anyhow::bail!(
+            "{label} timed out after {}s (PACM_SCRIPT_TIMEOUT)",
+            limit.unwrap_or_default().as_secs()
+        ),
+    }
+}
+
+type CapturedOutput = (Arc<Mutex<Vec<u8>>>, std::thread::JoinHandle<()>);
+
+/// Drain `pipe` on a background thread into a shared buffer.
+fn capture<R: Read + Send + 'static>(pipe: Option<R>) -> CapturedOutput {
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&buf);
+    let reader = std::thread::spawn(move || {
+        let Some(mut pipe) = pipe else {
+            return;
+        };
+        let mut chunk = [0u8; 8192];
+        while let Ok(n) = pipe.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            sink.lock().unwrap_or_else(|e| e.into_inner()).extend_from_slice(&chunk[..n]);
+        }
+    });
+    (buf, reader)
+}
+
 pub(crate) fn quote_arg_for_shell(arg: &str) -> String {
     if cfg!(windows) {
         // Simple Windows quoting: wrap in double quotes if spaces or special chars
@@ -77,12 +163,112 @@ pub(crate) fn build_script_command(script: &str, pass_args: &[String]) -> String
     }
 }
 
-pub fn cmd_run(args: Vec<String>) -> Result<()> {
-    if args.is_empty() {
-        println!("Usage: pacm run <script-or-binary> [args...]");
-        return Ok(());
+/// Names of the executables in `node_modules/.bin`, sorted. On Windows the `.exe`/`.cmd`
+/// suffixes are dropped and `.shim` sidecars are skipped.
+pub(crate) fn list_bin_names(bin_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(bin_dir)
+        .map(|iter| {
+            iter.filter_map(|entry| entry.ok().and_then(|e| e.file_name().into_string().ok()))
+                .filter(|name| !name.ends_with(".shim"))
+                .map(|name| {
+                    if cfg!(windows) {
+                        for ext in [".exe", ".cmd", ".ps1"] {
+                            if let Some(stem) = name.strip_suffix(ext) {
+                                return stem.to_string();
+                            }
+                        }
+                    }
+                    name
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Lines printed by a bare `pacm run`: every script with its command, then the local binaries.
+pub(crate) fn format_run_listing(
+    scripts: Option<&serde_json::Map<String, serde_json::Value>>,
+    bins: &[String],
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut entries: Vec<(&String, &str)> = scripts
+        .map(|map| map.iter().filter_map(|(k, v)| v.as_str().map(|cmd| (k, cmd))).collect())
+        .unwrap_or_default();
+    entries.sort();
+    if entries.is_empty() {
+        lines.push(format!("{C_GRAY}[pacm]{C_RESET} {C_DIM}no scripts in package.json{C_RESET}"));
+    } else {
+        lines.push(format!("{C_GRAY}[pacm]{C_RESET} available scripts:"));
+        for (name, cmd) in entries {
+            lines.push(format!("  {C_CYAN}{name}{C_RESET}\n    {C_DIM}{cmd}{C_RESET}"));
+        }
     }
+    if !bins.is_empty() {
+        lines.push(format!("{C_GRAY}[pacm]{C_RESET} binaries in node_modules/.bin:"));
+        for name in bins {
+            lines.push(format!("  {name}"));
+        }
+    }
+    lines
+}
+
+/// Run script `name` from `scripts`, wrapped by `pre<name>` and `post<name>` when those exist.
+/// Pass-through args only reach the main script, and a failing step stops the sequence.
+pub(crate) fn run_script_with_hooks(
+    project_root: &Path,
+    scripts: &serde_json::Map<String, serde_json::Value>,
+    name: &str,
+    pass_args: &[String],
+    path_env: Option<&OsString>,
+    quiet: bool,
+) -> Result<()> {
+    let steps =
+        [(format!("pre{name}"), false), (name.to_string(), true), (format!("post{name}"), false)];
+    for (step, is_main) in steps {
+        let Some(cmd_str) = scripts.get(&step).and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let final_cmd = build_script_command(cmd_str, if is_main { pass_args } else { &[] });
+        println!("{C_GRAY}[pacm]{C_RESET} running script: {step} -> {final_cmd}");
+        let mut c = if cfg!(windows) {
+            let mut cc = std::process::Command::new("cmd");
+            cc.arg("/C").arg(&final_cmd);
+            cc
+        } else {
+            let mut cc = std::process::Command::new("sh");
+            cc.arg("-c").arg(&final_cmd);
+            cc
+        };
+        c.current_dir(project_root);
+        if let Some(p) = path_env {
+            c.env("PATH", p);
+            if cfg!(windows) {
+                c.env("Path", p);
+            }
+        }
+        let status = run_script_process(c, &format!("script {step}"), quiet)?;
+        if !status.success() {
+            anyhow::bail!("script {step} failed");
+        }
+    }
+    Ok(())
+}
 
+/// `if_present` turns a missing script or binary into a no-op instead of a shell fallback;
+/// `quiet` hides the child's output unless it fails.
+pub fn cmd_run(args: Vec<String>, if_present: bool, quiet: bool) -> Result<()> {
+    // Like npm, `--if-present` anywhere before the `--` separator is pacm's own flag.
+    let sep = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let if_present = if_present || args[..sep].iter().any(|a| a == "--if-present");
+    let args: Vec<String> = args
+        .into_iter()
+        .enumerate()
+        .filter(|(i, a)| *i >= sep || a != "--if-present")
+        .map(|(_, a)| a)
+        .collect();
     let project_root = std::env::current_dir()?;
     let bin_dir = project_root.join("node_modules").join(".bin");
 
@@ -101,6 +287,13 @@ pub fn cmd_run(args: Vec<String>) -> Result<()> {
         }
     }
 
+    if args.is_empty() {
+        for line in format_run_listing(root_scripts.as_ref(), &list_bin_names(&bin_dir)) {
+            println!("{line}");
+        }
+        return Ok(());
+    }
+
     // Interpret `--` separator: everything after `--` is passed as args to script/binary
     let sep_pos = args.iter().position(|s| s == "--");
     let (first, pass_args_vec): (String, Vec<String>) = if let Some(pos) = sep_pos {
@@ -118,32 +311,15 @@ pub fn cmd_run(args: Vec<String>) -> Result<()> {
 
     // If the first arg matches a script name in package.json, run it via shell
     if let Some(scripts) = &root_scripts {
-        if let Some(cmd_val) = scripts.get(&first) {
-            if let Some(cmd_str) = cmd_val.as_str() {
-                let final_cmd = build_script_command(cmd_str, &pass_args_vec);
-                println!("{C_GRAY}[pacm]{C_RESET} running script: {first} -> {final_cmd}");
-                let mut c = if cfg!(windows) {
-                    let mut cc = std::process::Command::new("cmd");
-                    cc.arg("/C").arg(&final_cmd);
-                    cc
-                } else {
-                    let mut cc = std::process::Command::new("sh");
-                    cc.arg("-c").arg(&final_cmd);
-                    cc
-                };
-                c.current_dir(&project_root);
-                if let Some(p) = &new_path {
-                    c.env("PATH", p);
-                    if cfg!(windows) {
-                        c.env("Path", p);
-                    }
-                }
-                let status = c.status().with_context(|| format!("spawn script {first}"))?;
-                if !status.success() {
-                    anyhow::bail!("script {first} failed");
-                }
-                return Ok(());
-            }
+        if scripts.get(&first).and_then(|v| v.as_str()).is_some() {
+            return run_script_with_hooks(
+                &project_root,
+                scripts,
+                &first,
+                &pass_args_vec,
+                new_path.as_ref(),
+                quiet,
+            );
         }
     }
 
@@ -173,7 +349,7 @@ pub fn cmd_run(args: Vec<String>) -> Result<()> {
                         cmd.env("Path", p);
                     }
                 }
-                let status = cmd.status().with_context(|| format!("spawn binary {first}"))?;
+                let status = run_script_process(cmd, &format!("binary {first}"), quiet)?;
                 if !status.success() {
                     anyhow::bail!("binary {first} failed");
                 }
@@ -182,6 +358,13 @@ pub fn cmd_run(args: Vec<String>) -> Result<()> {
         }
     }
 
+    if if_present {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_DIM}no script or binary named {first}; skipping (--if-present){C_RESET}"
+        );
+        return Ok(());
+    }
+
     // Fallback: run as a shell command (this will use PATH which we've prefixed)
     let joined = args.join(" ");
     println!("{C_GRAY}[pacm]{C_RESET} running shell: {joined}");
@@ -201,7 +384,7 @@ pub fn cmd_run(args: Vec<String>) -> Result<()> {
             sh.env("Path", p);
         }
     }
-    let status = sh.status().with_context(|| "spawn fallback shell")?;
+    let status = run_script_process(sh, "fallback shell", quiet)?;
     if !status.success() {
         anyhow::bail!("command failed");
     }
@@ -1,14 +1,21 @@
+use super::run::{path_with_bin_prefix, run_script_process};
 use crate::colors::*;
 use crate::lockfile::Lockfile;
-use anyhow::{Context, Result};
-use std::path::PathBuf;
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 
+#[allow(clippy::too_many_arguments)]
 pub fn cmd_scripts_run(
     packages: Vec<String>,
     all: bool,
+    root: bool,
     ignore_scripts: bool,
     yes: bool,
     per_package: bool,
+    force: bool,
+    quiet: bool,
 ) -> Result<()> {
     if ignore_scripts {
         println!("{C_GRAY}[pacm]{C_RESET} scripts are ignored by flag");
@@ -18,10 +25,17 @@ pub fn cmd_scripts_run(
     let project_root = std::env::current_dir()?;
     let lock_path = project_root.join("pacm.lockb");
     let lock = Lockfile::load_or_default(lock_path)?;
+    if lock.settings.ignore_scripts && !force {
+        bail!(
+            "lifecycle scripts are disabled for this project (installed with --ignore-scripts); pass --force to run them anyway"
+        );
+    }
 
     // gather candidate packages
     let mut candidates: Vec<String> = Vec::new();
-    if all {
+    if root {
+        // --root: only the project's own scripts below, no dependencies
+    } else if all {
         for k in lock.packages.keys() {
             if k.starts_with("node_modules/") {
                 if let Some(name) = k.strip_prefix("node_modules/") {
@@ -46,6 +60,9 @@ pub fn cmd_scripts_run(
         }
     }
 
+    let candidates = filter_allowed_scripts(&project_root, candidates, force)?;
+    let candidates = dependency_order(&lock, candidates);
+
     // also optionally include root
     let local_pkg = project_root.join("package.json");
     let mut root_scripts = None;
@@ -85,9 +102,11 @@ pub fn cmd_scripts_run(
                                     }
                                 }
                                 run_lifecycle_for_package(
+                                    &project_root,
                                     pkg,
                                     &project_root.join("node_modules").join(pkg),
                                     scripts,
+                                    quiet,
                                 )?;
                             }
                         }
@@ -98,6 +117,9 @@ pub fn cmd_scripts_run(
     }
 
     // If root selected or all, run root lifecycle scripts at end
+    if root && root_scripts.is_none() {
+        println!("{C_GRAY}[pacm]{C_RESET} project root has no scripts");
+    }
     if let Some(scripts) = root_scripts {
         if !yes {
             println!("{C_GRAY}[pacm]{C_RESET} run scripts for project root? [y/N]");
@@ -109,20 +131,262 @@ pub fn cmd_scripts_run(
             }
         }
         // per the requested order: root preinstall before deps already not applicable since install refused to run scripts.
-        run_lifecycle_for_package("<root>", &project_root, &scripts)?;
+        run_lifecycle_for_package(&project_root, "<root>", &project_root, &scripts, quiet)?;
+    }
+
+    Ok(())
+}
+
+/// Re-run the install lifecycle scripts of installed packages, e.g. to rebuild native addons
+/// after switching Node versions. Scripts run in each package's `node_modules` directory.
+pub fn cmd_rebuild(packages: Vec<String>, all: bool, force: bool) -> Result<()> {
+    let project_root = std::env::current_dir()?;
+    let lock = Lockfile::load_or_default(project_root.join("pacm.lockb"))?;
+    if lock.settings.ignore_scripts && !force {
+        bail!(
+            "lifecycle scripts are disabled for this project (installed with --ignore-scripts); pass --force to rebuild anyway"
+        );
+    }
+
+    let candidates: Vec<String> = if all {
+        lock.packages
+            .keys()
+            .filter_map(|k| k.strip_prefix("node_modules/"))
+            .map(String::from)
+            .collect()
+    } else if packages.is_empty() {
+        bail!("name the packages to rebuild, or pass --all");
+    } else {
+        for pkg in &packages {
+            if !lock.packages.contains_key(&format!("node_modules/{pkg}")) {
+                bail!("{pkg} is not installed");
+            }
+        }
+        packages
+    };
+    let candidates = filter_allowed_scripts(&project_root, candidates, force)?;
+    let candidates = dependency_order(&lock, candidates);
+
+    let mut rebuilt = 0usize;
+    for pkg in &candidates {
+        let entry = &lock.packages[&format!("node_modules/{pkg}")];
+        let Some(scripts) = entry.store_path.as_deref().and_then(store_scripts) else {
+            continue;
+        };
+        if !["preinstall", "install", "postinstall"]
+            .iter()
+            .any(|phase| scripts.get(phase).is_some())
+        {
+            continue;
+        }
+        println!("{C_GRAY}[pacm]{C_RESET} rebuilding {pkg}");
+        run_lifecycle_for_package(
+            &project_root,
+            pkg,
+            &project_root.join("node_modules").join(pkg),
+            &scripts,
+            false,
+        )?;
+        rebuilt += 1;
     }
 
+    if rebuilt == 0 {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_DIM}nothing to rebuild{C_RESET}");
+    } else {
+        println!("{C_GRAY}[pacm]{C_RESET} {C_GREEN}rebuilt{C_RESET} {rebuilt} package(s)");
+    }
     Ok(())
 }
 
+/// Run the install lifecycle scripts of `packages` in dependency order, honoring
+/// `allowedScripts`, then the project root's; used by `pacm install --run-scripts`.
+pub(crate) fn run_install_scripts(
+    project_root: &Path,
+    lock: &Lockfile,
+    packages: Vec<String>,
+) -> Result<()> {
+    let install_scripts = |store_path: Option<&str>| {
+        store_path.and_then(store_scripts).filter(|scripts| {
+            ["preinstall", "install", "postinstall"]
+                .iter()
+                .any(|phase| scripts.get(phase).is_some())
+        })
+    };
+    let with_scripts: Vec<String> = packages
+        .into_iter()
+        .filter(|pkg| {
+            lock.packages
+                .get(&format!("node_modules/{pkg}"))
+                .and_then(|entry| install_scripts(entry.store_path.as_deref()))
+                .is_some()
+        })
+        .collect();
+    let candidates = filter_allowed_scripts(project_root, with_scripts, false)?;
+    for pkg in dependency_order(lock, candidates) {
+        let entry = &lock.packages[&format!("node_modules/{pkg}")];
+        if let Some(scripts) = install_scripts(entry.store_path.as_deref()) {
+            run_lifecycle_for_package(
+                project_root,
+                &pkg,
+                &project_root.join("node_modules").join(&pkg),
+                &scripts,
+                false,
+            )?;
+        }
+    }
+
+    let root_scripts = std::fs::read_to_string(project_root.join("package.json"))
+        .ok()
+        .and_then(|txt| serde_json::from_str::<serde_json::Value>(&txt).ok())
+        .and_then(|val| val.get("scripts").cloned());
+    if let Some(scripts) = root_scripts {
+        run_lifecycle_for_package(project_root, "<root>", project_root, &scripts, false)?;
+    }
+    Ok(())
+}
+
+/// Order `candidates` so each package's scripts run after those of the candidates it depends on
+/// (through `dependencies` or `optionalDependencies` in the lockfile). Ties break
+/// alphabetically; a dependency cycle falls back to plain alphabetical order with a warning.
+fn dependency_order(lock: &Lockfile, candidates: Vec<String>) -> Vec<String> {
+    let set: BTreeSet<String> = candidates.into_iter().collect();
+    let mut pending: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for name in &set {
+        let deps = lock
+            .packages
+            .get(&format!("node_modules/{name}"))
+            .map(|entry| {
+                entry
+                    .dependencies
+                    .keys()
+                    .chain(entry.optional_dependencies.keys())
+                    .filter(|dep| *dep != name && set.contains(*dep))
+                    .map(String::as_str)
+                    .collect()
+            })
+            .unwrap_or_default();
+        pending.insert(name.as_str(), deps);
+    }
+
+    let mut ordered: Vec<String> = Vec::with_capacity(set.len());
+    while !pending.is_empty() {
+        let ready: Vec<&str> =
+            pending.iter().filter(|(_, deps)| deps.is_empty()).map(|(name, _)| *name).collect();
+        if ready.is_empty() {
+            let cycle: Vec<&str> = pending.keys().copied().collect();
+            println!(
+                "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}warning{C_RESET} dependency cycle among {}; running their scripts alphabetically",
+                cycle.join(", ")
+            );
+            return set.into_iter().collect();
+        }
+        for name in ready {
+            pending.remove(name);
+            for deps in pending.values_mut() {
+                deps.remove(name);
+            }
+            ordered.push(name.to_string());
+        }
+    }
+    ordered
+}
+
+/// Only allowlisted packages may run scripts when the manifest declares `allowedScripts`;
+/// `force` lifts the restriction.
+fn filter_allowed_scripts(
+    project_root: &Path,
+    candidates: Vec<String>,
+    force: bool,
+) -> Result<Vec<String>> {
+    let manifest_path = project_root.join("package.json");
+    let allowed = if manifest_path.exists() {
+        crate::manifest::load(&manifest_path)?.allowed_scripts
+    } else {
+        None
+    };
+    let Some(allowed) = allowed.filter(|_| !force) else {
+        return Ok(candidates);
+    };
+    let (permitted, blocked): (Vec<String>, Vec<String>) =
+        candidates.into_iter().partition(|name| allowed.iter().any(|a| a == name));
+    if !blocked.is_empty() {
+        println!(
+            "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}skipped{C_RESET} scripts for packages not in allowedScripts: {}",
+            blocked.join(", ")
+        );
+    }
+    Ok(permitted)
+}
+
+/// Environment for a lifecycle script, modelled on what npm provides: the project's
+/// `node_modules/.bin` ahead of PATH, `npm_package_*` from the package manifest, `INIT_CWD`
+/// and the `npm_lifecycle_*` pair describing the running phase.
+pub(crate) fn lifecycle_env(
+    project_root: &Path,
+    pkg_dir: &Path,
+    phase: &str,
+    script: &str,
+) -> BTreeMap<String, OsString> {
+    let mut env: BTreeMap<String, OsString> = BTreeMap::new();
+    if let Some(path) = path_with_bin_prefix(&project_root.join("node_modules").join(".bin")) {
+        env.insert("PATH".into(), path);
+    }
+    env.insert("INIT_CWD".into(), project_root.as_os_str().to_owned());
+    env.insert("npm_lifecycle_event".into(), phase.into());
+    env.insert("npm_lifecycle_script".into(), script.into());
+
+    let manifest_path = pkg_dir.join("package.json");
+    if let Some(manifest) = std::fs::read_to_string(&manifest_path)
+        .ok()
+        .and_then(|txt| serde_json::from_str::<serde_json::Value>(&txt).ok())
+    {
+        for key in ["name", "version"] {
+            if let Some(value) = manifest.get(key).and_then(|v| v.as_str()) {
+                env.insert(format!("npm_package_{key}"), value.into());
+            }
+        }
+        env.insert("npm_package_json".into(), manifest_path.into_os_string());
+    }
+    env
+}
+
 fn run_lifecycle_for_package(
+    project_root: &Path,
     name: &str,
-    pkg_dir: &PathBuf,
+    pkg_dir: &Path,
     scripts: &serde_json::Value,
+    quiet: bool,
 ) -> Result<()> {
-    use std::process::Command;
     // execute preinstall -> install -> postinstall if present
-    for phase in ["preinstall", "install", "postinstall"] {
+    run_lifecycle_phases(
+        project_root,
+        name,
+        pkg_dir,
+        scripts,
+        &["preinstall", "install", "postinstall"],
+        quiet,
+    )
+}
+
+/// Read the `scripts` object recorded in a store entry's metadata.json.
+pub(crate) fn store_scripts(store_path: &str) -> Option<serde_json::Value> {
+    let txt = std::fs::read_to_string(Path::new(store_path).join("metadata.json")).ok()?;
+    let val: serde_json::Value = serde_json::from_str(&txt).ok()?;
+    val.get("scripts").cloned()
+}
+
+/// Run the given lifecycle `phases` of `scripts` in order, skipping phases that aren't defined.
+/// `quiet` hides each script's output unless it fails.
+pub(crate) fn run_lifecycle_phases(
+    project_root: &Path,
+    name: &str,
+    pkg_dir: &Path,
+    scripts: &serde_json::Value,
+    phases: &[&str],
+    quiet: bool,
+) -> Result<()> {
+    use std::process::Command;
+    for &phase in phases {
         if let Some(cmd_val) = scripts.get(phase) {
             if let Some(cmd_str) = cmd_val.as_str() {
                 println!("{C_GRAY}[pacm]{C_RESET} running {phase} for {name}: {cmd_str}");
@@ -136,8 +400,15 @@ fn run_lifecycle_for_package(
                     cc
                 };
                 c.current_dir(pkg_dir);
-                // inherit env
-                let status = c.status().with_context(|| format!("spawn {phase} for {name}"))?;
+                // Drop npm_* variables inherited from a parent script so they can't leak into
+                // this package's run, then layer the lifecycle env over the remaining env.
+                for (key, _) in std::env::vars_os() {
+                    if key.to_string_lossy().starts_with("npm_") {
+                        c.env_remove(key);
+                    }
+                }
+                c.envs(lifecycle_env(project_root, pkg_dir, phase, cmd_str));
+                let status = run_script_process(c, &format!("{phase} for {name}"), quiet)?;
                 if !status.success() {
                     anyhow::bail!("script {phase} failed for {name}");
                 }
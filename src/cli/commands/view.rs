@@ -0,0 +1,110 @@
+use crate::colors::*;
+use crate::config::Config;
+use crate::fetch::Fetcher;
+use crate::output::{print_json, OutputFormat};
+use crate::resolver::spec::parse_spec;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+
+/// `pacm view <pkg> [field]`: show registry information for a package, like `npm view`.
+pub fn cmd_view(
+    package: String,
+    field: Option<String>,
+    format: OutputFormat,
+    config: &Config,
+) -> Result<()> {
+    let (name, tag) = parse_spec(&package);
+    let fetcher = Fetcher::from_config(config)?;
+    let doc = fetcher.package_document(&name)?;
+    let version = pick_view_version(&doc, &tag)
+        .ok_or_else(|| anyhow!("no version of {name} matches '{tag}'"))?;
+
+    if let Some(field) = field {
+        let value = view_field(&doc, &version, &field)
+            .ok_or_else(|| anyhow!("{name}@{version} has no field '{field}'"))?;
+        match (&value, format.is_json()) {
+            (Value::String(text), false) => println!("{text}"),
+            _ => print_json(&value)?,
+        }
+        return Ok(());
+    }
+
+    let summary = view_summary(&doc, &version);
+    if format.is_json() {
+        print_json(&summary)
+    } else {
+        print_summary(&summary);
+        Ok(())
+    }
+}
+
+/// The version `pacm view` describes: the `latest` dist-tag when no tag or version is given,
+/// otherwise the named dist-tag or exact version.
+pub(crate) fn pick_view_version(doc: &Value, tag: &str) -> Option<String> {
+    let tag = if tag == "*" || tag.is_empty() { "latest" } else { tag };
+    if let Some(version) = doc.pointer(&format!("/dist-tags/{tag}")).and_then(Value::as_str) {
+        return Some(version.to_string());
+    }
+    doc.get("versions")?.get(tag).map(|_| tag.to_string())
+}
+
+/// A field of `version`'s metadata, with dotted paths such as `dist.tarball`. Fields the
+/// version does not have fall back to the package document (`dist-tags`, `time`).
+pub(crate) fn view_field(doc: &Value, version: &str, field: &str) -> Option<Value> {
+    let pointer = format!("/{}", field.replace('.', "/"));
+    let version_meta = doc.get("versions")?.get(version)?;
+    version_meta.pointer(&pointer).or_else(|| doc.pointer(&pointer)).cloned()
+}
+
+/// Name, version, description, license, dist-tags and every published version (oldest first).
+pub(crate) fn view_summary(doc: &Value, version: &str) -> Value {
+    let meta = doc.pointer(&format!("/versions/{version}")).cloned().unwrap_or(Value::Null);
+    let mut versions: Vec<&String> = doc
+        .get("versions")
+        .and_then(Value::as_object)
+        .map(|v| v.keys().collect())
+        .unwrap_or_default();
+    versions.sort_by(|a, b| match (semver::Version::parse(a), semver::Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    });
+    json!({
+        "name": doc.get("name").or_else(|| meta.get("name")).cloned().unwrap_or(Value::Null),
+        "version": version,
+        "description": meta.get("description").or_else(|| doc.get("description")).cloned(),
+        "license": meta.get("license").cloned().map(license_text),
+        "distTags": doc.get("dist-tags").cloned().unwrap_or_else(|| Value::Object(Map::new())),
+        "versions": versions,
+    })
+}
+
+/// Old packages declare `license` as `{ "type": "MIT" }`.
+fn license_text(license: Value) -> Value {
+    match license.get("type") {
+        Some(kind) => kind.clone(),
+        None => license,
+    }
+}
+
+fn print_summary(summary: &Value) {
+    let text = |key: &str| summary.get(key).and_then(Value::as_str).unwrap_or("");
+    println!("{C_CYAN}{}{C_RESET}@{C_GREEN}{}{C_RESET}", text("name"), text("version"));
+    if !text("description").is_empty() {
+        println!("{}", text("description"));
+    }
+    if !text("license").is_empty() {
+        println!("{C_DIM}license:{C_RESET} {}", text("license"));
+    }
+    if let Some(tags) = summary.get("distTags").and_then(Value::as_object) {
+        if !tags.is_empty() {
+            println!("\n{C_DIM}dist-tags:{C_RESET}");
+            for (tag, version) in tags {
+                println!("  {tag}: {}", version.as_str().unwrap_or_default());
+            }
+        }
+    }
+    if let Some(versions) = summary.get("versions").and_then(Value::as_array) {
+        let list: Vec<&str> = versions.iter().filter_map(Value::as_str).collect();
+        println!("\n{C_DIM}versions ({}):{C_RESET} {}", list.len(), list.join(", "));
+    }
+}
@@ -1,3 +1,7 @@
+use crate::colors::ColorMode;
+use crate::config::Config;
+use crate::output::OutputFormat;
+use crate::resolver::platform::TargetPlatform;
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 
@@ -11,6 +15,22 @@ pub mod commands;
     long_about = "pacm — a blazing fast, cache-first package manager.\n\nExamples:\n  pacm init --name my-app\n  pacm install\n  pacm add axios\n  pacm cache path\n  pacm cache clean"
 )]
 pub struct PacmCli {
+    /// Print diagnostics to stderr; repeat (-vv) for more detail
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count, global = true)]
+    pub(crate) verbose: u8,
+    /// Print machine-readable JSON instead of text (list, audit, view, cache size); errors are
+    /// reported on stderr as `{"error", "context"}` JSON
+    #[arg(long, global = true)]
+    pub(crate) json: bool,
+    /// When to color output; `auto` respects NO_COLOR/FORCE_COLOR and whether stdout is a TTY
+    #[arg(
+        long,
+        global = true,
+        value_name = "WHEN",
+        default_value = "auto",
+        value_parser = ["auto", "always", "never"]
+    )]
+    pub(crate) color: String,
     #[command(subcommand)]
     pub(crate) command: Option<Commands>,
 }
@@ -27,35 +47,130 @@ pub enum Commands {
     /// Remove one or more dependencies
     Remove {
         packages: Vec<String>,
+        /// Run preuninstall/postuninstall scripts of removed packages
+        #[arg(long)]
+        run_scripts: bool,
+        /// Delete store entries this project no longer references
+        #[arg(long)]
+        gc: bool,
     },
     /// Install all dependencies or add specific packages
     #[command(alias = "i")]
     Install {
         packages: Vec<String>,
-        #[arg(long, short = 'D')]
+        /// Save to devDependencies. Only valid when adding packages: a bare install always
+        /// installs every dependency section
+        #[arg(
+            long,
+            short = 'D',
+            visible_alias = "save-dev",
+            conflicts_with = "optional",
+            requires = "packages"
+        )]
         dev: bool,
-        #[arg(long)]
+        /// Save to optionalDependencies. Only valid when adding packages
+        #[arg(long, short = 'O', visible_alias = "save-optional", requires = "packages")]
         optional: bool,
+        /// Save to dependencies (the default). Only valid when adding packages
+        #[arg(long, short = 'P', conflicts_with_all = ["dev", "optional"], requires = "packages")]
+        save_prod: bool,
         #[arg(long = "no-save")]
         no_save: bool,
         #[arg(long)]
         exact: bool,
         #[arg(long)]
         prefer_offline: bool,
+        /// Never touch the network; fail if anything is missing from the cache
+        #[arg(long, conflicts_with = "prefer_offline")]
+        offline: bool,
         #[arg(long)]
         no_progress: bool,
         #[arg(long)]
         link: bool,
         #[arg(long)]
         copy: bool,
+        /// Resolve and update pacm.lockb without touching node_modules
+        #[arg(long)]
+        lockfile_only: bool,
+        /// Install into the global prefix instead of the current project
+        #[arg(long, short = 'g')]
+        global: bool,
+        /// Permanently disable lifecycle scripts for this project (recorded in pacm.lockb)
+        #[arg(long)]
+        ignore_scripts: bool,
+        /// Only install dependencies up to this many levels below package.json (0 = direct
+        /// dependencies only); leaves node_modules incomplete and pacm.lockb unchanged
+        #[arg(long, value_name = "N", conflicts_with = "lockfile_only")]
+        depth: Option<usize>,
+        /// Run install lifecycle scripts after installing instead of only listing them
+        #[arg(long, alias = "foreground-scripts", conflicts_with = "ignore_scripts")]
+        run_scripts: bool,
+        /// Permanently skip creating node_modules/.bin shims (recorded in pacm.lockb)
+        #[arg(long)]
+        no_bin_links: bool,
+        /// Ignore the cache and store; download and extract every package again
+        #[arg(long)]
+        force: bool,
+        /// Delete store entries this project no longer references
+        #[arg(long)]
+        gc: bool,
+        /// Install from the lockfile when it satisfies package.json, resolving only when it is
+        /// out of date (default); pass --prefer-frozen=false to always resolve
+        #[arg(
+            long,
+            value_name = "BOOL",
+            num_args = 0..=1,
+            default_value_t = true,
+            default_missing_value = "true",
+            action = clap::ArgAction::Set
+        )]
+        prefer_frozen: bool,
+        /// Fail when package.json's packageManager names another pacm version or another tool
+        #[arg(long)]
+        strict: bool,
+        /// Prefer one version that every dependent's range accepts over the newest match for
+        /// each range
+        #[arg(long)]
+        prefer_dedupe: bool,
+        /// Copy workspace packages into node_modules through the store (default); pass
+        /// --install-links=false to symlink them to their source instead (recorded in pacm.lockb)
+        #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+        install_links: Option<bool>,
+        /// Install platform-specific packages for this OS instead of the host's (e.g. linux,
+        /// darwin, win32); leaves pacm.lockb unchanged
+        #[arg(long, value_name = "OS", conflicts_with_all = ["lockfile_only", "gc", "global"])]
+        os: Option<String>,
+        /// Install platform-specific packages for this CPU instead of the host's (e.g. x64, arm64)
+        #[arg(long, value_name = "CPU", conflicts_with_all = ["lockfile_only", "gc", "global"])]
+        cpu: Option<String>,
+        /// Install platform-specific packages for this libc (glibc or musl) on a Linux target
+        #[arg(long, value_name = "LIBC", conflicts_with_all = ["lockfile_only", "gc", "global"])]
+        libc: Option<String>,
+        /// Regenerate pacm.lockb from package.json after a merge conflict instead of reusing it;
+        /// an unreadable lockfile is discarded
+        #[arg(long, conflicts_with_all = ["depth", "global", "os", "cpu", "libc"])]
+        merge_lockfile: bool,
+        /// DANGEROUS: accept downloads that fail integrity verification, recording their actual
+        /// hash. Only for debugging a registry that serves mismatched integrity
+        #[arg(long)]
+        no_verify: bool,
+        /// Verify registry signatures of every registry package against the registry's signing
+        /// keys; an invalid signature fails the install
+        #[arg(long, conflicts_with = "offline")]
+        audit_signatures: bool,
     },
     /// Alias for install <pkg>
     Add {
         package: String,
-        #[arg(long, short = 'D')]
+        /// Save to devDependencies
+        #[arg(long, short = 'D', visible_alias = "save-dev", conflicts_with = "optional")]
         dev: bool,
-        #[arg(long)]
+        /// Save to optionalDependencies
+        #[arg(long, short = 'O', visible_alias = "save-optional")]
         optional: bool,
+        /// Save to dependencies (the default)
+        #[arg(long, short = 'P', conflicts_with_all = ["dev", "optional"])]
+        save_prod: bool,
         #[arg(long = "no-save")]
         no_save: bool,
         #[arg(long)]
@@ -64,8 +179,50 @@ pub enum Commands {
         link: bool,
         #[arg(long)]
         copy: bool,
+        /// Install into the global prefix instead of the current project
+        #[arg(long, short = 'g')]
+        global: bool,
+        /// Resolve the registry's `latest` and rewrite the declared range to it
+        #[arg(long)]
+        latest: bool,
+        /// Install the version behind this dist-tag and save the tag itself as the range
+        #[arg(long, value_name = "DIST_TAG", conflicts_with = "latest")]
+        tag: Option<String>,
+    },
+    /// Without arguments, register the current package for linking; with package names,
+    /// symlink those registered packages into this project's node_modules
+    Link { packages: Vec<String> },
+    /// Without arguments, unregister the current package; with package names, remove those
+    /// links from this project's node_modules
+    Unlink { packages: Vec<String> },
+    /// Show registry information for a package: its latest version, description, license,
+    /// dist-tags and published versions, or a single field such as `version` or `dist.tarball`
+    View {
+        /// Package name, optionally with a dist-tag or exact version (`react@next`)
+        package: String,
+        field: Option<String>,
+    },
+    /// Check node, the store, the lockfile and node_modules for common problems
+    Doctor,
+    /// Create pacm.lockb from a package-lock.json or pnpm-lock.yaml
+    ImportLock { file: String },
+    /// Report known vulnerabilities in locked packages using the registry advisory database
+    Audit {
+        /// Exit non-zero when advisories at or above this severity are found
+        #[arg(long, default_value = "high")]
+        audit_level: String,
+    },
+    List {
+        /// List globally installed packages
+        #[arg(long, short = 'g')]
+        global: bool,
+        /// Only packages needed by production dependencies
+        #[arg(long, conflicts_with = "dev")]
+        prod: bool,
+        /// Only packages installed solely for devDependencies
+        #[arg(long)]
+        dev: bool,
     },
-    List,
     Cache {
         #[command(subcommand)]
         cmd: CacheCmd,
@@ -79,10 +236,34 @@ pub enum Commands {
         #[command(subcommand)]
         cmd: ScriptsCmd,
     },
-    /// Run a script from package.json or execute a local binary in node_modules/.bin
+    /// Clean install from pacm.lockb: removes node_modules and installs offline without
+    /// resolving, failing if the lockfile is missing, outdated, or references uncached packages
+    Ci {
+        #[arg(long)]
+        no_progress: bool,
+    },
+    /// Re-run install scripts (preinstall/install/postinstall) of installed packages, e.g. to
+    /// rebuild native addons after a Node upgrade
+    Rebuild {
+        packages: Vec<String>,
+        /// Rebuild every installed package that has install scripts
+        #[arg(long, conflicts_with = "packages")]
+        all: bool,
+        /// Run even if scripts are disabled (--ignore-scripts) or not in allowedScripts
+        #[arg(long)]
+        force: bool,
+    },
+    /// Run a script from package.json or execute a local binary in node_modules/.bin;
+    /// lists the available scripts and binaries when called without arguments
     Run {
+        /// Succeed without doing anything when no such script or binary exists
+        #[arg(long)]
+        if_present: bool,
+        /// Only show the script's output if it fails
+        #[arg(long)]
+        quiet: bool,
         /// script name or binary to run; remaining args are passed-through
-        #[arg(trailing_var_arg = true, required = true)]
+        #[arg(trailing_var_arg = true)]
         args: Vec<String>,
     },
 }
@@ -94,6 +275,9 @@ pub enum ScriptsCmd {
         packages: Vec<String>,
         #[arg(long)]
         all: bool,
+        /// Run only the project root's lifecycle scripts, skipping dependencies
+        #[arg(long, conflicts_with_all = ["packages", "all"])]
+        root: bool,
         #[arg(long)]
         ignore_scripts: bool,
         /// Skip confirmation prompts and run immediately
@@ -102,6 +286,12 @@ pub enum ScriptsCmd {
         /// Prompt for each package individually instead of a single confirmation
         #[arg(long = "per-package")]
         per_package: bool,
+        /// Run even if scripts are disabled (--ignore-scripts) or not in allowedScripts
+        #[arg(long)]
+        force: bool,
+        /// Only show a script's output if it fails
+        #[arg(long)]
+        quiet: bool,
     },
 }
 
@@ -109,8 +299,25 @@ pub enum ScriptsCmd {
 pub enum CacheCmd {
     /// Show the cache path on this machine
     Path,
+    /// Show how much disk the cache and the store use
+    Size,
     /// Clean the cache (remove all cached packages)
-    Clean,
+    Clean {
+        /// Only remove this package (`name` or `name@version`)
+        #[arg(long)]
+        package: Option<String>,
+    },
+    /// Delete store entries that no project's pacm.lockb uses any more
+    Gc,
+    /// Write the cached packages to a tar archive for seeding another machine
+    Export {
+        file: String,
+        /// Include the content-addressed store as well
+        #[arg(long)]
+        store: bool,
+    },
+    /// Merge a cache archive from `pacm cache export`, keeping entries that already exist
+    Import { file: String },
 }
 
 #[derive(Subcommand, Debug)]
@@ -120,8 +327,15 @@ pub enum PmCmd {
         format: String,
         #[arg(long, short = 's')]
         save: bool,
+        /// Show the package versions an install would add, remove or change, without writing
+        #[arg(long, conflicts_with_all = ["format", "save"])]
+        diff: bool,
+    },
+    Prune {
+        /// Also delete store entries the pruned packages used, unless another project needs them
+        #[arg(long)]
+        store: bool,
     },
-    Prune,
     Ls,
 }
 
@@ -130,7 +344,17 @@ impl PacmCli {
         <Self as Parser>::parse()
     }
 
+    /// The output format selected by the global `--json` flag.
+    pub fn output_format(&self) -> OutputFormat {
+        OutputFormat::from_json_flag(self.json)
+    }
+
     pub fn run(&self) -> Result<()> {
+        crate::logging::set_verbosity(self.verbose);
+        crate::colors::set_color_mode(ColorMode::parse(&self.color).unwrap_or_default());
+        let format = self.output_format();
+        // Commands share one view of the environment and rc files instead of rereading them.
+        let config = Config::load();
         match &self.command {
             None => {
                 self.print_help();
@@ -143,72 +367,202 @@ impl PacmCli {
                 packages,
                 dev,
                 optional,
+                save_prod: _,
                 no_save,
                 exact,
                 prefer_offline,
+                offline,
                 no_progress,
                 link,
                 copy,
-            }) => commands::cmd_install(
-                packages.clone(),
-                commands::InstallOptions {
+                lockfile_only,
+                global,
+                ignore_scripts,
+                no_bin_links,
+                force,
+                gc,
+                prefer_frozen,
+                strict,
+                run_scripts,
+                depth,
+                prefer_dedupe,
+                install_links,
+                os,
+                cpu,
+                libc,
+                merge_lockfile,
+                no_verify,
+                audit_signatures,
+            }) => {
+                let options = commands::InstallOptions {
                     dev: *dev,
                     optional: *optional,
                     no_save: *no_save,
                     exact: *exact,
                     prefer_offline: *prefer_offline,
+                    offline: *offline,
                     no_progress: *no_progress,
                     link: *link,
                     copy: *copy,
-                },
-            ),
-            Some(Commands::Add { package, dev, optional, no_save, exact, link, copy }) => {
-                commands::cmd_install(
-                    vec![package.clone()],
+                    lockfile_only: *lockfile_only,
+                    ignore_scripts: *ignore_scripts,
+                    // Only the flag is saved to the lockfile; an .pacmrc/env default skips
+                    // scripts for this run, and --run-scripts overrides it.
+                    skip_scripts: !*run_scripts && config.ignore_scripts.unwrap_or(false),
+                    force: *force,
+                    gc: *gc,
+                    prefer_frozen: *prefer_frozen,
+                    latest: false,
+                    no_bin_links: *no_bin_links,
+                    strict: *strict,
+                    run_scripts: *run_scripts,
+                    frozen_lockfile: false,
+                    depth: *depth,
+                    prefer_dedupe: *prefer_dedupe,
+                    install_links: *install_links,
+                    save_tag: false,
+                    platform: TargetPlatform::with_overrides(
+                        os.as_deref(),
+                        cpu.as_deref(),
+                        libc.as_deref(),
+                    )?,
+                    merge_lockfile: *merge_lockfile,
+                    no_verify: *no_verify,
+                    audit_signatures: *audit_signatures,
+                    config,
+                };
+                if *global {
+                    commands::cmd_install_global(packages.clone(), options)
+                } else {
+                    commands::cmd_install(packages.clone(), options)
+                }
+            }
+            Some(Commands::Add {
+                package,
+                dev,
+                optional,
+                save_prod: _,
+                no_save,
+                exact,
+                link,
+                copy,
+                global,
+                latest,
+                tag,
+            }) => {
+                let package = match tag {
+                    Some(tag) => commands::install::manifest_updates::tagged_spec(package, tag)?,
+                    None => package.clone(),
+                };
+                let install =
+                    if *global { commands::cmd_install_global } else { commands::cmd_install };
+                install(
+                    vec![package],
                     commands::InstallOptions {
                         dev: *dev,
                         optional: *optional,
                         no_save: *no_save,
                         exact: *exact,
                         prefer_offline: false,
+                        offline: false,
                         no_progress: false,
                         link: *link,
                         copy: *copy,
+                        lockfile_only: false,
+                        ignore_scripts: false,
+                        skip_scripts: config.ignore_scripts.unwrap_or(false),
+                        force: false,
+                        gc: false,
+                        prefer_frozen: true,
+                        latest: *latest,
+                        no_bin_links: false,
+                        strict: false,
+                        run_scripts: false,
+                        frozen_lockfile: false,
+                        depth: None,
+                        prefer_dedupe: false,
+                        install_links: None,
+                        save_tag: tag.is_some(),
+                        platform: TargetPlatform::host(),
+                        merge_lockfile: false,
+                        no_verify: false,
+                        audit_signatures: false,
+                        config,
                     },
                 )
             }
-            Some(Commands::Remove { packages }) => commands::cmd_remove(packages.clone()),
-            Some(Commands::List) => commands::cmd_list(),
+            Some(Commands::Remove { packages, run_scripts, gc }) => {
+                commands::cmd_remove(packages.clone(), *run_scripts, *gc, &config)
+            }
+            Some(Commands::Link { packages }) => commands::cmd_link(packages.clone(), &config),
+            Some(Commands::Unlink { packages }) => commands::cmd_unlink(packages.clone(), &config),
+            Some(Commands::View { package, field }) => {
+                commands::cmd_view(package.clone(), field.clone(), format, &config)
+            }
+            Some(Commands::Doctor) => commands::cmd_doctor(&config),
+            Some(Commands::ImportLock { file }) => commands::cmd_import_lock(file.clone()),
+            Some(Commands::Audit { audit_level }) => {
+                commands::cmd_audit(format, audit_level.clone(), &config)
+            }
+            Some(Commands::List { global, prod, dev }) => {
+                commands::cmd_list(*global, *prod, *dev, format, &config)
+            }
             Some(Commands::Cache { cmd }) => match cmd {
                 CacheCmd::Path => commands::cmd_cache_path(),
-                CacheCmd::Clean => commands::cmd_cache_clean(),
+                CacheCmd::Size => commands::cmd_cache_size(format, &config),
+                CacheCmd::Clean { package } => commands::cmd_cache_clean(package.clone()),
+                CacheCmd::Gc => commands::cmd_cache_gc(format, &config),
+                CacheCmd::Export { file, store } => {
+                    commands::cmd_cache_export(file, *store, &config)
+                }
+                CacheCmd::Import { file } => commands::cmd_cache_import(file, &config),
             },
             Some(Commands::Pm { cmd }) => match cmd {
-                PmCmd::Lockfile { format, save } => {
-                    commands::cmd_pm_lockfile(format.clone(), *save)
+                PmCmd::Lockfile { diff: true, .. } => {
+                    commands::cmd_pm_lockfile_diff(format, &config)
+                }
+                PmCmd::Lockfile { format: lock_format, save, diff: false } => {
+                    commands::cmd_pm_lockfile(lock_format.clone(), *save)
                 }
-                PmCmd::Prune => commands::cmd_pm_prune(),
-                PmCmd::Ls => commands::cmd_list(),
+                PmCmd::Prune { store } => commands::cmd_pm_prune(*store, &config),
+                PmCmd::Ls => commands::cmd_list(false, false, false, format, &config),
             },
             Some(Commands::Scripts { cmd }) => match cmd {
-                ScriptsCmd::Run { packages, all, ignore_scripts, yes, per_package } => {
-                    commands::cmd_scripts_run(
-                        packages.clone(),
-                        *all,
-                        *ignore_scripts,
-                        *yes,
-                        *per_package,
-                    )
-                }
+                ScriptsCmd::Run {
+                    packages,
+                    all,
+                    root,
+                    ignore_scripts,
+                    yes,
+                    per_package,
+                    force,
+                    quiet,
+                } => commands::cmd_scripts_run(
+                    packages.clone(),
+                    *all,
+                    *root,
+                    *ignore_scripts,
+                    *yes,
+                    *per_package,
+                    *force,
+                    *quiet,
+                ),
             },
-            Some(Commands::Run { args }) => commands::cmd_run(args.clone()),
+            Some(Commands::Ci { no_progress }) => commands::cmd_ci(*no_progress, &config),
+            Some(Commands::Rebuild { packages, all, force }) => {
+                commands::cmd_rebuild(packages.clone(), *all, *force)
+            }
+            Some(Commands::Run { if_present, quiet, args }) => {
+                commands::cmd_run(args.clone(), *if_present, *quiet)
+            }
         }
     }
 
     fn print_help(&self) {
         println!("pacm - Fast, cache-first package manager\n");
         println!(
-            "Commands:\n  init [--name --version]\n  install [pkg..] [--dev|--optional] [--no-save] [--prefer-offline] [--no-progress]\n  add <pkg> [--dev|--optional] [--no-save]\n  remove <pkg..>\n  list\n  cache <path|clean>\n  pm <lockfile|prune|ls> [options]"
+            "Commands:\n  init [--name --version]\n  install [pkg..] [-D|-O|-P] [--no-save] [--prefer-offline|--offline] [--no-progress] [--lockfile-only] [--ignore-scripts|--run-scripts] [--no-bin-links] [--depth <n>] [--force] [--gc] [--prefer-frozen[=false]] [--strict] [--prefer-dedupe] [--os <os>] [--cpu <cpu>] [--libc <libc>] [--merge-lockfile] [--no-verify] [--audit-signatures] [-g]\n  ci [--no-progress]\n  add <pkg> [-D|-O|-P] [--no-save] [--latest] [--tag <dist-tag>] [-g]\n  remove <pkg..> [--run-scripts] [--gc]\n  link [pkg..]\n  unlink [pkg..]\n  rebuild <pkg..|--all> [--force]\n  list [-g] [--prod|--dev] [--json]\n  view <pkg>[@tag|@version] [field] [--json]\n  doctor\n  import-lock <package-lock.json|pnpm-lock.yaml>\n  audit [--json] [--audit-level <level>]\n  cache <path|size [--json]|clean|gc|export <file> [--store]|import <file>>\n  pm <lockfile [--diff]|prune [--store]|ls> [options]"
         );
+        println!("\nGlobal options:\n  -v, --verbose  print phase timings to stderr (-vv for every package)\n  --color <auto|always|never>  color output (auto honors NO_COLOR and FORCE_COLOR)");
     }
 }
@@ -1,8 +1,92 @@
-pub const C_RESET: &str = "\x1b[0m";
-pub const C_DIM: &str = "\x1b[2m";
-pub const C_CYAN: &str = "\x1b[36m";
-pub const C_GREEN: &str = "\x1b[32m";
-pub const C_MAGENTA: &str = "\x1b[35m";
-pub const C_YELLOW: &str = "\x1b[33m";
-pub const C_RED: &str = "\x1b[31m";
-pub const C_GRAY: &str = "\x1b[90m";
+//! ANSI styles for terminal output.
+//!
+//! The `C_*` values print as escape codes only while color is enabled, so they can be used
+//! directly in format strings. With the default `--color auto`, color is off when `NO_COLOR` is
+//! set, forced on by `FORCE_COLOR` (anything but `0`), and otherwise used only when stdout is a
+//! terminal.
+
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The `--color` setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Parse a `--color` value: `auto`, `always` or `never`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(ColorMode::Auto),
+            "always" => Some(ColorMode::Always),
+            "never" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+}
+
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_color_mode(mode: ColorMode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn color_mode() -> ColorMode {
+    match MODE.load(Ordering::Relaxed) {
+        1 => ColorMode::Always,
+        2 => ColorMode::Never,
+        _ => ColorMode::Auto,
+    }
+}
+
+/// Whether the `C_*` styles currently print escape codes.
+pub fn color_enabled() -> bool {
+    match color_mode() {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => {
+            if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                return false;
+            }
+            if let Some(force) = std::env::var_os("FORCE_COLOR") {
+                return force != "0";
+            }
+            std::io::stdout().is_terminal()
+        }
+    }
+}
+
+/// An ANSI style that renders as nothing while color is disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style(&'static str);
+
+impl Style {
+    /// The raw escape sequence, regardless of the color setting.
+    pub fn code(self) -> &'static str {
+        self.0
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if color_enabled() {
+            f.write_str(self.0)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub const C_RESET: Style = Style("\x1b[0m");
+pub const C_DIM: Style = Style("\x1b[2m");
+pub const C_CYAN: Style = Style("\x1b[36m");
+pub const C_GREEN: Style = Style("\x1b[32m");
+pub const C_MAGENTA: Style = Style("\x1b[35m");
+pub const C_YELLOW: Style = Style("\x1b[33m");
+pub const C_RED: Style = Style("\x1b[31m");
+pub const C_GRAY: Style = Style("\x1b[90m");
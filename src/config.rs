@@ -0,0 +1,148 @@
+//! Project and user defaults for pacm settings.
+//!
+//! Each setting is looked up in order, and the first source that sets it wins:
+//!
+//! 1. command-line flags (layered on by the caller with [`Config::or`]);
+//! 2. `PACM_*` environment variables;
+//! 3. the project's `.pacmrc`, then `~/.pacmrc`;
+//! 4. the project's `.npmrc`, then `~/.npmrc`, so npm-configured projects keep working.
+//!
+//! `.pacmrc` uses the same flat `key=value` form as `.npmrc`, with npm's key names where one
+//! exists: `registry`, `save-prefix`, `store-dir`, `ignore-scripts`, `legacy-peer-deps` and
+//! `always-auth`, plus per-scope registries (`@scope:registry=<url>`) and per-registry tokens
+//! (`//host/path/:_authToken=<token>`). `cafile` is read from `.npmrc` for TLS; other npm keys
+//! are ignored.
+
+use crate::npmrc::Npmrc;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Registry base URL (`PACM_REGISTRY`, `registry=`).
+    pub registry: Option<String>,
+    /// Prefix for versions saved to package.json, such as `^` or `~` (`PACM_SAVE_PREFIX`,
+    /// `save-prefix=`). Unset saves exact versions.
+    pub save_prefix: Option<String>,
+    /// Content-addressable store location (`PACM_STORE_DIR`, `store-dir=`). A relative path in
+    /// an rc file is resolved against that file's directory.
+    pub store_dir: Option<PathBuf>,
+    /// Skip lifecycle scripts by default (`PACM_IGNORE_SCRIPTS`, `ignore-scripts=`). Unlike
+    /// `pacm install --ignore-scripts`, this is never recorded in the project's lockfile.
+    pub ignore_scripts: Option<bool>,
+    /// Leave peer dependencies to the project: never install them and don't warn when they are
+    /// missing (`PACM_LEGACY_PEER_DEPS`, `legacy-peer-deps=`).
+    pub legacy_peer_deps: Option<bool>,
+    /// Send a registry's token to every URL on its host, not only under its path, e.g. for
+    /// tarballs served from another path (`PACM_ALWAYS_AUTH`, `always-auth=`).
+    pub always_auth: Option<bool>,
+    /// Registry URL for each `@scope` (`@scope:registry=`).
+    pub scope_registries: BTreeMap<String, String>,
+    /// Bearer tokens keyed by registry URL without its scheme, such as `//npm.example.com/`
+    /// (`//npm.example.com/:_authToken=`).
+    pub auth_tokens: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Settings for the current directory: environment, then `.pacmrc` and `.npmrc` files.
+    pub fn load() -> Self {
+        let project = std::env::current_dir().ok();
+        let home = dirs::home_dir();
+        Self::from_sources(|key| std::env::var(key).ok(), project.as_deref(), home.as_deref())
+    }
+
+    /// Merge `env`, then the rc files in `project` and `home`. Missing or unreadable files are
+    /// skipped.
+    pub fn from_sources(
+        env: impl Fn(&str) -> Option<String>,
+        project: Option<&Path>,
+        home: Option<&Path>,
+    ) -> Self {
+        let mut config = Self::from_env(env);
+        for file in [".pacmrc", ".npmrc"] {
+            for dir in project.into_iter().chain(home) {
+                if let Ok(text) = std::fs::read_to_string(dir.join(file)) {
+                    config = config.or(Self::from_rc(&Npmrc::parse(&text, dir)));
+                }
+            }
+        }
+        config
+    }
+
+    fn from_env(env: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |key: &str| env(key).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        Self {
+            registry: var("PACM_REGISTRY"),
+            save_prefix: env("PACM_SAVE_PREFIX").map(|v| v.trim().to_string()),
+            store_dir: var("PACM_STORE_DIR").map(PathBuf::from),
+            ignore_scripts: var("PACM_IGNORE_SCRIPTS").and_then(|v| parse_bool(&v)),
+            legacy_peer_deps: var("PACM_LEGACY_PEER_DEPS").and_then(|v| parse_bool(&v)),
+            always_auth: var("PACM_ALWAYS_AUTH").and_then(|v| parse_bool(&v)),
+            ..Self::default()
+        }
+    }
+
+    fn from_rc(rc: &Npmrc) -> Self {
+        let mut scope_registries = BTreeMap::new();
+        let mut auth_tokens = BTreeMap::new();
+        for (key, value) in rc.iter().filter(|(_, value)| !value.is_empty()) {
+            if let Some(scope) = key.strip_suffix(":registry").filter(|k| k.starts_with('@')) {
+                scope_registries.insert(scope.to_string(), value.to_string());
+            } else if let Some(registry) =
+                key.strip_suffix(":_authToken").filter(|k| k.starts_with("//"))
+            {
+                auth_tokens.insert(nerf_dart(registry), value.to_string());
+            }
+        }
+        Self {
+            registry: rc.get("registry").filter(|v| !v.is_empty()).map(String::from),
+            save_prefix: rc.get("save-prefix").map(String::from),
+            store_dir: rc.get_path("store-dir"),
+            ignore_scripts: rc.get("ignore-scripts").and_then(parse_bool),
+            legacy_peer_deps: rc.get("legacy-peer-deps").and_then(parse_bool),
+            always_auth: rc.get("always-auth").and_then(parse_bool),
+            scope_registries,
+            auth_tokens,
+        }
+    }
+
+    /// Keep every setting made here and take the rest from `fallback`.
+    pub fn or(self, fallback: Config) -> Config {
+        Config {
+            registry: self.registry.or(fallback.registry),
+            save_prefix: self.save_prefix.or(fallback.save_prefix),
+            store_dir: self.store_dir.or(fallback.store_dir),
+            ignore_scripts: self.ignore_scripts.or(fallback.ignore_scripts),
+            legacy_peer_deps: self.legacy_peer_deps.or(fallback.legacy_peer_deps),
+            always_auth: self.always_auth.or(fallback.always_auth),
+            scope_registries: merge_maps(self.scope_registries, fallback.scope_registries),
+            auth_tokens: merge_maps(self.auth_tokens, fallback.auth_tokens),
+        }
+    }
+}
+
+/// Entries of `primary`, plus the keys of `fallback` it does not set.
+fn merge_maps(
+    mut primary: BTreeMap<String, String>,
+    fallback: BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    for (key, value) in fallback {
+        primary.entry(key).or_insert(value);
+    }
+    primary
+}
+
+/// npm's "nerf dart" form of a registry URL: the scheme dropped and a trailing slash added, so
+/// `https://npm.example.com/private` becomes `//npm.example.com/private/`.
+pub fn nerf_dart(registry: &str) -> String {
+    let rest = registry.split_once("//").map_or(registry, |(_, rest)| rest);
+    format!("//{}/", rest.trim_end_matches('/'))
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
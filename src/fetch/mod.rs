@@ -1,62 +1,445 @@
+use crate::config::{nerf_dart, Config};
+use crate::npmrc::Npmrc;
 use anyhow::{Context, Result};
 use once_cell::sync::Lazy;
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, ClientBuilder, Response};
+use reqwest::header::{ACCEPT, LOCATION};
+use reqwest::{Certificate, NoProxy, Proxy, Url};
 use serde::Deserialize;
-use std::collections::HashMap;
-use std::sync::Mutex;
-use std::time::Duration;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Mutex, Once};
+use std::time::{Duration, Instant};
 
-static CLIENT: Lazy<Client> = Lazy::new(|| {
-    Client::builder()
+mod signatures;
+
+use signatures::RegistryKeys;
+pub use signatures::{verify_signatures, InvalidSignature, RegistryKey, SignatureStatus};
+
+const DEFAULT_FETCH_TIMEOUT_SECS: u64 = 120;
+const MAX_REDIRECTS: usize = 10;
+/// npm's abbreviated "corgi" metadata: only what installs need, without READMEs, per-version
+/// `scripts` or other bulk. Falls back to the full document on registries that lack it.
+pub const ABBREVIATED_METADATA: &str =
+    "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*";
+/// Minimum time between progress callbacks while streaming a tarball.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+static CLIENT: Lazy<Result<Client, String>> = Lazy::new(|| env_client(true));
+
+/// Client used by [`Fetcher`]; redirects are followed by hand so credentials never leave the
+/// origin they were issued for.
+static FETCH_CLIENT: Lazy<Result<Client, String>> = Lazy::new(|| env_client(false));
+
+/// The error is kept as text so every later request can report it again.
+fn env_client(follow_redirects: bool) -> Result<Client, String> {
+    let npmrc = Npmrc::load();
+    let proxy = ProxyConfig::from_env();
+    let tls = TlsConfig::from_env(&npmrc);
+    if !tls.strict_ssl {
+        static WARNED: Once = Once::new();
+        WARNED.call_once(|| {
+            eprintln!(
+                "[pacm] WARNING: PACM_STRICT_SSL=false disables TLS certificate verification; \
+                 registry responses can be forged by anyone on the network"
+            );
+        });
+    }
+    build_client_or_fallback(&proxy, &tls, follow_redirects).map_err(|err| format!("{err:#}"))
+}
+
+fn shared_client(client: &'static Result<Client, String>) -> Result<&'static Client> {
+    client.as_ref().map_err(|err| anyhow::anyhow!("cannot set up the HTTP client: {err}"))
+}
+
+/// [`build_client`], falling back to the platform roots when the CA bundle cannot be loaded.
+/// The proxy settings are kept unless they are themselves invalid: behind a mandatory proxy,
+/// going direct only fails later and more confusingly. Any other failure, such as a malformed
+/// `PACM_FETCH_TIMEOUT`, is returned.
+pub fn build_client_or_fallback(
+    proxy: &ProxyConfig,
+    tls: &TlsConfig,
+    follow_redirects: bool,
+) -> Result<Client> {
+    build_client(proxy, tls, follow_redirects).or_else(|_| {
+        let mut tls = tls.clone();
+        if let Err(err) = tls.apply(Client::builder()) {
+            eprintln!("[pacm] warning: ignoring CA bundle: {err:#}");
+            tls.ca_file = None;
+        }
+        let mut proxy = proxy.clone();
+        if let Err(err) = proxy.apply(Client::builder()) {
+            eprintln!("[pacm] warning: ignoring proxy settings: {err:#}");
+            proxy = ProxyConfig::default();
+        }
+        build_client(&proxy, &tls, follow_redirects)
+    })
+}
+
+/// Build an HTTP client that uses exactly the proxies in `proxy` and trusts `tls`.
+pub fn build_client(
+    proxy: &ProxyConfig,
+    tls: &TlsConfig,
+    follow_redirects: bool,
+) -> Result<Client> {
+    let mut builder = Client::builder()
         // Allow larger payloads and slower mirrors; installs still stay parallelized
-        .timeout(Duration::from_secs(120))
-        .user_agent("pacm/0.1.0 (+https://github.com/pacmpkg/pacm)")
-        .build()
-        .expect("http client")
-});
+        .timeout(fetch_timeout()?)
+        .user_agent("pacm/0.1.0 (+https://github.com/pacmpkg/pacm)");
+    if !follow_redirects {
+        builder = builder.redirect(reqwest::redirect::Policy::none());
+    }
+    builder = tls.apply(builder)?;
+    Ok(proxy.apply(builder)?.build()?)
+}
 
-static META_CACHE: Lazy<Mutex<HashMap<String, NpmMetadata>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+/// Certificate trust for registry connections: an extra CA bundle from `PACM_CAFILE` (or
+/// `cafile=` in `.npmrc`), added to the platform roots, and the `PACM_STRICT_SSL=false` escape
+/// hatch that turns verification off entirely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub ca_file: Option<PathBuf>,
+    pub strict_ssl: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self { ca_file: None, strict_ssl: true }
+    }
+}
+
+impl TlsConfig {
+    pub fn from_env(npmrc: &Npmrc) -> Self {
+        let ca_file = std::env::var_os("PACM_CAFILE")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| npmrc.get_path("cafile"));
+        let strict_ssl = std::env::var("PACM_STRICT_SSL").map_or(true, |raw| {
+            !matches!(raw.trim().to_ascii_lowercase().as_str(), "false" | "0" | "no")
+        });
+        Self { ca_file, strict_ssl }
+    }
+
+    fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        if let Some(path) = &self.ca_file {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("read CA bundle {}", path.display()))?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("parse CA bundle {}", path.display()))?;
+            if certs.is_empty() {
+                anyhow::bail!("no certificates found in {}", path.display());
+            }
+            builder = builder.tls_certs_merge(certs);
+        }
+        if !self.strict_ssl {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder)
+    }
+}
 
+/// Proxies for registry traffic. `PACM_PROXY` overrides the standard variables for both schemes;
+/// otherwise `HTTPS_PROXY` (falling back to `HTTP_PROXY`, like npm) and `HTTP_PROXY` apply, in
+/// either case. Hosts listed in `NO_PROXY` are always contacted directly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyConfig {
+    pub http: Option<String>,
+    pub https: Option<String>,
+    pub no_proxy: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |keys: &[&str]| {
+            keys.iter()
+                .filter_map(|key| lookup(key))
+                .map(|value| value.trim().to_string())
+                .find(|value| !value.is_empty())
+        };
+        let http = var(&["PACM_PROXY", "HTTP_PROXY", "http_proxy"]);
+        let https = var(&["PACM_PROXY", "HTTPS_PROXY", "https_proxy"]).or_else(|| http.clone());
+        Self { http, https, no_proxy: var(&["NO_PROXY", "no_proxy"]) }
+    }
+
+    fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder> {
+        // Replace reqwest's own system-proxy detection so these rules are the only ones.
+        let mut builder = builder.no_proxy();
+        let no_proxy = || self.no_proxy.as_deref().and_then(NoProxy::from_string);
+        if let Some(url) = &self.http {
+            let proxy = Proxy::http(url).with_context(|| format!("invalid proxy url {url}"))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy()));
+        }
+        if let Some(url) = &self.https {
+            let proxy = Proxy::https(url).with_context(|| format!("invalid proxy url {url}"))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy()));
+        }
+        Ok(builder)
+    }
+}
+
+static ABBREVIATED_META_CACHE: Lazy<Mutex<HashMap<String, NpmMetadata>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 static VERSION_META_CACHE: Lazy<Mutex<HashMap<String, NpmVersion>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
-pub fn http_client() -> &'static Client {
-    &CLIENT
+/// Signing keys by registry URL.
+static KEYS_CACHE: Lazy<Mutex<HashMap<String, Vec<RegistryKey>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn http_client() -> Result<&'static Client> {
+    shared_client(&CLIENT)
+}
+
+/// Per-request timeout, overridable in whole seconds via `PACM_FETCH_TIMEOUT`; `0` keeps the
+/// default. A value that is not a number of seconds is an error.
+pub fn fetch_timeout() -> Result<Duration> {
+    let secs = match std::env::var("PACM_FETCH_TIMEOUT") {
+        Ok(raw) if !raw.trim().is_empty() => raw
+            .trim()
+            .parse::<u64>()
+            .with_context(|| format!("invalid PACM_FETCH_TIMEOUT '{raw}' (expected seconds)"))?,
+        _ => 0,
+    };
+    Ok(Duration::from_secs(if secs > 0 { secs } else { DEFAULT_FETCH_TIMEOUT_SECS }))
 }
 
 #[derive(Debug, Clone)]
 pub struct Fetcher {
     registry: String,
+    auth_token: Option<String>,
+    /// Registry URL per `@scope`, used instead of `registry` for that scope's metadata.
+    scope_registries: BTreeMap<String, String>,
+    /// Bearer tokens keyed by nerf-darted registry URL (`//host/path/`).
+    registry_tokens: BTreeMap<String, String>,
+    offline: bool,
+    /// Match registry tokens by host alone (`always-auth`).
+    always_auth: bool,
+    /// `(from, to)` URL prefixes applied to tarball URLs before download.
+    mirrors: Vec<(String, String)>,
+}
+
+/// Parse `PACM_REGISTRY_MIRROR`: comma-separated `from=>to` URL prefix pairs. Malformed pairs are
+/// ignored.
+pub fn parse_mirror_rules(raw: &str) -> Vec<(String, String)> {
+    raw.split(',')
+        .filter_map(|rule| {
+            let (from, to) = rule.split_once("=>")?;
+            let (from, to) = (from.trim().trim_end_matches('/'), to.trim().trim_end_matches('/'));
+            (!from.is_empty() && !to.is_empty()).then(|| (from.to_string(), to.to_string()))
+        })
+        .collect()
 }
 
 impl Fetcher {
     pub fn new(registry: Option<String>) -> Result<Self> {
-        Ok(Self { registry: registry.unwrap_or_else(|| "https://registry.npmjs.org".into()) })
+        // Surface a malformed timeout here rather than when the shared clients are first built.
+        fetch_timeout()?;
+        Ok(Self {
+            registry: registry.unwrap_or_else(|| "https://registry.npmjs.org".into()),
+            auth_token: std::env::var("PACM_AUTH_TOKEN").ok().filter(|t| !t.is_empty()),
+            scope_registries: BTreeMap::new(),
+            registry_tokens: BTreeMap::new(),
+            offline: false,
+            always_auth: false,
+            mirrors: std::env::var("PACM_REGISTRY_MIRROR")
+                .map(|raw| parse_mirror_rules(&raw))
+                .unwrap_or_default(),
+        })
+    }
+
+    /// A fetcher for the configured registry, scope registries and registry tokens.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut fetcher = Self::new(config.registry.clone())?;
+        for (scope, registry) in &config.scope_registries {
+            fetcher = fetcher.with_scope_registry(scope, registry);
+        }
+        for (registry, token) in &config.auth_tokens {
+            fetcher = fetcher.with_registry_token(registry, token);
+        }
+        Ok(fetcher.with_always_auth(config.always_auth.unwrap_or(false)))
+    }
+
+    /// Fetch metadata for `@scope/...` packages from `registry`.
+    pub fn with_scope_registry(mut self, scope: &str, registry: &str) -> Self {
+        let scope = scope.trim_start_matches('@');
+        self.scope_registries
+            .insert(format!("@{scope}"), registry.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Send `token` on every request under `registry`, given as a URL or as `//host/path/`.
+    pub fn with_registry_token(mut self, registry: &str, token: impl Into<String>) -> Self {
+        self.registry_tokens.insert(nerf_dart(registry), token.into());
+        self
+    }
+
+    /// Send a registry's token to any URL on the same host and port, such as tarballs served
+    /// outside the registry path, instead of only to URLs under that path (`always-auth`).
+    pub fn with_always_auth(mut self, always_auth: bool) -> Self {
+        self.always_auth = always_auth;
+        self
     }
 
+    /// The registry serving `name`: its scope's registry if one is configured.
+    fn registry_for(&self, name: &str) -> &str {
+        name.split_once('/')
+            .filter(|(scope, _)| scope.starts_with('@'))
+            .and_then(|(scope, _)| self.scope_registries.get(scope))
+            .unwrap_or(&self.registry)
+    }
+
+    /// The token for `url`: the longest configured registry prefix it falls under, else the
+    /// default token when `url` is on the default registry's origin. With `always-auth`, a
+    /// registry's token also covers the rest of its host.
+    fn token_for(&self, url: &Url) -> Option<&str> {
+        let host = url.host_str()?;
+        let port = url.port().map(|p| format!(":{p}")).unwrap_or_default();
+        let authority = format!("//{host}{port}");
+        let dart = format!("{authority}{}", url.path());
+        let scoped = self
+            .registry_tokens
+            .iter()
+            .filter(|(prefix, _)| dart.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len());
+        if let Some((_, token)) = scoped {
+            return Some(token);
+        }
+        if self.always_auth {
+            let same_host = self.registry_tokens.iter().find(|(prefix, _)| {
+                prefix.strip_prefix(authority.as_str()).is_some_and(|rest| rest.starts_with('/'))
+            });
+            if let Some((_, token)) = same_host {
+                return Some(token);
+            }
+        }
+        let registry_origin = Url::parse(&self.registry).ok().map(|u| u.origin());
+        (registry_origin == Some(url.origin())).then_some(self.auth_token.as_deref()).flatten()
+    }
+
+    /// Download tarballs under the `from` URL prefix from `to` instead. Lockfiles keep the
+    /// original URL.
+    pub fn with_mirror(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        let (from, to) = (from.into(), to.into());
+        self.mirrors
+            .push((from.trim_end_matches('/').to_string(), to.trim_end_matches('/').to_string()));
+        self
+    }
+
+    /// Apply the first matching mirror rule to `url`. A rule only matches on a path boundary, so
+    /// `https://registry.example` never rewrites `https://registry.example.evil/...`.
+    pub fn mirror_url(&self, url: &str) -> String {
+        for (from, to) in &self.mirrors {
+            if let Some(rest) = url.strip_prefix(from.as_str()) {
+                if rest.is_empty() || rest.starts_with('/') {
+                    return format!("{to}{rest}");
+                }
+            }
+        }
+        url.to_string()
+    }
+
+    /// Send `token` as a bearer credential on requests to the registry origin.
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Refuse every request instead of touching the network (`--offline`).
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// GET `url`, following redirects manually. A token is only attached when `url` is under a
+    /// registry it was configured for; it is dropped for good once a redirect changes host,
+    /// port or scheme.
+    fn get(&self, url: &str) -> Result<Response> {
+        self.get_accepting(url, None)
+    }
+
+    /// [`Fetcher::get`] with an `Accept` header, sent again on every redirect hop.
+    fn get_accepting(&self, url: &str, accept: Option<&str>) -> Result<Response> {
+        if self.offline {
+            anyhow::bail!("offline: {url} not cached");
+        }
+        let mut current = Url::parse(url).with_context(|| format!("invalid url {url}"))?;
+        let mut token = self.token_for(&current);
+        for _ in 0..=MAX_REDIRECTS {
+            let mut req = shared_client(&FETCH_CLIENT)?.get(current.clone());
+            if let Some(accept) = accept {
+                req = req.header(ACCEPT, accept);
+            }
+            if let Some(token) = token {
+                req = req.bearer_auth(token);
+            }
+            let resp = req.send().with_context(|| format!("GET {current}"))?;
+            let location = resp.headers().get(LOCATION).and_then(|v| v.to_str().ok());
+            let (true, Some(location)) = (resp.status().is_redirection(), location) else {
+                if current.as_str() != url && std::env::var_os("PACM_DEBUG_FETCH").is_some() {
+                    eprintln!("[pacm] GET {url} resolved to {current}");
+                }
+                return Ok(resp);
+            };
+            let next = current
+                .join(location)
+                .with_context(|| format!("invalid redirect from {current}"))?;
+            if next.origin() != current.origin() {
+                token = None;
+            }
+            current = next;
+        }
+        anyhow::bail!("too many redirects fetching {url}")
+    }
+
+    /// Version list and dist-tags for `name`, as the resolver reads them. Served from the
+    /// abbreviated document ([`Fetcher::package_metadata_abbreviated`]), so versions carry
+    /// `hasInstallScript` instead of `scripts`; use [`Fetcher::package_version_metadata`] or the
+    /// package's own package.json when the scripts themselves are needed.
     pub fn package_metadata(&self, name: &str) -> Result<NpmMetadata> {
-        if let Some(hit) = META_CACHE.lock().unwrap().get(name).cloned() {
+        self.package_metadata_abbreviated(name)
+    }
+
+    /// Like [`Fetcher::package_metadata`], but requests the abbreviated document, which is a
+    /// fraction of the size for packages with many versions (`@types/node`). Its versions carry
+    /// `hasInstallScript` instead of `scripts`.
+    pub fn package_metadata_abbreviated(&self, name: &str) -> Result<NpmMetadata> {
+        if let Some(hit) = ABBREVIATED_META_CACHE.lock().unwrap().get(name).cloned() {
             return Ok(hit);
         }
-        let url = format!("{}/{}", self.registry, name);
-        let resp = CLIENT.get(&url).send().with_context(|| format!("GET {url}"))?;
+        let url = format!("{}/{}", self.registry_for(name), name);
+        let resp = self.get_accepting(&url, Some(ABBREVIATED_METADATA))?;
         if !resp.status().is_success() {
             anyhow::bail!("registry returned {} for {}", resp.status(), name);
         }
-        let meta: NpmMetadata = resp.json()?;
-        META_CACHE.lock().unwrap().insert(name.to_string(), meta.clone());
+        let meta: NpmMetadata =
+            resp.json().with_context(|| format!("parse abbreviated metadata for {name}"))?;
+        ABBREVIATED_META_CACHE.lock().unwrap().insert(name.to_string(), meta.clone());
         Ok(meta)
     }
 
+    /// The registry's full metadata document for `name`, untyped, for commands that show fields
+    /// [`NpmMetadata`] does not keep (`pacm view`). Not cached.
+    pub fn package_document(&self, name: &str) -> Result<serde_json::Value> {
+        let url = format!("{}/{}", self.registry_for(name), name);
+        let resp = self.get(&url)?;
+        if !resp.status().is_success() {
+            anyhow::bail!("registry returned {} for {}", resp.status(), name);
+        }
+        resp.json().with_context(|| format!("parse registry metadata for {name}"))
+    }
+
     pub fn package_version_metadata(&self, name: &str, spec: &str) -> Result<NpmVersion> {
         let trimmed = spec.trim();
         let key = format!("{name}@{trimmed}");
         if let Some(hit) = VERSION_META_CACHE.lock().unwrap().get(&key).cloned() {
             return Ok(hit);
         }
-        let url = format!("{}/{}/{}", self.registry, name, trimmed);
-        let resp = CLIENT.get(&url).send().with_context(|| format!("GET {url}"))?;
+        let url = format!("{}/{}/{}", self.registry_for(name), name, trimmed);
+        let resp = self.get(&url)?;
         if !resp.status().is_success() {
             anyhow::bail!("registry returned {} for {}@{}", resp.status(), name, trimmed);
         }
@@ -65,46 +448,102 @@ impl Fetcher {
         Ok(meta)
     }
 
+    /// The signing keys published at `/-/npm/v1/keys` by the registry serving `name`. Empty when
+    /// the registry does not sign packages (it has no such endpoint).
+    pub fn signing_keys(&self, name: &str) -> Result<Vec<RegistryKey>> {
+        let registry = self.registry_for(name).trim_end_matches('/').to_string();
+        if let Some(hit) = KEYS_CACHE.lock().unwrap().get(&registry).cloned() {
+            return Ok(hit);
+        }
+        let url = format!("{registry}/-/npm/v1/keys");
+        let resp = self.get(&url)?;
+        let keys = if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            Vec::new()
+        } else if resp.status().is_success() {
+            let doc: RegistryKeys =
+                resp.json().with_context(|| format!("parse signing keys from {url}"))?;
+            doc.keys
+        } else {
+            anyhow::bail!("registry returned {} for {url}", resp.status());
+        };
+        KEYS_CACHE.lock().unwrap().insert(registry, keys.clone());
+        Ok(keys)
+    }
+
+    /// POST an npm audit payload to the registry's `/-/npm/v1/security/audits` endpoint and
+    /// return the raw report.
+    pub fn security_audit(&self, payload: &serde_json::Value) -> Result<serde_json::Value> {
+        if self.offline {
+            anyhow::bail!("offline: cannot reach the audit endpoint");
+        }
+        let url = format!("{}/-/npm/v1/security/audits", self.registry.trim_end_matches('/'));
+        let audit_url = Url::parse(&url).with_context(|| format!("invalid url {url}"))?;
+        let token = self.token_for(&audit_url);
+        let mut req = shared_client(&FETCH_CLIENT)?.post(audit_url).json(payload);
+        if let Some(token) = token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req.send().with_context(|| format!("POST {url}"))?;
+        if !resp.status().is_success() {
+            anyhow::bail!("audit endpoint returned {}", resp.status());
+        }
+        resp.json().with_context(|| "parse audit response")
+    }
+
     pub fn download_tarball(&self, url: &str) -> Result<Vec<u8>> {
-        let resp = CLIENT.get(url).send().with_context(|| format!("GET {url}"))?;
+        let resp = self.get(&self.mirror_url(url))?;
         if !resp.status().is_success() {
-            anyhow::bail!("tarball fetch {} status {}", url, resp.status());
+            anyhow::bail!("tarball fetch {} status {}", resp.url(), resp.status());
         }
         let bytes = resp.bytes()?;
         Ok(bytes.to_vec())
     }
 
     /// Stream a tarball while invoking a callback with (downloaded_bytes, total_opt). Returns bytes.
-    pub fn download_tarball_stream<F>(&self, url: &str, mut on_progress: F) -> Result<Vec<u8>>
+    pub fn download_tarball_stream<F>(&self, url: &str, on_progress: F) -> Result<Vec<u8>>
     where
         F: FnMut(u64, Option<u64>),
     {
-        use std::io::Read;
-        let mut resp = CLIENT.get(url).send().with_context(|| format!("GET {url}"))?;
+        let resp = self.get(&self.mirror_url(url))?;
         if !resp.status().is_success() {
-            anyhow::bail!("tarball fetch {} status {}", url, resp.status());
+            anyhow::bail!("tarball fetch {} status {}", resp.url(), resp.status());
         }
         let total = resp.content_length();
-        let mut buf: Vec<u8> = Vec::with_capacity(total.unwrap_or(0) as usize);
-        let mut downloaded: u64 = 0;
-        let mut tmp = [0u8; 32 * 1024];
-        on_progress(0, total);
-        loop {
-            let n = resp.read(&mut tmp)?;
-            if n == 0 {
-                break;
-            }
-            buf.extend_from_slice(&tmp[..n]);
-            downloaded += n as u64;
-            // Throttle updates: every 64KiB or on completion
-            if downloaded % (64 * 1024) < n as u64
-                || total.map(|t| downloaded >= t).unwrap_or(false)
-            {
-                on_progress(downloaded, total);
-            }
+        read_with_progress(resp, total, PROGRESS_INTERVAL, on_progress)
+    }
+}
+
+/// Read `reader` to the end, reporting (downloaded_bytes, total_opt) at the start, at most once
+/// per `interval` while reading, and once more when the stream is done.
+pub(crate) fn read_with_progress<R, F>(
+    mut reader: R,
+    total: Option<u64>,
+    interval: Duration,
+    mut on_progress: F,
+) -> Result<Vec<u8>>
+where
+    R: std::io::Read,
+    F: FnMut(u64, Option<u64>),
+{
+    let mut buf: Vec<u8> = Vec::with_capacity(total.unwrap_or(0) as usize);
+    let mut downloaded: u64 = 0;
+    let mut tmp = [0u8; 32 * 1024];
+    on_progress(0, total);
+    let mut last_report = Instant::now();
+    loop {
+        let n = reader.read(&mut tmp)?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&tmp[..n]);
+        downloaded += n as u64;
+        if last_report.elapsed() >= interval {
+            on_progress(downloaded, total);
+            last_report = Instant::now();
         }
-        Ok(buf)
     }
+    on_progress(downloaded, total);
+    Ok(buf)
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -120,6 +559,10 @@ pub struct NpmVersion {
     pub dist: NpmDist,
     #[serde(default)]
     pub scripts: std::collections::BTreeMap<String, String>,
+    /// Set in abbreviated metadata, which omits `scripts`, when the package has a `preinstall`,
+    /// `install` or `postinstall` script.
+    #[serde(default, rename = "hasInstallScript")]
+    pub has_install_script: bool,
     #[serde(default)]
     pub dependencies: std::collections::HashMap<String, String>,
     #[serde(default, rename = "optionalDependencies")]
@@ -132,6 +575,21 @@ pub struct NpmVersion {
     pub os: Vec<String>,
     #[serde(default, rename = "cpu")]
     pub cpu_arch: Vec<String>,
+    #[serde(default)]
+    pub libc: Vec<String>,
+    /// Deprecation message set by `npm deprecate`; `false` or an empty string means none.
+    #[serde(default, deserialize_with = "deprecation_message")]
+    pub deprecated: Option<String>,
+}
+
+fn deprecation_message<'de, D>(deserializer: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(msg) if !msg.trim().is_empty() => Ok(Some(msg)),
+        _ => Ok(None),
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -139,6 +597,21 @@ pub struct NpmDist {
     pub tarball: String,
     pub integrity: Option<String>,
     pub shasum: Option<String>,
+    /// Size of the extracted package in bytes, when the registry reports it.
+    #[serde(rename = "unpackedSize")]
+    pub unpacked_size: Option<u64>,
+    #[serde(rename = "fileCount")]
+    pub file_count: Option<u64>,
+    /// Registry signatures over `<name>@<version>:<integrity>`; see [`verify_signatures`].
+    #[serde(default)]
+    pub signatures: Vec<NpmSignature>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NpmSignature {
+    pub keyid: String,
+    /// Base64 DER-encoded ECDSA signature.
+    pub sig: String,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
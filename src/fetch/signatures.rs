@@ -0,0 +1,91 @@
+//! Registry signatures. npm's registry signs `<name>@<version>:<integrity>` for every version it
+//! serves (`dist.signatures`) with ECDSA P-256 keys it publishes at `/-/npm/v1/keys`, so a
+//! tarball whose integrity matches a valid signature is the one the registry published.
+
+use super::NpmSignature;
+use aws_lc_rs::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+
+/// The only key type npm's registry signs with.
+const ECDSA_P256: &str = "ecdsa-sha2-nistp256";
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RegistryKey {
+    pub keyid: String,
+    #[serde(default)]
+    pub keytype: String,
+    /// Base64 DER-encoded SubjectPublicKeyInfo.
+    pub key: String,
+    /// When the key was retired, if it has been. Not enforced: that needs each version's
+    /// publish time, which abbreviated metadata leaves out.
+    #[serde(default)]
+    pub expires: Option<String>,
+}
+
+/// Body of `/-/npm/v1/keys`.
+#[derive(Debug, Deserialize)]
+pub(super) struct RegistryKeys {
+    #[serde(default)]
+    pub(super) keys: Vec<RegistryKey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Verified,
+    /// The version carries no signatures.
+    Missing,
+    Invalid(String),
+}
+
+/// A package's registry signature does not verify (`pacm install --audit-signatures`).
+#[derive(Debug, thiserror::Error)]
+#[error("invalid registry signature for {package}: {reason}")]
+pub struct InvalidSignature {
+    pub package: String,
+    pub reason: String,
+}
+
+/// Check `signatures` for `name@version` against the registry's `keys`. A version is verified
+/// when a signature made with one of the keys matches `integrity`; a signature that fails, or
+/// signatures only from unknown keys, make it invalid.
+pub fn verify_signatures(
+    keys: &[RegistryKey],
+    name: &str,
+    version: &str,
+    integrity: &str,
+    signatures: &[NpmSignature],
+) -> SignatureStatus {
+    if signatures.is_empty() {
+        return SignatureStatus::Missing;
+    }
+    let message = format!("{name}@{version}:{integrity}");
+    let mut verified = false;
+    for signature in signatures {
+        let Some(key) = keys.iter().find(|k| k.keyid == signature.keyid) else {
+            continue;
+        };
+        if let Err(reason) = verify_one(key, message.as_bytes(), &signature.sig) {
+            return SignatureStatus::Invalid(reason);
+        }
+        verified = true;
+    }
+    if verified {
+        SignatureStatus::Verified
+    } else {
+        let ids: Vec<&str> = signatures.iter().map(|s| s.keyid.as_str()).collect();
+        SignatureStatus::Invalid(format!("signed with unknown key {}", ids.join(", ")))
+    }
+}
+
+fn verify_one(key: &RegistryKey, message: &[u8], sig: &str) -> Result<(), String> {
+    if key.keytype != ECDSA_P256 {
+        return Err(format!("key {} has unsupported type '{}'", key.keyid, key.keytype));
+    }
+    let public_key =
+        STANDARD.decode(&key.key).map_err(|e| format!("key {} is not base64: {e}", key.keyid))?;
+    let sig = STANDARD.decode(sig).map_err(|e| format!("signature is not base64: {e}"))?;
+    UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, &public_key)
+        .verify(message, &sig)
+        .map_err(|_| format!("signature does not match key {}", key.keyid))
+}
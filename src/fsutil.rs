@@ -1,3 +1,4 @@
+use crate::config::Config;
 use dirs::data_local_dir;
 use std::path::{Path, PathBuf};
 
@@ -9,7 +10,12 @@ pub fn cache_root() -> PathBuf {
     root
 }
 
-pub fn store_root() -> PathBuf {
+/// The store lives under the data directory unless `store-dir` (or `PACM_STORE_DIR`) moves it.
+/// Callers load `config` once per command and pass it here.
+pub fn store_root(config: &Config) -> PathBuf {
+    if let Some(dir) = &config.store_dir {
+        return dir.join("v1");
+    }
     let mut root = data_local_dir().unwrap_or_else(|| PathBuf::from("."));
     root.push("pacm");
     root.push("store");
@@ -17,6 +23,127 @@ pub fn store_root() -> PathBuf {
     root
 }
 
+/// Prefix for `--global` installs; laid out like a project with its own lockfile.
+pub fn global_root(config: &Config) -> PathBuf {
+    store_root(config).join("global")
+}
+
+/// Global link registry for `pacm link`: one symlink per package name, pointing at its source.
+pub fn links_root(config: &Config) -> PathBuf {
+    store_root(config).join("links")
+}
+
+/// Recreate the symlink `src` at `dest` with the same (usually package-relative) target. Where
+/// symlinks cannot be created, such as Windows without the privilege, a file target is copied.
+///
+/// `src` lives under the package directory `root`. A target that is absolute or climbs out of
+/// `root` is rejected, so a tarball cannot plant a link that later writes would follow outside
+/// the package.
+pub fn copy_symlink(root: &Path, src: &Path, dest: &Path) -> std::io::Result<()> {
+    let target = std::fs::read_link(src)?;
+    if !symlink_stays_within(root, src, &target) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("symlink {} points outside its package ({})", src.display(), target.display()),
+        ));
+    }
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(&target, dest);
+    #[cfg(windows)]
+    let linked = if src.is_dir() {
+        std::os::windows::fs::symlink_dir(&target, dest)
+    } else {
+        std::os::windows::fs::symlink_file(&target, dest)
+    };
+    match linked {
+        Ok(()) => Ok(()),
+        Err(_) if src.is_file() => std::fs::copy(src, dest).map(|_| ()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Whether the link at `link` (inside `root`) with `target` resolves to a path inside `root`.
+/// The target is walked the way the OS would, following links that already exist under `root`,
+/// so a chain of links cannot climb out even when each one looks harmless on its own. Missing
+/// components are taken as plain directories, so the check holds whether or not the target
+/// exists.
+pub fn symlink_stays_within(root: &Path, link: &Path, target: &Path) -> bool {
+    use std::path::Component;
+    /// Links followed before giving up, like the kernel's `ELOOP` limit.
+    const MAX_HOPS: usize = 40;
+
+    let Ok(rel) = link.strip_prefix(root) else {
+        return false;
+    };
+    // Components still to walk, last one on top, so a link's target can replace it in place.
+    let mut pending: Vec<PathBuf> = Vec::new();
+    if !push_components(&mut pending, target) {
+        return false;
+    }
+    if let Some(parent) = rel.parent() {
+        push_components(&mut pending, parent);
+    }
+    let mut resolved = PathBuf::new();
+    let mut hops = 0;
+    while let Some(next) = pending.pop() {
+        match next.components().next() {
+            Some(Component::Normal(part)) => {
+                resolved.push(part);
+                if let Ok(inner) = std::fs::read_link(root.join(&resolved)) {
+                    hops += 1;
+                    resolved.pop();
+                    if hops > MAX_HOPS || !push_components(&mut pending, &inner) {
+                        return false;
+                    }
+                }
+            }
+            // Popping past the root climbs out of the package.
+            Some(Component::ParentDir) if !resolved.pop() => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+/// Push the components of the relative `path` onto `pending` so they pop in order. Returns false,
+/// pushing nothing, for an absolute path.
+fn push_components(pending: &mut Vec<PathBuf>, path: &Path) -> bool {
+    use std::path::Component;
+    if path.components().any(|c| matches!(c, Component::RootDir | Component::Prefix(_))) {
+        return false;
+    }
+    pending.extend(path.components().rev().map(|c| PathBuf::from(c.as_os_str())));
+    true
+}
+
+/// Total size of the regular files under `dir`; symlinks are not followed.
+pub fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .follow_links(false)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Format a byte count for humans, e.g. `1.5 MiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
 pub fn ensure_dir(p: &Path) -> std::io::Result<()> {
     std::fs::create_dir_all(p)
 }
@@ -1,4 +1,6 @@
 use crate::cache::StoreEntry;
+use crate::colors::*;
+use crate::fsutil::copy_symlink;
 use crate::lockfile::Lockfile;
 use anyhow::{Context, Result};
 use rayon::prelude::*;
@@ -10,7 +12,9 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use walkdir::WalkDir;
 
-type ProgressCallback = Arc<dyn Fn(usize, usize, &str) + Send + Sync>;
+/// Called as `(done, total, package)` each time a package finishes materializing. It runs on
+/// the worker threads, so it must be cheap and synchronize its own output.
+pub type ProgressCallback = Arc<dyn Fn(usize, usize, &str) + Send + Sync>;
 
 #[derive(Debug, Clone)]
 pub struct PackageInstance {
@@ -48,11 +52,27 @@ pub struct InstallOutcome {
 #[derive(Debug)]
 pub struct Installer {
     mode: InstallMode,
+    bin_links: bool,
+    workspace_symlinks: bool,
 }
 
 impl Installer {
     pub fn new(mode: InstallMode) -> Self {
-        Self { mode }
+        Self { mode, bin_links: true, workspace_symlinks: false }
+    }
+
+    /// Whether to create `node_modules/.bin` shims. When disabled, an existing `.bin` left by an
+    /// earlier install is removed.
+    pub fn with_bin_links(mut self, bin_links: bool) -> Self {
+        self.bin_links = bin_links;
+        self
+    }
+
+    /// Symlink packages with a source directory (workspaces) straight to it, so edits show up
+    /// without reinstalling. Falls back to the store copy where symlinks are unavailable.
+    pub fn with_workspace_symlinks(mut self, workspace_symlinks: bool) -> Self {
+        self.workspace_symlinks = workspace_symlinks;
+        self
     }
 
     pub fn install(
@@ -90,15 +110,18 @@ impl Installer {
         let install_results: Result<Vec<(String, InstallMode)>> = names
             .par_iter()
             .map(|name| -> Result<(String, InstallMode)> {
+                crate::cancel::check_at(crate::cancel::Cancelled::WhileLinking)?;
                 let entry =
                     plan.get(name).expect("plan entries should remain stable across iteration");
                 let mut dest = pacm_root.clone();
                 for part in entry.package.name.split('/') {
                     dest.push(part);
                 }
-                let outcome_mode = self
-                    .materialize_fast(&entry.store_entry, &dest)
-                    .with_context(|| format!("materialize {} into project", entry.package.name))?;
+                let outcome_mode = match self.source_link(entry) {
+                    Some(source) => self.link_source(source, &entry.store_entry, &dest),
+                    None => self.materialize_fast(&entry.store_entry, &dest),
+                }
+                .with_context(|| format!("materialize {} into project", entry.package.name))?;
                 if let Some(cb) = &on_progress {
                     let done = counter.fetch_add(1, Ordering::SeqCst) + 1;
                     cb(done, total, name);
@@ -140,7 +163,10 @@ impl Installer {
             if !hoist_roots.contains(pkg_name) {
                 continue;
             }
-            let src = pacm_root.join(pkg_name);
+            let src = match plan.get(pkg_name).and_then(|entry| self.source_link(entry)) {
+                Some(source) if links_to(&pacm_root.join(pkg_name), source) => source.to_path_buf(),
+                _ => pacm_root.join(pkg_name),
+            };
             let dest = node_modules.join(pkg_name);
             if let Some(parent) = dest.parent() {
                 fs::create_dir_all(parent)?;
@@ -247,10 +273,14 @@ impl Installer {
         });
 
         // Create `.bin` shims in parallel for all installed packages.
-        install_results.par_iter().for_each(|(package_name, _mode)| {
-            let pkg_dest_dir = pacm_root.join(package_name);
-            let _ = create_bin_shims(project_root, package_name, &pkg_dest_dir);
-        });
+        if self.bin_links {
+            install_results.par_iter().for_each(|(package_name, _mode)| {
+                let pkg_dest_dir = pacm_root.join(package_name);
+                let _ = create_bin_shims(project_root, package_name, &pkg_dest_dir);
+            });
+        } else {
+            let _ = fs::remove_dir_all(node_modules.join(".bin"));
+        }
 
         let mut outcomes = Vec::with_capacity(install_results.len());
         for (package_name, outcome_mode) in install_results {
@@ -260,7 +290,11 @@ impl Installer {
                 {
                     lock_entry.store_key = Some(entry.store_entry.store_key.clone());
                     lock_entry.content_hash = Some(entry.store_entry.content_hash.clone());
+                    let symlinked = self
+                        .source_link(entry)
+                        .is_some_and(|source| links_to(&pacm_root.join(&package_name), source));
                     lock_entry.link_mode = Some(match outcome_mode {
+                        _ if symlinked => "symlink".to_string(),
                         InstallMode::Link => "link".to_string(),
                         InstallMode::Copy => "copy".to_string(),
                     });
@@ -275,6 +309,30 @@ impl Installer {
         Ok(outcomes)
     }
 
+    /// The source directory `entry` should be symlinked to, when workspace symlinks are on.
+    fn source_link<'a>(&self, entry: &'a InstallPlanEntry) -> Option<&'a Path> {
+        entry.package.source.as_deref().filter(|_| self.workspace_symlinks)
+    }
+
+    fn link_source(
+        &self,
+        source: &Path,
+        store_entry: &StoreEntry,
+        dest: &Path,
+    ) -> Result<InstallMode> {
+        if std::fs::symlink_metadata(dest).is_ok() {
+            // Removes a previous symlink itself, never the tree it points to.
+            fs::remove_dir_all(dest).or_else(|_| fs::remove_file(dest))?;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if try_symlink_dir(source, dest)? {
+            return Ok(InstallMode::Link);
+        }
+        self.materialize_fast(store_entry, dest)
+    }
+
     fn materialize_fast(&self, store_entry: &StoreEntry, dest: &Path) -> Result<InstallMode> {
         if dest.exists() || std::fs::symlink_metadata(dest).is_ok() {
             fs::remove_dir_all(dest).or_else(|_| {
@@ -289,6 +347,12 @@ impl Installer {
             fs::create_dir_all(parent)?;
         }
 
+        // Compressed entries have no tree to link to; extracting them is always a copy.
+        if store_entry.compressed {
+            crate::cache::unpack_compressed(&store_entry.archive_path(), dest)?;
+            return Ok(InstallMode::Copy);
+        }
+
         match self.mode {
             InstallMode::Copy => {
                 copy_tree_only(store_entry.package_dir(), dest)?;
@@ -317,7 +381,30 @@ impl Default for Installer {
     }
 }
 
+/// Packages with at least this many files are materialized with one rayon task per file;
+/// smaller ones stay on the calling thread, where the per-task overhead would dominate.
+pub(crate) const PARALLEL_FILE_THRESHOLD: usize = 256;
+
 fn copy_tree_only(from: &Path, to: &Path) -> Result<()> {
+    materialize_tree(from, to, false, PARALLEL_FILE_THRESHOLD).map(|_| ())
+}
+
+fn link_or_copy_tree(from: &Path, to: &Path) -> Result<bool> {
+    materialize_tree(from, to, true, PARALLEL_FILE_THRESHOLD)
+}
+
+/// Recreate `from` under `to`, hard-linking files when `link` is set and copying otherwise (or
+/// when a link fails). Directories are all created first, so files can then be placed in any
+/// order; trees with `parallel_threshold` or more files are placed in parallel. Returns whether
+/// every file was linked.
+pub(crate) fn materialize_tree(
+    from: &Path,
+    to: &Path,
+    link: bool,
+    parallel_threshold: usize,
+) -> Result<bool> {
+    fs::create_dir_all(to)?;
+    let mut files: Vec<(walkdir::DirEntry, PathBuf)> = Vec::new();
     for entry in WalkDir::new(from).follow_links(false) {
         let entry = entry?;
         let rel = entry.path().strip_prefix(from)?;
@@ -327,45 +414,90 @@ fn copy_tree_only(from: &Path, to: &Path) -> Result<()> {
         let dest = to.join(rel);
         if entry.file_type().is_dir() {
             fs::create_dir_all(&dest)?;
-            continue;
-        }
-        if let Some(parent) = dest.parent() {
-            fs::create_dir_all(parent)?;
+        } else {
+            files.push((entry, dest));
         }
-        fs::copy(entry.path(), &dest)?;
-        let perms = entry.metadata()?.permissions();
-        fs::set_permissions(&dest, perms)?;
     }
-    Ok(())
-}
 
-fn link_or_copy_tree(from: &Path, to: &Path) -> Result<bool> {
-    let mut all_linked = true;
-    for entry in WalkDir::new(from).follow_links(false) {
-        let entry = entry?;
-        let rel = entry.path().strip_prefix(from)?;
-        if rel.as_os_str().is_empty() {
-            continue;
+    let place = |(entry, dest): &(walkdir::DirEntry, PathBuf)| -> Result<bool> {
+        if entry.file_type().is_symlink() {
+            copy_symlink(from, entry.path(), dest)?;
+            return Ok(true);
         }
-        let dest = to.join(rel);
-        if entry.file_type().is_dir() {
-            fs::create_dir_all(&dest)?;
-            continue;
+        let linked = link && fs::hard_link(entry.path(), dest).is_ok();
+        if !linked {
+            fs::copy(entry.path(), dest)?;
         }
+        fs::set_permissions(dest, entry.metadata()?.permissions())?;
+        Ok(linked || !link)
+    };
+    if files.len() >= parallel_threshold {
+        files.par_iter().map(place).try_reduce(|| true, |a, b| Ok(a && b))
+    } else {
+        files.iter().map(place).try_fold(true, |all, placed| placed.map(|p| all && p))
+    }
+}
+
+/// Symlink the local package at `target` into `project_root/node_modules/<name>`, plus its
+/// `.pacm` entry and, with `bin_links`, its `.bin` shims. Unlike an install this never falls back
+/// to copying, so edits to `target` show up in the project immediately.
+pub(crate) fn link_local_package(
+    project_root: &Path,
+    name: &str,
+    target: &Path,
+    bin_links: bool,
+) -> Result<()> {
+    let node_modules = project_root.join("node_modules");
+    let pacm_entry = node_modules.join(".pacm").join(name);
+    for dest in [&pacm_entry, &node_modules.join(name)] {
         if let Some(parent) = dest.parent() {
             fs::create_dir_all(parent)?;
         }
-        match fs::hard_link(entry.path(), &dest) {
-            Ok(_) => {}
-            Err(_) => {
-                fs::copy(entry.path(), &dest)?;
-                all_linked = false;
-            }
+        let _ = fs::remove_dir_all(dest);
+        let _ = fs::remove_file(dest);
+        if !try_symlink_dir(target, dest)? {
+            anyhow::bail!("could not create a symlink at {}", dest.display());
+        }
+    }
+    if !bin_links {
+        return Ok(());
+    }
+    create_bin_shims(project_root, name, &pacm_entry)
+}
+
+/// The directory `node_modules/<name>` was linked to by [`link_local_package`], or `None` when
+/// it is missing or an installed package.
+pub(crate) fn linked_package_target(project_root: &Path, name: &str) -> Option<PathBuf> {
+    let node_modules = project_root.join("node_modules");
+    let target = fs::read_link(node_modules.join(name)).ok()?;
+    // Installed packages are hoisted from `node_modules/.pacm`; links point elsewhere.
+    (!target.starts_with(&node_modules)).then_some(target)
+}
+
+/// Undo [`link_local_package`]. Returns false, touching nothing, when `name` is not linked.
+pub(crate) fn unlink_local_package(project_root: &Path, name: &str) -> Result<bool> {
+    let Some(target) = linked_package_target(project_root, name) else {
+        return Ok(false);
+    };
+    let node_modules = project_root.join("node_modules");
+    let bin_dir = node_modules.join(".bin");
+    for (bin_name, _) in bin_entries(name, &target).unwrap_or_default() {
+        for suffix in ["", ".exe", ".cmd", ".ps1"] {
+            let _ = fs::remove_file(bin_dir.join(format!("{bin_name}{suffix}")));
+        }
+    }
+    for dest in [node_modules.join(".pacm").join(name), node_modules.join(name)] {
+        if fs::symlink_metadata(&dest).is_ok_and(|meta| meta.file_type().is_symlink()) {
+            // Removes the link itself; the linked package is left alone.
+            fs::remove_file(&dest).or_else(|_| fs::remove_dir(&dest))?;
         }
-        let perms = entry.metadata()?.permissions();
-        fs::set_permissions(&dest, perms)?;
     }
-    Ok(all_linked)
+    Ok(true)
+}
+
+/// Whether `path` is a symlink pointing at `target`.
+fn links_to(path: &Path, target: &Path) -> bool {
+    fs::read_link(path).is_ok_and(|dest| dest == target)
 }
 
 fn try_symlink_dir(from: &Path, to: &Path) -> Result<bool> {
@@ -395,12 +527,11 @@ fn try_symlink_dir(from: &Path, to: &Path) -> Result<bool> {
     }
 }
 
-fn create_bin_shims(project_root: &Path, package_name: &str, pkg_dest_dir: &Path) -> Result<()> {
-    // Read cached manifest to get bin entries
-    // Determine version by reading the installed package.json to avoid relying on the caller
-    let manifest_path = pkg_dest_dir.join("package.json");
+/// `(bin name, package-relative path)` for each `bin` entry declared in `pkg_dir/package.json`.
+fn bin_entries(package_name: &str, pkg_dir: &Path) -> Result<Vec<(String, String)>> {
+    let manifest_path = pkg_dir.join("package.json");
     if !manifest_path.exists() {
-        return Ok(());
+        return Ok(Vec::new());
     }
     let txt = fs::read_to_string(&manifest_path)?;
     #[derive(serde::Deserialize)]
@@ -411,14 +542,10 @@ fn create_bin_shims(project_root: &Path, package_name: &str, pkg_dest_dir: &Path
     }
     let mf: LocalMf =
         serde_json::from_str(&txt).with_context(|| "parse package.json for bin field")?;
-    let debug_shims = std::env::var("PACM_DEBUG_SHIMS").is_ok();
     let bin_field = match mf.bin {
-        None => return Ok(()),
+        None => return Ok(Vec::new()),
         Some(b) => b,
     };
-    let nm_dir = project_root.join("node_modules");
-    let bin_dir = nm_dir.join(".bin");
-    fs::create_dir_all(&bin_dir)?;
     // Build mapping name -> relative js path (within package)
     let entries: Vec<(String, String)> = match bin_field {
         crate::cache::BinField::Single(path) => {
@@ -426,11 +553,39 @@ fn create_bin_shims(project_root: &Path, package_name: &str, pkg_dest_dir: &Path
             vec![(name, path)]
         }
         crate::cache::BinField::Map(map) => map.into_iter().collect(),
+        crate::cache::BinField::Array(paths) => paths
+            .into_iter()
+            .filter_map(|path| match Path::new(&path).file_name().and_then(|n| n.to_str()) {
+                Some(name) => Some((name.to_string(), path.clone())),
+                None => {
+                    println!(
+                        "{C_GRAY}[pacm]{C_RESET} {C_YELLOW}warning{C_RESET} {package_name}: skipping bin entry '{path}' without a file name"
+                    );
+                    None
+                }
+            })
+            .collect(),
     };
-    for (mut bin_name, rel_path) in entries {
-        if let Some(idx) = bin_name.rfind('/') {
-            bin_name = bin_name[(idx + 1)..].to_string();
-        }
+    Ok(entries
+        .into_iter()
+        .map(|(name, path)| match name.rfind('/') {
+            Some(idx) => (name[(idx + 1)..].to_string(), path),
+            None => (name, path),
+        })
+        .collect())
+}
+
+fn create_bin_shims(project_root: &Path, package_name: &str, pkg_dest_dir: &Path) -> Result<()> {
+    // Read the installed package.json rather than relying on the caller for the bin field.
+    let entries = bin_entries(package_name, pkg_dest_dir)?;
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let debug_shims = std::env::var("PACM_DEBUG_SHIMS").is_ok();
+    let nm_dir = project_root.join("node_modules");
+    let bin_dir = nm_dir.join(".bin");
+    fs::create_dir_all(&bin_dir)?;
+    for (bin_name, rel_path) in entries {
         // Absolute JS target path (under node_modules/<pkg>/...)
         let target_js_abs = normalize_pkg_path(pkg_dest_dir, &rel_path);
         let within_pkg = target_js_abs.starts_with(pkg_dest_dir);
@@ -462,9 +617,15 @@ fn create_bin_shims(project_root: &Path, package_name: &str, pkg_dest_dir: &Path
 
         #[cfg(windows)]
         {
-            // Only create .exe and .exe.shim on Windows
-            let exe_path = bin_dir.join(format!("{bin_name}.exe"));
-            write_windows_exe_shim(&exe_path, &rel_from_bin)?;
+            let kinds = WindowsShimKinds::from_env();
+            if kinds.exe {
+                let exe_path = bin_dir.join(format!("{bin_name}.exe"));
+                write_windows_exe_shim(&exe_path, &rel_from_bin)?;
+            }
+            if kinds.scripts {
+                fs::write(bin_dir.join(format!("{bin_name}.cmd")), cmd_shim(&rel_from_bin))?;
+                fs::write(bin_dir.join(format!("{bin_name}.ps1")), ps1_shim(&rel_from_bin))?;
+            }
         }
         #[cfg(unix)]
         {
@@ -490,6 +651,82 @@ fn normalize_pkg_path(base: &Path, rel: &str) -> PathBuf {
     p
 }
 
+/// Which `.bin` shims to write on Windows, from `PACM_WINDOWS_SHIMS`: `exe` for the copied
+/// pacm launcher only, `scripts` for npm-style `.cmd`/`.ps1` wrappers only, and both otherwise.
+#[cfg_attr(not(windows), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct WindowsShimKinds {
+    pub exe: bool,
+    pub scripts: bool,
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl WindowsShimKinds {
+    pub(crate) fn from_env() -> Self {
+        Self::parse(std::env::var("PACM_WINDOWS_SHIMS").ok().as_deref())
+    }
+
+    pub(crate) fn parse(raw: Option<&str>) -> Self {
+        match raw.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            Some("exe") => Self { exe: true, scripts: false },
+            Some("scripts") => Self { exe: false, scripts: true },
+            _ => Self { exe: true, scripts: true },
+        }
+    }
+}
+
+/// npm-compatible `.cmd` wrapper running `node` on `relative_target` (relative to `.bin`),
+/// preferring a `node.exe` that sits next to the shim.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn cmd_shim(relative_target: &Path) -> String {
+    let target = relative_target.to_string_lossy().replace('/', "\\");
+    format!(
+        "@ECHO off\r\n\
+         GOTO start\r\n\
+         :find_dp0\r\n\
+         SET dp0=%~dp0\r\n\
+         EXIT /b\r\n\
+         :start\r\n\
+         SETLOCAL\r\n\
+         CALL :find_dp0\r\n\
+         \r\n\
+         IF EXIST \"%dp0%\\node.exe\" (\r\n  \
+           SET \"_prog=%dp0%\\node.exe\"\r\n\
+         ) ELSE (\r\n  \
+           SET \"_prog=node\"\r\n  \
+           SET PATHEXT=%PATHEXT:;.JS;=;%\r\n\
+         )\r\n\
+         \r\n\
+         endLocal & goto #_undefined_# 2>NUL || title %COMSPEC% & \"%_prog%\"  \"%dp0%\\{target}\" %*\r\n"
+    )
+}
+
+/// PowerShell counterpart of [`cmd_shim`], forwarding pipeline input and the exit code.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn ps1_shim(relative_target: &Path) -> String {
+    let target = relative_target.to_string_lossy().replace('\\', "/");
+    format!(
+        r#"#!/usr/bin/env pwsh
+$basedir=Split-Path $MyInvocation.MyCommand.Definition -Parent
+
+$exe=""
+if ($PSVersionTable.PSVersion -lt "6.0" -or $IsWindows) {{
+  $exe=".exe"
+}}
+$node="node$exe"
+if (Test-Path "$basedir/node$exe") {{
+  $node="$basedir/node$exe"
+}}
+if ($MyInvocation.ExpectingInput) {{
+  $input | & $node "$basedir/{target}" $args
+}} else {{
+  & $node "$basedir/{target}" $args
+}}
+exit $LASTEXITCODE
+"#
+    )
+}
+
 #[cfg(windows)]
 fn write_windows_exe_shim(dest_exe: &Path, relative_target: &Path) -> Result<()> {
     // Copy current pacm.exe as a generic shim and write a sidecar with target path.
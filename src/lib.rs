@@ -1,12 +1,18 @@
 pub mod cache;
+pub mod cancel;
 pub mod cli;
 pub mod colors;
+pub mod config;
 pub mod error;
 pub mod fetch;
 pub mod fsutil;
 pub mod installer;
 pub mod lockfile;
+pub mod lockfile_import;
+pub mod logging;
 pub mod manifest;
+pub mod npmrc;
+pub mod output;
 pub mod resolver;
 #[cfg(test)]
 pub mod tests;
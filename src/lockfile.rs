@@ -13,7 +13,7 @@ pub struct PeerMeta {
     pub optional: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
 pub struct PackageEntry {
     pub version: Option<String>,
     #[serde(default)]
@@ -34,6 +34,8 @@ pub struct PackageEntry {
     pub os: Vec<String>,
     #[serde(default, rename = "cpu")]
     pub cpu_arch: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub libc: Vec<String>,
     #[serde(default, rename = "storeKey")]
     pub store_key: Option<String>,
     #[serde(default, rename = "contentHash")]
@@ -42,6 +44,91 @@ pub struct PackageEntry {
     pub link_mode: Option<String>,
     #[serde(default, rename = "storePath")]
     pub store_path: Option<String>,
+    /// Where the package was resolved from. Absent for placeholder entries and for lockfiles
+    /// written before wire version 6; see [`PackageEntry::source_kind`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PackageSource>,
+}
+
+impl PackageEntry {
+    /// The recorded source, falling back to a guess from `resolved` for older entries.
+    pub fn source_kind(&self) -> Option<PackageSource> {
+        self.source.or_else(|| self.resolved.as_deref().map(PackageSource::infer))
+    }
+}
+
+/// Kind of location a locked package was resolved from.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageSource {
+    Registry,
+    Github,
+    Git,
+    Tarball,
+    File,
+    Workspace,
+}
+
+impl PackageSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PackageSource::Registry => "registry",
+            PackageSource::Github => "github",
+            PackageSource::Git => "git",
+            PackageSource::Tarball => "tarball",
+            PackageSource::File => "file",
+            PackageSource::Workspace => "workspace",
+        }
+    }
+
+    /// Best guess from a `resolved` value, for entries that predate the recorded source. Any
+    /// URL without the registry's `/-/` tarball path is taken to be a plain tarball.
+    pub fn infer(resolved: &str) -> Self {
+        if resolved.starts_with("workspace:") {
+            PackageSource::Workspace
+        } else if resolved.starts_with("file:") {
+            PackageSource::File
+        } else if resolved.starts_with("git+") {
+            PackageSource::Git
+        } else if resolved.starts_with("https://codeload.github.com/") {
+            PackageSource::Github
+        } else if resolved.contains("/-/") {
+            PackageSource::Registry
+        } else {
+            PackageSource::Tarball
+        }
+    }
+
+    fn wire_tag(source: Option<Self>) -> u8 {
+        match source {
+            None => 0,
+            Some(PackageSource::Registry) => 1,
+            Some(PackageSource::Github) => 2,
+            Some(PackageSource::Git) => 3,
+            Some(PackageSource::Tarball) => 4,
+            Some(PackageSource::File) => 5,
+            Some(PackageSource::Workspace) => 6,
+        }
+    }
+
+    fn from_wire_tag(tag: u8) -> anyhow::Result<Option<Self>> {
+        Ok(Some(match tag {
+            0 => return Ok(None),
+            1 => PackageSource::Registry,
+            2 => PackageSource::Github,
+            3 => PackageSource::Git,
+            4 => PackageSource::Tarball,
+            5 => PackageSource::File,
+            6 => PackageSource::Workspace,
+            other => bail!("invalid package source tag {other}"),
+        }))
+    }
+}
+
+impl std::fmt::Display for PackageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -49,11 +136,141 @@ pub struct Lockfile {
     pub format: u32,
     #[serde(default)]
     pub packages: BTreeMap<String, PackageEntry>,
+    #[serde(default, skip_serializing_if = "LockSettings::is_default")]
+    pub settings: LockSettings,
+    #[serde(default, skip_serializing_if = "LockMetadata::is_empty")]
+    pub metadata: LockMetadata,
+}
+
+/// Project-wide preferences recorded alongside the resolved packages. Stored in the binary
+/// extras section so readers that predate a setting simply skip it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct LockSettings {
+    /// Lifecycle scripts are disabled for this project (`pacm install --ignore-scripts`).
+    #[serde(default, rename = "ignoreScripts")]
+    pub ignore_scripts: bool,
+    /// `node_modules/.bin` shims are not created for this project (`--no-bin-links`).
+    #[serde(default, rename = "noBinLinks")]
+    pub no_bin_links: bool,
+    /// Workspace packages are symlinked to their source directory rather than copied through
+    /// the store (`--install-links=false`).
+    #[serde(default, rename = "symlinkWorkspaces")]
+    pub symlink_workspaces: bool,
+    /// Peer dependencies were left to the project when resolving (`legacy-peer-deps`), so they
+    /// have no lock entries and the lockfile walkers must not follow them.
+    #[serde(default, rename = "legacyPeerDeps")]
+    pub legacy_peer_deps: bool,
+}
+
+impl LockSettings {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn to_map(&self) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        if self.ignore_scripts {
+            map.insert("ignoreScripts".to_string(), "true".to_string());
+        }
+        if self.no_bin_links {
+            map.insert("noBinLinks".to_string(), "true".to_string());
+        }
+        if self.symlink_workspaces {
+            map.insert("symlinkWorkspaces".to_string(), "true".to_string());
+        }
+        if self.legacy_peer_deps {
+            map.insert("legacyPeerDeps".to_string(), "true".to_string());
+        }
+        map
+    }
+
+    fn from_map(map: &BTreeMap<String, String>) -> Self {
+        let flag = |key: &str| map.get(key).map(|v| v == "true").unwrap_or(false);
+        Self {
+            ignore_scripts: flag("ignoreScripts"),
+            no_bin_links: flag("noBinLinks"),
+            symlink_workspaces: flag("symlinkWorkspaces"),
+            legacy_peer_deps: flag("legacyPeerDeps"),
+        }
+    }
+}
+
+/// Who wrote the lockfile and when. Informational only: it is refreshed by every write and
+/// ignored when deciding whether the lockfile changed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct LockMetadata {
+    #[serde(default, rename = "pacmVersion", skip_serializing_if = "Option::is_none")]
+    pub pacm_version: Option<String>,
+    /// Seconds since the Unix epoch.
+    #[serde(default, rename = "writtenAt", skip_serializing_if = "Option::is_none")]
+    pub written_at: Option<u64>,
+}
+
+impl LockMetadata {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn current() -> Self {
+        let written_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .ok();
+        Self { pacm_version: Some(env!("CARGO_PKG_VERSION").to_string()), written_at }
+    }
+
+    fn extend_map(&self, map: &mut BTreeMap<String, String>) {
+        if let Some(version) = &self.pacm_version {
+            map.insert("pacmVersion".to_string(), version.clone());
+        }
+        if let Some(written_at) = self.written_at {
+            map.insert("writtenAt".to_string(), written_at.to_string());
+        }
+    }
+
+    fn from_map(map: &BTreeMap<String, String>) -> Self {
+        Self {
+            pacm_version: map.get("pacmVersion").cloned(),
+            written_at: map.get("writtenAt").and_then(|v| v.parse().ok()),
+        }
+    }
+}
+
+/// What changed between two lockfiles; see [`Lockfile::diff`].
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, Default)]
+pub struct LockfileDiff {
+    pub added: Vec<PackageVersion>,
+    pub removed: Vec<PackageVersion>,
+    pub changed: Vec<VersionChange>,
+}
+
+impl LockfileDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct PackageVersion {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct VersionChange {
+    pub name: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
 }
 
 impl Default for Lockfile {
     fn default() -> Self {
-        Self { format: 1, packages: BTreeMap::new() }
+        Self {
+            format: 1,
+            packages: BTreeMap::new(),
+            settings: LockSettings::default(),
+            metadata: LockMetadata::default(),
+        }
     }
 }
 
@@ -66,6 +283,47 @@ impl Lockfile {
         }
     }
 
+    /// Equal apart from [`LockMetadata`].
+    pub fn same_content(&self, other: &Lockfile) -> bool {
+        self.format == other.format
+            && self.packages == other.packages
+            && self.settings == other.settings
+    }
+
+    /// Package versions that differ between `self` and `newer`, by installed path.
+    pub fn diff(&self, newer: &Lockfile) -> LockfileDiff {
+        let versions = |lf: &Lockfile| -> BTreeMap<String, Option<String>> {
+            lf.packages
+                .iter()
+                .filter_map(|(key, entry)| {
+                    let name = key.strip_prefix("node_modules/")?;
+                    Some((name.to_string(), entry.version.clone()))
+                })
+                .collect()
+        };
+        let (old, new) = (versions(self), versions(newer));
+        let mut diff = LockfileDiff::default();
+        for (name, version) in &new {
+            match old.get(name) {
+                None => {
+                    diff.added.push(PackageVersion { name: name.clone(), version: version.clone() })
+                }
+                Some(previous) if previous != version => diff.changed.push(VersionChange {
+                    name: name.clone(),
+                    from: previous.clone(),
+                    to: version.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (name, version) in old {
+            if !new.contains_key(&name) {
+                diff.removed.push(PackageVersion { name, version });
+            }
+        }
+        diff
+    }
+
     pub fn sync_from_manifest(&mut self, manifest: &Manifest) {
         let root = self.packages.entry("".into()).or_insert(PackageEntry {
             version: None,
@@ -78,10 +336,12 @@ impl Lockfile {
             peer_dependencies_meta: BTreeMap::new(),
             os: Vec::new(),
             cpu_arch: Vec::new(),
+            libc: Vec::new(),
             store_key: None,
             content_hash: None,
             link_mode: None,
             store_path: None,
+            source: None,
         });
         root.version = Some(manifest.version.clone());
         // Persist each root section separately
@@ -115,10 +375,12 @@ impl Lockfile {
                 peer_dependencies_meta: BTreeMap::new(),
                 os: Vec::new(),
                 cpu_arch: Vec::new(),
+                libc: Vec::new(),
                 store_key: None,
                 content_hash: None,
                 link_mode: None,
                 store_path: None,
+                source: None,
             });
         }
     }
@@ -126,7 +388,20 @@ impl Lockfile {
 
 const MAX_LOCKFILE_SIZE: usize = 16 * 1024 * 1024;
 pub const LOCKFILE_MAGIC: &[u8; 8] = b"PACMLOCK";
-const CURRENT_WIRE_VERSION: u16 = 3;
+const CURRENT_WIRE_VERSION: u16 = 6;
+/// Header flag: a SHA-256 of the packages section follows the format field.
+const FLAG_PACKAGES_CHECKSUM: u16 = 1;
+
+/// The packages section of a binary lockfile does not hash to the checksum in its header,
+/// meaning the file was corrupted or edited by hand.
+#[derive(Debug, thiserror::Error)]
+#[error("lockfile checksum mismatch: the packages section may be corrupted or hand-edited")]
+pub struct LockfileChecksumMismatch;
+
+fn packages_checksum(packages_section: &[u8]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(packages_section).into()
+}
 
 fn write_u16(buf: &mut Vec<u8>, value: u16) {
     buf.extend_from_slice(&value.to_le_bytes());
@@ -204,6 +479,8 @@ pub fn encode_current_binary(lf: &Lockfile) -> Result<Vec<u8>> {
         write_option_string(&mut packages_buf, &entry.content_hash)?;
         write_option_string(&mut packages_buf, &entry.link_mode)?;
         write_option_string(&mut packages_buf, &entry.store_path)?;
+        write_string_list(&mut packages_buf, &entry.libc)?;
+        packages_buf.push(PackageSource::wire_tag(entry.source));
     }
 
     ensure!(packages_buf.len() <= MAX_LOCKFILE_SIZE, "lockfile data exceeds limit");
@@ -211,11 +488,21 @@ pub fn encode_current_binary(lf: &Lockfile) -> Result<Vec<u8>> {
     let mut buf = Vec::with_capacity(LOCKFILE_MAGIC.len() + 16 + packages_buf.len());
     buf.extend_from_slice(LOCKFILE_MAGIC);
     write_u16(&mut buf, CURRENT_WIRE_VERSION);
-    write_u16(&mut buf, 0); // reserved
+    write_u16(&mut buf, FLAG_PACKAGES_CHECKSUM);
     write_u32(&mut buf, lf.format);
+    buf.extend_from_slice(&packages_checksum(&packages_buf));
     write_len(&mut buf, packages_buf.len(), "packages section")?;
     buf.extend_from_slice(&packages_buf);
-    write_u32(&mut buf, 0); // reserved (extra section length)
+    let mut extras_map = lf.settings.to_map();
+    lf.metadata.extend_map(&mut extras_map);
+    if extras_map.is_empty() {
+        write_u32(&mut buf, 0);
+    } else {
+        let mut extras = Vec::new();
+        write_string_map(&mut extras, &extras_map)?;
+        write_len(&mut buf, extras.len(), "extras section")?;
+        buf.extend_from_slice(&extras);
+    }
     ensure!(buf.len() <= MAX_LOCKFILE_SIZE, "lockfile data exceeds limit");
     Ok(buf)
 }
@@ -347,6 +634,21 @@ fn parse_packages_section(
         } else {
             (None, None, None, None)
         };
+        let libc = if wire_version >= 4 {
+            read_string_list(packages_slice, &mut packages_pos)?
+        } else {
+            Vec::new()
+        };
+        let source = if wire_version >= 6 {
+            let tag = packages_slice
+                .get(packages_pos)
+                .copied()
+                .ok_or_else(|| anyhow!("unexpected eof reading package source"))?;
+            packages_pos += 1;
+            PackageSource::from_wire_tag(tag)?
+        } else {
+            None
+        };
 
         let entry = PackageEntry {
             version,
@@ -359,10 +661,12 @@ fn parse_packages_section(
             peer_dependencies_meta,
             os,
             cpu_arch,
+            libc,
             store_key,
             content_hash,
             link_mode,
             store_path,
+            source,
         };
         packages.insert(key, entry);
     }
@@ -372,20 +676,32 @@ fn parse_packages_section(
     Ok(packages)
 }
 
+/// Decode a `PACMLOCK` lockfile, failing with [`LockfileChecksumMismatch`] when its packages
+/// section does not match the recorded checksum. Files written before checksums existed decode
+/// without one.
 pub fn decode_current_binary(data: &[u8]) -> anyhow::Result<Lockfile> {
+    decode_binary(data, true)
+}
+
+fn decode_binary(data: &[u8], verify_checksum: bool) -> anyhow::Result<Lockfile> {
     ensure!(data.len() <= MAX_LOCKFILE_SIZE, "lockfile exceeds maximum size");
     ensure!(data.starts_with(LOCKFILE_MAGIC), "missing lockfile magic header");
 
     let mut pos = LOCKFILE_MAGIC.len();
     let version = read_u16(data, &mut pos)?;
-    if version != CURRENT_WIRE_VERSION && version != 2 && version != 1 {
+    if version == 0 || version > CURRENT_WIRE_VERSION {
         bail!("unsupported lockfile wire version {version}");
     }
 
-    // Skip reserved field
-    let _reserved = read_u16(data, &mut pos)?;
+    // Header flags; writers before wire version 5 always left this zero.
+    let flags = read_u16(data, &mut pos)?;
 
     let format = read_u32(data, &mut pos)?;
+    let checksum = if flags & FLAG_PACKAGES_CHECKSUM != 0 {
+        Some(read_exact(data, &mut pos, 32, "packages checksum")?)
+    } else {
+        None
+    };
 
     let packages_section_len = read_len(data, &mut pos, "packages section length")?;
     let packages_section_start = pos;
@@ -396,15 +712,31 @@ pub fn decode_current_binary(data: &[u8]) -> anyhow::Result<Lockfile> {
         .get(packages_section_start..packages_section_end)
         .ok_or_else(|| anyhow!("unexpected eof reading packages section"))?;
     pos = packages_section_end;
+    if let Some(expected) = checksum {
+        if verify_checksum && expected != packages_checksum(packages_slice) {
+            return Err(LockfileChecksumMismatch.into());
+        }
+    }
 
     let packages = parse_packages_section(packages_slice, version)?;
 
-    // Reserved section length (currently unused)
+    // Extras: a string map of project settings and writer metadata (empty when nothing is
+    // recorded).
     let extras_len = read_len(data, &mut pos, "extras section length")?;
-    let _extras = read_exact(data, &mut pos, extras_len, "extras section")?;
+    let extras = read_exact(data, &mut pos, extras_len, "extras section")?;
     ensure!(pos == data.len(), "unexpected trailing data");
+    let extras_map = if extras.is_empty() {
+        BTreeMap::new()
+    } else {
+        let mut extras_pos = 0usize;
+        let map = read_string_map(extras, &mut extras_pos)?;
+        ensure!(extras_pos == extras.len(), "unexpected trailing data in extras section");
+        map
+    };
+    let settings = LockSettings::from_map(&extras_map);
+    let metadata = LockMetadata::from_map(&extras_map);
 
-    Ok(Lockfile { format, packages })
+    Ok(Lockfile { format, packages, settings, metadata })
 }
 
 fn try_decode_standard<T>(data: &[u8]) -> Option<T>
@@ -550,32 +882,34 @@ fn decode_manual_legacy(data: &[u8]) -> anyhow::Result<Lockfile> {
             peer_dependencies_meta,
             os: Vec::new(),
             cpu_arch: Vec::new(),
+            libc: Vec::new(),
             store_key: None,
             content_hash: None,
             link_mode: None,
             store_path: None,
+            source: None,
         };
         packages.insert(key, entry);
     }
 
-    Ok(Lockfile { format, packages })
+    Ok(Lockfile { format, packages, ..Lockfile::default() })
 }
 
 fn try_decode_previous_formats(data: &[u8]) -> Option<Lockfile> {
-    if let Some(v) = try_decode_v1_varint::<Lockfile>(data) {
-        return Some(v);
+    if let Some(v) = try_decode_v1_varint::<BincodeLockfile>(data) {
+        return Some(v.into());
     }
     if let Some(legacy) = try_decode_v1_varint::<LegacyLockfile>(data) {
         return Some(legacy.into());
     }
-    if let Some(v) = try_decode_v1_fixint::<Lockfile>(data) {
-        return Some(v);
+    if let Some(v) = try_decode_v1_fixint::<BincodeLockfile>(data) {
+        return Some(v.into());
     }
     if let Some(legacy) = try_decode_v1_fixint::<LegacyLockfile>(data) {
         return Some(legacy.into());
     }
-    if let Some(v) = try_decode_standard::<Lockfile>(data) {
-        return Some(v);
+    if let Some(v) = try_decode_standard::<BincodeLockfile>(data) {
+        return Some(v.into());
     }
     if let Some(legacy) = try_decode_standard::<LegacyLockfile>(data) {
         return Some(legacy.into());
@@ -583,8 +917,8 @@ fn try_decode_previous_formats(data: &[u8]) -> Option<Lockfile> {
     if let Some(legacy) = try_decode_legacy::<LegacyLockfile>(data) {
         return Some(legacy.into());
     }
-    if let Some(v) = try_decode_legacy::<Lockfile>(data) {
-        return Some(v);
+    if let Some(v) = try_decode_legacy::<BincodeLockfile>(data) {
+        return Some(v.into());
     }
     if let Ok(manual) = decode_manual_legacy(data) {
         return Some(manual);
@@ -592,10 +926,31 @@ fn try_decode_previous_formats(data: &[u8]) -> Option<Lockfile> {
     None
 }
 
+/// Load a lockfile in any supported format. A binary lockfile whose checksum does not match is
+/// still loaded, with a warning; see [`load_strict`] to refuse it instead.
 pub fn load(path: &PathBuf) -> Result<Lockfile> {
+    load_with(path, false)
+}
+
+/// Like [`load`], but a checksum mismatch is an error rather than a warning.
+pub fn load_strict(path: &PathBuf) -> Result<Lockfile> {
+    load_with(path, true)
+}
+
+fn load_with(path: &PathBuf, strict: bool) -> Result<Lockfile> {
     let data = fs::read(path)?;
     let lf = if data.starts_with(LOCKFILE_MAGIC) {
-        decode_current_binary(&data)?
+        match decode_current_binary(&data) {
+            Err(err) if !strict && err.is::<LockfileChecksumMismatch>() => {
+                eprintln!(
+                    "[pacm] warning: {} may be corrupted or hand-edited (checksum mismatch); \
+                     run `pacm install` to rewrite it",
+                    path.display()
+                );
+                decode_binary(&data, false)?
+            }
+            result => result?,
+        }
     } else if let Some(decoded) = try_decode_previous_formats(&data) {
         decoded
     } else if let Ok(txt) = std::str::from_utf8(&data) {
@@ -615,7 +970,16 @@ pub fn load(path: &PathBuf) -> Result<Lockfile> {
 }
 
 pub fn write(lf: &Lockfile, path: PathBuf) -> Result<()> {
-    let data = encode_current_binary(lf)?;
+    // Leave an unchanged lockfile untouched so its mtime and VCS status stay quiet.
+    let unchanged = fs::read(&path)
+        .ok()
+        .and_then(|existing| decode_current_binary(&existing).ok())
+        .is_some_and(|existing| existing.same_content(lf));
+    if unchanged {
+        return Ok(());
+    }
+    let stamped = Lockfile { metadata: LockMetadata::current(), ..lf.clone() };
+    let data = encode_current_binary(&stamped)?;
     fs::write(path, data)?;
     Ok(())
 }
@@ -680,14 +1044,30 @@ impl From<LegacyLockfile> for Lockfile {
                         peer_dependencies_meta: v.peer_dependencies_meta,
                         os: Vec::new(),
                         cpu_arch: Vec::new(),
+                        libc: Vec::new(),
                         store_key: None,
                         content_hash: None,
                         link_mode: None,
                         store_path: None,
+                        source: None,
                     },
                 )
             })
             .collect();
-        Lockfile { format: old.format, packages }
+        Lockfile { format: old.format, packages, ..Lockfile::default() }
+    }
+}
+
+/// Shape of `Lockfile` as serialized by bincode before settings existed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct BincodeLockfile {
+    pub format: u32,
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageEntry>,
+}
+
+impl From<BincodeLockfile> for Lockfile {
+    fn from(old: BincodeLockfile) -> Self {
+        Lockfile { format: old.format, packages: old.packages, ..Lockfile::default() }
     }
 }
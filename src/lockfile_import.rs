@@ -0,0 +1,252 @@
+//! Seed a `pacm.lockb` from another package manager's lockfile.
+//!
+//! pacm keeps a single version per package name, so nested npm installs and duplicate pnpm
+//! versions are dropped; the next `pacm install` resolves whatever the import left out.
+
+use crate::lockfile::{Lockfile, PackageEntry, PeerMeta};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Result of an import: the lockfile plus how many source entries could not be represented.
+#[derive(Debug)]
+pub struct ImportedLock {
+    pub lockfile: Lockfile,
+    pub skipped: usize,
+}
+
+/// Parse `path` as `package-lock.json` (lockfile v2/v3) or `pnpm-lock.yaml`, based on its
+/// extension.
+pub fn import_file(path: &Path) -> Result<ImportedLock> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => from_package_lock(&text),
+        Some("yaml") | Some("yml") => from_pnpm_lock(&text),
+        _ => bail!(
+            "unrecognized lockfile {} (expected package-lock.json or pnpm-lock.yaml)",
+            path.display()
+        ),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NpmLock {
+    #[serde(default, rename = "lockfileVersion")]
+    lockfile_version: u32,
+    #[serde(default)]
+    packages: BTreeMap<String, NpmLockPackage>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct NpmLockPackage {
+    version: Option<String>,
+    resolved: Option<String>,
+    integrity: Option<String>,
+    #[serde(default)]
+    link: bool,
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+    #[serde(default, rename = "devDependencies")]
+    dev_dependencies: BTreeMap<String, String>,
+    #[serde(default, rename = "optionalDependencies")]
+    optional_dependencies: BTreeMap<String, String>,
+    #[serde(default, rename = "peerDependencies")]
+    peer_dependencies: BTreeMap<String, String>,
+    #[serde(default, rename = "peerDependenciesMeta")]
+    peer_dependencies_meta: BTreeMap<String, PeerMeta>,
+    #[serde(default)]
+    os: Vec<String>,
+    #[serde(default)]
+    cpu: Vec<String>,
+    #[serde(default)]
+    libc: Vec<String>,
+}
+
+pub fn from_package_lock(text: &str) -> Result<ImportedLock> {
+    let npm: NpmLock = serde_json::from_str(text).context("parse package-lock.json")?;
+    if npm.lockfile_version < 2 {
+        bail!(
+            "package-lock.json v{} has no `packages` map; regenerate it with npm 7 or newer",
+            npm.lockfile_version
+        );
+    }
+    let mut lockfile = Lockfile::default();
+    let mut skipped = 0;
+    for (key, pkg) in npm.packages {
+        let is_root = key.is_empty();
+        let top_level =
+            key.strip_prefix("node_modules/").is_some_and(|name| !name.contains("/node_modules/"));
+        // Nested installs and workspace links have no flat pacm equivalent.
+        if !is_root && (!top_level || pkg.link) {
+            skipped += 1;
+            continue;
+        }
+        let entry = PackageEntry {
+            version: pkg.version,
+            integrity: pkg.integrity,
+            resolved: pkg.resolved,
+            dependencies: pkg.dependencies,
+            dev_dependencies: if is_root { pkg.dev_dependencies } else { BTreeMap::new() },
+            optional_dependencies: pkg.optional_dependencies,
+            peer_dependencies: pkg.peer_dependencies,
+            peer_dependencies_meta: pkg.peer_dependencies_meta,
+            os: pkg.os,
+            cpu_arch: pkg.cpu,
+            libc: pkg.libc,
+            ..PackageEntry::default()
+        };
+        lockfile.packages.insert(key, entry);
+    }
+    Ok(ImportedLock { lockfile, skipped })
+}
+
+#[derive(Debug, Deserialize)]
+struct PnpmLock {
+    #[serde(default)]
+    importers: BTreeMap<String, PnpmImporter>,
+    #[serde(default)]
+    packages: BTreeMap<String, PnpmPackage>,
+    /// pnpm 9 keeps dependency edges here instead of under `packages`.
+    #[serde(default)]
+    snapshots: BTreeMap<String, PnpmPackage>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PnpmImporter {
+    #[serde(default)]
+    dependencies: BTreeMap<String, PnpmImporterDep>,
+    #[serde(default, rename = "devDependencies")]
+    dev_dependencies: BTreeMap<String, PnpmImporterDep>,
+    #[serde(default, rename = "optionalDependencies")]
+    optional_dependencies: BTreeMap<String, PnpmImporterDep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PnpmImporterDep {
+    specifier: String,
+    version: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PnpmPackage {
+    #[serde(default)]
+    resolution: Option<PnpmResolution>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+    #[serde(default, rename = "optionalDependencies")]
+    optional_dependencies: BTreeMap<String, String>,
+    #[serde(default, rename = "peerDependencies")]
+    peer_dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    os: Vec<String>,
+    #[serde(default)]
+    cpu: Vec<String>,
+    #[serde(default)]
+    libc: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PnpmResolution {
+    integrity: Option<String>,
+    tarball: Option<String>,
+}
+
+pub fn from_pnpm_lock(text: &str) -> Result<ImportedLock> {
+    let pnpm: PnpmLock = serde_yaml::from_str(text).context("parse pnpm-lock.yaml")?;
+    let mut lockfile = Lockfile::default();
+    let mut skipped = 0;
+
+    let root = pnpm.importers.get(".");
+    let mut root_versions: BTreeMap<String, String> = BTreeMap::new();
+    if let Some(root) = root {
+        let specifiers = |deps: &BTreeMap<String, PnpmImporterDep>| {
+            deps.iter().map(|(k, d)| (k.clone(), d.specifier.clone())).collect()
+        };
+        lockfile.packages.insert(
+            String::new(),
+            PackageEntry {
+                dependencies: specifiers(&root.dependencies),
+                dev_dependencies: specifiers(&root.dev_dependencies),
+                optional_dependencies: specifiers(&root.optional_dependencies),
+                ..PackageEntry::default()
+            },
+        );
+        for (name, dep) in root
+            .dependencies
+            .iter()
+            .chain(&root.dev_dependencies)
+            .chain(&root.optional_dependencies)
+        {
+            root_versions.insert(name.clone(), strip_peer_suffix(&dep.version).to_string());
+        }
+    }
+    skipped += pnpm.importers.len().saturating_sub(usize::from(root.is_some()));
+
+    // Group every locked version by name, then keep the one the root uses (or the highest).
+    let mut by_name: BTreeMap<String, Vec<(String, &str)>> = BTreeMap::new();
+    for key in pnpm.packages.keys() {
+        match split_pnpm_key(key) {
+            Some((name, version)) => {
+                by_name.entry(name.to_string()).or_default().push((version.to_string(), key))
+            }
+            None => skipped += 1,
+        }
+    }
+    for (name, mut versions) in by_name {
+        versions.sort_by(|a, b| compare_versions(&b.0, &a.0));
+        let chosen = root_versions
+            .get(&name)
+            .and_then(|v| versions.iter().position(|(ver, _)| ver == v))
+            .unwrap_or(0);
+        skipped += versions.len() - 1;
+        let (version, key) = versions.swap_remove(chosen);
+        let pkg = &pnpm.packages[key];
+        let edges = pnpm
+            .snapshots
+            .iter()
+            .find(|(snap, _)| strip_peer_suffix(snap) == strip_peer_suffix(key))
+            .map(|(_, snap)| snap)
+            .unwrap_or(pkg);
+        let exact = |deps: &BTreeMap<String, String>| {
+            deps.iter().map(|(k, v)| (k.clone(), strip_peer_suffix(v).to_string())).collect()
+        };
+        let resolution = pkg.resolution.as_ref();
+        lockfile.packages.insert(
+            format!("node_modules/{name}"),
+            PackageEntry {
+                version: Some(version),
+                integrity: resolution.and_then(|r| r.integrity.clone()),
+                resolved: resolution.and_then(|r| r.tarball.clone()),
+                dependencies: exact(&edges.dependencies),
+                optional_dependencies: exact(&edges.optional_dependencies),
+                peer_dependencies: pkg.peer_dependencies.clone(),
+                os: pkg.os.clone(),
+                cpu_arch: pkg.cpu.clone(),
+                libc: pkg.libc.clone(),
+                ..PackageEntry::default()
+            },
+        );
+    }
+    Ok(ImportedLock { lockfile, skipped })
+}
+
+/// `/name@1.0.0`, `name@1.0.0` and `/@scope/name@1.0.0(peer@2.0.0)` → `(name, version)`.
+fn split_pnpm_key(key: &str) -> Option<(&str, &str)> {
+    let key = strip_peer_suffix(key.trim_start_matches('/'));
+    let at = key.get(1..)?.rfind('@')? + 1;
+    let (name, version) = (&key[..at], &key[at + 1..]);
+    semver::Version::parse(version).ok()?;
+    Some((name, version))
+}
+
+fn strip_peer_suffix(version: &str) -> &str {
+    version.split_once('(').map(|(v, _)| v).unwrap_or(version)
+}
+
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    match (semver::Version::parse(a), semver::Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
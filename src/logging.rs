@@ -0,0 +1,82 @@
+//! Leveled diagnostics controlled by the global `-v` flag.
+//!
+//! Everything here writes to stderr so stdout stays reserved for regular command output and
+//! machine-readable formats. Nothing is printed at the default verbosity of 0.
+
+use crate::colors::*;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+/// Per-package resolve times at or above this are listed in the `-v` summary; `-vv` lists all.
+pub const SLOW_RESOLVE_THRESHOLD: Duration = Duration::from_millis(100);
+
+pub fn set_verbosity(level: u8) {
+    VERBOSITY.store(level, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> u8 {
+    VERBOSITY.load(Ordering::Relaxed)
+}
+
+pub fn enabled(level: u8) -> bool {
+    verbosity() >= level
+}
+
+/// Print `message` to stderr when running with at least `level` `-v` flags.
+pub fn log(level: u8, message: impl Display) {
+    if enabled(level) {
+        eprintln!("{C_GRAY}[pacm]{C_RESET} {C_DIM}verbose{C_RESET} {message}");
+    }
+}
+
+/// Wall-clock breakdown of an install, reported with `-v`.
+#[derive(Debug, Default)]
+pub struct PhaseTimings {
+    phases: Vec<(&'static str, Duration)>,
+    packages: Vec<(String, Duration)>,
+}
+
+impl PhaseTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, phase: &'static str, elapsed: Duration) {
+        self.phases.push((phase, elapsed));
+    }
+
+    pub fn record_packages(&mut self, packages: impl IntoIterator<Item = (String, Duration)>) {
+        self.packages.extend(packages);
+    }
+
+    /// Summary lines for the given verbosity; empty at level 0.
+    pub fn lines(&self, level: u8) -> Vec<String> {
+        if level == 0 {
+            return Vec::new();
+        }
+        let mut lines: Vec<String> = self
+            .phases
+            .iter()
+            .map(|(phase, elapsed)| format!("{phase:<8} {elapsed:.2?}"))
+            .collect();
+        let mut packages: Vec<&(String, Duration)> = self
+            .packages
+            .iter()
+            .filter(|(_, elapsed)| level >= 2 || *elapsed >= SLOW_RESOLVE_THRESHOLD)
+            .collect();
+        packages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        for (label, elapsed) in packages {
+            lines.push(format!("  resolve {label} {elapsed:.2?}"));
+        }
+        lines
+    }
+
+    pub fn report(&self) {
+        for line in self.lines(verbosity()) {
+            log(1, line);
+        }
+    }
+}
@@ -1,12 +1,22 @@
 use anyhow::{Context, Result};
 use pacm::cli::PacmCli;
 use pacm::colors::*;
+use pacm::output::{eprint_json, ErrorOutput, OutputFormat};
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
 
 fn main() {
-    if let Err(e) = real_main() {
+    let mut format = OutputFormat::Human;
+    if let Err(e) = real_main(&mut format) {
+        if format.is_json() {
+            let _ = eprint_json(&ErrorOutput::from_error(&e));
+            std::process::exit(if e.is::<pacm::cancel::Cancelled>() { 130 } else { 1 });
+        }
+        if e.is::<pacm::cancel::Cancelled>() {
+            eprintln!("{C_GRAY}[pacm]{C_RESET} {e}");
+            std::process::exit(130);
+        }
         eprintln!(
             "{gray}[pacm]{reset} {red}error{reset}: {e:#}",
             gray = C_GRAY,
@@ -17,7 +27,8 @@ fn main() {
     }
 }
 
-fn real_main() -> Result<()> {
+/// Runs the CLI, recording the selected output format in `format` so errors can be reported in it.
+fn real_main(format: &mut OutputFormat) -> Result<()> {
     // Shim mode: if current exe has a sidecar .shim file, treat this as a bin shim
     if let Ok(exe_path) = std::env::current_exe() {
         let sidecar = PathBuf::from(format!("{}.shim", exe_path.to_string_lossy()));
@@ -37,5 +48,6 @@ fn real_main() -> Result<()> {
         }
     }
     let cli = PacmCli::parse();
+    *format = cli.output_format();
     cli.run()
 }
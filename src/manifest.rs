@@ -20,6 +20,21 @@ pub struct Manifest {
     pub os: Vec<String>,
     #[serde(default, rename = "cpu", skip_serializing_if = "Vec::is_empty")]
     pub cpu_arch: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub libc: Vec<String>,
+    /// Packages permitted to run lifecycle scripts. `None` leaves scripts unrestricted.
+    #[serde(default, rename = "allowedScripts", skip_serializing_if = "Option::is_none")]
+    pub allowed_scripts: Option<Vec<String>>,
+    /// Corepack-style `tool@version` naming the package manager the project expects.
+    #[serde(default, rename = "packageManager", skip_serializing_if = "Option::is_none")]
+    pub package_manager: Option<String>,
+    /// Ranges that dependencies declared as `catalog:` resolve to. Only the root manifest's
+    /// catalogs are consulted.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub catalog: BTreeMap<String, String>,
+    /// Named catalogs, referenced as `catalog:<name>`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub catalogs: BTreeMap<String, BTreeMap<String, String>>,
 }
 
 impl Manifest {
@@ -34,8 +49,91 @@ impl Manifest {
             workspaces: Workspaces::default(),
             os: Vec::new(),
             cpu_arch: Vec::new(),
+            libc: Vec::new(),
+            allowed_scripts: None,
+            package_manager: None,
+            catalog: BTreeMap::new(),
+            catalogs: BTreeMap::new(),
+        }
+    }
+
+    /// The range a `catalog:` or `catalog:<name>` spec for `package` stands for in this
+    /// manifest's catalogs, or `None` when `spec` does not use the catalog protocol.
+    /// `catalog:` and `catalog:default` both name the top-level `catalog`.
+    pub fn catalog_range(&self, package: &str, spec: &str) -> Result<Option<String>> {
+        let Some(catalog_name) = spec.trim().strip_prefix("catalog:") else {
+            return Ok(None);
+        };
+        let catalog_name = catalog_name.trim();
+        let entries = if catalog_name.is_empty() || catalog_name == "default" {
+            Some(&self.catalog).filter(|c| !c.is_empty()).or(self.catalogs.get("default"))
+        } else {
+            self.catalogs.get(catalog_name)
+        };
+        let label = if catalog_name.is_empty() { "default" } else { catalog_name };
+        let Some(entries) = entries else {
+            anyhow::bail!("{package}@{spec}: no catalog named '{label}' in package.json");
+        };
+        match entries.get(package) {
+            Some(range) => Ok(Some(range.clone())),
+            None => anyhow::bail!("{package}@{spec}: {package} is not in the '{label}' catalog"),
         }
     }
+
+    /// Replace `catalog:` specs in `target`'s dependency sections with the ranges this
+    /// manifest's catalogs define.
+    pub fn apply_catalogs(&self, target: &mut Manifest) -> Result<()> {
+        for deps in [
+            &mut target.dependencies,
+            &mut target.dev_dependencies,
+            &mut target.optional_dependencies,
+            &mut target.peer_dependencies,
+        ] {
+            for (name, spec) in deps.iter_mut() {
+                if let Some(range) = self.catalog_range(name, spec)? {
+                    *spec = range;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compare the `packageManager` field with the running pacm `version`.
+    pub fn package_manager_status(&self, version: &str) -> PackageManagerStatus {
+        let Some(field) = self.package_manager.as_deref().map(str::trim) else {
+            return PackageManagerStatus::Unset;
+        };
+        let (tool, wanted) = match field.rsplit_once('@').filter(|(tool, _)| !tool.is_empty()) {
+            Some((tool, wanted)) => (tool, wanted),
+            None => (field, ""),
+        };
+        if tool != "pacm" {
+            return PackageManagerStatus::OtherTool(tool.to_string());
+        }
+        // Corepack allows a `+sha512.<hex>` suffix pinning the tarball; it is not part of the
+        // version.
+        let wanted = wanted.split_once("+sha").map_or(wanted, |(version, _)| version);
+        let matches = match (semver::Version::parse(wanted), semver::Version::parse(version)) {
+            (Ok(wanted), Ok(running)) => wanted.cmp_precedence(&running).is_eq(),
+            _ => wanted == version,
+        };
+        if matches {
+            PackageManagerStatus::Matches
+        } else {
+            PackageManagerStatus::OtherVersion(wanted.to_string())
+        }
+    }
+}
+
+/// How a manifest's `packageManager` field relates to the running pacm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackageManagerStatus {
+    Unset,
+    Matches,
+    /// The project pins a different pacm version.
+    OtherVersion(String),
+    /// The project expects another tool, such as npm, pnpm or yarn.
+    OtherTool(String),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -0,0 +1,66 @@
+//! Read settings from `.npmrc` files so projects configured for npm keep working.
+//!
+//! Only the flat `key=value` form is understood. The project's `.npmrc` takes precedence over
+//! the one in the home directory, as with npm.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct Npmrc {
+    /// Each value with the directory of the file it came from, for resolving relative paths.
+    values: BTreeMap<String, (String, PathBuf)>,
+}
+
+impl Npmrc {
+    /// Load `./.npmrc` and `~/.npmrc`. Missing or unreadable files are skipped.
+    pub fn load() -> Self {
+        let mut npmrc = Npmrc::default();
+        let project = std::env::current_dir().ok();
+        for dir in project.into_iter().chain(dirs::home_dir()) {
+            if let Ok(text) = std::fs::read_to_string(dir.join(".npmrc")) {
+                npmrc.merge(Npmrc::parse(&text, &dir));
+            }
+        }
+        npmrc
+    }
+
+    pub fn parse(text: &str, dir: &Path) -> Self {
+        let mut values = BTreeMap::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+            values.insert(key.trim().to_string(), (value.to_string(), dir.to_path_buf()));
+        }
+        Self { values }
+    }
+
+    /// Add the entries of `other` that this file does not set itself.
+    fn merge(&mut self, other: Npmrc) {
+        for (key, value) in other.values {
+            self.values.entry(key).or_insert(value);
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|(value, _)| value.as_str())
+    }
+
+    /// Every setting as `(key, value)`, in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(key, (value, _))| (key.as_str(), value.as_str()))
+    }
+
+    /// A path setting, resolved against the directory of the `.npmrc` that set it.
+    pub fn get_path(&self, key: &str) -> Option<PathBuf> {
+        let (value, dir) = self.values.get(key)?;
+        (!value.is_empty()).then(|| dir.join(value))
+    }
+}
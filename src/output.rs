@@ -0,0 +1,59 @@
+//! Shared conventions for machine-readable command output.
+//!
+//! Commands that support `--json` take an [`OutputFormat`] and print exactly one pretty-printed
+//! JSON document to stdout through [`print_json`]; notes and warnings go to stderr so the output
+//! stays parseable. A failing command under `--json` reports an [`ErrorOutput`] on stderr.
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// How a read command should render its result, chosen by the global `--json` flag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_json_flag(json: bool) -> Self {
+        if json {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Human
+        }
+    }
+
+    pub fn is_json(self) -> bool {
+        self == OutputFormat::Json
+    }
+}
+
+/// Print `value` to stdout as pretty JSON.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// JSON shape of a failed command under `--json`: the top-level message, then the causes beneath
+/// it, outermost first.
+#[derive(Debug, Serialize)]
+pub struct ErrorOutput {
+    pub error: String,
+    pub context: Vec<String>,
+}
+
+impl ErrorOutput {
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        ErrorOutput {
+            error: err.to_string(),
+            context: err.chain().skip(1).map(|cause| cause.to_string()).collect(),
+        }
+    }
+}
+
+/// Print `value` to stderr as compact JSON, one line.
+pub fn eprint_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    eprintln!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Resolution of generic git repositories by shelling out to the `git` binary.
+
+use crate::resolver::spec::GitSpec;
+use anyhow::{bail, Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A checked-out repository packed the same way a registry tarball would be.
+#[derive(Debug)]
+pub struct GitArchive {
+    /// Gzipped tarball with every file under `package/`.
+    pub bytes: Vec<u8>,
+    /// Full commit sha the archive was produced from.
+    pub commit: String,
+}
+
+/// Clone `spec`, check out its reference and archive the working tree.
+pub fn archive_checkout(spec: &GitSpec) -> Result<GitArchive> {
+    ensure_git_available(&spec.url)?;
+    // `--` keeps the URL and path from being read as options; a ref can't sit behind `--` (git
+    // would take it as a path), so one that looks like an option is refused instead.
+    if let Some(reference) = spec.reference.as_deref().filter(|r| r.starts_with('-')) {
+        bail!("invalid git reference '{reference}' in {}", spec.url);
+    }
+
+    let checkout = ScratchDir::new();
+    run_git(
+        None,
+        &["clone", "--quiet", "--", &spec.url, &checkout.path.to_string_lossy()],
+        &format!("clone {}", spec.url),
+    )?;
+    if let Some(reference) = &spec.reference {
+        run_git(
+            Some(&checkout.path),
+            &["checkout", "--quiet", reference, "--"],
+            &format!("checkout {reference} in {}", spec.url),
+        )?;
+    }
+    let commit = String::from_utf8_lossy(&run_git(
+        Some(&checkout.path),
+        &["rev-parse", "HEAD"],
+        "read checked out commit",
+    )?)
+    .trim()
+    .to_string();
+    let tar = run_git(
+        Some(&checkout.path),
+        &["archive", "--format=tar", "--prefix=package/", "HEAD"],
+        &format!("archive {}", spec.url),
+    )?;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&tar)?;
+    let bytes = encoder.finish()?;
+    Ok(GitArchive { bytes, commit })
+}
+
+fn ensure_git_available(url: &str) -> Result<()> {
+    let found = Command::new("git")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if !found {
+        bail!(
+            "git is required to install {url} but was not found on PATH; install git or depend on a tarball/registry version instead"
+        );
+    }
+    Ok(())
+}
+
+fn run_git(cwd: Option<&Path>, args: &[&str], what: &str) -> Result<Vec<u8>> {
+    let mut cmd = Command::new("git");
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
+    }
+    // Never block on credential prompts; installs are non-interactive.
+    cmd.env("GIT_TERMINAL_PROMPT", "0");
+    let output = cmd.args(args).output().with_context(|| format!("run git to {what}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git failed to {what}: {}", stderr.trim());
+    }
+    Ok(output.stdout)
+}
+
+struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    fn new() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+        let path = std::env::temp_dir().join(format!("pacm-git-{}-{nanos}", std::process::id()));
+        Self { path }
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,1826 @@
+//! Dependency graph resolution shared by `pacm install` and library callers.
+//!
+//! [`resolve_graph`] walks the root manifest, workspaces and any explicit specs, picks versions
+//! (reusing lockfile entries and cached packages where possible), downloads missing tarballs into
+//! the cache and records everything in the lockfile. It never prints, touches `node_modules`, or
+//! writes the lockfile to disk; callers decide what to do with the result.
+
+use crate::cache::CachedManifest;
+use crate::fetch::Fetcher;
+use crate::fsutil::human_size;
+use crate::installer::PackageInstance;
+use crate::lockfile::{Lockfile, PackageEntry, PackageSource};
+use crate::manifest::Manifest;
+use crate::resolver::platform::TargetPlatform;
+use crate::resolver::spec::{parse_spec, rebase_local_range, PackageSpec};
+use crate::workspaces::{workspace_dep_satisfies, WorkspaceInfo};
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tar::Archive;
+
+/// Status callback invoked with a short kind (`resolving`, `downloading`, ...) and a detail line.
+pub type StatusFn<'a> = &'a (dyn Fn(&str, &str) + Sync);
+
+/// Inputs that shape a resolution run beyond the manifest and lockfile.
+#[derive(Clone, Copy, Default)]
+pub struct ResolveOptions<'a> {
+    /// Workspace packages keyed by name; these resolve to their local directories.
+    pub workspaces: Option<&'a BTreeMap<String, WorkspaceInfo>>,
+    /// Directory relative `file:` specs are resolved against; the current directory when `None`.
+    /// Workspace manifests' `file:` specs are first made relative to it.
+    pub project_root: Option<&'a std::path::Path>,
+    /// Explicit `name@range` specs. When non-empty they replace the root manifest dependencies
+    /// as resolution roots.
+    pub specs: &'a [String],
+    /// Treat explicit specs as optional roots (failures are skipped rather than fatal).
+    pub optional_specs: bool,
+    /// Never hit the network for packages that are missing from the cache.
+    pub prefer_offline: bool,
+    /// Never hit the network at all; metadata, dist-tags and tarballs must come from the cache.
+    pub offline: bool,
+    /// Ignore cached tarballs and download every package again (`pacm install --force`).
+    pub force: bool,
+    /// Receives progress updates, plus `"warning"` messages meant to stay on screen; `None`
+    /// keeps resolution silent.
+    pub on_status: Option<StatusFn<'a>>,
+    /// Abort with an error once this instant passes. Checked before each resolution step and
+    /// around download waves, so an in-flight request is never cut short.
+    pub deadline: Option<Instant>,
+    /// Stop following dependencies this many levels below the roots; `Some(0)` resolves only
+    /// direct dependencies. The resulting graph is knowingly incomplete.
+    pub max_depth: Option<usize>,
+    /// When another range for an already-selected package rejects its version, pick the newest
+    /// version that every range seen for it accepts rather than the newest match for the new
+    /// range alone (`--prefer-dedupe`).
+    pub prefer_dedupe: bool,
+    /// Platform that package `os`/`cpu`/`libc` fields are checked against; the host by default.
+    pub platform: TargetPlatform,
+    /// Don't follow packages' `peerDependencies`; the project provides them (`legacy-peer-deps`).
+    pub legacy_peer_deps: bool,
+    /// Accept downloads whatever their hash and record the computed integrity instead of the
+    /// expected one (`--no-verify`). Only for debugging a registry serving bad metadata.
+    pub no_verify: bool,
+}
+
+/// Outcome of [`resolve_graph`].
+#[derive(Debug)]
+pub struct ResolvedGraph {
+    /// Every package selected for installation, keyed by name.
+    pub instances: BTreeMap<String, PackageInstance>,
+    /// The input lockfile updated with the resolved entries.
+    pub lockfile: Lockfile,
+    /// Number of packages that were fetched into the cache during this run.
+    pub downloaded: usize,
+    /// Wall time spent downloading tarballs (parallel wave plus optional inline downloads).
+    pub download_time: Duration,
+    /// How long each `name@range` task took to resolve, in queue order.
+    pub resolve_times: Vec<(String, Duration)>,
+    /// Deprecation messages for resolved versions, keyed by `name@version`. Only versions whose
+    /// registry metadata was read during this run are checked.
+    pub deprecated: BTreeMap<String, String>,
+}
+
+/// Default for `PACM_MAX_PACKAGES`: far beyond any real dependency tree.
+pub const DEFAULT_MAX_PACKAGES: usize = 100_000;
+
+/// Pending resolution tasks allowed per permitted package. Shared dependencies are queued once
+/// per dependent, so the queue legitimately outgrows the package count.
+const MAX_QUEUE_PER_PACKAGE: usize = 10;
+
+/// Most packages a single resolution may select, overridable via `PACM_MAX_PACKAGES`; `0` lifts
+/// the cap. Guards against a broken or malicious registry generating an endless graph.
+pub fn max_packages() -> usize {
+    std::env::var("PACM_MAX_PACKAGES")
+        .ok()
+        .and_then(|raw| raw.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_PACKAGES)
+}
+
+/// Fail once resolution has selected more than `max` packages or queued more than
+/// [`MAX_QUEUE_PER_PACKAGE`] tasks per permitted package.
+fn check_resolution_limits(resolved: usize, queued: usize, max: usize) -> Result<()> {
+    if max == 0 {
+        return Ok(());
+    }
+    if resolved > max {
+        bail!(
+            "resolution selected more than {max} packages; the dependency graph looks runaway \
+             (raise PACM_MAX_PACKAGES if it is genuine)"
+        );
+    }
+    let max_queue = max.saturating_mul(MAX_QUEUE_PER_PACKAGE);
+    if queued > max_queue {
+        bail!(
+            "resolution queue grew past {max_queue} pending dependencies; the dependency graph \
+             looks runaway (raise PACM_MAX_PACKAGES if it is genuine)"
+        );
+    }
+    Ok(())
+}
+
+/// Default for `PACM_SIZE_WARN`: packages that unpack to more than 100 MiB are called out.
+pub const DEFAULT_SIZE_WARN_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Unpacked size in bytes above which a package is called out before it is downloaded,
+/// overridable via `PACM_SIZE_WARN`; `0` turns the warning off. A value that is not a byte count
+/// is an error.
+pub fn size_warn_threshold() -> Result<u64> {
+    match std::env::var("PACM_SIZE_WARN") {
+        Ok(raw) if !raw.trim().is_empty() => raw
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid PACM_SIZE_WARN '{raw}' (expected bytes)")),
+        _ => Ok(DEFAULT_SIZE_WARN_BYTES),
+    }
+}
+
+/// Mention the registry's declared size of `label` before downloading it: always with `-v`, and
+/// as a `"warning"` status when it exceeds `threshold` (see [`size_warn_threshold`]).
+fn report_unpacked_size(
+    status: impl Fn(&str, &str),
+    label: &str,
+    unpacked_size: Option<u64>,
+    file_count: Option<u64>,
+    threshold: u64,
+) {
+    let Some(size) = unpacked_size else {
+        return;
+    };
+    let files = file_count.map(|n| format!(", {n} files")).unwrap_or_default();
+    crate::logging::log(1, format!("{label} unpacks to {}{files}", human_size(size)));
+    if threshold > 0 && size > threshold {
+        status(
+            "warning",
+            &format!(
+                "{label} is large: {} unpacked (over {}; set PACM_SIZE_WARN to adjust)",
+                human_size(size),
+                human_size(threshold)
+            ),
+        );
+    }
+}
+
+/// Resolve the full dependency graph for `manifest`, starting from `lockfile`.
+pub fn resolve_graph(
+    manifest: &Manifest,
+    mut lockfile: Lockfile,
+    fetcher: &Fetcher,
+    options: &ResolveOptions<'_>,
+) -> Result<ResolvedGraph> {
+    let empty_workspaces = BTreeMap::new();
+    let workspace_map = options.workspaces.unwrap_or(&empty_workspaces);
+    let specs = options.specs;
+    let offline = options.offline;
+    let prefer_offline = options.prefer_offline || offline;
+    let platform = options.platform;
+    let legacy_peer_deps = options.legacy_peer_deps;
+    let force = options.force;
+    let mut refreshed: HashSet<(String, String)> = HashSet::new();
+    let mut deprecated: BTreeMap<String, String> = BTreeMap::new();
+    let mut dedupe_ranges: HashMap<String, Vec<String>> = HashMap::new();
+    let max_packages = max_packages();
+    let size_warn = size_warn_threshold()?;
+    let status = |kind: &str, detail: &str| {
+        if let Some(cb) = options.on_status {
+            cb(kind, detail);
+        }
+    };
+    let lock = &mut lockfile;
+
+    let resolver = crate::resolver::Resolver::new();
+
+    #[derive(Clone)]
+    struct Task {
+        name: String,
+        range: String,
+        optional_root: bool,
+        /// Levels below the project (or workspace) roots; roots themselves are 0.
+        depth: usize,
+    }
+
+    let mut queue: VecDeque<Task> = VecDeque::new();
+    for ws in workspace_map.values() {
+        queue.push_back(Task {
+            name: ws.name.clone(),
+            range: format!("workspace:{}", ws.version),
+            optional_root: false,
+            depth: 0,
+        });
+        for (n, r) in &ws.manifest.dependencies {
+            queue.push_back(Task {
+                name: n.clone(),
+                range: rebase_local_range(r, &ws.relative_path),
+                optional_root: false,
+                depth: 0,
+            });
+        }
+        for (n, r) in &ws.manifest.dev_dependencies {
+            queue.push_back(Task {
+                name: n.clone(),
+                range: rebase_local_range(r, &ws.relative_path),
+                optional_root: false,
+                depth: 0,
+            });
+        }
+        for (n, r) in &ws.manifest.optional_dependencies {
+            queue.push_back(Task {
+                name: n.clone(),
+                range: rebase_local_range(r, &ws.relative_path),
+                optional_root: true,
+                depth: 0,
+            });
+        }
+    }
+    if specs.is_empty() {
+        for (n, r) in &manifest.dependencies {
+            queue.push_back(Task {
+                name: n.clone(),
+                range: r.clone(),
+                optional_root: false,
+                depth: 0,
+            });
+        }
+        for (n, r) in &manifest.dev_dependencies {
+            queue.push_back(Task {
+                name: n.clone(),
+                range: r.clone(),
+                optional_root: false,
+                depth: 0,
+            });
+        }
+        for (n, r) in &manifest.optional_dependencies {
+            queue.push_back(Task {
+                name: n.clone(),
+                range: r.clone(),
+                optional_root: true,
+                depth: 0,
+            });
+        }
+    } else {
+        for spec in specs {
+            let (name, req) = parse_spec(spec);
+            queue.push_back(Task {
+                name,
+                range: req,
+                optional_root: options.optional_specs,
+                depth: 0,
+            });
+        }
+    }
+
+    // Dist-tags named explicitly on the command line are re-resolved; tags coming from manifests
+    // stay pinned to whatever the lockfile recorded.
+    let explicit_tag_specs: HashSet<String> = specs
+        .iter()
+        .map(|spec| parse_spec(spec))
+        .filter(|(_, req)| crate::resolver::looks_like_dist_tag(req))
+        .map(|(name, _)| name)
+        .collect();
+
+    let mut visited_name_version: HashSet<(String, String)> = HashSet::new();
+    let mut downloaded = 0usize;
+    let mut pending_downloads: Vec<PendingDownload> = Vec::new();
+    let mut pending_set: HashSet<(String, String)> = HashSet::new();
+
+    let mut instances: BTreeMap<String, PackageInstance> = BTreeMap::new();
+    let mut download_time = Duration::ZERO;
+    let resolve_times: RefCell<Vec<(String, Duration)>> = RefCell::new(Vec::new());
+
+    while let Some(Task { name, range, optional_root, depth }) = queue.pop_front() {
+        check_deadline(options.deadline)?;
+        crate::cancel::check()?;
+        check_resolution_limits(instances.len(), queue.len(), max_packages)?;
+        if options.max_depth.is_some_and(|max| depth > max) {
+            continue;
+        }
+        let range = manifest.catalog_range(&name, &range)?.unwrap_or(range);
+        let _timer = ResolveTimer::start(&resolve_times, format!("{name}@{range}"));
+        if let Some(ws) = workspace_map.get(&name) {
+            let ws_version = ws.manifest.version.clone();
+            if !workspace_dep_satisfies(&range, &ws_version) {
+                if optional_root {
+                    continue;
+                }
+                bail!("workspace {name}@{ws_version} does not satisfy range {range}");
+            }
+            if visited_name_version.contains(&(name.clone(), ws_version.clone())) {
+                continue;
+            }
+            let package_os = ws.manifest.os.clone();
+            let package_cpu = ws.manifest.cpu_arch.clone();
+            let package_libc = ws.manifest.libc.clone();
+            let platform_ok = platform.supports(&package_os, &package_cpu, &package_libc);
+            let resolved_hint = Some(format!("workspace:{}", ws.relative_path));
+            if !platform_ok {
+                if optional_root {
+                    write_lock_entry(
+                        lock,
+                        &name,
+                        &ws_version,
+                        None,
+                        resolved_hint.as_deref(),
+                        PackageSource::Workspace,
+                        &ws.manifest.dependencies,
+                        &ws.manifest.dev_dependencies,
+                        &ws.manifest.optional_dependencies,
+                        &ws.manifest.peer_dependencies,
+                        &BTreeMap::new(),
+                        &package_os,
+                        &package_cpu,
+                        &package_libc,
+                    );
+                    visited_name_version.insert((name.clone(), ws_version.clone()));
+                    continue;
+                } else {
+                    bail!("workspace {name}@{ws_version} is not supported on {platform}");
+                }
+            }
+
+            write_lock_entry(
+                lock,
+                &name,
+                &ws_version,
+                None,
+                resolved_hint.as_deref(),
+                PackageSource::Workspace,
+                &ws.manifest.dependencies,
+                &ws.manifest.dev_dependencies,
+                &ws.manifest.optional_dependencies,
+                &ws.manifest.peer_dependencies,
+                &BTreeMap::new(),
+                &package_os,
+                &package_cpu,
+                &package_libc,
+            );
+            instances.insert(
+                name.clone(),
+                PackageInstance {
+                    name: name.clone(),
+                    version: ws_version.clone(),
+                    dependencies: ws.manifest.dependencies.clone(),
+                    optional_dependencies: ws.manifest.optional_dependencies.clone(),
+                    peer_dependencies: ws.manifest.peer_dependencies.clone(),
+                    dev_dependencies: ws.manifest.dev_dependencies.clone(),
+                    source: Some(ws.dir.clone()),
+                },
+            );
+            visited_name_version.insert((name.clone(), ws_version.clone()));
+
+            let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
+            for (dn, dr) in ws.manifest.dependencies.iter() {
+                to_enqueue.push((dn.clone(), rebase_local_range(dr, &ws.relative_path), false));
+            }
+            for (dn, dr) in ws.manifest.dev_dependencies.iter() {
+                to_enqueue.push((dn.clone(), rebase_local_range(dr, &ws.relative_path), false));
+            }
+            for (dn, dr) in ws.manifest.optional_dependencies.iter() {
+                to_enqueue.push((dn.clone(), rebase_local_range(dr, &ws.relative_path), true));
+            }
+            for (dn, dr) in ws.manifest.peer_dependencies.iter() {
+                to_enqueue.push((dn.clone(), dr.clone(), false));
+            }
+            // A workspace is a project root of its own, so its dependencies stay at its depth.
+            for (dn, dr, optflag) in to_enqueue {
+                queue.push_back(Task { name: dn, range: dr, optional_root: optflag, depth });
+            }
+            continue;
+        }
+
+        // Without a matching workspace, `workspace:` would otherwise be sent to the registry.
+        if range.trim_start().starts_with("workspace:") {
+            if optional_root {
+                continue;
+            }
+            bail!("no workspace named {name} found for workspace: dependency ({name}@{range})");
+        }
+
+        let is_registry_range = matches!(PackageSpec::parse(&range), PackageSpec::Registry { .. })
+            && !crate::resolver::looks_like_dist_tag(&range);
+        if options.prefer_dedupe && is_registry_range {
+            dedupe_ranges.entry(name.clone()).or_default().push(range.clone());
+        }
+
+        // Fast path: reuse an existing lock entry if it still satisfies the requested range and the
+        // package is already cached (or has a resolved URL we can download without re-resolving).
+        if matches!(PackageSpec::parse(&range), PackageSpec::Registry { .. }) {
+            let lock_key = format!("node_modules/{name}");
+            if let Some(lock_entry) = lock.packages.get(&lock_key) {
+                if let Some(ver_str) = &lock_entry.version {
+                    if let Ok(ver) = semver::Version::parse(ver_str) {
+                        let pinned_tag = crate::resolver::looks_like_dist_tag(&range)
+                            && !explicit_tag_specs.contains(&name);
+                        let matches_range = match crate::resolver::version_satisfies(&range, &ver) {
+                            Ok(m) => m || pinned_tag,
+                            Err(e) => {
+                                if optional_root {
+                                    false
+                                } else {
+                                    return Err(e);
+                                }
+                            }
+                        };
+                        if matches_range {
+                            // Cycles through lock entries would otherwise re-enqueue forever.
+                            if visited_name_version.contains(&(name.clone(), ver_str.clone())) {
+                                continue;
+                            }
+                            let platform_ok = platform.supports(
+                                &lock_entry.os,
+                                &lock_entry.cpu_arch,
+                                &lock_entry.libc,
+                            );
+                            if !platform_ok {
+                                if optional_root {
+                                    visited_name_version.insert((name.clone(), ver_str.clone()));
+                                    continue;
+                                } else {
+                                    bail!("{name}@{ver_str} is not supported on {platform}");
+                                }
+                            }
+
+                            let cache_exists = cache_present(&name, ver_str, force, &refreshed);
+                            let mut queued_download = false;
+                            if !cache_exists {
+                                if prefer_offline {
+                                    if optional_root {
+                                        visited_name_version
+                                            .insert((name.clone(), ver_str.clone()));
+                                        continue;
+                                    }
+                                    if offline {
+                                        bail!("offline: {name}@{ver_str} not cached");
+                                    }
+                                    bail!(
+                                        "{name}@{ver_str} not in cache and --prefer-offline is set"
+                                    );
+                                }
+                                if let Some(url) = &lock_entry.resolved {
+                                    status("queued", &format!("download {name}@{ver_str} (lock)"));
+                                    let key = (name.clone(), ver_str.clone());
+                                    refreshed.insert(key.clone());
+                                    if pending_set.insert(key) {
+                                        pending_downloads.push(PendingDownload {
+                                            name: name.clone(),
+                                            version: ver_str.clone(),
+                                            url: url.clone(),
+                                            integrity_hint: lock_entry.integrity.clone(),
+                                            scripts: None,
+                                            replace: force,
+                                        });
+                                        queued_download = true;
+                                    } else {
+                                        queued_download = true;
+                                    }
+                                }
+                            }
+
+                            if cache_exists || queued_download {
+                                status("fast", &format!("reuse {name}@{ver_str}"));
+                                instances
+                                    .insert(name.clone(), entry_to_instance(&name, lock_entry));
+                                visited_name_version.insert((name.clone(), ver_str.clone()));
+
+                                let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
+                                for (dn, dr) in lock_entry.dependencies.iter() {
+                                    to_enqueue.push((dn.clone(), dr.clone(), optional_root));
+                                }
+                                for (dn, dr) in lock_entry.optional_dependencies.iter() {
+                                    to_enqueue.push((dn.clone(), dr.clone(), true));
+                                }
+                                for (dn, dr) in lock_entry.peer_dependencies.iter() {
+                                    let is_optional = lock_entry
+                                        .peer_dependencies_meta
+                                        .get(dn)
+                                        .map(|m| m.optional)
+                                        .unwrap_or(false);
+                                    if !is_optional && !legacy_peer_deps {
+                                        to_enqueue.push((dn.clone(), dr.clone(), false));
+                                    }
+                                }
+                                for (dn, dr, optflag) in to_enqueue {
+                                    queue.push_back(Task {
+                                        name: dn,
+                                        range: dr,
+                                        optional_root: optflag,
+                                        depth: depth + 1,
+                                    });
+                                }
+                                continue;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // --prefer-dedupe: the version selected so far no longer fits. Rather than the newest
+        // match for this range alone, take one that every range seen for the package accepts.
+        let range = match dedupe_ranges.get(&name).filter(|ranges| ranges.len() > 1) {
+            Some(ranges) if is_registry_range => {
+                shared_version(fetcher, &name, ranges, prefer_offline, force)
+                    .map(|version| version.to_string())
+                    .unwrap_or(range)
+            }
+            _ => range,
+        };
+
+        // Cached store fast path: if a cached package satisfies the range, reuse it without
+        // touching the network. Helps for optional deps and repeated installs.
+        if let Some((picked_ver, cached_mf)) =
+            pick_cached_satisfying_manifest(&resolver, &name, &range).filter(|_| !force)
+        {
+            let picked_version = picked_ver.to_string();
+            if visited_name_version.contains(&(name.clone(), picked_version.clone())) {
+                continue;
+            }
+            let package_os = cached_mf.os.clone();
+            let package_cpu = cached_mf.cpu_arch.clone();
+            let package_libc = cached_mf.libc.clone();
+            let peer_meta_map: BTreeMap<String, crate::lockfile::PeerMeta> = cached_mf
+                .peer_dependencies_meta
+                .iter()
+                .map(|(k, v)| (k.clone(), crate::lockfile::PeerMeta { optional: v.optional }))
+                .collect();
+            let platform_ok = platform.supports(&package_os, &package_cpu, &package_libc);
+            if !platform_ok {
+                if optional_root {
+                    // Record entry in lockfile even when optional package is not
+                    // supported on this platform so tests can see the declared
+                    // version and platform metadata (store_key will remain None).
+                    write_lock_entry(
+                        lock,
+                        &name,
+                        &picked_version,
+                        None,
+                        None,
+                        PackageSource::Registry,
+                        &cached_mf.dependencies,
+                        &cached_mf.dev_dependencies,
+                        &cached_mf.optional_dependencies,
+                        &cached_mf.peer_dependencies,
+                        &peer_meta_map,
+                        &package_os,
+                        &package_cpu,
+                        &package_libc,
+                    );
+                    visited_name_version.insert((name.clone(), picked_version.clone()));
+                    continue;
+                } else {
+                    bail!("{name}@{picked_version} is not supported on {platform}");
+                }
+            }
+
+            write_lock_entry(
+                lock,
+                &name,
+                &picked_version,
+                None,
+                None,
+                PackageSource::Registry,
+                &cached_mf.dependencies,
+                &cached_mf.dev_dependencies,
+                &cached_mf.optional_dependencies,
+                &cached_mf.peer_dependencies,
+                &peer_meta_map,
+                &package_os,
+                &package_cpu,
+                &package_libc,
+            );
+
+            instances.insert(
+                name.clone(),
+                PackageInstance {
+                    name: name.clone(),
+                    version: picked_version.clone(),
+                    dependencies: cached_mf.dependencies.clone(),
+                    optional_dependencies: cached_mf.optional_dependencies.clone(),
+                    peer_dependencies: cached_mf.peer_dependencies.clone(),
+                    dev_dependencies: cached_mf.dev_dependencies.clone(),
+                    source: None,
+                },
+            );
+            visited_name_version.insert((name.clone(), picked_version.clone()));
+
+            let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
+            for (dn, dr) in cached_mf.dependencies.into_iter() {
+                to_enqueue.push((dn, dr, optional_root));
+            }
+            for (dn, dr) in cached_mf.optional_dependencies.into_iter() {
+                to_enqueue.push((dn, dr, true));
+            }
+            for (dn, dr) in cached_mf.peer_dependencies.into_iter() {
+                let is_optional_peer = peer_meta_map.get(&dn).map(|m| m.optional).unwrap_or(false);
+                if !is_optional_peer && !legacy_peer_deps {
+                    to_enqueue.push((dn, dr, false));
+                }
+            }
+            for (dn, dr, optflag) in to_enqueue {
+                queue.push_back(Task {
+                    name: dn,
+                    range: dr,
+                    optional_root: optflag,
+                    depth: depth + 1,
+                });
+            }
+            continue;
+        }
+
+        status("resolving", &format!("{name}@{range}"));
+
+        let spec_kind = PackageSpec::parse(&range);
+        if offline
+            && matches!(
+                spec_kind,
+                PackageSpec::Github(_) | PackageSpec::Git(_) | PackageSpec::Tarball { .. }
+            )
+        {
+            if optional_root {
+                continue;
+            }
+            bail!("offline: {name} not cached ({range} must be fetched)");
+        }
+
+        let git_source = match &spec_kind {
+            PackageSpec::Github(gh_spec) => {
+                status("resolving", &format!("{name} (github)"));
+                Some(resolve_github_tarball(gh_spec).and_then(|resolved| {
+                    let bytes = fetcher.download_tarball(&resolved.tarball_url)?;
+                    Ok(GitSource {
+                        bytes,
+                        commit: resolved.commit,
+                        resolved: resolved.tarball_url,
+                        source: PackageSource::Github,
+                    })
+                }))
+            }
+            PackageSpec::Git(git_spec) => {
+                status("resolving", &format!("{name} (git)"));
+                Some(crate::resolver::git::archive_checkout(git_spec).map(|archive| GitSource {
+                    bytes: archive.bytes,
+                    resolved: format!("git+{}#{}", git_spec.url, archive.commit),
+                    commit: archive.commit,
+                    source: PackageSource::Git,
+                }))
+            }
+            _ => None,
+        };
+
+        if let Some(source) = git_source {
+            let GitSource { bytes, commit, resolved, source } = match source {
+                Ok(s) => s,
+                Err(e) => {
+                    if optional_root {
+                        continue;
+                    }
+                    return Err(e);
+                }
+            };
+
+            let manifest_from_tar = match read_manifest_from_tarball(&bytes) {
+                Ok(mf) => mf,
+                Err(e) => {
+                    if optional_root {
+                        continue;
+                    }
+                    return Err(e);
+                }
+            };
+
+            let base_version = manifest_from_tar.version.clone().unwrap_or_else(|| "0.0.0".into());
+            let short = commit.chars().take(8).collect::<String>();
+            let picked_version = append_build(&base_version, &format!("git.{short}"));
+            let cache_exists = cache_present(&name, &picked_version, force, &refreshed);
+            let integrity_for_entry_string =
+                match cache_tarball(&name, &picked_version, &bytes, None, force) {
+                    Ok(i) => Some(i),
+                    Err(e) => {
+                        if optional_root {
+                            continue;
+                        }
+                        return Err(e);
+                    }
+                };
+            refreshed.insert((name.clone(), picked_version.clone()));
+
+            write_scripts_sidecar(&name, &picked_version, &manifest_from_tar.scripts);
+
+            let package_os = manifest_from_tar.os.clone();
+            let package_cpu = manifest_from_tar.cpu_arch.clone();
+            let package_libc = manifest_from_tar.libc.clone();
+            let platform_ok = platform.supports(&package_os, &package_cpu, &package_libc);
+            if !platform_ok {
+                if optional_root {
+                    write_lock_entry(
+                        lock,
+                        &name,
+                        &picked_version,
+                        integrity_for_entry_string.as_deref(),
+                        Some(resolved.as_str()),
+                        source,
+                        &manifest_from_tar.dependencies,
+                        &BTreeMap::new(),
+                        &manifest_from_tar.optional_dependencies,
+                        &manifest_from_tar.peer_dependencies,
+                        &manifest_from_tar
+                            .peer_dependencies_meta
+                            .into_iter()
+                            .map(|(k, v)| (k, crate::lockfile::PeerMeta { optional: v.optional }))
+                            .collect(),
+                        &package_os,
+                        &package_cpu,
+                        &package_libc,
+                    );
+                    visited_name_version.insert((name.clone(), picked_version.clone()));
+                    continue;
+                }
+                bail!("{name}@{picked_version} is not supported on {platform}");
+            }
+
+            let peer_meta_map: BTreeMap<String, crate::lockfile::PeerMeta> = manifest_from_tar
+                .peer_dependencies_meta
+                .iter()
+                .map(|(k, v)| (k.clone(), crate::lockfile::PeerMeta { optional: v.optional }))
+                .collect();
+
+            write_lock_entry(
+                lock,
+                &name,
+                &picked_version,
+                integrity_for_entry_string.as_deref(),
+                Some(resolved.as_str()),
+                source,
+                &manifest_from_tar.dependencies,
+                &BTreeMap::new(),
+                &manifest_from_tar.optional_dependencies,
+                &manifest_from_tar.peer_dependencies,
+                &peer_meta_map,
+                &package_os,
+                &package_cpu,
+                &package_libc,
+            );
+
+            instances.insert(
+                name.clone(),
+                PackageInstance {
+                    name: name.clone(),
+                    version: picked_version.clone(),
+                    dependencies: manifest_from_tar.dependencies.clone(),
+                    optional_dependencies: manifest_from_tar.optional_dependencies.clone(),
+                    peer_dependencies: manifest_from_tar.peer_dependencies.clone(),
+                    dev_dependencies: BTreeMap::new(),
+                    source: None,
+                },
+            );
+            visited_name_version.insert((name.clone(), picked_version.clone()));
+            if !cache_exists {
+                downloaded += 1;
+            }
+
+            let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
+            for (dn, dr) in manifest_from_tar.dependencies.into_iter() {
+                to_enqueue.push((dn, dr, optional_root));
+            }
+            for (dn, dr) in manifest_from_tar.optional_dependencies.into_iter() {
+                to_enqueue.push((dn, dr, true));
+            }
+            for (dn, dr) in manifest_from_tar.peer_dependencies.into_iter() {
+                let is_optional_peer = peer_meta_map.get(&dn).map(|m| m.optional).unwrap_or(false);
+                if !is_optional_peer && !legacy_peer_deps {
+                    to_enqueue.push((dn, dr, false));
+                }
+            }
+            for (dn, dr, optflag) in to_enqueue {
+                queue.push_back(Task {
+                    name: dn,
+                    range: dr,
+                    optional_root: optflag,
+                    depth: depth + 1,
+                });
+            }
+            continue;
+        }
+
+        let tarball_source = match &spec_kind {
+            PackageSpec::Tarball { url } => {
+                status("resolving", &format!("{name} (tarball)"));
+                Some(fetcher.download_tarball(url).map(|bytes| {
+                    let build = format!("remote.{}", short_hash(url));
+                    (bytes, url.clone(), build, PackageSource::Tarball)
+                }))
+            }
+            PackageSpec::LocalTarball { path } => {
+                status("resolving", &format!("{name} (file)"));
+                // Local archives can change in place, so they are versioned by content.
+                let on_disk = match options.project_root {
+                    Some(root) => root.join(path),
+                    None => std::path::PathBuf::from(path),
+                };
+                Some(
+                    std::fs::read(on_disk)
+                        .with_context(|| format!("read local tarball {path} for {name}"))
+                        .map(|bytes| {
+                            let build = format!("local.{}", short_hash(&bytes));
+                            (bytes, format!("file:{path}"), build, PackageSource::File)
+                        }),
+                )
+            }
+            _ => None,
+        };
+        if let Some(source) = tarball_source {
+            let (bytes, url, build_tag, source) = match source {
+                Ok(b) => b,
+                Err(e) => {
+                    if optional_root {
+                        continue;
+                    }
+                    return Err(e);
+                }
+            };
+
+            let manifest_from_tar = match read_manifest_from_tarball(&bytes) {
+                Ok(mf) => mf,
+                Err(e) => {
+                    if optional_root {
+                        continue;
+                    }
+                    return Err(e);
+                }
+            };
+
+            let base_version = manifest_from_tar.version.clone().unwrap_or_else(|| "0.0.0".into());
+            let version_tag = append_build(&base_version, &build_tag);
+            let cache_exists = cache_present(&name, &version_tag, force, &refreshed);
+            let integrity_for_entry_string =
+                match cache_tarball(&name, &version_tag, &bytes, None, force) {
+                    Ok(i) => Some(i),
+                    Err(e) => {
+                        if optional_root {
+                            continue;
+                        }
+                        return Err(e);
+                    }
+                };
+            refreshed.insert((name.clone(), version_tag.clone()));
+            write_scripts_sidecar(&name, &version_tag, &manifest_from_tar.scripts);
+
+            let package_os = manifest_from_tar.os.clone();
+            let package_cpu = manifest_from_tar.cpu_arch.clone();
+            let package_libc = manifest_from_tar.libc.clone();
+            let platform_ok = platform.supports(&package_os, &package_cpu, &package_libc);
+            if !platform_ok {
+                if optional_root {
+                    write_lock_entry(
+                        lock,
+                        &name,
+                        &version_tag,
+                        integrity_for_entry_string.as_deref(),
+                        Some(url.as_str()),
+                        source,
+                        &manifest_from_tar.dependencies,
+                        &BTreeMap::new(),
+                        &manifest_from_tar.optional_dependencies,
+                        &manifest_from_tar.peer_dependencies,
+                        &manifest_from_tar
+                            .peer_dependencies_meta
+                            .into_iter()
+                            .map(|(k, v)| (k, crate::lockfile::PeerMeta { optional: v.optional }))
+                            .collect(),
+                        &package_os,
+                        &package_cpu,
+                        &package_libc,
+                    );
+                    visited_name_version.insert((name.clone(), version_tag.clone()));
+                    continue;
+                }
+                bail!("{name}@{version_tag} is not supported on {platform}");
+            }
+
+            let peer_meta_map: BTreeMap<String, crate::lockfile::PeerMeta> = manifest_from_tar
+                .peer_dependencies_meta
+                .iter()
+                .map(|(k, v)| (k.clone(), crate::lockfile::PeerMeta { optional: v.optional }))
+                .collect();
+
+            write_lock_entry(
+                lock,
+                &name,
+                &version_tag,
+                integrity_for_entry_string.as_deref(),
+                Some(url.as_str()),
+                source,
+                &manifest_from_tar.dependencies,
+                &BTreeMap::new(),
+                &manifest_from_tar.optional_dependencies,
+                &manifest_from_tar.peer_dependencies,
+                &peer_meta_map,
+                &package_os,
+                &package_cpu,
+                &package_libc,
+            );
+
+            instances.insert(
+                name.clone(),
+                PackageInstance {
+                    name: name.clone(),
+                    version: version_tag.clone(),
+                    dependencies: manifest_from_tar.dependencies.clone(),
+                    optional_dependencies: manifest_from_tar.optional_dependencies.clone(),
+                    peer_dependencies: manifest_from_tar.peer_dependencies.clone(),
+                    dev_dependencies: BTreeMap::new(),
+                    source: None,
+                },
+            );
+            visited_name_version.insert((name.clone(), version_tag.clone()));
+            if !cache_exists {
+                downloaded += 1;
+            }
+
+            let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
+            for (dn, dr) in manifest_from_tar.dependencies.into_iter() {
+                to_enqueue.push((dn, dr, optional_root));
+            }
+            for (dn, dr) in manifest_from_tar.optional_dependencies.into_iter() {
+                to_enqueue.push((dn, dr, true));
+            }
+            for (dn, dr) in manifest_from_tar.peer_dependencies.into_iter() {
+                let is_optional_peer = peer_meta_map.get(&dn).map(|m| m.optional).unwrap_or(false);
+                if !is_optional_peer && !legacy_peer_deps {
+                    to_enqueue.push((dn, dr, false));
+                }
+            }
+            for (dn, dr, optflag) in to_enqueue {
+                queue.push_back(Task {
+                    name: dn,
+                    range: dr,
+                    optional_root: optflag,
+                    depth: depth + 1,
+                });
+            }
+            continue;
+        }
+
+        let range = match spec_kind {
+            PackageSpec::Registry { range } => range,
+            _ => range,
+        };
+
+        let picked_result: anyhow::Result<(semver::Version, String)> = (|| {
+            let cached = if force { Vec::new() } else { crate::cache::cached_versions(&name) };
+            let canon = crate::resolver::canonicalize_npm_range(&range);
+            let parsed_req = semver::VersionReq::parse(&canon).ok();
+            let looks_like_tag =
+                !range.contains(' ') && !range.contains("||") && !range.contains(',');
+            let is_tag_spec = parsed_req.is_none()
+                && canon != "*"
+                && !range.eq_ignore_ascii_case("latest")
+                && looks_like_tag;
+            if is_tag_spec {
+                if offline {
+                    bail!("offline: {name} not cached (dist-tag '{range}' needs the registry)");
+                }
+                if prefer_offline {
+                    bail!("cannot resolve dist-tag '{range}' for {name} offline");
+                }
+                let meta = fetcher
+                    .package_metadata(&name)
+                    .with_context(|| format!("fetch metadata for {name}"))?;
+                if let Some(tags) = &meta.dist_tags {
+                    if let Some(ver_s) = tags.get(&range) {
+                        let ver = semver::Version::parse(ver_s).with_context(|| {
+                            format!("invalid version '{ver_s}' for tag '{range}'")
+                        })?;
+                        let tar = meta
+                            .versions
+                            .get(ver_s)
+                            .map(|v| v.dist.tarball.clone())
+                            .unwrap_or_default();
+                        Ok((ver, tar))
+                    } else {
+                        bail!("unknown dist-tag '{range}' for {name}");
+                    }
+                } else {
+                    bail!("no dist-tags available for {name}");
+                }
+            } else {
+                if range.contains("||") || canon.contains("||") {
+                    let mut map: BTreeMap<semver::Version, String> = BTreeMap::new();
+                    for v in cached.into_iter() {
+                        map.insert(v, String::new());
+                    }
+                    if !map.is_empty() {
+                        if let Ok((ver, _)) = resolver.pick_version(&map, &range) {
+                            return Ok((ver, String::new()));
+                        }
+                    }
+                } else {
+                    let req = if canon == "*" {
+                        semver::VersionReq::STAR
+                    } else {
+                        parsed_req.unwrap_or(semver::VersionReq::STAR)
+                    };
+                    if let Some(ver) = cached.iter().find(|candidate| req.matches(candidate)) {
+                        return Ok((ver.clone(), String::new()));
+                    }
+                }
+                if offline {
+                    bail!("offline: {name} not cached (no cached version matches {range})");
+                }
+                let meta = fetcher
+                    .package_metadata(&name)
+                    .with_context(|| format!("fetch metadata for {name}"))?;
+                if range.eq_ignore_ascii_case("latest") {
+                    if let Some(tags) = &meta.dist_tags {
+                        if let Some(ver_s) = tags.get("latest") {
+                            let ver = semver::Version::parse(ver_s)?;
+                            let tar = meta
+                                .versions
+                                .get(ver_s)
+                                .map(|v| v.dist.tarball.clone())
+                                .unwrap_or_default();
+                            Ok((ver, tar))
+                        } else {
+                            let version_map = crate::resolver::map_versions(&meta);
+                            resolver.pick_version(&version_map, "*")
+                        }
+                    } else {
+                        let version_map = crate::resolver::map_versions(&meta);
+                        resolver.pick_version(&version_map, "*")
+                    }
+                } else {
+                    let version_map = crate::resolver::map_versions(&meta);
+                    resolver.pick_version(&version_map, &range)
+                }
+            }
+        })();
+
+        let (picked_ver, tarball_url) = match picked_result {
+            Ok(v) => v,
+            Err(e) => {
+                if optional_root {
+                    status("fast", &format!("skip optional {name} (resolve failed)"));
+                    continue;
+                }
+                return Err(e);
+            }
+        };
+
+        let picked_version = picked_ver.to_string();
+        if visited_name_version.contains(&(name.clone(), picked_version.clone())) {
+            continue;
+        }
+
+        let mut package_os: Vec<String> = Vec::new();
+        let mut package_cpu: Vec<String> = Vec::new();
+        let mut package_libc: Vec<String> = Vec::new();
+        let mut unpacked_size: Option<u64> = None;
+        let mut file_count: Option<u64> = None;
+        #[allow(clippy::type_complexity)]
+        let (integrity_owned, dep_map, opt_map, peer_map, peer_meta_map, resolved_url, scripts_map): (
+            Option<String>,
+            BTreeMap<String, String>,
+            BTreeMap<String, String>,
+            BTreeMap<String, String>,
+            BTreeMap<String, crate::lockfile::PeerMeta>,
+            Option<String>,
+            Option<std::collections::BTreeMap<String, String>>,
+        ) = if tarball_url.is_empty() {
+            match crate::cache::read_cached_manifest(&name, &picked_version) {
+                Ok(mut cached_mf) => {
+                    package_os = std::mem::take(&mut cached_mf.os);
+                    package_cpu = std::mem::take(&mut cached_mf.cpu_arch);
+                    package_libc = std::mem::take(&mut cached_mf.libc);
+                    // Try to fetch registry metadata for scripts if possible (don't if prefer_offline)
+                    let scripts = if !prefer_offline {
+                        match fetcher.package_version_metadata(&name, &picked_version) {
+                            Ok(vm) => vm.scripts.clone().into_iter().collect::<std::collections::BTreeMap<_, _>>(),
+                            Err(_) => std::collections::BTreeMap::new(),
+                        }
+                    } else {
+                        std::collections::BTreeMap::new()
+                    };
+                    (
+                        None,
+                        cached_mf.dependencies.into_iter().collect(),
+                        cached_mf
+                            .optional_dependencies
+                            .into_iter()
+                            .filter(|(n, _)| {
+                                if let Some(ver) = lock
+                                    .packages
+                                    .get(&format!("node_modules/{n}"))
+                                    .and_then(|e| e.version.clone())
+                                {
+                                    if let Ok(m) = crate::cache::read_cached_manifest(n, &ver) {
+                                        return platform.supports(&m.os, &m.cpu_arch, &m.libc);
+                                    }
+                                }
+                                true
+                            })
+                            .collect(),
+                        cached_mf.peer_dependencies.into_iter().collect(),
+                        cached_mf
+                            .peer_dependencies_meta
+                            .into_iter()
+                            .map(|(k, v)| (k, crate::lockfile::PeerMeta { optional: v.optional }))
+                            .collect(),
+                        None,
+                        Some(scripts),
+                    )
+                }
+                Err(e) => {
+                    if optional_root {
+                        (
+                            None,
+                            BTreeMap::new(),
+                            BTreeMap::new(),
+                            BTreeMap::new(),
+                            BTreeMap::new(),
+                            None,
+                            None,
+                        )
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        } else {
+            let meta2 = match fetcher
+                .package_metadata(&name)
+                .with_context(|| format!("fetch metadata for {name}"))
+            {
+                Ok(m) => m,
+                Err(e) => {
+                    if optional_root {
+                        continue;
+                    } else {
+                        return Err(e);
+                    }
+                }
+            };
+            let version_meta = match meta2.versions.get(&picked_version) {
+                Some(v) => v,
+                None => {
+                    if optional_root {
+                        continue;
+                    } else {
+                        anyhow::bail!("version metadata missing for {name}@{picked_ver}");
+                    }
+                }
+            };
+            package_os = version_meta.os.clone();
+            package_cpu = version_meta.cpu_arch.clone();
+            package_libc = version_meta.libc.clone();
+            unpacked_size = version_meta.dist.unpacked_size;
+            file_count = version_meta.dist.file_count;
+            if let Some(msg) = &version_meta.deprecated {
+                deprecated.insert(format!("{name}@{picked_version}"), msg.clone());
+            }
+            let integrity_owned = version_meta.dist.integrity.clone();
+            let mut dm = BTreeMap::new();
+            for (dn, dr) in &version_meta.dependencies {
+                dm.insert(dn.clone(), dr.clone());
+            }
+            let mut om = BTreeMap::new();
+            for (dn, dr) in &version_meta.optional_dependencies {
+                om.insert(dn.clone(), dr.clone());
+            }
+            let mut pm = BTreeMap::new();
+            for (dn, dr) in &version_meta.peer_dependencies {
+                pm.insert(dn.clone(), dr.clone());
+            }
+            let mut pmm = BTreeMap::new();
+            for (n, m) in &version_meta.peer_dependencies_meta {
+                pmm.insert(n.clone(), crate::lockfile::PeerMeta { optional: m.optional });
+            }
+            (integrity_owned, dm, om, pm, pmm, Some(version_meta.dist.tarball.clone()), Some(version_meta.scripts.clone()))
+        };
+
+        let resolved_for_lock = resolved_url.clone().or_else(|| {
+            if !tarball_url.is_empty() {
+                Some(tarball_url.clone())
+            } else {
+                None
+            }
+        });
+
+        let platform_ok = platform.supports(&package_os, &package_cpu, &package_libc);
+        if !platform_ok && optional_root {
+            status("fast", &format!("{name}@{picked_version} skipped (platform mismatch)"));
+            write_lock_entry(
+                lock,
+                &name,
+                &picked_version,
+                integrity_owned.as_deref(),
+                resolved_for_lock.as_deref(),
+                PackageSource::Registry,
+                &dep_map,
+                &BTreeMap::new(),
+                &opt_map,
+                &peer_map,
+                &peer_meta_map,
+                &package_os,
+                &package_cpu,
+                &package_libc,
+            );
+            visited_name_version.insert((name.clone(), picked_version.clone()));
+            continue;
+        }
+        let mut reused = false;
+        let cached = cache_present(&name, &picked_version, force, &refreshed);
+        let integrity_for_entry_string: Option<String>;
+
+        if cached {
+            reused = true;
+            integrity_for_entry_string = integrity_owned.clone();
+        } else {
+            if prefer_offline {
+                if optional_root {
+                    continue;
+                }
+                if offline {
+                    bail!("offline: {name}@{picked_ver} not cached");
+                }
+                bail!("{name}@{picked_ver} not in cache and --prefer-offline is set");
+            }
+            let url = resolved_url
+                .as_deref()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| tarball_url.clone());
+            refreshed.insert((name.clone(), picked_version.clone()));
+            report_unpacked_size(
+                status,
+                &format!("{name}@{picked_version}"),
+                unpacked_size,
+                file_count,
+                size_warn,
+            );
+
+            if optional_root {
+                status("downloading", &format!("{name}@{picked_version}"));
+                let download_started = Instant::now();
+                let download_result = download_into_cache(
+                    fetcher,
+                    &name,
+                    &picked_version,
+                    &url,
+                    integrity_owned.as_deref().filter(|_| !options.no_verify),
+                    scripts_map.as_ref(),
+                    force,
+                );
+                download_time += download_started.elapsed();
+                match download_result {
+                    Ok(integrity) => {
+                        integrity_for_entry_string = Some(integrity);
+                    }
+                    Err(e) => {
+                        if optional_root {
+                            status(
+                                "fast",
+                                &format!("skip optional {name}@{picked_version} (download failed)"),
+                            );
+                            continue;
+                        } else {
+                            return Err(e);
+                        }
+                    }
+                }
+            } else {
+                status("queued", &format!("download {name}@{picked_version}"));
+                let key = (name.clone(), picked_version.clone());
+                if pending_set.insert(key) {
+                    pending_downloads.push(PendingDownload {
+                        name: name.clone(),
+                        version: picked_version.clone(),
+                        url,
+                        integrity_hint: integrity_owned.clone(),
+                        scripts: scripts_map.clone(),
+                        replace: force,
+                    });
+                }
+                integrity_for_entry_string = integrity_owned.clone();
+            }
+        }
+
+        let integrity_for_entry = integrity_for_entry_string.as_deref();
+        write_lock_entry(
+            lock,
+            &name,
+            &picked_version,
+            integrity_for_entry,
+            resolved_for_lock.as_deref(),
+            PackageSource::Registry,
+            &dep_map,
+            &BTreeMap::new(),
+            &opt_map,
+            &peer_map,
+            &peer_meta_map,
+            &package_os,
+            &package_cpu,
+            &package_libc,
+        );
+        instances.insert(
+            name.clone(),
+            PackageInstance {
+                name: name.clone(),
+                version: picked_version.clone(),
+                dependencies: dep_map.clone(),
+                optional_dependencies: opt_map.clone(),
+                peer_dependencies: peer_map.clone(),
+                dev_dependencies: BTreeMap::new(),
+                source: None,
+            },
+        );
+        visited_name_version.insert((name.clone(), picked_version.clone()));
+        if !reused {
+            downloaded += 1;
+        }
+
+        let mut to_enqueue: Vec<(String, String, bool)> = Vec::new();
+        for (dn, dr) in dep_map.into_iter() {
+            to_enqueue.push((dn, dr, optional_root));
+        }
+        for (dn, dr) in opt_map.into_iter() {
+            to_enqueue.push((dn, dr, true));
+        }
+        for (dn, dr) in peer_map.into_iter() {
+            let is_optional_peer = peer_meta_map.get(&dn).map(|m| m.optional).unwrap_or(false);
+            if !is_optional_peer && !legacy_peer_deps {
+                to_enqueue.push((dn, dr, false));
+            }
+        }
+        for (dn, dr, optflag) in to_enqueue {
+            queue.push_back(Task { name: dn, range: dr, optional_root: optflag, depth: depth + 1 });
+        }
+    }
+    check_resolution_limits(instances.len(), 0, max_packages)?;
+
+    if options.no_verify {
+        for pd in &mut pending_downloads {
+            pd.integrity_hint = None;
+        }
+    }
+    if !pending_downloads.is_empty() {
+        check_deadline(options.deadline)?;
+        status("downloading", &format!("{} packages in parallel", pending_downloads.len()));
+        let total_downloads = pending_downloads.len();
+        let counter = AtomicUsize::new(0);
+        let wave_started = Instant::now();
+
+        let download_results: Result<Vec<(String, String)>> = pending_downloads
+            .par_iter()
+            .map(|pd| -> Result<(String, String)> {
+                crate::cancel::check()?;
+                let integrity = retry_download_into_cache(
+                    fetcher,
+                    pd,
+                    &status,
+                    &counter,
+                    total_downloads,
+                    options.deadline,
+                )?;
+                Ok((pd.name.clone(), integrity))
+            })
+            .collect();
+
+        download_time += wave_started.elapsed();
+        let download_results = download_results?;
+        for (pkg_name, integrity) in download_results {
+            if let Some(entry) = lock.packages.get_mut(&format!("node_modules/{pkg_name}")) {
+                entry.integrity = Some(integrity);
+            }
+        }
+
+        status("cached", &format!("downloaded {} packages", pending_downloads.len()));
+        check_deadline(options.deadline)?;
+    }
+
+    Ok(ResolvedGraph {
+        instances,
+        lockfile,
+        downloaded,
+        download_time,
+        resolve_times: resolve_times.into_inner(),
+        deprecated,
+    })
+}
+
+/// Records how long one queue task took when dropped, so every `continue` path is timed.
+struct ResolveTimer<'a> {
+    sink: &'a RefCell<Vec<(String, Duration)>>,
+    label: String,
+    started: Instant,
+}
+
+impl<'a> ResolveTimer<'a> {
+    fn start(sink: &'a RefCell<Vec<(String, Duration)>>, label: String) -> Self {
+        Self { sink, label, started: Instant::now() }
+    }
+}
+
+impl Drop for ResolveTimer<'_> {
+    fn drop(&mut self) {
+        let label = std::mem::take(&mut self.label);
+        self.sink.borrow_mut().push((label, self.started.elapsed()));
+    }
+}
+
+fn check_deadline(deadline: Option<Instant>) -> Result<()> {
+    match deadline {
+        Some(d) if Instant::now() >= d => bail!("timed out: install deadline exceeded"),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn ensure_lock_entry<'a>(lock: &'a mut Lockfile, name: &str) -> &'a mut PackageEntry {
+    let key = format!("node_modules/{name}");
+    lock.packages.entry(key).or_insert(PackageEntry {
+        version: None,
+        integrity: None,
+        resolved: None,
+        dependencies: BTreeMap::new(),
+        dev_dependencies: BTreeMap::new(),
+        optional_dependencies: BTreeMap::new(),
+        peer_dependencies: BTreeMap::new(),
+        peer_dependencies_meta: BTreeMap::new(),
+        os: Vec::new(),
+        cpu_arch: Vec::new(),
+        libc: Vec::new(),
+        store_key: None,
+        content_hash: None,
+        link_mode: None,
+        store_path: None,
+        source: None,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_lock_entry(
+    lock: &mut Lockfile,
+    name: &str,
+    version: &str,
+    integrity: Option<&str>,
+    resolved: Option<&str>,
+    source: PackageSource,
+    dependencies: &BTreeMap<String, String>,
+    dev_dependencies: &BTreeMap<String, String>,
+    optional_dependencies: &BTreeMap<String, String>,
+    peer_dependencies: &BTreeMap<String, String>,
+    peer_meta: &BTreeMap<String, crate::lockfile::PeerMeta>,
+    os: &[String],
+    cpu_arch: &[String],
+    libc: &[String],
+) {
+    let entry = ensure_lock_entry(lock, name);
+    entry.version = Some(version.to_string());
+    entry.integrity = integrity.map(|s| s.to_string());
+    entry.resolved = resolved.map(|s| s.to_string());
+    entry.source = Some(source);
+    entry.dependencies = dependencies.clone();
+    entry.dev_dependencies = dev_dependencies.clone();
+    entry.optional_dependencies = optional_dependencies.clone();
+    entry.peer_dependencies = peer_dependencies.clone();
+    entry.peer_dependencies_meta = peer_meta.clone();
+    entry.os = os.to_vec();
+    entry.cpu_arch = cpu_arch.to_vec();
+    entry.libc = libc.to_vec();
+    entry.store_key = None;
+    entry.content_hash = None;
+    entry.link_mode = None;
+    entry.store_path = None;
+}
+
+fn entry_to_instance(name: &str, entry: &PackageEntry) -> PackageInstance {
+    PackageInstance {
+        name: name.to_string(),
+        version: entry.version.clone().unwrap_or_default(),
+        dependencies: entry.dependencies.clone(),
+        optional_dependencies: entry.optional_dependencies.clone(),
+        peer_dependencies: entry.peer_dependencies.clone(),
+        dev_dependencies: entry.dev_dependencies.clone(),
+        source: None,
+    }
+}
+
+/// Whether `name@version` can be served from the cache. Under `--force` a package counts as
+/// missing until this resolution has fetched it again (`refreshed`); the cached copy is only
+/// replaced once that download verifies (see [`crate::cache::replace_cached_package`]).
+fn cache_present(
+    name: &str,
+    version: &str,
+    force: bool,
+    refreshed: &HashSet<(String, String)>,
+) -> bool {
+    if force && !refreshed.contains(&(name.to_string(), version.to_string())) {
+        return false;
+    }
+    crate::cache::cache_package_path(name, version).exists()
+}
+
+/// Cache tarball `bytes`, replacing an existing copy under `--force`.
+fn cache_tarball(
+    name: &str,
+    version: &str,
+    bytes: &[u8],
+    integrity_hint: Option<&str>,
+    force: bool,
+) -> Result<String> {
+    if force {
+        crate::cache::replace_cached_package(name, version, bytes, integrity_hint)
+    } else {
+        crate::cache::ensure_cached_package(name, version, bytes, integrity_hint)
+    }
+}
+
+/// The newest version of `name` that satisfies every one of `ranges`, from the cache and, unless
+/// offline, the registry.
+fn shared_version(
+    fetcher: &Fetcher,
+    name: &str,
+    ranges: &[String],
+    prefer_offline: bool,
+    force: bool,
+) -> Option<semver::Version> {
+    let mut candidates = if force { Vec::new() } else { crate::cache::cached_versions(name) };
+    if !prefer_offline {
+        if let Ok(meta) = fetcher.package_metadata(name) {
+            candidates.extend(crate::resolver::map_versions(&meta).into_keys());
+        }
+    }
+    candidates
+        .into_iter()
+        .filter(|version| version.pre.is_empty())
+        .filter(|version| {
+            ranges
+                .iter()
+                .all(|range| crate::resolver::version_satisfies(range, version).unwrap_or(false))
+        })
+        .max()
+}
+
+fn pick_cached_satisfying_manifest(
+    resolver: &crate::resolver::Resolver,
+    name: &str,
+    range: &str,
+) -> Option<(semver::Version, CachedManifest)> {
+    // Only attempt semver selection for registry specs; git/tarball/url ranges should be handled
+    // by the main resolution path.
+    if !matches!(
+        crate::resolver::spec::PackageSpec::parse(range),
+        crate::resolver::spec::PackageSpec::Registry { .. }
+    ) {
+        return None;
+    }
+    let cached_versions = crate::cache::cached_versions(name);
+    if cached_versions.is_empty() {
+        return None;
+    }
+    let mut map: BTreeMap<semver::Version, String> = BTreeMap::new();
+    for v in cached_versions {
+        map.insert(v, String::new());
+    }
+    let picked = resolver.pick_version(&map, range).ok()?;
+    let ver_str = picked.0.to_string();
+    if !crate::cache::cache_package_path(name, &ver_str).exists() {
+        return None;
+    }
+    let manifest = crate::cache::read_cached_manifest(name, &ver_str).ok()?;
+    Some((picked.0, manifest))
+}
+
+/// Download `pd` into the cache, retrying transient failures up to three times. Integrity
+/// mismatches are returned at once: [`download_into_cache`] has already retried those.
+pub(crate) fn retry_download_into_cache(
+    fetcher: &Fetcher,
+    pd: &PendingDownload,
+    status: &(dyn Fn(&str, &str) + Sync),
+    counter: &AtomicUsize,
+    total: usize,
+    deadline: Option<Instant>,
+) -> Result<String> {
+    let mut last_err: Option<anyhow::Error> = None;
+    let max_attempts = 3;
+    for attempt in 1..=max_attempts {
+        check_deadline(deadline)?;
+        match download_into_cache(
+            fetcher,
+            &pd.name,
+            &pd.version,
+            &pd.url,
+            pd.integrity_hint.as_deref(),
+            pd.scripts.as_ref(),
+            pd.replace,
+        ) {
+            Ok(integrity) => {
+                let done = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                status(
+                    "downloading",
+                    &format!("{done}/{total} {name}@{ver}", name = pd.name, ver = pd.version),
+                );
+                return Ok(integrity);
+            }
+            Err(e) if e.is::<crate::cache::IntegrityMismatch>() => return Err(e),
+            Err(e) => {
+                last_err = Some(e);
+                if attempt < max_attempts {
+                    std::thread::sleep(Duration::from_millis(200 * attempt as u64));
+                    continue;
+                }
+            }
+        }
+    }
+    Err(last_err.unwrap())
+}
+
+#[derive(Clone)]
+pub(crate) struct PendingDownload {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) url: String,
+    pub(crate) integrity_hint: Option<String>,
+    pub(crate) scripts: Option<std::collections::BTreeMap<String, String>>,
+    /// Replace a cached copy once the download verifies (`--force`).
+    pub(crate) replace: bool,
+}
+
+/// Tarball bytes for a github/git dependency plus the commit it was built from.
+struct GitSource {
+    bytes: Vec<u8>,
+    commit: String,
+    resolved: String,
+    source: PackageSource,
+}
+
+#[derive(Debug, Clone)]
+struct GithubResolved {
+    tarball_url: String,
+    commit: String,
+}
+
+fn resolve_github_tarball(spec: &crate::resolver::spec::GithubSpec) -> Result<GithubResolved> {
+    #[derive(serde::Deserialize)]
+    struct RepoInfo {
+        default_branch: Option<String>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct CommitInfo {
+        sha: String,
+    }
+
+    let client = crate::fetch::http_client()?;
+    let base = format!("https://api.github.com/repos/{}/{}", spec.owner, spec.repo);
+    let reference = if let Some(r) = &spec.reference {
+        r.clone()
+    } else {
+        let resp = client.get(&base).send().with_context(|| format!("GET {base}"))?;
+        if resp.status().is_success() {
+            let info: RepoInfo = resp.json()?;
+            info.default_branch.unwrap_or_else(|| "main".to_string())
+        } else {
+            // Fall back to common defaults if API rate limits or errors
+            "main".to_string()
+        }
+    };
+
+    let commit_url = format!("{base}/commits/{reference}");
+    let resp = client.get(&commit_url).send().with_context(|| format!("GET {commit_url}"))?;
+    if !resp.status().is_success() {
+        // Last-resort fallback to master if main/default failed
+        if reference != "master" {
+            let fallback_url = format!("{base}/commits/master");
+            let resp_fb =
+                client.get(&fallback_url).send().with_context(|| format!("GET {fallback_url}"))?;
+            if resp_fb.status().is_success() {
+                let commit: CommitInfo = resp_fb.json()?;
+                let tarball_url = format!(
+                    "https://codeload.github.com/{}/{}/tar.gz/{}",
+                    spec.owner, spec.repo, commit.sha
+                );
+                return Ok(GithubResolved { tarball_url, commit: commit.sha });
+            }
+        }
+        // Try direct tarball for the provided ref even if commit lookup failed (e.g., repo uses
+        // non-main/master default branch or branch protection blocks commit API).
+        let tarball_url = format!(
+            "https://codeload.github.com/{}/{}/tar.gz/{}",
+            spec.owner, spec.repo, reference
+        );
+        return Ok(GithubResolved { tarball_url, commit: reference });
+    }
+
+    let commit: CommitInfo = resp.json()?;
+    let tarball_url =
+        format!("https://codeload.github.com/{}/{}/tar.gz/{}", spec.owner, spec.repo, commit.sha);
+    Ok(GithubResolved { tarball_url, commit: commit.sha })
+}
+
+/// The `name` a git, github or tarball source declares in its `package.json`, so a dependency
+/// added by URL is recorded under its real name rather than one guessed from the URL. Registry
+/// specs, and sources whose manifest has no name, return `None`.
+pub(crate) fn source_package_name(spec: &PackageSpec, fetcher: &Fetcher) -> Result<Option<String>> {
+    let bytes = match spec {
+        PackageSpec::Registry { .. } => return Ok(None),
+        PackageSpec::Github(gh_spec) => {
+            let resolved = resolve_github_tarball(gh_spec)?;
+            fetcher.download_tarball(&resolved.tarball_url)?
+        }
+        PackageSpec::Git(git_spec) => crate::resolver::git::archive_checkout(git_spec)?.bytes,
+        PackageSpec::Tarball { url } => fetcher.download_tarball(url)?,
+        PackageSpec::LocalTarball { path } => {
+            std::fs::read(path).with_context(|| format!("read local tarball {path}"))?
+        }
+    };
+    let manifest = read_manifest_from_tarball(&bytes)?;
+    Ok(manifest.name.filter(|name| !name.trim().is_empty()))
+}
+
+fn read_manifest_from_tarball(bytes: &[u8]) -> Result<CachedManifest> {
+    let gz = GzDecoder::new(bytes);
+    let mut ar = Archive::new(gz);
+    for entry in ar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        if path.file_name().map(|n| n == "package.json").unwrap_or(false) {
+            let mut buf = String::new();
+            entry.read_to_string(&mut buf)?;
+            let mf: CachedManifest = serde_json::from_str(&buf)?;
+            return Ok(mf);
+        }
+    }
+    anyhow::bail!("package.json not found in tarball")
+}
+
+fn short_hash(input: impl AsRef<[u8]>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(input.as_ref());
+    let digest = hasher.finalize();
+    let mut hex = String::with_capacity(64);
+    for byte in digest {
+        let _ = std::fmt::Write::write_fmt(&mut hex, format_args!("{:02x}", byte));
+    }
+    hex.chars().take(8).collect()
+}
+
+fn append_build(base: &str, build_tag: &str) -> String {
+    if base.contains('+') {
+        format!("{base}.{build_tag}")
+    } else {
+        format!("{base}+{build_tag}")
+    }
+}
+
+fn write_scripts_sidecar(package: &str, version: &str, scripts: &BTreeMap<String, String>) {
+    if scripts.is_empty() {
+        return;
+    }
+    let sidecar = crate::cache::cache_package_path(package, version).join(".registry-scripts.json");
+    if let Ok(txt) = serde_json::to_string_pretty(scripts) {
+        let _ = std::fs::write(sidecar, txt);
+    }
+}
+
+/// Download `url` into the cache for `name@version`. An integrity mismatch is retried once with a
+/// fresh download, since transient corruption is far more common than a bad registry. With
+/// `replace`, an already cached copy is swapped for the download once it verifies.
+pub(crate) fn download_into_cache(
+    fetcher: &Fetcher,
+    name: &str,
+    version: &str,
+    url: &str,
+    integrity_hint: Option<&str>,
+    scripts: Option<&std::collections::BTreeMap<String, String>>,
+    replace: bool,
+) -> Result<String> {
+    let mut retried = false;
+    let integrity = loop {
+        let bytes = fetcher
+            .download_tarball(url)
+            .with_context(|| format!("download tarball for {name}@{version}"))?;
+        match cache_tarball(name, version, &bytes, integrity_hint, replace) {
+            Ok(integrity) => break integrity,
+            Err(e) if !retried && e.is::<crate::cache::IntegrityMismatch>() => retried = true,
+            Err(e) => return Err(crate::cache::explain_integrity_error(e, name, version)),
+        }
+    };
+    // write registry scripts sidecar if provided
+    if let Some(s) = scripts {
+        // Abbreviated metadata omits `scripts`; the package's own manifest has them.
+        let from_manifest = s
+            .is_empty()
+            .then(|| crate::cache::read_cached_manifest(name, version).ok().map(|m| m.scripts))
+            .flatten();
+        let cache_path = crate::cache::cache_package_path(name, version);
+        let sidecar = cache_path.join(".registry-scripts.json");
+        if let Ok(txt) = serde_json::to_string_pretty(from_manifest.as_ref().unwrap_or(s)) {
+            let _ = std::fs::write(&sidecar, txt);
+        }
+    }
+    Ok(integrity)
+}
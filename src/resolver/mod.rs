@@ -1,3 +1,6 @@
+pub mod git;
+pub mod graph;
+pub mod platform;
 pub mod spec;
 
 use anyhow::{anyhow, Result};
@@ -26,7 +29,11 @@ impl Resolver {
     ) -> Result<(Version, String)> {
         let reqs = parse_range_to_reqs(range)?;
         let mut candidates: Vec<_> = versions.iter().collect();
-        candidates.sort_by(|a, b| b.0.cmp(a.0)); // descending
+        // Descending by precedence. Build metadata does not count towards precedence, so
+        // `1.0.0+git.abc` and `1.0.0+git.def` tie; the full version string breaks the tie.
+        candidates.sort_by(|a, b| {
+            b.0.cmp_precedence(a.0).then_with(|| b.0.to_string().cmp(&a.0.to_string()))
+        });
         for (ver, tarball) in candidates {
             // Any-of matching for OR sets; single element behaves as before
             if reqs.iter().any(|r| r.matches(ver)) {
@@ -180,7 +187,7 @@ pub fn canonicalize_npm_range(input: &str) -> String {
     s.to_string()
 }
 
-fn parse_range_to_reqs(range: &str) -> Result<Vec<VersionReq>> {
+pub(crate) fn parse_range_to_reqs(range: &str) -> Result<Vec<VersionReq>> {
     let norm = canonicalize_npm_range(range);
     let is_or = range.contains("||") || norm.contains("||");
     if is_or {
@@ -226,6 +233,29 @@ fn parse_range_to_reqs(range: &str) -> Result<Vec<VersionReq>> {
     }
 }
 
+/// Whether `spec` names a dist-tag such as `next` or `beta` rather than a version or range.
+/// `latest` is excluded because it canonicalizes to `*`.
+pub fn looks_like_dist_tag(spec: &str) -> bool {
+    let trimmed = spec.trim();
+    if trimmed.is_empty() || trimmed == "*" {
+        return false;
+    }
+    if trimmed.eq_ignore_ascii_case("latest") {
+        return false;
+    }
+    if trimmed.contains(' ') || trimmed.contains("||") || trimmed.contains(',') {
+        return false;
+    }
+    if !trimmed.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        return false;
+    }
+    let canon = canonicalize_npm_range(trimmed);
+    if canon == "*" {
+        return false;
+    }
+    semver::Version::parse(trimmed).is_err() && semver::VersionReq::parse(&canon).is_err()
+}
+
 pub fn version_satisfies(range: &str, version: &Version) -> Result<bool> {
     let reqs = match parse_range_to_reqs(range) {
         Ok(r) => r,
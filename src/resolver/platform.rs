@@ -0,0 +1,210 @@
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::Lazy;
+
+/// Node's `process.platform` values, accepted by `--os`.
+const NODE_PLATFORMS: &[&str] = &[
+    "aix", "android", "cygwin", "darwin", "freebsd", "haiku", "linux", "netbsd", "openbsd",
+    "sunos", "win32",
+];
+
+/// Node's `process.arch` values, accepted by `--cpu`.
+const NODE_ARCHES: &[&str] = &[
+    "arm", "arm64", "ia32", "loong64", "mips", "mipsel", "ppc", "ppc64", "riscv64", "s390",
+    "s390x", "x64",
+];
+
+const LIBC_FAMILIES: &[&str] = &["glibc", "musl"];
+
+/// The platform that package `os`/`cpu`/`libc` lists are checked against. This is the host
+/// unless `pacm install --os/--cpu/--libc` asks for another target, e.g. to build a deploy
+/// artifact for Linux on a Mac.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TargetPlatform {
+    pub os: &'static str,
+    pub cpu: &'static str,
+    /// Only set for Linux targets, where npm applies `libc`.
+    pub libc: Option<&'static str>,
+}
+
+impl Default for TargetPlatform {
+    fn default() -> Self {
+        Self::host()
+    }
+}
+
+impl TargetPlatform {
+    pub fn host() -> Self {
+        Self { os: node_platform(), cpu: node_arch(), libc: host_libc() }
+    }
+
+    /// The host with the given values replaced. Without `--libc`, a Linux target keeps the
+    /// host's libc when the host is Linux too, and accepts any libc otherwise.
+    pub fn with_overrides(os: Option<&str>, cpu: Option<&str>, libc: Option<&str>) -> Result<Self> {
+        let host = Self::host();
+        let os = os.map(|v| known_value("--os", v, NODE_PLATFORMS)).transpose()?.unwrap_or(host.os);
+        let cpu =
+            cpu.map(|v| known_value("--cpu", v, NODE_ARCHES)).transpose()?.unwrap_or(host.cpu);
+        let libc = match libc {
+            Some(_) if os != "linux" => bail!("--libc only applies to linux targets (--os {os})"),
+            Some(v) => Some(known_value("--libc", v, LIBC_FAMILIES)?),
+            None if os == host.os => host.libc,
+            None => None,
+        };
+        Ok(Self { os, cpu, libc })
+    }
+
+    pub fn is_host(&self) -> bool {
+        *self == Self::host()
+    }
+
+    /// Whether a package declaring these `os`/`cpu`/`libc` lists installs on this target.
+    pub fn supports(&self, os_list: &[String], cpu_list: &[String], libc_list: &[String]) -> bool {
+        list_allows(os_list, self.os)
+            && list_allows(cpu_list, self.cpu)
+            && libc_supported(libc_list, self.libc)
+    }
+}
+
+impl std::fmt::Display for TargetPlatform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.os, self.cpu)?;
+        if let Some(libc) = self.libc {
+            write!(f, "-{libc}")?;
+        }
+        Ok(())
+    }
+}
+
+fn known_value(flag: &str, value: &str, values: &[&'static str]) -> Result<&'static str> {
+    values.iter().copied().find(|known| *known == value).ok_or_else(|| {
+        anyhow!("unknown {flag} value '{value}' (expected one of {})", values.join(", "))
+    })
+}
+
+/// npm only applies `libc` on Linux, so a host without a detected libc accepts any list.
+pub fn libc_supported(libc_list: &[String], host: Option<&str>) -> bool {
+    match host {
+        Some(host) => list_allows(libc_list, host),
+        None => true,
+    }
+}
+
+/// Matches npm's `os`/`cpu`/`libc` semantics: `!value` blocks, and any positive entry turns the
+/// list into an allow-list. An empty list and npm's `["any"]` allow every host.
+pub fn list_allows(list: &[String], host: &str) -> bool {
+    if list.is_empty() || (list.len() == 1 && list[0] == "any") {
+        return true;
+    }
+    let mut allowed = None;
+    let mut blocked = false;
+    for item in list {
+        if let Some(stripped) = item.strip_prefix('!') {
+            if stripped == host {
+                blocked = true;
+            }
+        } else {
+            allowed.get_or_insert(false);
+            if item == host {
+                allowed = Some(true);
+            }
+        }
+    }
+    (!blocked) && allowed.unwrap_or(true)
+}
+
+static HOST_LIBC: Lazy<Option<&'static str>> = Lazy::new(detect_libc);
+
+/// `Some("musl")` or `Some("glibc")` on Linux, `None` elsewhere.
+pub fn host_libc() -> Option<&'static str> {
+    *HOST_LIBC
+}
+
+#[cfg(target_os = "linux")]
+fn detect_libc() -> Option<&'static str> {
+    // musl systems ship their dynamic loader as /lib/ld-musl-<arch>.so.1.
+    let musl = std::fs::read_dir("/lib")
+        .map(|entries| {
+            entries.flatten().any(|e| e.file_name().to_string_lossy().starts_with("ld-musl-"))
+        })
+        .unwrap_or(false);
+    Some(if musl { "musl" } else { "glibc" })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect_libc() -> Option<&'static str> {
+    None
+}
+
+pub fn node_platform() -> &'static str {
+    #[cfg(target_os = "windows")]
+    {
+        "win32"
+    }
+    #[cfg(target_os = "macos")]
+    {
+        "darwin"
+    }
+    #[cfg(target_os = "linux")]
+    {
+        "linux"
+    }
+    #[cfg(target_os = "freebsd")]
+    {
+        "freebsd"
+    }
+    #[cfg(target_os = "openbsd")]
+    {
+        "openbsd"
+    }
+    #[cfg(target_os = "netbsd")]
+    {
+        "netbsd"
+    }
+    #[cfg(target_os = "aix")]
+    {
+        "aix"
+    }
+    #[cfg(target_os = "solaris")]
+    {
+        "sunos"
+    }
+}
+
+pub fn node_arch() -> &'static str {
+    #[cfg(target_arch = "x86_64")]
+    {
+        "x64"
+    }
+    #[cfg(target_arch = "x86")]
+    {
+        "ia32"
+    }
+    #[cfg(target_arch = "arm")]
+    {
+        "arm"
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        "arm64"
+    }
+    #[cfg(target_arch = "mips")]
+    {
+        "mips"
+    }
+    #[cfg(target_arch = "powerpc")]
+    {
+        "ppc"
+    }
+    #[cfg(target_arch = "powerpc64")]
+    {
+        "ppc64"
+    }
+    #[cfg(target_arch = "s390x")]
+    {
+        "s390x"
+    }
+    #[cfg(target_arch = "riscv64")]
+    {
+        "riscv64"
+    }
+}
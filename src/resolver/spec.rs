@@ -1,10 +1,20 @@
+use anyhow::{bail, Result};
 use std::borrow::Cow;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PackageSpec {
-    Registry { range: String },
+    Registry {
+        range: String,
+    },
     Github(GithubSpec),
-    Tarball { url: String },
+    Git(GitSpec),
+    Tarball {
+        url: String,
+    },
+    /// `file:` path to a gzipped tarball on disk, relative to the project root.
+    LocalTarball {
+        path: String,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -14,10 +24,24 @@ pub struct GithubSpec {
     pub reference: Option<String>,
 }
 
+/// A repository reachable through the `git` binary (`git+https://`, `git+ssh://`, `git://`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSpec {
+    /// Clone URL with the `git+` prefix and `#ref` fragment removed.
+    pub url: String,
+    pub reference: Option<String>,
+}
+
 impl PackageSpec {
     pub fn parse(raw: &str) -> Self {
         let trimmed = raw.trim();
 
+        if let Some(rest) = trimmed.strip_prefix("file:") {
+            if is_tarball_path(rest) {
+                return PackageSpec::LocalTarball { path: rest.to_string() };
+            }
+        }
+
         if let Some(rest) = trimmed.strip_prefix("npm:") {
             return PackageSpec::Registry { range: rest.to_string() };
         }
@@ -32,6 +56,17 @@ impl PackageSpec {
             return PackageSpec::Github(spec);
         }
 
+        if let Some(spec) = parse_git_url(trimmed) {
+            if let Some(gh) = github_from_git(&spec) {
+                return PackageSpec::Github(gh);
+            }
+            return PackageSpec::Git(spec);
+        }
+
+        if let Some(spec) = parse_github_web_url(trimmed) {
+            return PackageSpec::Github(spec);
+        }
+
         if let Some(rest) = trimmed.strip_prefix("git+") {
             if is_http_url(rest) {
                 return PackageSpec::Tarball { url: rest.to_string() };
@@ -46,6 +81,25 @@ impl PackageSpec {
     }
 }
 
+/// Rewrite a `file:` tarball `range` declared in the package.json at `dir` (relative to the
+/// project root, as in [`crate::workspaces::WorkspaceInfo::relative_path`]) so that it is
+/// relative to the project root. Other ranges, and absolute paths, are returned unchanged.
+pub fn rebase_local_range(range: &str, dir: &str) -> String {
+    let PackageSpec::LocalTarball { path } = PackageSpec::parse(range) else {
+        return range.to_string();
+    };
+    let dir = dir.trim_end_matches('/');
+    if dir.is_empty() || dir == "." || std::path::Path::new(&path).is_absolute() {
+        return range.to_string();
+    }
+    format!("file:{dir}/{}", path.trim_start_matches("./"))
+}
+
+fn is_tarball_path(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.ends_with(".tgz") || lower.ends_with(".tar.gz")
+}
+
 fn parse_github(input: &str) -> Option<GithubSpec> {
     if input.starts_with('@') || input.contains(' ') {
         return None;
@@ -63,6 +117,10 @@ fn parse_github(input: &str) -> Option<GithubSpec> {
     if owner.is_empty() || repo.is_empty() || parts.next().is_some() {
         return None;
     }
+    // `alias@github:owner/repo` and URLs are not shorthands; let the caller split them.
+    if owner.contains(['@', ':']) {
+        return None;
+    }
 
     Some(GithubSpec {
         owner: owner.to_string(),
@@ -71,6 +129,59 @@ fn parse_github(input: &str) -> Option<GithubSpec> {
     })
 }
 
+fn parse_git_url(input: &str) -> Option<GitSpec> {
+    let url = if let Some(rest) = input.strip_prefix("git+") {
+        if !(rest.starts_with("https://")
+            || rest.starts_with("http://")
+            || rest.starts_with("ssh://"))
+        {
+            return None;
+        }
+        rest
+    } else if input.starts_with("git://") {
+        input
+    } else {
+        return None;
+    };
+
+    let (url, reference) = match url.split_once('#') {
+        Some((lhs, rhs)) if !rhs.trim().is_empty() => (lhs, Some(rhs.trim().to_string())),
+        Some((lhs, _)) => (lhs, None),
+        None => (url, None),
+    };
+    // `git+https://host/pkg.tgz` predates git support and still means a plain tarball download.
+    if is_archive_path(url) {
+        return None;
+    }
+    Some(GitSpec { url: url.to_string(), reference })
+}
+
+/// `https://github.com/owner/repo[.git][#ref]`, the form copied from a browser. Archive
+/// downloads from the same host stay plain tarballs.
+fn parse_github_web_url(input: &str) -> Option<GithubSpec> {
+    let rest = input.strip_prefix("https://github.com/")?;
+    let path = rest.split('#').next().unwrap_or(rest);
+    if is_archive_path(path) {
+        return None;
+    }
+    parse_github(rest.trim_end_matches('/'))
+}
+
+fn github_from_git(spec: &GitSpec) -> Option<GithubSpec> {
+    let rest = spec
+        .url
+        .strip_prefix("https://github.com/")
+        .or_else(|| spec.url.strip_prefix("ssh://git@github.com/"))
+        .or_else(|| spec.url.strip_prefix("git://github.com/"))?;
+    let github = parse_github(rest.trim_end_matches('/'))?;
+    Some(GithubSpec { reference: spec.reference.clone(), ..github })
+}
+
+fn is_archive_path(url: &str) -> bool {
+    let path = url.split('?').next().unwrap_or(url);
+    path.ends_with(".tgz") || path.ends_with(".tar.gz") || path.ends_with(".tar")
+}
+
 fn is_http_url(value: &str) -> bool {
     value.starts_with("http://") || value.starts_with("https://")
 }
@@ -85,9 +196,14 @@ pub fn guess_name_from_spec(raw: &str) -> Option<String> {
             }
             Some(repo_part.to_string())
         }
-        PackageSpec::Tarball { url } => {
-            let trimmed = url.split('?').next().unwrap_or(&url);
-            if let Some(file) = trimmed.rsplit('/').next() {
+        PackageSpec::Git(spec) => {
+            let last = spec.url.trim_end_matches('/').rsplit(['/', ':']).next()?;
+            let repo = last.trim_end_matches(".git");
+            (!repo.is_empty()).then(|| repo.to_string())
+        }
+        PackageSpec::Tarball { url: location } | PackageSpec::LocalTarball { path: location } => {
+            let trimmed = location.split('?').next().unwrap_or(&location);
+            if let Some(file) = trimmed.rsplit(['/', '\\']).next() {
                 let file = file.trim_end_matches(".tar.gz");
                 let file = file.trim_end_matches(".tgz");
                 let file = file.trim_end_matches(".tar");
@@ -106,3 +222,76 @@ impl GithubSpec {
         self.reference.as_deref().map(Cow::Borrowed)
     }
 }
+
+/// Split a CLI spec such as `@scope/pkg@^1.2.3` into its package name and range. Bare git,
+/// github and tarball specs get a name guessed from their location; `name@<spec>` names them
+/// explicitly.
+pub fn parse_spec(spec: &str) -> (String, String) {
+    if let Some(guessed) = guess_name_from_spec(spec) {
+        return (guessed, spec.to_string());
+    }
+    if let Some(scoped) = spec.strip_prefix('@') {
+        // The range may contain `@` itself (`git+ssh://git@host/...`), the scoped name cannot.
+        return match scoped.find('@') {
+            Some(idx) => {
+                let (name, range) = spec.split_at(idx + 1);
+                (name.to_string(), range[1..].to_string())
+            }
+            None => (spec.to_string(), "*".to_string()),
+        };
+    } else if let Some((name, range)) = spec.split_once('@') {
+        let range = if range.is_empty() { "*" } else { range };
+        return (name.to_string(), range.to_string());
+    }
+
+    (spec.to_string(), "*".to_string())
+}
+
+/// Check a name being added to a project against npm's rules for package names, so a typo
+/// fails with a clear message instead of a registry 404.
+pub fn validate_package_name(name: &str) -> Result<()> {
+    check_package_name(name, false)
+}
+
+/// Like [`validate_package_name`], but tolerates uppercase: dependencies declared in a
+/// manifest may point at packages published before npm banned it (`JSONStream`).
+pub fn validate_dependency_name(name: &str) -> Result<()> {
+    check_package_name(name, true)
+}
+
+fn check_package_name(name: &str, allow_uppercase: bool) -> Result<()> {
+    let problem = if name.is_empty() {
+        Some("name cannot be empty")
+    } else if name.len() > 214 {
+        Some("name cannot be longer than 214 characters")
+    } else if name.trim() != name {
+        Some("name cannot have leading or trailing spaces")
+    } else if name.starts_with('.') || name.starts_with('_') {
+        Some("name cannot start with '.' or '_'")
+    } else if !allow_uppercase && name.chars().any(|c| c.is_ascii_uppercase()) {
+        Some("name cannot contain uppercase letters")
+    } else if matches!(name, "node_modules" | "favicon.ico") {
+        Some("name is reserved")
+    } else {
+        let bare = match name.strip_prefix('@') {
+            Some(scoped) => match scoped.split_once('/') {
+                Some((scope, pkg)) if !scope.is_empty() && !pkg.is_empty() => Some([scope, pkg]),
+                _ => None,
+            },
+            None => Some([name, ""]),
+        };
+        match bare {
+            None => Some("scoped names must look like @scope/name"),
+            Some(parts) => {
+                let url_safe = parts.iter().all(|part| {
+                    part.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_'))
+                });
+                (!url_safe).then_some("name can only contain URL-safe characters")
+            }
+        }
+    };
+    match problem {
+        Some(reason) => bail!("invalid package name '{name}': {reason}"),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,80 @@
+use super::fetch::{http_response, spawn_server};
+use crate::cli::commands::audit::{build_audit_payload, AuditReport, Severity};
+use crate::fetch::Fetcher;
+use crate::lockfile::{Lockfile, PackageEntry};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+fn locked(version: &str, resolved: Option<&str>, deps: &[(&str, &str)]) -> PackageEntry {
+    PackageEntry {
+        version: Some(version.to_string()),
+        resolved: resolved.map(str::to_string),
+        dependencies: deps.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        ..PackageEntry::default()
+    }
+}
+
+#[test]
+fn audit_payload_lists_registry_packages_and_report_counts_severities() {
+    let mut lock = Lockfile::default();
+    let mut root = locked("1.0.0", None, &[("lodash", "^4.17.0")]);
+    root.dev_dependencies = BTreeMap::from([("minimist".to_string(), "^1.2.0".to_string())]);
+    lock.packages.insert(String::new(), root);
+    lock.packages.insert("node_modules/lodash".into(), locked("4.17.20", None, &[]));
+    lock.packages.insert("node_modules/minimist".into(), locked("1.2.5", None, &[]));
+    lock.packages.insert("node_modules/forked".into(), locked("1.0.0+git.abcdef12", None, &[]));
+    lock.packages.insert(
+        "node_modules/local-ws".into(),
+        locked("0.1.0", Some("workspace:packages/ws"), &[]),
+    );
+
+    let payload = build_audit_payload("audited", &lock);
+    assert_eq!(payload["name"], "audited");
+    assert_eq!(payload["requires"]["lodash"], "^4.17.0");
+    assert_eq!(payload["requires"]["minimist"], "^1.2.0");
+    assert_eq!(payload["dependencies"]["lodash"]["version"], "4.17.20");
+    assert!(payload["dependencies"].get("forked").is_none());
+    assert!(payload["dependencies"].get("local-ws").is_none());
+
+    let response = json!({
+        "advisories": {
+            "1": {
+                "module_name": "lodash",
+                "severity": "high",
+                "title": "Prototype Pollution",
+                "vulnerable_versions": "<4.17.21",
+                "url": "https://example.test/advisories/1"
+            },
+            "2": {
+                "module_name": "minimist",
+                "severity": "moderate",
+                "title": "Prototype Pollution",
+                "vulnerable_versions": "<1.2.6"
+            }
+        }
+    });
+    let (registry, requests) =
+        spawn_server(vec![http_response("200 OK", "", response.to_string().as_bytes())]);
+    let fetcher = Fetcher::new(Some(registry)).unwrap();
+    let report = AuditReport::from_response(&fetcher.security_audit(&payload).unwrap());
+    assert!(requests.recv().unwrap().starts_with("post /-/npm/v1/security/audits"));
+
+    assert_eq!(report.advisories[0].module, "lodash");
+    assert_eq!(report.count(Severity::High), 1);
+    assert_eq!(report.count(Severity::Moderate), 1);
+    assert_eq!(report.at_or_above(Severity::High), 1);
+    assert_eq!(report.at_or_above(Severity::Low), 2);
+    assert_eq!(report.at_or_above(Severity::Critical), 0);
+}
+
+#[test]
+fn audit_request_uses_the_registry_scoped_token() {
+    let (server, requests) = spawn_server(vec![http_response("200 OK", "", b"{}")]);
+    let registry = format!("{server}/npm/private");
+    let fetcher =
+        Fetcher::new(Some(registry.clone())).unwrap().with_registry_token(&registry, "s3cret");
+    fetcher.security_audit(&json!({})).unwrap();
+    let request = requests.recv().unwrap();
+    assert!(request.starts_with("post /npm/private/-/npm/v1/security/audits"), "{request}");
+    assert!(request.contains("authorization: bearer s3cret"), "{request}");
+}
@@ -1,11 +1,15 @@
 use super::common::DataHomeGuard;
-use crate::cache::{cache_package_path, ensure_cached_package};
+use crate::cache::{
+    cache_package_path, dropped_dependency_fields, ensure_cached_package, extract_tarball_to,
+    read_cached_manifest,
+};
 use base64::{engine::general_purpose::STANDARD, Engine as _};
 use std::io::Cursor;
 use std::path::Path;
 use tar::Builder;
+use walkdir::WalkDir;
 
-fn build_tarball(entries: &[(&str, &str)]) -> Vec<u8> {
+pub(super) fn build_tarball(entries: &[(&str, &str)]) -> Vec<u8> {
     let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
     let mut builder = Builder::new(encoder);
     for (path, contents) in entries {
@@ -56,3 +60,228 @@ fn ensure_cached_package_rejects_bad_integrity() {
     let pkg_dir = cache_package_path("theta", "1.0.0");
     assert!(!pkg_dir.exists());
 }
+
+#[test]
+fn forced_redownload_keeps_the_cached_copy_until_it_verifies() -> anyhow::Result<()> {
+    use super::fetch::{http_response, spawn_server};
+    use crate::cache::verify_integrity;
+    use crate::fetch::Fetcher;
+    use crate::resolver::graph::download_into_cache;
+
+    let _sandbox = DataHomeGuard::new();
+    let old = build_tarball(&[("package/package.json", r#"{"name":"nu","version":"1.0.0"}"#)]);
+    let new = build_tarball(&[
+        ("package/package.json", r#"{"name":"nu","version":"1.0.0"}"#),
+        ("package/fresh.js", "module.exports = 1;\n"),
+    ]);
+    ensure_cached_package("nu", "1.0.0", &old, None)?;
+    let pkg_dir = cache_package_path("nu", "1.0.0");
+
+    let (server, _requests) = spawn_server(vec![
+        http_response("200 OK", "", &old),
+        http_response("200 OK", "", &old),
+        http_response("200 OK", "", &new),
+    ]);
+    let fetcher = Fetcher::new(Some(server.clone()))?;
+    let url = format!("{server}/nu.tgz");
+    let expected = verify_integrity(&new, None)?;
+
+    // Both attempts serve the wrong bytes: the cached copy must survive untouched.
+    download_into_cache(&fetcher, "nu", "1.0.0", &url, Some(&expected), None, true).unwrap_err();
+    assert!(pkg_dir.join("package.json").exists());
+    assert!(!pkg_dir.join("fresh.js").exists());
+
+    download_into_cache(&fetcher, "nu", "1.0.0", &url, Some(&expected), None, true)?;
+    assert!(pkg_dir.join("fresh.js").exists(), "a verified download replaces the cached copy");
+    Ok(())
+}
+
+fn tree_listing(root: &Path) -> Vec<String> {
+    let mut out: Vec<String> = WalkDir::new(root)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|e| {
+            let rel = e.path().strip_prefix(root).ok()?.to_string_lossy().replace('\\', "/");
+            (!rel.is_empty()).then_some(rel)
+        })
+        .collect();
+    out.sort();
+    out
+}
+
+#[test]
+fn cache_and_direct_extraction_share_layout() -> anyhow::Result<()> {
+    let _sandbox = DataHomeGuard::new();
+    let cases: [(&str, &str, Vec<u8>); 3] = [
+        (
+            "kappa",
+            "1.0.0",
+            build_tarball(&[
+                ("package/package.json", r#"{"name":"kappa","version":"1.0.0"}"#),
+                ("package/lib/index.js", "module.exports = 'kappa';\n"),
+            ]),
+        ),
+        (
+            "@scope/lambda",
+            "2.0.0",
+            build_tarball(&[
+                ("package/package.json", r#"{"name":"@scope/lambda","version":"2.0.0"}"#),
+                ("package/dist/main.js", "module.exports = 'lambda';\n"),
+            ]),
+        ),
+        (
+            "mu",
+            "0.1.0",
+            // codeload-style archive with a single top-level directory
+            build_tarball(&[
+                ("mu-abc123/package.json", r#"{"name":"mu","version":"0.1.0"}"#),
+                ("mu-abc123/src/mu.js", "module.exports = 'mu';\n"),
+            ]),
+        ),
+    ];
+
+    for (name, version, bytes) in &cases {
+        let cached_integrity = ensure_cached_package(name, version, bytes, None)?;
+        let direct = tempfile::tempdir()?;
+        let direct_integrity = extract_tarball_to(bytes, direct.path(), None)?;
+        assert_eq!(cached_integrity, direct_integrity);
+
+        let cached_listing = tree_listing(&cache_package_path(name, version));
+        assert!(cached_listing.contains(&"package.json".to_string()), "{name}: {cached_listing:?}");
+        assert_eq!(cached_listing, tree_listing(direct.path()), "layout differs for {name}");
+    }
+    Ok(())
+}
+
+#[test]
+fn download_retries_once_on_integrity_mismatch() -> anyhow::Result<()> {
+    use super::fetch::{http_response, spawn_server};
+    use crate::cache::verify_integrity;
+    use crate::fetch::Fetcher;
+    use crate::resolver::graph::download_into_cache;
+
+    let _sandbox = DataHomeGuard::new();
+    let good = build_tarball(&[("package/package.json", r#"{"name":"iota","version":"1.0.0"}"#)]);
+    let corrupt =
+        build_tarball(&[("package/package.json", r#"{"name":"iota","version":"6.6.6"}"#)]);
+    let integrity = verify_integrity(&good, None)?;
+
+    let (server, requests) = spawn_server(vec![
+        http_response("200 OK", "", &corrupt),
+        http_response("200 OK", "", &good),
+    ]);
+    let fetcher = Fetcher::new(Some(server.clone()))?;
+    let url = format!("{server}/iota.tgz");
+    let recorded =
+        download_into_cache(&fetcher, "iota", "1.0.0", &url, Some(&integrity), None, false)?;
+    assert_eq!(recorded, integrity);
+    assert_eq!(requests.try_iter().count(), 2, "expected exactly one retry");
+    assert!(cache_package_path("iota", "1.0.0").join("package.json").exists());
+
+    let (server, requests) = spawn_server(vec![
+        http_response("200 OK", "", &corrupt),
+        http_response("200 OK", "", &corrupt),
+        http_response("200 OK", "", &good),
+    ]);
+    let url = format!("{server}/iota.tgz");
+    let err = download_into_cache(&fetcher, "kappa", "1.0.0", &url, Some(&integrity), None, false)
+        .expect_err("second mismatch should fail");
+    let message = format!("{err:#}");
+    assert!(message.contains("pacm cache clean --package kappa@1.0.0"), "{message}");
+    assert!(message.contains("integrity mismatch"), "{message}");
+    assert_eq!(requests.try_iter().count(), 2);
+    Ok(())
+}
+
+#[test]
+fn persistent_integrity_mismatch_is_not_retried_by_the_outer_loop() -> anyhow::Result<()> {
+    use super::fetch::{http_response, spawn_server};
+    use crate::cache::verify_integrity;
+    use crate::fetch::Fetcher;
+    use crate::resolver::graph::{retry_download_into_cache, PendingDownload};
+    use std::sync::atomic::AtomicUsize;
+
+    let _sandbox = DataHomeGuard::new();
+    let good = build_tarball(&[("package/package.json", r#"{"name":"lambda","version":"1.0.0"}"#)]);
+    let corrupt =
+        build_tarball(&[("package/package.json", r#"{"name":"lambda","version":"6.6.6"}"#)]);
+    let (server, requests) =
+        spawn_server((0..6).map(|_| http_response("200 OK", "", &corrupt)).collect());
+    let fetcher = Fetcher::new(Some(server.clone()))?;
+    let pd = PendingDownload {
+        name: "lambda".into(),
+        version: "1.0.0".into(),
+        url: format!("{server}/lambda.tgz"),
+        integrity_hint: Some(verify_integrity(&good, None)?),
+        scripts: None,
+        replace: false,
+    };
+    let err = retry_download_into_cache(&fetcher, &pd, &|_, _| {}, &AtomicUsize::new(0), 1, None)
+        .expect_err("a persistent mismatch should fail");
+    assert!(format!("{err:#}").contains("integrity mismatch"), "{err:#}");
+    assert_eq!(requests.try_iter().count(), 2, "one download plus one retry");
+    Ok(())
+}
+
+#[test]
+fn concurrent_ensure_cached_package_calls_all_succeed() {
+    let _guard = DataHomeGuard::new();
+    let tarball = build_tarball(&[
+        ("package/package.json", r#"{"name":"contended","version":"4.17.21"}"#),
+        ("package/index.js", "module.exports = 'contended';\n"),
+    ]);
+    let barrier = std::sync::Barrier::new(8);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                scope.spawn(|| {
+                    barrier.wait();
+                    ensure_cached_package("contended", "4.17.21", &tarball, None)
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().expect("thread panicked").expect("ensure_cached_package");
+        }
+    });
+
+    let package_dir = cache_package_path("contended", "4.17.21");
+    assert_eq!(
+        std::fs::read_to_string(package_dir.join("index.js")).unwrap(),
+        "module.exports = 'contended';\n"
+    );
+    let name_dir = package_dir.parent().unwrap().parent().unwrap();
+    let leftovers: Vec<_> = std::fs::read_dir(name_dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(leftovers, ["4.17.21"], "temporary extraction dirs must be cleaned up");
+}
+
+#[test]
+fn null_dependency_fields_are_reported_but_tolerated() -> anyhow::Result<()> {
+    let _guard = DataHomeGuard::new();
+    let dir = cache_package_path("loose-deps", "1.0.0");
+    std::fs::create_dir_all(&dir)?;
+    let raw = serde_json::json!({
+        "name": "loose-deps",
+        "version": "1.0.0",
+        "dependencies": null,
+        "peerDependencies": ["react"],
+        "optionalDependencies": {"fsevents": "^2.0.0"}
+    });
+    std::fs::write(dir.join("package.json"), raw.to_string())?;
+
+    let manifest = read_cached_manifest("loose-deps", "1.0.0")?;
+    assert!(manifest.dependencies.is_empty());
+    assert!(manifest.peer_dependencies.is_empty());
+    assert_eq!(manifest.optional_dependencies.len(), 1);
+    assert_eq!(
+        dropped_dependency_fields(&raw),
+        vec![
+            "`dependencies` is null; treating it as empty".to_string(),
+            "`peerDependencies` is an array; treating it as empty".to_string(),
+        ]
+    );
+    Ok(())
+}
@@ -32,6 +32,7 @@ fn cas_store_creates_and_loads_entry() {
         source_dir: pkg_dir.as_path(),
         integrity: Some("sha512-test"),
         resolved: Some("https://example.com/foo.tgz"),
+        project: None,
     };
 
     let entry = store.ensure_entry(&params).expect("ensure foo store entry");
@@ -82,6 +83,7 @@ fn cas_store_dependency_order_deterministic() {
             source_dir: dep_a_dir.as_path(),
             integrity: Some("sha512-dep-a"),
             resolved: Some("https://example.com/dep-a.tgz"),
+            project: None,
         })
         .expect("ensure dep-a entry");
     let dep_b_entry = store
@@ -92,6 +94,7 @@ fn cas_store_dependency_order_deterministic() {
             source_dir: dep_b_dir.as_path(),
             integrity: Some("sha512-dep-b"),
             resolved: Some("https://example.com/dep-b.tgz"),
+            project: None,
         })
         .expect("ensure dep-b entry");
 
@@ -115,6 +118,7 @@ fn cas_store_dependency_order_deterministic() {
             source_dir: parent_dir.as_path(),
             integrity: Some("sha512-parent"),
             resolved: Some("https://example.com/parent.tgz"),
+            project: None,
         })
         .expect("ensure parent forward order");
 
@@ -127,6 +131,7 @@ fn cas_store_dependency_order_deterministic() {
             source_dir: parent_dir.as_path(),
             integrity: Some("sha512-parent"),
             resolved: Some("https://example.com/parent.tgz"),
+            project: None,
         })
         .expect("ensure parent reverse order");
 
@@ -143,3 +148,203 @@ fn cas_store_dependency_order_deterministic() {
     // Store path should live under the cas store root directory.
     assert!(first.root_dir.starts_with(store.root()));
 }
+
+#[test]
+fn cache_size_counts_cached_packages_and_store_entries() {
+    let _sandbox = DataHomeGuard::new();
+
+    let plain = cache_package_path("foo", "1.2.3");
+    write_package_json(&plain, "foo", "1.2.3");
+    let scoped = cache_package_path("@scope/bar", "2.0.0");
+    write_package_json(&scoped, "@scope/bar", "2.0.0");
+    // A nested directory called `package` inside a package is not another cached package.
+    fs::create_dir_all(plain.join("package")).expect("create nested dir");
+
+    let store = CasStore::open().expect("open cas store");
+    let deps: Vec<DependencyFingerprint> = Vec::new();
+    let entry = store
+        .ensure_entry(&EnsureParams {
+            name: "@scope/bar",
+            version: "2.0.0",
+            dependencies: &deps,
+            source_dir: scoped.as_path(),
+            integrity: None,
+            resolved: None,
+            project: None,
+        })
+        .expect("ensure store entry");
+
+    let size = crate::cache::size(&crate::config::Config::load()).expect("measure cache");
+    assert_eq!(size.cached_packages, 2);
+    assert_eq!(size.store_entries, 1);
+    assert_eq!(size.store_bytes, entry.size);
+    assert!(size.cache_bytes >= entry.size);
+    assert_eq!(crate::fsutil::human_size(1536), "1.5 KiB");
+    assert_eq!(crate::fsutil::human_size(12), "12 B");
+}
+
+#[test]
+fn list_entries_returns_every_store_entry() {
+    let _sandbox = DataHomeGuard::new();
+    let store = CasStore::open().expect("open cas store");
+    assert!(store.list_entries().expect("list empty store").is_empty());
+
+    let deps: Vec<DependencyFingerprint> = Vec::new();
+    let mut created = Vec::new();
+    for (name, version) in [("foo", "1.2.3"), ("@scope/bar", "2.0.0")] {
+        let dir = cache_package_path(name, version);
+        write_package_json(&dir, name, version);
+        let entry = store
+            .ensure_entry(&EnsureParams {
+                name,
+                version,
+                dependencies: &deps,
+                source_dir: dir.as_path(),
+                integrity: None,
+                resolved: None,
+                project: None,
+            })
+            .expect("ensure store entry");
+        created.push(entry.store_key);
+    }
+    created.sort();
+
+    let listed = store.list_entries().expect("list store entries");
+    let keys: Vec<_> = listed.iter().map(|e| e.store_key.clone()).collect();
+    assert_eq!(keys, created);
+    let bar = listed.iter().find(|e| e.name == "@scope/bar").expect("scoped entry listed");
+    assert_eq!(bar.version, "2.0.0");
+    assert!(bar.package_dir.join("package.json").exists());
+}
+
+#[test]
+fn cache_archive_round_trips_into_a_fresh_cache() {
+    use crate::cache::{export_archive, import_archive, ArchiveSummary};
+
+    let archive_dir = tempfile::tempdir().expect("archive dir");
+    let archive = archive_dir.path().join("cache.tar");
+    let store_key = {
+        let _source = DataHomeGuard::new();
+        for (name, version) in [("foo", "1.0.0"), ("@scope/bar", "2.0.0")] {
+            write_package_json(&cache_package_path(name, version), name, version);
+        }
+        let store = CasStore::open().expect("open cas store");
+        let pkg_dir = cache_package_path("foo", "1.0.0");
+        let entry = store
+            .ensure_entry(&EnsureParams {
+                name: "foo",
+                version: "1.0.0",
+                dependencies: &[],
+                source_dir: &pkg_dir,
+                integrity: None,
+                resolved: None,
+                project: None,
+            })
+            .expect("ensure store entry");
+        let summary =
+            export_archive(&archive, true, &crate::config::Config::load()).expect("export cache");
+        assert_eq!(summary, ArchiveSummary { packages: 2, store_entries: 1, skipped: 0 });
+        entry.store_key
+    };
+
+    let _target = DataHomeGuard::new();
+    // An entry the target already has must survive the import untouched.
+    let local = cache_package_path("foo", "1.0.0");
+    write_package_json(&local, "foo", "1.0.0");
+    fs::write(local.join("local.txt"), "keep me").expect("write marker");
+
+    let summary = import_archive(&archive, &crate::config::Config::load()).expect("import cache");
+    assert_eq!(summary, ArchiveSummary { packages: 1, store_entries: 1, skipped: 1 });
+    assert!(local.join("local.txt").exists());
+    assert!(cache_package_path("@scope/bar", "2.0.0").join("bin.js").exists());
+    let store = CasStore::open().expect("open cas store");
+    let entry = store.load_entry(&store_key).expect("load entry").expect("imported entry");
+    assert!(store.verify_entry(&entry).expect("verify entry"));
+
+    // Importing again changes nothing.
+    let again =
+        import_archive(&archive, &crate::config::Config::load()).expect("import cache again");
+    assert_eq!(again, ArchiveSummary { packages: 0, store_entries: 0, skipped: 3 });
+}
+
+#[test]
+fn stale_tmp_dirs_are_cleaned_and_the_package_installs_again() {
+    use crate::cache::{ensure_cached_package, remove_stale_cache_tmp, STALE_TMP_AGE};
+    use std::time::Duration;
+
+    let _sandbox = DataHomeGuard::new();
+    let store = CasStore::open().expect("open cas store");
+
+    // Leftovers of an install killed mid-extraction and mid-staging.
+    let cache_leftover = crate::fsutil::cache_root().join("pkgs/foo/.1.0.0.tmp-1-2-3");
+    fs::create_dir_all(cache_leftover.join("package")).unwrap();
+    fs::write(cache_leftover.join("package/index.js"), "partial").unwrap();
+    let store_leftover = store.root().join("tmp/foo-1.0.0-1-2-3");
+    fs::create_dir_all(store_leftover.join("package")).unwrap();
+    fs::write(store.root().join("tmp/metadata-1-2-3.json"), "{").unwrap();
+
+    // Fresh scratch space may belong to an install still running.
+    assert_eq!(remove_stale_cache_tmp(STALE_TMP_AGE), 0);
+    assert_eq!(store.remove_stale_tmp(STALE_TMP_AGE), 0);
+    assert!(cache_leftover.exists() && store_leftover.exists());
+
+    assert_eq!(remove_stale_cache_tmp(Duration::ZERO), 1);
+    assert_eq!(store.remove_stale_tmp(Duration::ZERO), 2);
+    assert!(!cache_leftover.exists() && !store_leftover.exists());
+
+    let tarball = super::cache_integrity::build_tarball(&[(
+        "package/package.json",
+        r#"{"name":"foo","version":"1.0.0"}"#,
+    )]);
+    ensure_cached_package("foo", "1.0.0", &tarball, None).expect("extract after cleanup");
+    let pkg_dir = cache_package_path("foo", "1.0.0");
+    let deps: Vec<DependencyFingerprint> = Vec::new();
+    let entry = store
+        .ensure_entry(&EnsureParams {
+            name: "foo",
+            version: "1.0.0",
+            dependencies: &deps,
+            source_dir: pkg_dir.as_path(),
+            integrity: None,
+            resolved: None,
+            project: None,
+        })
+        .expect("stage entry after cleanup");
+    assert!(entry.package_dir.join("package.json").exists());
+    assert_eq!(fs::read_dir(store.root().join("tmp")).unwrap().count(), 0);
+}
+
+#[test]
+fn concurrent_installs_keep_every_reference() {
+    let _sandbox = DataHomeGuard::new();
+    let workspace = tempfile::tempdir().expect("projects dir");
+    let pkg_dir = cache_package_path("shared", "1.0.0");
+    write_package_json(&pkg_dir, "shared", "1.0.0");
+    let store = CasStore::open().expect("open cas store");
+    let projects: Vec<_> = (0..8).map(|i| workspace.path().join(format!("project-{i}"))).collect();
+
+    std::thread::scope(|scope| {
+        for project in &projects {
+            let (store, pkg_dir) = (&store, &pkg_dir);
+            scope.spawn(move || {
+                store
+                    .ensure_entry(&EnsureParams {
+                        name: "shared",
+                        version: "1.0.0",
+                        dependencies: &[],
+                        source_dir: pkg_dir,
+                        integrity: None,
+                        resolved: None,
+                        project: Some(project),
+                    })
+                    .expect("ensure shared entry");
+            });
+        }
+    });
+
+    let entries = store.list_entries().expect("list store entries");
+    assert_eq!(entries.len(), 1);
+    let mut expected: Vec<_> = projects.iter().map(|p| crate::cache::project_key(p)).collect();
+    expected.sort();
+    assert_eq!(entries[0].referencing_projects, expected);
+}
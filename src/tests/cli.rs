@@ -0,0 +1,147 @@
+use crate::cli::{Commands, PacmCli};
+use clap::Parser;
+
+fn save_flags(args: &[&str]) -> Result<(bool, bool), clap::Error> {
+    let cli = PacmCli::try_parse_from(std::iter::once("pacm").chain(args.iter().copied()))?;
+    match cli.command {
+        Some(Commands::Install { dev, optional, .. })
+        | Some(Commands::Add { dev, optional, .. }) => Ok((dev, optional)),
+        other => panic!("unexpected command {other:?}"),
+    }
+}
+
+#[test]
+fn npm_save_flags_map_to_dev_and_optional() {
+    assert_eq!(save_flags(&["install", "react", "--save-dev"]).unwrap(), (true, false));
+    assert_eq!(save_flags(&["add", "react", "-D"]).unwrap(), (true, false));
+    assert_eq!(save_flags(&["install", "react", "--save-optional"]).unwrap(), (false, true));
+    assert_eq!(save_flags(&["add", "react", "-O"]).unwrap(), (false, true));
+    assert_eq!(save_flags(&["install", "react", "--save-prod"]).unwrap(), (false, false));
+    assert_eq!(save_flags(&["i", "react", "-P"]).unwrap(), (false, false));
+}
+
+#[test]
+fn conflicting_save_flags_are_rejected() {
+    for args in [
+        &["install", "react", "-D", "-O"][..],
+        &["add", "react", "--save-dev", "--optional"],
+        &["install", "react", "-P", "--save-dev"],
+        &["add", "react", "-P", "-O"],
+    ] {
+        let err = save_flags(args).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict, "{args:?}");
+    }
+}
+
+#[test]
+fn save_flags_require_packages_to_add() {
+    for args in [
+        &["install", "--dev"][..],
+        &["install", "-D"],
+        &["i", "--save-optional"],
+        &["install", "-P"],
+        &["install", "--dev", "--no-save"],
+    ] {
+        let err = save_flags(args).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument, "{args:?}");
+    }
+    assert_eq!(save_flags(&["install"]).unwrap(), (false, false));
+    assert_eq!(save_flags(&["install", "--save-dev", "react"]).unwrap(), (true, false));
+}
+
+#[test]
+fn prefer_frozen_defaults_on_and_can_be_disabled() {
+    let prefer_frozen = |args: &[&str]| {
+        let cli =
+            PacmCli::try_parse_from(std::iter::once("pacm").chain(args.iter().copied())).unwrap();
+        match cli.command {
+            Some(Commands::Install { prefer_frozen, .. }) => prefer_frozen,
+            other => panic!("unexpected command {other:?}"),
+        }
+    };
+    assert!(prefer_frozen(&["install"]));
+    assert!(prefer_frozen(&["install", "--prefer-frozen"]));
+    assert!(!prefer_frozen(&["install", "--prefer-frozen=false"]));
+}
+
+#[test]
+fn json_flag_is_global() {
+    for args in [
+        &["pacm", "list", "--json"][..],
+        &["pacm", "--json", "cache", "size"],
+        &["pacm", "audit", "--json", "--audit-level", "low"],
+        &["pacm", "pm", "ls", "--json"],
+    ] {
+        let cli = PacmCli::try_parse_from(args).unwrap();
+        assert!(cli.json, "{args:?}");
+    }
+    assert!(!PacmCli::try_parse_from(["pacm", "list"]).unwrap().json);
+}
+
+#[test]
+fn list_json_output_is_keyed_by_package_name() {
+    use crate::cli::commands::list::ListOutput;
+    use crate::lockfile::{Lockfile, PackageEntry};
+
+    let mut lock = Lockfile::default();
+    lock.packages.insert(String::new(), PackageEntry::default());
+    for (name, version) in [("react", "18.2.0"), ("@types/node", "20.0.1")] {
+        lock.packages.insert(
+            format!("node_modules/{name}"),
+            PackageEntry { version: Some(version.into()), ..PackageEntry::default() },
+        );
+    }
+    let all = serde_json::to_value(ListOutput::from_lock(&lock, None)).unwrap();
+    assert_eq!(
+        all,
+        serde_json::json!({
+            "packages": {
+                "@types/node": { "version": "20.0.1" },
+                "react": { "version": "18.2.0" }
+            }
+        })
+    );
+    let only_react = ListOutput::from_lock(&lock, Some(&["react"]));
+    assert_eq!(only_react.packages.keys().collect::<Vec<_>>(), ["react"]);
+}
+
+#[test]
+fn run_accepts_if_present_before_the_script() {
+    let cli = PacmCli::try_parse_from(["pacm", "run", "--if-present", "test", "--watch"]).unwrap();
+    match cli.command {
+        Some(Commands::Run { if_present, args, .. }) => {
+            assert!(if_present);
+            assert_eq!(args, ["test", "--watch"]);
+        }
+        other => panic!("unexpected command {other:?}"),
+    }
+}
+
+#[test]
+fn color_flag_accepts_only_known_modes() {
+    for mode in ["auto", "always", "never"] {
+        let cli = PacmCli::try_parse_from(["pacm", "--color", mode, "list"]).unwrap();
+        assert_eq!(cli.color, mode);
+    }
+    assert!(PacmCli::try_parse_from(["pacm", "list", "--color=sometimes"]).is_err());
+}
+
+#[test]
+fn json_errors_carry_message_and_context() {
+    use crate::output::{ErrorOutput, OutputFormat};
+    use anyhow::Context;
+
+    let cli = PacmCli::try_parse_from(["pacm", "--json", "list"]).unwrap();
+    assert_eq!(cli.output_format(), OutputFormat::Json);
+
+    let err = std::fs::read("/nonexistent/pacm.lockb")
+        .context("read pacm.lockb")
+        .context("load lockfile")
+        .unwrap_err();
+    let json = serde_json::to_value(ErrorOutput::from_error(&err)).unwrap();
+    assert_eq!(json["error"], "load lockfile");
+    let context = json["context"].as_array().unwrap();
+    assert_eq!(context.len(), 2);
+    assert_eq!(context[0], "read pacm.lockb");
+    assert!(context[1].is_string());
+}
@@ -0,0 +1,41 @@
+use crate::cli::commands::run::format_run_listing;
+use crate::colors::{color_enabled, set_color_mode, ColorMode, C_GRAY};
+
+fn listing() -> String {
+    let scripts = serde_json::json!({ "build": "tsc" });
+    format_run_listing(scripts.as_object(), &["eslint".to_string()]).join("\n")
+}
+
+#[test]
+fn no_color_strips_ansi_codes() {
+    let _env = super::common::lock_env();
+    let previous = (std::env::var_os("NO_COLOR"), std::env::var_os("FORCE_COLOR"));
+    std::env::remove_var("FORCE_COLOR");
+
+    std::env::set_var("NO_COLOR", "1");
+    set_color_mode(ColorMode::Auto);
+    assert!(!color_enabled());
+    let plain = listing();
+    assert!(!plain.contains('\x1b'), "{plain:?}");
+    assert!(plain.contains("build"));
+    assert_eq!(format!("{C_GRAY}"), "");
+
+    // An explicit --color always wins over NO_COLOR.
+    set_color_mode(ColorMode::Always);
+    assert!(listing().contains(C_GRAY.code()));
+
+    std::env::remove_var("NO_COLOR");
+    std::env::set_var("FORCE_COLOR", "1");
+    set_color_mode(ColorMode::Auto);
+    assert!(color_enabled());
+    set_color_mode(ColorMode::Never);
+    assert!(!listing().contains('\x1b'));
+
+    set_color_mode(ColorMode::Auto);
+    for (key, value) in [("NO_COLOR", previous.0), ("FORCE_COLOR", previous.1)] {
+        match value {
+            Some(value) => std::env::set_var(key, value),
+            None => std::env::remove_var(key),
+        }
+    }
+}
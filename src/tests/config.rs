@@ -0,0 +1,98 @@
+use crate::config::{nerf_dart, Config};
+use std::collections::HashMap;
+use std::path::Path;
+
+fn env_from(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let map: HashMap<String, String> =
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    move |key| map.get(key).cloned()
+}
+
+fn write_rc(dir: &Path, file: &str, text: &str) {
+    std::fs::write(dir.join(file), text).expect("write rc");
+}
+
+#[test]
+fn config_precedence_is_cli_env_project_home() {
+    let project = tempfile::tempdir().expect("project");
+    let home = tempfile::tempdir().expect("home");
+    write_rc(
+        home.path(),
+        ".pacmrc",
+        "registry=https://home.example\nsave-prefix=~\nstore-dir=home-store\nignore-scripts=true\n",
+    );
+    write_rc(project.path(), ".pacmrc", "registry=https://project.example\nsave-prefix=^\n");
+
+    let env = env_from(&[("PACM_REGISTRY", "https://env.example")]);
+    let config = Config::from_sources(env, Some(project.path()), Some(home.path()));
+    assert_eq!(config.registry.as_deref(), Some("https://env.example"));
+    assert_eq!(config.save_prefix.as_deref(), Some("^"), "project rc beats home rc");
+    assert_eq!(config.store_dir, Some(home.path().join("home-store")));
+    assert_eq!(config.ignore_scripts, Some(true), "home rc fills what nothing else sets");
+
+    let cli = Config {
+        registry: Some("https://cli.example".into()),
+        ignore_scripts: Some(false),
+        ..Config::default()
+    };
+    let merged = cli.or(config);
+    assert_eq!(merged.registry.as_deref(), Some("https://cli.example"));
+    assert_eq!(merged.ignore_scripts, Some(false));
+    assert_eq!(merged.save_prefix.as_deref(), Some("^"));
+}
+
+#[test]
+fn npmrc_is_the_lowest_layer() {
+    let project = tempfile::tempdir().expect("project");
+    write_rc(project.path(), ".npmrc", "registry=https://npm.example\nsave-prefix=~\n");
+    write_rc(project.path(), ".pacmrc", "save-prefix=^\n");
+
+    let config = Config::from_sources(env_from(&[]), Some(project.path()), None);
+    assert_eq!(config.registry.as_deref(), Some("https://npm.example"));
+    assert_eq!(config.save_prefix.as_deref(), Some("^"));
+
+    let empty = Config::from_sources(env_from(&[]), None, None);
+    assert_eq!(empty, Config::default());
+}
+
+#[test]
+fn scope_registries_and_tokens_come_from_rc_files() {
+    let project = tempfile::tempdir().expect("project");
+    let home = tempfile::tempdir().expect("home");
+    write_rc(
+        project.path(),
+        ".npmrc",
+        "@mycorp:registry=https://npm.mycorp.example/\n//npm.mycorp.example/:_authToken=project\n",
+    );
+    write_rc(
+        home.path(),
+        ".npmrc",
+        "//npm.mycorp.example/:_authToken=home\n//other.example/npm:_authToken=other\n",
+    );
+
+    let config = Config::from_sources(env_from(&[]), Some(project.path()), Some(home.path()));
+    assert_eq!(
+        config.scope_registries.get("@mycorp").map(String::as_str),
+        Some("https://npm.mycorp.example/")
+    );
+    assert_eq!(
+        config.auth_tokens.get("//npm.mycorp.example/").map(String::as_str),
+        Some("project")
+    );
+    assert_eq!(config.auth_tokens.get("//other.example/npm/").map(String::as_str), Some("other"));
+    assert_eq!(nerf_dart("https://npm.mycorp.example/private"), "//npm.mycorp.example/private/");
+}
+
+#[test]
+fn npm_peer_and_auth_flags_come_from_npmrc() {
+    let project = tempfile::tempdir().expect("project");
+    write_rc(project.path(), ".npmrc", "legacy-peer-deps=true\nalways-auth=true\n");
+
+    let config = Config::from_sources(env_from(&[]), Some(project.path()), None);
+    assert_eq!(config.legacy_peer_deps, Some(true));
+    assert_eq!(config.always_auth, Some(true));
+
+    let env = env_from(&[("PACM_LEGACY_PEER_DEPS", "false")]);
+    let config = Config::from_sources(env, Some(project.path()), None);
+    assert_eq!(config.legacy_peer_deps, Some(false), "env beats .npmrc");
+}
@@ -1,11 +1,14 @@
 use super::common::DataHomeGuard;
 use crate::cache::{cache_package_path, CasStore, EnsureParams, StoreEntry};
-use crate::installer::{InstallMode, InstallPlanEntry, Installer, PackageInstance};
+use crate::installer::{
+    InstallMode, InstallPlanEntry, Installer, PackageInstance, ProgressCallback,
+};
 use crate::lockfile::{Lockfile, PackageEntry};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use tempfile::tempdir;
 
 fn prepare_cached_package(name: &str, version: &str) -> PathBuf {
@@ -32,10 +35,12 @@ fn lock_entry(version: &str, integrity: &str) -> PackageEntry {
         peer_dependencies_meta: BTreeMap::new(),
         os: Vec::new(),
         cpu_arch: Vec::new(),
+        libc: Vec::new(),
         store_key: None,
         content_hash: None,
         link_mode: None,
         store_path: None,
+        source: None,
     }
 }
 
@@ -95,6 +100,7 @@ fn installer_links_package_and_updates_lock() {
         source_dir: pkg_dir.as_path(),
         integrity: Some("sha512-foo"),
         resolved: Some("https://example.com/foo.tgz"),
+        project: None,
     };
     let store_entry = store.ensure_entry(&params).expect("ensure store entry for foo");
     assert_store_contains(&store_entry, "index.js");
@@ -146,6 +152,7 @@ fn installer_copy_mode_materializes_files() {
         source_dir: pkg_dir.as_path(),
         integrity: Some("sha512-bar"),
         resolved: Some("https://example.com/bar.tgz"),
+        project: None,
     };
     let store_entry = store.ensure_entry(&params).expect("ensure store entry for bar");
     assert_store_contains(&store_entry, "index.js");
@@ -181,3 +188,247 @@ fn installer_copy_mode_materializes_files() {
     let expected_path = store_entry.root_dir.to_string_lossy().to_string();
     assert_eq!(lock_entry.store_path.as_deref(), Some(expected_path.as_str()));
 }
+
+#[test]
+fn installer_reports_progress_once_per_package() {
+    let _sandbox = DataHomeGuard::new();
+    let store = CasStore::open().expect("open cas store");
+    let mut lock = Lockfile::default();
+    let mut plan = HashMap::new();
+    let mut names = Vec::new();
+    for _ in 0..4 {
+        let name = unique_package("progress");
+        let pkg_dir = prepare_cached_package(&name, "1.0.0");
+        let params = EnsureParams {
+            name: &name,
+            version: "1.0.0",
+            dependencies: &[],
+            source_dir: pkg_dir.as_path(),
+            integrity: Some("sha512-progress"),
+            resolved: None,
+            project: None,
+        };
+        let store_entry = store.ensure_entry(&params).expect("ensure store entry");
+        lock.packages
+            .insert(format!("node_modules/{name}"), lock_entry("1.0.0", "sha512-progress"));
+        plan.insert(
+            name.clone(),
+            InstallPlanEntry { package: package_instance(&name, "1.0.0"), store_entry },
+        );
+        names.push(name);
+    }
+
+    let seen: Arc<Mutex<Vec<(usize, usize, String)>>> = Arc::default();
+    let recorder = Arc::clone(&seen);
+    let callback: ProgressCallback = Arc::new(move |done, total, pkg| {
+        recorder.lock().unwrap().push((done, total, pkg.to_string()));
+    });
+
+    let project = tempdir().expect("create project dir");
+    let hoist: HashSet<String> = plan.keys().cloned().collect();
+    Installer::new(InstallMode::Copy)
+        .install_with_progress(
+            project.path(),
+            &plan,
+            &mut lock,
+            &hoist,
+            &HashSet::new(),
+            Some(callback),
+        )
+        .expect("install with progress");
+
+    let mut seen = seen.lock().unwrap().clone();
+    seen.sort();
+    let counts: Vec<usize> = seen.iter().map(|(done, _, _)| *done).collect();
+    assert_eq!(counts, vec![1, 2, 3, 4]);
+    assert!(seen.iter().all(|(_, total, _)| *total == names.len()));
+    let mut reported: Vec<String> = seen.into_iter().map(|(_, _, pkg)| pkg).collect();
+    reported.sort();
+    names.sort();
+    assert_eq!(reported, names);
+}
+
+#[test]
+fn lock_drift_flags_versions_outside_manifest_ranges() {
+    use crate::cli::commands::install::lock_drift;
+    use crate::manifest::Manifest;
+
+    let mut lock = Lockfile::default();
+    for (name, version) in [("lodash", "4.17.21"), ("react", "18.2.0"), ("next-tagged", "1.0.0")] {
+        lock.packages.insert(format!("node_modules/{name}"), lock_entry(version, "sha512-x"));
+    }
+    let mut manifest = Manifest::new("app".into(), "1.0.0".into());
+    manifest.dependencies.insert("lodash".into(), "^5.0.0".into());
+    manifest.dependencies.insert("next-tagged".into(), "canary".into());
+    manifest.dev_dependencies.insert("react".into(), ">=17 <20".into());
+    assert_eq!(lock_drift([&manifest], &lock), vec!["lodash@4.17.21 does not satisfy ^5.0.0"]);
+
+    // Widening a range the locked version still satisfies is not drift.
+    manifest.dependencies.insert("lodash".into(), "^4.0.0 || ^5.0.0".into());
+    assert!(lock_drift([&manifest], &lock).is_empty());
+
+    let mut workspace = Manifest::new("ws".into(), "1.0.0".into());
+    workspace.dependencies.insert("react".into(), "^19.0.0".into());
+    assert_eq!(
+        lock_drift([&manifest, &workspace], &lock),
+        vec!["react@18.2.0 does not satisfy ^19.0.0"]
+    );
+}
+
+#[test]
+fn fast_instances_follow_required_peers_only() {
+    use crate::cli::commands::install::build_fast_instances;
+    use crate::lockfile::PeerMeta;
+    use crate::manifest::Manifest;
+
+    let _sandbox = DataHomeGuard::new();
+    let host = unique_package("peer-host");
+    let peer = unique_package("peer-required");
+    let optional_peer = unique_package("peer-optional");
+    prepare_cached_package(&host, "1.0.0");
+
+    let mut host_entry = lock_entry("1.0.0", "sha512-host");
+    host_entry.peer_dependencies.insert(peer.clone(), "^1.0.0".into());
+    host_entry.peer_dependencies.insert(optional_peer.clone(), "^1.0.0".into());
+    host_entry.peer_dependencies_meta.insert(optional_peer.clone(), PeerMeta { optional: true });
+    let mut lock = Lockfile::default();
+    lock.packages.insert(format!("node_modules/{host}"), host_entry);
+    let mut manifest = Manifest::new("app".into(), "1.0.0".into());
+    manifest.dependencies.insert(host.clone(), "^1.0.0".into());
+
+    // The required peer has no lock entry yet, so the fast path must defer to resolution.
+    assert!(build_fast_instances(&manifest, &lock, &[]).is_none());
+
+    // Once it is locked and cached the fast path installs it; the optional peer is not needed.
+    prepare_cached_package(&peer, "1.0.0");
+    lock.packages.insert(format!("node_modules/{peer}"), lock_entry("1.0.0", "sha512-peer"));
+    let instances = build_fast_instances(&manifest, &lock, &[]).expect("fast path available");
+    let names: Vec<&String> = instances.keys().collect();
+    let mut expected = vec![&host, &peer];
+    expected.sort();
+    assert_eq!(names, expected);
+}
+
+/// A package tree shaped like a large real-world package: `dirs` nested directories holding
+/// `files_per_dir` files each.
+fn write_large_package(root: &Path, dirs: usize, files_per_dir: usize) {
+    for d in 0..dirs {
+        let dir = root.join("lib").join(format!("mod{d}")).join("nested");
+        fs::create_dir_all(&dir).expect("create package dir");
+        for f in 0..files_per_dir {
+            fs::write(dir.join(format!("file{f}.js")), format!("module.exports = {d}{f};\n"))
+                .expect("write package file");
+        }
+    }
+    fs::write(root.join("package.json"), r#"{"name":"large","version":"1.0.0"}"#)
+        .expect("write package.json");
+}
+
+fn relative_files(root: &Path) -> Vec<(PathBuf, String)> {
+    let mut files: Vec<_> = walkdir::WalkDir::new(root)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .map(|e| {
+            let rel = e.path().strip_prefix(root).unwrap().to_path_buf();
+            (rel, fs::read_to_string(e.path()).unwrap())
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn parallel_materialization_matches_sequential() {
+    use crate::installer::materialize_tree;
+
+    let temp = tempdir().expect("tempdir");
+    let source = temp.path().join("source");
+    write_large_package(&source, 8, 40);
+    for link in [false, true] {
+        let sequential = temp.path().join(format!("sequential-{link}"));
+        let parallel = temp.path().join(format!("parallel-{link}"));
+        let seq_linked = materialize_tree(&source, &sequential, link, usize::MAX).unwrap();
+        let par_linked = materialize_tree(&source, &parallel, link, 0).unwrap();
+        assert_eq!(seq_linked, par_linked);
+        assert_eq!(relative_files(&sequential), relative_files(&source));
+        assert_eq!(relative_files(&parallel), relative_files(&source));
+    }
+}
+
+/// Compare per-file parallel materialization against a single thread on a large package.
+/// Run with `cargo test --release bench_large_package_materialization -- --ignored --nocapture`.
+#[test]
+#[ignore = "benchmark"]
+fn bench_large_package_materialization() {
+    use crate::installer::materialize_tree;
+    use std::time::Instant;
+
+    let temp = tempdir().expect("tempdir");
+    let source = temp.path().join("source");
+    write_large_package(&source, 200, 40);
+    for link in [false, true] {
+        let mode = if link { "link" } else { "copy" };
+        let started = Instant::now();
+        materialize_tree(&source, &temp.path().join(format!("seq-{mode}")), link, usize::MAX)
+            .unwrap();
+        let sequential = started.elapsed();
+        let started = Instant::now();
+        materialize_tree(&source, &temp.path().join(format!("par-{mode}")), link, 0).unwrap();
+        let parallel = started.elapsed();
+        println!("{mode}: 8000 files sequential {sequential:.2?}, parallel {parallel:.2?}");
+    }
+}
+
+#[test]
+fn compressed_store_entries_round_trip_through_install() {
+    let _sandbox = DataHomeGuard::new();
+    let name = unique_package("zst");
+    let pkg_dir = prepare_cached_package(&name, "1.0.0");
+    fs::create_dir_all(pkg_dir.join("lib")).expect("create lib dir");
+    fs::write(pkg_dir.join("lib").join("util.js"), "exports.util = true;\n").expect("write util");
+
+    let store = CasStore::open().expect("open cas store");
+    std::env::set_var("PACM_STORE_COMPRESS", "1");
+    let created = store.ensure_entry(&EnsureParams {
+        name: &name,
+        version: "1.0.0",
+        dependencies: &[],
+        source_dir: pkg_dir.as_path(),
+        integrity: None,
+        resolved: None,
+        project: None,
+    });
+    std::env::remove_var("PACM_STORE_COMPRESS");
+    let store_entry = created.expect("ensure compressed store entry");
+
+    assert!(store_entry.compressed);
+    assert!(store_entry.archive_path().is_file());
+    assert!(!store_entry.package_dir().exists(), "no expanded tree next to the archive");
+    assert!(store.verify_entry(&store_entry).expect("verify compressed entry"));
+    let reloaded = store.load_entry(&store_entry.store_key).expect("load").expect("entry exists");
+    assert!(reloaded.compressed);
+
+    let mut lock = Lockfile::default();
+    lock.packages.insert(format!("node_modules/{name}"), lock_entry("1.0.0", "sha512-zst"));
+    let mut plan = HashMap::new();
+    plan.insert(
+        name.clone(),
+        InstallPlanEntry {
+            package: package_instance(&name, "1.0.0"),
+            store_entry: store_entry.clone(),
+        },
+    );
+    let project = tempdir().expect("create project dir");
+    let outcomes = Installer::new(InstallMode::Link)
+        .install(project.path(), &plan, &mut lock)
+        .expect("install from compressed entry");
+    assert_eq!(outcomes[0].link_mode, InstallMode::Copy, "compressed entries are extracted");
+
+    let installed = node_modules_path(project.path(), &name);
+    assert_eq!(fs::read_to_string(installed.join("index.js")).unwrap(), "module.exports = 42;\n");
+    assert_eq!(
+        fs::read_to_string(installed.join("lib").join("util.js")).unwrap(),
+        "exports.util = true;\n"
+    );
+}
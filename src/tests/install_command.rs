@@ -1,10 +1,16 @@
 use super::common::lock_env;
 use crate::cache::cache_package_path;
 use crate::cli::commands::{
-    cmd_scripts_run,
-    install::{cmd_install, InstallOptions},
+    cmd_link, cmd_pm_prune, cmd_rebuild, cmd_remove, cmd_run, cmd_scripts_run, cmd_unlink,
+    install::{
+        cmd_ci, cmd_install, cmd_install_global, install_project, InstallKind, InstallOptions,
+    },
+    pm::lockfile_diff,
 };
+use crate::config::Config;
+use crate::fsutil::links_root;
 use crate::lockfile::Lockfile;
+use crate::output::OutputFormat;
 use anyhow::Result;
 use once_cell::sync::Lazy;
 use serde_json::{json, Value};
@@ -120,7 +126,12 @@ fn lockfile_path(project_root: &Path) -> PathBuf {
 }
 
 fn install_options_copy() -> InstallOptions {
-    InstallOptions { copy: true, no_progress: true, ..InstallOptions::default() }
+    InstallOptions {
+        copy: true,
+        no_progress: true,
+        config: Config::load(),
+        ..InstallOptions::default()
+    }
 }
 
 fn host_node_platform() -> &'static str {
@@ -201,7 +212,7 @@ fn scripts_run_executes_registry_scripts() -> Result<()> {
     let _cwd = CwdGuard::change_to(&project_root)?;
     cmd_install(Vec::new(), install_options_copy())?;
 
-    cmd_scripts_run(vec!["scripty".to_string()], false, false, true, false)?;
+    cmd_scripts_run(vec!["scripty".to_string()], false, false, false, true, false, false, false)?;
 
     let sdir = project_root.join("node_modules").join("scripty");
     assert!(sdir.join("pre.txt").exists());
@@ -428,3 +439,2606 @@ fn install_from_specs_updates_manifest() -> Result<()> {
     assert!(lock.packages.get("node_modules/zeta").is_some());
     Ok(())
 }
+
+#[test]
+fn pacmrc_save_prefix_applies_unless_exact() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({ "name": "rc-app", "version": "0.1.0", "dependencies": {} }),
+    );
+    fs::write(project_root.join(".pacmrc"), "save-prefix=^\n")?;
+    for name in ["omicron", "pi"] {
+        seed_cached_package(
+            name,
+            "1.2.0",
+            json!({ "name": name, "version": "1.2.0" }),
+            &[("index.js", "module.exports = 1;\n")],
+        );
+    }
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(vec!["omicron".to_string()], install_options_copy())?;
+    cmd_install(vec!["pi".to_string()], InstallOptions { exact: true, ..install_options_copy() })?;
+
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(project_root.join("package.json"))?)?;
+    assert_eq!(manifest["dependencies"]["omicron"], "^1.2.0");
+    assert_eq!(manifest["dependencies"]["pi"], "1.2.0", "--exact overrides save-prefix");
+    Ok(())
+}
+
+#[test]
+fn pacmrc_ignore_scripts_is_not_saved_to_the_lockfile() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({ "name": "rc-scripts", "version": "0.1.0", "dependencies": { "rho": "1.0.0" } }),
+    );
+    fs::write(project_root.join(".pacmrc"), "ignore-scripts=true\n")?;
+    seed_cached_package("rho", "1.0.0", json!({ "name": "rho", "version": "1.0.0" }), &[]);
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), InstallOptions { skip_scripts: true, ..install_options_copy() })?;
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(!lock.settings.ignore_scripts, "a per-user default must not become a project setting");
+
+    cmd_install(Vec::new(), InstallOptions { ignore_scripts: true, ..install_options_copy() })?;
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(lock.settings.ignore_scripts, "the explicit flag is recorded");
+    Ok(())
+}
+
+#[test]
+fn legacy_peer_deps_is_recorded_and_keeps_the_fast_path() -> Result<()> {
+    use crate::cli::commands::install::build_fast_instances;
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({ "name": "legacy-app", "version": "0.1.0", "dependencies": { "plugin": "1.0.0" } }),
+    );
+    fs::write(project_root.join(".pacmrc"), "legacy-peer-deps=true\n")?;
+    seed_cached_package(
+        "plugin",
+        "1.0.0",
+        json!({ "name": "plugin", "version": "1.0.0", "peerDependencies": { "host": "^1.0.0" } }),
+        &[],
+    );
+    seed_cached_package("host", "1.0.0", json!({ "name": "host", "version": "1.0.0" }), &[]);
+
+    // The command loads its config in the project, so the rc file is picked up there.
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    install_project(
+        &project_root,
+        Vec::new(),
+        InstallOptions { offline: true, ..install_options_copy() },
+    )?;
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(lock.settings.legacy_peer_deps);
+    assert!(!lock.packages.contains_key("node_modules/host"));
+    let manifest = crate::manifest::load(&project_root.join("package.json"))?;
+    assert!(build_fast_instances(&manifest, &lock, &[]).is_some(), "peers are not walked");
+
+    // Without the rc setting the project keeps what it resolved with.
+    fs::remove_file(project_root.join(".pacmrc"))?;
+    fs::remove_dir_all(project_root.join("node_modules"))?;
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    let report = install_project(&project_root, Vec::new(), options)?;
+    assert_eq!(report.kind, InstallKind::Frozen);
+    assert!(!project_root.join("node_modules").join("host").exists());
+    Ok(())
+}
+
+#[test]
+fn strict_install_rejects_mismatched_package_manager() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "pinned-app",
+            "version": "0.1.0",
+            "packageManager": "pacm@999.0.0",
+            "dependencies": {}
+        }),
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let err = cmd_install(Vec::new(), InstallOptions { strict: true, ..install_options_copy() })
+        .expect_err("strict install should refuse another pacm version");
+    assert!(err.to_string().contains("pacm@999.0.0"), "{err}");
+
+    // Without --strict the mismatch is only a warning.
+    cmd_install(Vec::new(), install_options_copy())?;
+    Ok(())
+}
+
+#[test]
+fn ci_reinstalls_node_modules_from_lockfile() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "ci-app",
+            "version": "0.1.0",
+            "dependencies": { "theta": "^1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "theta",
+        "1.0.0",
+        json!({ "name": "theta", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 'theta';\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let err = cmd_ci(true, &Config::load()).expect_err("ci needs a lockfile");
+    assert!(err.to_string().contains("pacm.lockb"), "{err}");
+
+    cmd_install(Vec::new(), install_options_copy())?;
+    let manifest_before = fs::read_to_string(project_root.join("package.json"))?;
+    let stray = project_root.join("node_modules").join("stray.txt");
+    fs::write(&stray, "left over")?;
+
+    cmd_ci(true, &Config::load())?;
+    assert!(!stray.exists(), "ci starts from an empty node_modules");
+    assert!(project_root.join("node_modules").join("theta").join("index.js").exists());
+    assert_eq!(fs::read_to_string(project_root.join("package.json"))?, manifest_before);
+
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "ci-app",
+            "version": "0.1.0",
+            "dependencies": { "theta": "^1.0.0", "iota": "1.0.0" }
+        }),
+    );
+    let err =
+        cmd_ci(true, &Config::load()).expect_err("ci refuses a lockfile that misses a dependency");
+    assert!(err.to_string().contains("does not satisfy package.json"), "{err}");
+    assert!(
+        project_root.join("node_modules").join("theta").join("index.js").exists(),
+        "a failed ci leaves node_modules alone"
+    );
+
+    // Store entries that were collected are rebuilt from the package cache.
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "ci-app",
+            "version": "0.1.0",
+            "dependencies": { "theta": "^1.0.0" }
+        }),
+    );
+    let store = crate::cache::CasStore::open()?;
+    for entry in store.list_entries()? {
+        store.remove_entry(&entry.store_key)?;
+    }
+    cmd_ci(true, &Config::load())?;
+    assert!(project_root.join("node_modules").join("theta").join("index.js").exists());
+    assert_eq!(store.list_entries()?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn install_project_reports_what_changed() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "report-app",
+            "version": "0.1.0",
+            "dependencies": { "kappa": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "kappa",
+        "1.0.0",
+        json!({ "name": "kappa", "version": "1.0.0", "dependencies": { "lambda": "^2.0.0" } }),
+        &[("index.js", "module.exports = 'kappa';\n")],
+    );
+    seed_cached_package(
+        "lambda",
+        "2.1.0",
+        json!({ "name": "lambda", "version": "2.1.0" }),
+        &[("index.js", "module.exports = 'lambda';\n")],
+    );
+
+    let report = install_project(&project_root, Vec::new(), install_options_copy())?;
+    assert_eq!(report.kind, InstallKind::Resolved);
+    let added: Vec<_> =
+        report.added.iter().map(|p| (p.name.as_str(), p.version.as_deref())).collect();
+    assert_eq!(added, [("kappa", Some("1.0.0"))]);
+    assert!(report.removed.is_empty());
+    assert_eq!(report.installed, 2);
+    assert_eq!(report.linked + report.copied, 2);
+    assert_eq!(report.copied, 2, "copy mode never links");
+    let instances: Vec<_> =
+        report.instances.iter().map(|i| (i.name.as_str(), i.version.as_str())).collect();
+    assert_eq!(instances, [("kappa", "1.0.0"), ("lambda", "2.1.0")]);
+    assert!(project_root.join("node_modules").join("kappa").join("index.js").exists());
+
+    let again = install_project(&project_root, Vec::new(), install_options_copy())?;
+    assert_eq!(again.kind, InstallKind::UpToDate);
+    assert_eq!(again.instances.len(), 2);
+
+    // A failed install still hands the caller back its own working directory.
+    let cwd = env::current_dir()?;
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    install_project(&project_root, vec!["not-cached@9.9.9".into()], options).unwrap_err();
+    assert_eq!(env::current_dir()?, cwd);
+    Ok(())
+}
+
+#[test]
+fn depth_zero_installs_only_direct_dependencies() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "shallow-app",
+            "version": "0.1.0",
+            "dependencies": { "mu": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "mu",
+        "1.0.0",
+        json!({ "name": "mu", "version": "1.0.0", "dependencies": { "nu": "1.0.0" } }),
+        &[("index.js", "module.exports = 'mu';\n")],
+    );
+    seed_cached_package(
+        "nu",
+        "1.0.0",
+        json!({ "name": "nu", "version": "1.0.0", "dependencies": { "xi": "1.0.0" } }),
+        &[("index.js", "module.exports = 'nu';\n")],
+    );
+    seed_cached_package(
+        "xi",
+        "1.0.0",
+        json!({ "name": "xi", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 'xi';\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    // Every installed package gets a directory in the virtual store, hoisted or not.
+    let installed =
+        |name: &str| project_root.join("node_modules").join(".pacm").join(name).exists();
+    cmd_install(Vec::new(), InstallOptions { depth: Some(0), ..install_options_copy() })?;
+    assert!(project_root.join("node_modules").join("mu").join("index.js").exists());
+    assert!(installed("mu"));
+    assert!(!installed("nu"));
+    assert!(!installed("xi"));
+    assert!(!lockfile_path(&project_root).exists(), "an incomplete graph is never locked");
+
+    cmd_install(Vec::new(), InstallOptions { depth: Some(1), ..install_options_copy() })?;
+    assert!(installed("nu"));
+    assert!(!installed("xi"));
+
+    cmd_install(Vec::new(), install_options_copy())?;
+    assert!(installed("xi"));
+    Ok(())
+}
+
+#[test]
+fn lockfile_only_skips_node_modules() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "lock-only-app",
+            "version": "0.1.0",
+            "dependencies": { "eta": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "eta",
+        "1.0.0",
+        json!({ "name": "eta", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 'eta';\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), InstallOptions { lockfile_only: true, ..install_options_copy() })?;
+
+    assert!(!project_root.join("node_modules").exists());
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let entry = lock.packages.get("node_modules/eta").expect("eta locked");
+    assert_eq!(entry.version.as_deref(), Some("1.0.0"));
+    assert!(entry.store_key.is_some(), "store entry should be prepared for later installs");
+
+    cmd_install(Vec::new(), install_options_copy())?;
+    assert!(project_root.join("node_modules").join("eta").join("index.js").exists());
+    Ok(())
+}
+
+fn spawn_slow_registry(body: String, delay: std::time::Duration) -> String {
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind fake registry");
+    let addr = listener.local_addr().expect("registry addr");
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf);
+            std::thread::sleep(delay);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    format!("http://{addr}")
+}
+
+#[test]
+fn install_deadline_aborts_slow_resolution() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "deadline-app",
+            "version": "0.1.0",
+            "dependencies": { "deadline-slow": "^1.0.0" }
+        }),
+    );
+    let metadata = json!({
+        "name": "deadline-slow",
+        "dist-tags": { "latest": "1.0.0" },
+        "versions": {
+            "1.0.0": {
+                "name": "deadline-slow",
+                "version": "1.0.0",
+                "dependencies": { "deadline-next": "1.0.0" },
+                "dist": { "tarball": "http://127.0.0.1:9/deadline-slow.tgz" }
+            }
+        }
+    });
+    let registry =
+        spawn_slow_registry(metadata.to_string(), std::time::Duration::from_millis(1500));
+
+    let prev_registry = env::var_os("PACM_REGISTRY");
+    let prev_deadline = env::var_os("PACM_INSTALL_DEADLINE");
+    env::set_var("PACM_REGISTRY", &registry);
+    env::set_var("PACM_INSTALL_DEADLINE", "1");
+    let result = {
+        let _cwd = CwdGuard::change_to(&project_root)?;
+        cmd_install(Vec::new(), install_options_copy())
+    };
+    restore_env("PACM_REGISTRY", &prev_registry);
+    restore_env("PACM_INSTALL_DEADLINE", &prev_deadline);
+
+    let err = result.expect_err("install should time out");
+    assert!(err.to_string().contains("deadline"), "unexpected error: {err:#}");
+    assert!(!project_root.join("node_modules").exists());
+    Ok(())
+}
+
+#[test]
+fn global_install_uses_global_prefix() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    fs::create_dir_all(&project_root)?;
+    seed_cached_package(
+        "globaltool",
+        "2.0.0",
+        json!({ "name": "globaltool", "version": "2.0.0", "bin": { "globaltool": "cli.js" } }),
+        &[("cli.js", "#!/usr/bin/env node\nconsole.log('hi');\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install_global(vec!["globaltool@2.0.0".into()], install_options_copy())?;
+
+    assert_eq!(env::current_dir()?, project_root.canonicalize()?);
+    assert!(!project_root.join("package.json").exists());
+    assert!(!project_root.join("node_modules").exists());
+    let prefix = crate::fsutil::global_root(&crate::config::Config::load());
+    assert!(prefix.join("node_modules").join(".bin").join("globaltool").exists());
+    let lock = Lockfile::load_or_default(prefix.join("pacm.lockb"))?;
+    assert_eq!(
+        lock.packages.get("node_modules/globaltool").and_then(|e| e.version.as_deref()),
+        Some("2.0.0")
+    );
+    assert!(lock.packages[""].dependencies.contains_key("globaltool"));
+    crate::cli::commands::cmd_list(true, false, false, OutputFormat::Human, &Config::load())?;
+    Ok(())
+}
+
+#[test]
+fn ignore_scripts_is_recorded_and_enforced() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "no-scripts-app",
+            "version": "0.1.0",
+            "dependencies": { "hooky": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "hooky",
+        "1.0.0",
+        json!({
+            "name": "hooky",
+            "version": "1.0.0",
+            "scripts": { "postinstall": "sh -c 'echo ran > ran.txt'" }
+        }),
+        &[("index.js", "module.exports = 'hooky';\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), InstallOptions { ignore_scripts: true, ..install_options_copy() })?;
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(lock.settings.ignore_scripts);
+
+    // A plain install keeps the recorded preference.
+    cmd_install(Vec::new(), install_options_copy())?;
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(lock.settings.ignore_scripts);
+
+    let err = cmd_scripts_run(Vec::new(), true, false, false, true, false, false, false)
+        .expect_err("scripts should be refused");
+    assert!(err.to_string().contains("--ignore-scripts"));
+    let marker = project_root.join("node_modules").join("hooky").join("ran.txt");
+    assert!(!marker.exists());
+
+    cmd_scripts_run(Vec::new(), true, false, false, true, false, true, false)?;
+    assert!(marker.exists());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn rebuild_reruns_install_scripts() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "rebuild-app",
+            "version": "0.1.0",
+            "dependencies": { "native-addon": "1.0.0", "plain-lib": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "native-addon",
+        "1.0.0",
+        json!({
+            "name": "native-addon",
+            "version": "1.0.0",
+            "scripts": { "install": "sh -c 'echo built >> build.log'" }
+        }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+    seed_cached_package(
+        "plain-lib",
+        "1.0.0",
+        json!({ "name": "plain-lib", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 2;\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    let log = project_root.join("node_modules").join("native-addon").join("build.log");
+    assert!(!log.exists(), "install itself does not run scripts");
+
+    cmd_rebuild(vec!["native-addon".into()], false, false)?;
+    assert_eq!(fs::read_to_string(&log)?, "built\n");
+    cmd_rebuild(Vec::new(), true, false)?;
+    assert_eq!(fs::read_to_string(&log)?, "built\nbuilt\n");
+
+    let err = cmd_rebuild(vec!["missing-pkg".into()], false, false).unwrap_err();
+    assert!(err.to_string().contains("missing-pkg is not installed"), "{err}");
+    assert!(cmd_rebuild(Vec::new(), false, false).is_err());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn link_consume_and_unlink_cycle() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(&project_root, &json!({ "name": "consumer", "version": "1.0.0" }));
+    let library = sandbox.temp.path().join("my-lib");
+    write_project_manifest(
+        &library,
+        &json!({ "name": "my-lib", "version": "0.1.0", "bin": { "my-lib": "cli.js" } }),
+    );
+    fs::write(library.join("cli.js"), "#!/usr/bin/env node\n")?;
+    fs::write(library.join("index.js"), "module.exports = 1;\n")?;
+
+    {
+        let _cwd = CwdGuard::change_to(&library)?;
+        cmd_link(Vec::new(), &Config::load())?;
+    }
+    assert_eq!(
+        fs::read_link(links_root(&crate::config::Config::load()).join("my-lib"))?,
+        library.canonicalize()?
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let err = cmd_link(vec!["other-lib".into()], &Config::load()).unwrap_err();
+    assert!(err.to_string().contains("other-lib is not linked"), "{err}");
+
+    cmd_link(vec!["my-lib".into()], &Config::load())?;
+    let linked = project_root.join("node_modules").join("my-lib");
+    assert!(fs::symlink_metadata(&linked)?.file_type().is_symlink(), "expected a symlink");
+    fs::write(library.join("index.js"), "module.exports = 2;\n")?;
+    assert_eq!(fs::read_to_string(linked.join("index.js"))?, "module.exports = 2;\n");
+    let shim = project_root.join("node_modules").join(".bin").join("my-lib");
+    assert!(shim.exists(), "bin shim for the linked package");
+
+    cmd_unlink(vec!["my-lib".into()], &Config::load())?;
+    assert!(fs::symlink_metadata(&linked).is_err(), "link should be removed");
+    assert!(!shim.exists(), "bin shim should be removed");
+    assert!(library.join("index.js").exists(), "unlink must not touch the library");
+
+    {
+        let _cwd = CwdGuard::change_to(&library)?;
+        cmd_unlink(Vec::new(), &Config::load())?;
+    }
+    assert!(
+        fs::symlink_metadata(links_root(&crate::config::Config::load()).join("my-lib")).is_err()
+    );
+    assert!(cmd_link(vec!["my-lib".into()], &Config::load()).is_err());
+
+    // A project that opted out of bin links gets the package but no shim.
+    {
+        let _cwd = CwdGuard::change_to(&library)?;
+        cmd_link(Vec::new(), &Config::load())?;
+    }
+    let mut lock = Lockfile::default();
+    lock.settings.no_bin_links = true;
+    crate::lockfile::write(&lock, lockfile_path(&project_root))?;
+    cmd_link(vec!["my-lib".into()], &Config::load())?;
+    assert!(linked.join("index.js").exists());
+    assert!(!shim.exists(), "no shim when the project records --no-bin-links");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn scripts_run_root_skips_dependencies() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "root-scripts-app",
+            "version": "0.1.0",
+            "dependencies": { "dep-hook": "1.0.0" },
+            "scripts": { "postinstall": "sh -c 'echo root > root.txt'" }
+        }),
+    );
+    seed_cached_package(
+        "dep-hook",
+        "1.0.0",
+        json!({
+            "name": "dep-hook",
+            "version": "1.0.0",
+            "scripts": { "postinstall": "sh -c 'echo dep > dep.txt'" }
+        }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    let root_marker = project_root.join("root.txt");
+    let dep_marker = project_root.join("node_modules").join("dep-hook").join("dep.txt");
+    let _ = fs::remove_file(&root_marker);
+    let _ = fs::remove_file(&dep_marker);
+
+    cmd_scripts_run(Vec::new(), false, true, false, true, false, false, false)?;
+    assert!(root_marker.exists(), "root postinstall should run");
+    assert!(!dep_marker.exists(), "dependency scripts must be skipped under --root");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn scripts_run_dependencies_before_dependents() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "ordered-scripts-app",
+            "version": "0.1.0",
+            "dependencies": { "a-hook": "1.0.0" }
+        }),
+    );
+    // a-hook sorts first alphabetically but depends on b-hook, so b-hook must run first.
+    seed_cached_package(
+        "a-hook",
+        "1.0.0",
+        json!({
+            "name": "a-hook",
+            "version": "1.0.0",
+            "dependencies": { "b-hook": "1.0.0" },
+            "scripts": { "postinstall": "sh -c 'echo a >> \"$INIT_CWD/order.txt\"'" }
+        }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+    seed_cached_package(
+        "b-hook",
+        "1.0.0",
+        json!({
+            "name": "b-hook",
+            "version": "1.0.0",
+            "scripts": { "postinstall": "sh -c 'echo b >> \"$INIT_CWD/order.txt\"'" }
+        }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    let order_file = project_root.join("order.txt");
+    let _ = fs::remove_file(&order_file);
+
+    cmd_scripts_run(Vec::new(), true, false, false, true, false, false, false)?;
+    assert_eq!(fs::read_to_string(&order_file)?, "b\na\n");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn install_runs_scripts_only_with_run_scripts() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "run-scripts-app",
+            "version": "0.1.0",
+            "dependencies": { "a-hook": "1.0.0" },
+            "scripts": { "postinstall": "sh -c 'echo root >> order.txt'" }
+        }),
+    );
+    seed_cached_package(
+        "a-hook",
+        "1.0.0",
+        json!({
+            "name": "a-hook",
+            "version": "1.0.0",
+            "dependencies": { "b-hook": "1.0.0" },
+            "scripts": { "postinstall": "sh -c 'echo a >> \"$INIT_CWD/order.txt\"'" }
+        }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+    seed_cached_package(
+        "b-hook",
+        "1.0.0",
+        json!({
+            "name": "b-hook",
+            "version": "1.0.0",
+            "scripts": { "install": "sh -c 'echo b >> \"$INIT_CWD/order.txt\"'" }
+        }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let order_file = project_root.join("order.txt");
+    cmd_install(Vec::new(), install_options_copy())?;
+    assert!(!order_file.exists(), "scripts must not run without --run-scripts");
+
+    // A fresh node_modules goes through the lockfile fast path, which must honor the flag too.
+    fs::remove_dir_all(project_root.join("node_modules"))?;
+    cmd_install(Vec::new(), InstallOptions { run_scripts: true, ..install_options_copy() })?;
+    assert_eq!(fs::read_to_string(&order_file)?, "b\na\nroot\n");
+
+    fs::remove_file(&order_file)?;
+    fs::remove_dir_all(project_root.join("node_modules"))?;
+    fs::remove_file(lockfile_path(&project_root))?;
+    cmd_install(Vec::new(), InstallOptions { run_scripts: true, ..install_options_copy() })?;
+    assert_eq!(fs::read_to_string(&order_file)?, "b\na\nroot\n");
+    Ok(())
+}
+
+#[test]
+fn scripts_run_respects_allowlist() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "allowlist-app",
+            "version": "0.1.0",
+            "dependencies": { "trusted-hook": "1.0.0", "untrusted-hook": "1.0.0" },
+            "allowedScripts": ["trusted-hook"]
+        }),
+    );
+    for name in ["trusted-hook", "untrusted-hook"] {
+        seed_cached_package(
+            name,
+            "1.0.0",
+            json!({
+                "name": name,
+                "version": "1.0.0",
+                "scripts": { "postinstall": "sh -c 'echo ran > ran.txt'" }
+            }),
+            &[("index.js", "module.exports = 1;\n")],
+        );
+    }
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    cmd_scripts_run(Vec::new(), true, false, false, true, false, false, false)?;
+
+    let nm = project_root.join("node_modules");
+    assert!(nm.join("trusted-hook").join("ran.txt").exists());
+    assert!(!nm.join("untrusted-hook").join("ran.txt").exists());
+
+    cmd_scripts_run(vec!["untrusted-hook".into()], false, false, false, true, false, true, false)?;
+    assert!(nm.join("untrusted-hook").join("ran.txt").exists());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn lifecycle_scripts_get_npm_env() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "env-app",
+            "version": "0.1.0",
+            "dependencies": { "envy": "3.1.4" }
+        }),
+    );
+    seed_cached_package(
+        "envy",
+        "3.1.4",
+        json!({
+            "name": "envy",
+            "version": "3.1.4",
+            "scripts": {
+                "postinstall": "echo \"$npm_package_name@$npm_package_version $npm_lifecycle_event $INIT_CWD\" > env.txt"
+            }
+        }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    cmd_scripts_run(vec!["envy".into()], false, false, false, true, false, false, false)?;
+
+    let out = fs::read_to_string(project_root.join("node_modules").join("envy").join("env.txt"))?;
+    let cwd = env::current_dir()?;
+    assert_eq!(out.trim(), format!("envy@3.1.4 postinstall {}", cwd.display()));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn remove_runs_uninstall_hooks_only_when_requested() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    let manifest = json!({
+        "name": "uninstall-app",
+        "version": "0.1.0",
+        "dependencies": { "hooked": "1.0.0" }
+    });
+    write_project_manifest(&project_root, &manifest);
+    seed_cached_package(
+        "hooked",
+        "1.0.0",
+        json!({
+            "name": "hooked",
+            "version": "1.0.0",
+            "scripts": {
+                "preuninstall": "touch \"$INIT_CWD/pre.txt\"",
+                "postuninstall": "touch \"$INIT_CWD/post.txt\" stray.txt"
+            }
+        }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    cmd_remove(vec!["hooked".into()], false, false, &Config::load())?;
+    assert!(!project_root.join("node_modules").join("hooked").exists());
+    assert!(!project_root.join("pre.txt").exists());
+    assert!(!project_root.join("post.txt").exists());
+
+    write_project_manifest(&project_root, &manifest);
+    cmd_install(Vec::new(), install_options_copy())?;
+    cmd_remove(vec!["hooked".into()], true, false, &Config::load())?;
+    assert!(!project_root.join("node_modules").join("hooked").exists());
+    assert!(project_root.join("pre.txt").exists());
+    assert!(project_root.join("post.txt").exists());
+    let store_writes: Vec<_> =
+        walkdir::WalkDir::new(crate::fsutil::store_root(&crate::config::Config::load()))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name() == "stray.txt")
+            .collect();
+    assert!(store_writes.is_empty(), "postuninstall wrote into the store: {store_writes:?}");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn failing_uninstall_hooks_leave_the_project_consistent() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "failing-hooks-app",
+            "version": "0.1.0",
+            "dependencies": { "fragile": "1.0.0", "late": "1.0.0" }
+        }),
+    );
+    for (name, phase) in [("fragile", "preuninstall"), ("late", "postuninstall")] {
+        seed_cached_package(
+            name,
+            "1.0.0",
+            json!({ "name": name, "version": "1.0.0", "scripts": { phase: "exit 3" } }),
+            &[("index.js", "module.exports = 1;\n")],
+        );
+    }
+    let scratch_dirs = || {
+        fs::read_dir(env::temp_dir())
+            .map(|dir| {
+                dir.flatten()
+                    .filter(|e| e.file_name().to_string_lossy().starts_with("pacm-postuninstall-"))
+                    .count()
+            })
+            .unwrap_or(0)
+    };
+    let scratch_before = scratch_dirs();
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    let declared = |name: &str| -> Result<bool> {
+        let manifest: Value =
+            serde_json::from_str(&fs::read_to_string(project_root.join("package.json"))?)?;
+        Ok(manifest["dependencies"].get(name).is_some())
+    };
+    let locked = |name: &str| -> Result<bool> {
+        let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+        Ok(lock.packages.contains_key(&format!("node_modules/{name}")))
+    };
+
+    // A failing preuninstall aborts before anything is written.
+    assert!(cmd_remove(vec!["fragile".into()], true, false, &Config::load()).is_err());
+    assert!(declared("fragile")? && locked("fragile")?);
+    assert!(project_root.join("node_modules").join("fragile").exists());
+
+    // A failing postuninstall runs after the removal is fully recorded.
+    assert!(cmd_remove(vec!["late".into()], true, false, &Config::load()).is_err());
+    assert!(!declared("late")? && !locked("late")?);
+    assert!(!project_root.join("node_modules").join("late").exists());
+    assert!(declared("fragile")? && locked("fragile")?);
+    assert_eq!(scratch_dirs(), scratch_before, "postuninstall scratch dirs leaked");
+    Ok(())
+}
+
+#[test]
+fn offline_install_never_touches_network() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "offline-app",
+            "version": "0.1.0",
+            "dependencies": { "theta": "^1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "theta",
+        "1.2.0",
+        json!({ "name": "theta", "version": "1.2.0", "dependencies": { "iota": "1.0.0" } }),
+        &[("index.js", "module.exports = 'theta';\n")],
+    );
+    seed_cached_package(
+        "iota",
+        "1.0.0",
+        json!({ "name": "iota", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 'iota';\n")],
+    );
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let registry = format!("http://{}", listener.local_addr()?);
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&connections);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            drop(stream);
+        }
+    });
+
+    let prev_registry = env::var_os("PACM_REGISTRY");
+    env::set_var("PACM_REGISTRY", &registry);
+    let offline = InstallOptions { offline: true, ..install_options_copy() };
+    let (installed, missing) = {
+        let _cwd = CwdGuard::change_to(&project_root)?;
+        let installed = cmd_install(Vec::new(), offline.clone());
+        let missing = cmd_install(vec!["omega@^2.0.0".to_string()], offline);
+        (installed, missing)
+    };
+    restore_env("PACM_REGISTRY", &prev_registry);
+
+    installed?;
+    let node_modules = project_root.join("node_modules");
+    assert!(node_modules.join("theta").join("index.js").exists());
+    assert!(node_modules.join("iota").join("index.js").exists());
+    let err = missing.expect_err("uncached package should fail offline");
+    assert!(err.to_string().contains("offline: omega not cached"), "unexpected error: {err:#}");
+    assert_eq!(connections.load(Ordering::SeqCst), 0, "offline install opened a connection");
+    Ok(())
+}
+
+#[test]
+fn dist_tag_dependencies_stay_pinned_to_lockfile() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "tag-app",
+            "version": "0.1.0",
+            "dependencies": { "lambda": "next" }
+        }),
+    );
+    for name in ["mu", "nu"] {
+        seed_cached_package(name, "1.0.0", json!({ "name": name, "version": "1.0.0" }), &[]);
+    }
+    for version in ["2.0.0-beta.1", "2.0.0-beta.2"] {
+        seed_cached_package(
+            "lambda",
+            version,
+            json!({ "name": "lambda", "version": version }),
+            &[("index.js", "module.exports = 'lambda';\n")],
+        );
+    }
+    let metadata = |next: &str| {
+        json!({
+            "name": "lambda",
+            "dist-tags": { "latest": "1.0.0", "next": next },
+            "versions": {
+                next: {
+                    "name": "lambda",
+                    "version": next,
+                    "dist": { "tarball": "http://127.0.0.1:9/lambda.tgz" }
+                }
+            }
+        })
+        .to_string()
+    };
+    let locked_version = |root: &Path| -> Result<Option<String>> {
+        let lock = Lockfile::load_or_default(lockfile_path(root))?;
+        Ok(lock.packages.get("node_modules/lambda").and_then(|e| e.version.clone()))
+    };
+
+    let prev_registry = env::var_os("PACM_REGISTRY");
+    let result = (|| -> Result<(Option<String>, Option<String>, Option<String>)> {
+        let _cwd = CwdGuard::change_to(&project_root)?;
+        let zero = std::time::Duration::ZERO;
+        env::set_var("PACM_REGISTRY", spawn_slow_registry(metadata("2.0.0-beta.1"), zero));
+        cmd_install(Vec::new(), install_options_copy())?;
+        let first = locked_version(&project_root)?;
+
+        // Adding a dependency forces a full resolution; the tag must not be looked up again.
+        let with_dep =
+            |deps: Value| json!({ "name": "tag-app", "version": "0.1.0", "dependencies": deps });
+        write_project_manifest(
+            &project_root,
+            &with_dep(json!({ "lambda": "next", "mu": "1.0.0" })),
+        );
+        cmd_install(Vec::new(), InstallOptions { offline: true, ..install_options_copy() })?;
+        let offline = locked_version(&project_root)?;
+
+        // The tag moved upstream; without an explicit update the lockfile keeps its pin.
+        env::set_var("PACM_REGISTRY", spawn_slow_registry(metadata("2.0.0-beta.2"), zero));
+        write_project_manifest(
+            &project_root,
+            &with_dep(json!({ "lambda": "next", "mu": "1.0.0", "nu": "1.0.0" })),
+        );
+        cmd_install(Vec::new(), install_options_copy())?;
+        Ok((first, offline, locked_version(&project_root)?))
+    })();
+    restore_env("PACM_REGISTRY", &prev_registry);
+
+    let (first, offline, moved) = result?;
+    assert_eq!(first.as_deref(), Some("2.0.0-beta.1"));
+    assert_eq!(offline, first);
+    assert_eq!(moved, first);
+    Ok(())
+}
+
+#[test]
+fn stale_state_file_triggers_reinstall() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "state-app",
+            "version": "0.1.0",
+            "dependencies": { "xi": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "xi",
+        "1.0.0",
+        json!({ "name": "xi", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 'xi';\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+
+    let state_path = project_root.join("node_modules").join(".pacm-state.json");
+    let state: Value = serde_json::from_str(&fs::read_to_string(&state_path)?)?;
+    let store_root = crate::fsutil::store_root(&crate::config::Config::load());
+    assert_eq!(state["storeRoot"].as_str(), Some(store_root.to_string_lossy().as_ref()));
+    assert_eq!(state["installMode"], "copy");
+    assert_eq!(state["pacmVersion"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(state["hoisted"], json!(["xi"]));
+
+    // Pretend the tree was linked from another store; the next install must relink it.
+    let mut stale = state.clone();
+    stale["storeRoot"] = json!(project_root.join("other-store"));
+    fs::write(&state_path, stale.to_string())?;
+    fs::remove_file(project_root.join("node_modules").join("xi").join("index.js"))?;
+
+    cmd_install(Vec::new(), install_options_copy())?;
+    let refreshed: Value = serde_json::from_str(&fs::read_to_string(&state_path)?)?;
+    assert_eq!(refreshed, state);
+    assert!(project_root.join("node_modules").join("xi").join("index.js").exists());
+    Ok(())
+}
+
+#[test]
+fn missing_transitive_package_triggers_reinstall() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "transitive-app",
+            "version": "0.1.0",
+            "dependencies": { "omicron": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "omicron",
+        "1.0.0",
+        json!({ "name": "omicron", "version": "1.0.0", "dependencies": { "pi": "1.0.0" } }),
+        &[("index.js", "module.exports = require('pi');\n")],
+    );
+    seed_cached_package(
+        "pi",
+        "1.0.0",
+        json!({ "name": "pi", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 3.14;\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    let transitive = project_root.join("node_modules").join(".pacm").join("pi");
+    assert!(transitive.join("index.js").exists());
+
+    fs::remove_dir_all(&transitive)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    assert!(transitive.join("index.js").exists(), "reinstall should restore the transitive dir");
+    Ok(())
+}
+
+#[test]
+fn corrupt_cached_manifest_forces_redownload() -> Result<()> {
+    use super::cache_integrity::build_tarball;
+    use super::fetch::{http_response, spawn_server};
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "corrupt-app",
+            "version": "0.1.0",
+            "dependencies": { "rho": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "rho",
+        "1.0.0",
+        json!({ "name": "rho", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 'rho';\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+
+    // Truncate the cached manifest and drop node_modules so the fast path is considered.
+    fs::write(cache_package_path("rho", "1.0.0").join("package.json"), "{\"name\": \"rh")?;
+    fs::remove_dir_all(project_root.join("node_modules"))?;
+
+    let tarball = build_tarball(&[
+        ("package/package.json", r#"{"name":"rho","version":"1.0.0"}"#),
+        ("package/index.js", "module.exports = 'fresh rho';\n"),
+    ]);
+    let (tarball_host, tarball_requests) =
+        spawn_server(vec![http_response("200 OK", "", &tarball)]);
+    let metadata = json!({
+        "name": "rho",
+        "dist-tags": { "latest": "1.0.0" },
+        "versions": {
+            "1.0.0": {
+                "name": "rho",
+                "version": "1.0.0",
+                "dist": { "tarball": format!("{tarball_host}/rho/-/rho-1.0.0.tgz") }
+            }
+        }
+    });
+    let (registry, metadata_requests) =
+        spawn_server(vec![http_response("200 OK", "", metadata.to_string().as_bytes())]);
+    let prev_registry = env::var_os("PACM_REGISTRY");
+    env::set_var("PACM_REGISTRY", &registry);
+    let result = cmd_install(Vec::new(), install_options_copy());
+    restore_env("PACM_REGISTRY", &prev_registry);
+    result?;
+
+    assert_eq!(metadata_requests.try_iter().count(), 1);
+    assert_eq!(tarball_requests.try_iter().count(), 1, "corrupt cache entry should be refetched");
+    let cached = fs::read_to_string(cache_package_path("rho", "1.0.0").join("package.json"))?;
+    assert!(serde_json::from_str::<Value>(&cached).is_ok());
+    let refetched = fs::read_to_string(cache_package_path("rho", "1.0.0").join("index.js"))?;
+    assert_eq!(refetched, "module.exports = 'fresh rho';\n");
+    assert!(project_root.join("node_modules").join("rho").join("index.js").exists());
+    Ok(())
+}
+
+#[test]
+fn force_install_replaces_corrupted_cache() -> Result<()> {
+    use super::cache_integrity::build_tarball;
+    use super::fetch::{http_response, spawn_server};
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "force-app",
+            "version": "0.1.0",
+            "dependencies": { "sigma": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "sigma",
+        "1.0.0",
+        json!({ "name": "sigma", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 'sigma';\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+
+    // A manifest that still parses keeps the cache entry looking valid to a normal install.
+    fs::write(cache_package_path("sigma", "1.0.0").join("index.js"), "corrupted")?;
+
+    let tarball = build_tarball(&[
+        ("package/package.json", r#"{"name":"sigma","version":"1.0.0"}"#),
+        ("package/index.js", "module.exports = 'fresh sigma';\n"),
+    ]);
+    let (tarball_host, tarball_requests) =
+        spawn_server(vec![http_response("200 OK", "", &tarball)]);
+    let metadata = json!({
+        "name": "sigma",
+        "dist-tags": { "latest": "1.0.0" },
+        "versions": {
+            "1.0.0": {
+                "name": "sigma",
+                "version": "1.0.0",
+                "dist": { "tarball": format!("{tarball_host}/sigma/-/sigma-1.0.0.tgz") }
+            }
+        }
+    });
+    let (registry, _metadata_requests) =
+        spawn_server(vec![http_response("200 OK", "", metadata.to_string().as_bytes())]);
+    let prev_registry = env::var_os("PACM_REGISTRY");
+    env::set_var("PACM_REGISTRY", &registry);
+    let result = cmd_install(Vec::new(), InstallOptions { force: true, ..install_options_copy() });
+    restore_env("PACM_REGISTRY", &prev_registry);
+    result?;
+
+    assert_eq!(tarball_requests.try_iter().count(), 1, "--force should redownload");
+    let cached = fs::read_to_string(cache_package_path("sigma", "1.0.0").join("index.js"))?;
+    assert_eq!(cached, "module.exports = 'fresh sigma';\n");
+    let installed = project_root.join("node_modules").join("sigma").join("index.js");
+    assert_eq!(fs::read_to_string(installed)?, "module.exports = 'fresh sigma';\n");
+    Ok(())
+}
+
+#[test]
+fn array_bin_field_creates_shim_per_basename() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "bin-array-app",
+            "version": "0.1.0",
+            "dependencies": { "sigma": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "sigma",
+        "1.0.0",
+        json!({
+            "name": "sigma",
+            "version": "1.0.0",
+            "bin": ["bin/sigma.js", "tools/sigma-lint", ".."]
+        }),
+        &[
+            ("bin/sigma.js", "#!/usr/bin/env node\nconsole.log('sigma');\n"),
+            ("tools/sigma-lint", "#!/usr/bin/env node\nconsole.log('lint');\n"),
+        ],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+
+    let bin_dir = project_root.join("node_modules").join(".bin");
+    for name in ["sigma.js", "sigma-lint"] {
+        #[cfg(windows)]
+        let shim = bin_dir.join(format!("{name}.exe"));
+        #[cfg(not(windows))]
+        let shim = bin_dir.join(name);
+        assert!(shim.exists(), "missing shim for {name}");
+    }
+    #[cfg(not(windows))]
+    assert_eq!(fs::read_dir(&bin_dir)?.count(), 2, "the '..' entry should be skipped");
+    Ok(())
+}
+
+#[test]
+fn installs_from_local_tarball_spec() -> Result<()> {
+    use super::cache_integrity::build_tarball;
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "vendored-app",
+            "version": "0.1.0",
+            "dependencies": { "tau": "file:./vendor/tau-1.0.0.tgz" }
+        }),
+    );
+    let vendor = project_root.join("vendor");
+    fs::create_dir_all(&vendor)?;
+    fs::write(
+        vendor.join("tau-1.0.0.tgz"),
+        build_tarball(&[
+            ("package/package.json", r#"{"name":"tau","version":"1.0.0"}"#),
+            ("package/index.js", "module.exports = 'tau';\n"),
+        ]),
+    )?;
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), InstallOptions { offline: true, ..install_options_copy() })?;
+
+    let installed = project_root.join("node_modules").join("tau").join("index.js");
+    assert_eq!(fs::read_to_string(installed)?, "module.exports = 'tau';\n");
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let entry = lock.packages.get("node_modules/tau").expect("tau locked");
+    assert_eq!(entry.resolved.as_deref(), Some("file:./vendor/tau-1.0.0.tgz"));
+    let version = entry.version.as_deref().unwrap_or_default();
+    assert!(version.starts_with("1.0.0+local."), "unexpected version {version}");
+    Ok(())
+}
+
+#[test]
+fn workspace_local_tarball_resolves_next_to_its_package_json() -> Result<()> {
+    use super::cache_integrity::build_tarball;
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "vendored-mono",
+            "version": "0.1.0",
+            "workspaces": ["packages/*"],
+            "dependencies": { "app": "workspace:*" }
+        }),
+    );
+    let app = project_root.join("packages").join("app");
+    write_project_manifest(
+        &app,
+        &json!({
+            "name": "app",
+            "version": "0.1.0",
+            "dependencies": { "upsilon": "file:./vendor/upsilon-1.0.0.tgz" }
+        }),
+    );
+    fs::create_dir_all(app.join("vendor"))?;
+    fs::write(
+        app.join("vendor").join("upsilon-1.0.0.tgz"),
+        build_tarball(&[
+            ("package/package.json", r#"{"name":"upsilon","version":"1.0.0"}"#),
+            ("package/index.js", "module.exports = 'upsilon';\n"),
+        ]),
+    )?;
+
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    install_project(&project_root, Vec::new(), options)?;
+
+    let installed = project_root.join("node_modules").join("upsilon").join("index.js");
+    assert_eq!(fs::read_to_string(installed)?, "module.exports = 'upsilon';\n");
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let entry = lock.packages.get("node_modules/upsilon").expect("upsilon locked");
+    assert_eq!(entry.resolved.as_deref(), Some("file:packages/app/vendor/upsilon-1.0.0.tgz"));
+    Ok(())
+}
+
+#[test]
+fn adding_a_tarball_spec_records_its_declared_name() -> Result<()> {
+    use super::cache_integrity::build_tarball;
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(&project_root, &json!({ "name": "named-app", "version": "0.1.0" }));
+    let vendor = project_root.join("vendor");
+    fs::create_dir_all(&vendor)?;
+    fs::write(
+        vendor.join("upsilon-js-1.0.0.tgz"),
+        build_tarball(&[
+            ("package/package.json", r#"{"name":"upsilon","version":"1.0.0"}"#),
+            ("package/index.js", "module.exports = 'upsilon';\n"),
+        ]),
+    )?;
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(
+        vec!["file:./vendor/upsilon-js-1.0.0.tgz".into()],
+        InstallOptions { offline: true, ..install_options_copy() },
+    )?;
+
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(project_root.join("package.json"))?)?;
+    assert_eq!(manifest["dependencies"]["upsilon"], "file:./vendor/upsilon-js-1.0.0.tgz");
+    assert!(manifest["dependencies"].get("upsilon-js").is_none());
+    let installed = project_root.join("node_modules").join("upsilon").join("index.js");
+    assert_eq!(fs::read_to_string(installed)?, "module.exports = 'upsilon';\n");
+    Ok(())
+}
+
+#[test]
+fn adding_existing_dev_dependency_keeps_its_section() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "section-app",
+            "version": "0.1.0",
+            "devDependencies": { "tooling": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "tooling",
+        "1.1.0",
+        json!({ "name": "tooling", "version": "1.1.0" }),
+        &[("index.js", "module.exports = 'tooling';\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(
+        vec!["tooling@1.1.0".to_string()],
+        InstallOptions { offline: true, ..install_options_copy() },
+    )?;
+
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(project_root.join("package.json"))?)?;
+    assert_eq!(manifest["devDependencies"]["tooling"], "1.1.0");
+    assert!(manifest.get("dependencies").and_then(|deps| deps.get("tooling")).is_none());
+    Ok(())
+}
+
+#[test]
+fn exact_prerelease_spec_installs_that_version() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(&project_root, &json!({ "name": "pre-app", "version": "0.1.0" }));
+    for version in ["1.2.2", "1.2.3-beta.3", "1.2.3-beta.4"] {
+        seed_cached_package(
+            "upsilon",
+            version,
+            json!({ "name": "upsilon", "version": version }),
+            &[("index.js", "module.exports = 'upsilon';\n")],
+        );
+    }
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(
+        vec!["upsilon@1.2.3-beta.4".to_string()],
+        InstallOptions { exact: true, offline: true, ..install_options_copy() },
+    )?;
+
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let entry = lock.packages.get("node_modules/upsilon").expect("upsilon locked");
+    assert_eq!(entry.version.as_deref(), Some("1.2.3-beta.4"));
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(project_root.join("package.json"))?)?;
+    assert_eq!(manifest["dependencies"]["upsilon"], "1.2.3-beta.4");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn internal_symlinks_survive_store_and_install() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "symlink-app",
+            "version": "0.1.0",
+            "dependencies": { "linky": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "linky",
+        "1.0.0",
+        json!({ "name": "linky", "version": "1.0.0" }),
+        &[("lib/index.js", "module.exports = 'linky';\n")],
+    );
+    std::os::unix::fs::symlink(
+        "index.js",
+        cache_package_path("linky", "1.0.0").join("lib/alias.js"),
+    )?;
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let store_key = lock
+        .packages
+        .get("node_modules/linky")
+        .and_then(|entry| entry.store_key.clone())
+        .expect("linky store key");
+    let stored =
+        crate::cache::CasStore::open()?.load_entry(&store_key)?.expect("linky store entry");
+    let installed = project_root.join("node_modules").join(".pacm").join("linky");
+    for root in [stored.package_dir().to_path_buf(), installed] {
+        let alias = root.join("lib").join("alias.js");
+        let meta = fs::symlink_metadata(&alias)?;
+        assert!(meta.file_type().is_symlink(), "{} is not a symlink", alias.display());
+        assert_eq!(fs::read_link(&alias)?, PathBuf::from("index.js"));
+        assert_eq!(fs::read_to_string(&alias)?, "module.exports = 'linky';\n");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_escaping_the_package_are_rejected() -> Result<()> {
+    use crate::fsutil::symlink_stays_within;
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "escape-app",
+            "version": "0.1.0",
+            "dependencies": { "sneaky": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "sneaky",
+        "1.0.0",
+        json!({ "name": "sneaky", "version": "1.0.0" }),
+        &[("lib/index.js", "module.exports = 'sneaky';\n")],
+    );
+    let pkg = cache_package_path("sneaky", "1.0.0");
+    std::os::unix::fs::symlink("../../etc", pkg.join("lib/etc"))?;
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let err = cmd_install(Vec::new(), install_options_copy()).expect_err("escaping link");
+    assert!(format!("{err:#}").contains("points outside its package"), "{err:#}");
+    assert!(!project_root.join("node_modules").join("sneaky").exists());
+
+    let link = pkg.join("lib/alias.js");
+    assert!(symlink_stays_within(&pkg, &link, Path::new("index.js")));
+    assert!(symlink_stays_within(&pkg, &link, Path::new("../package.json")));
+    assert!(!symlink_stays_within(&pkg, &link, Path::new("../../etc")));
+    assert!(!symlink_stays_within(&pkg, &link, Path::new("/etc/passwd")));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn chained_symlinks_escaping_the_package_are_rejected() -> Result<()> {
+    use crate::fsutil::symlink_stays_within;
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "chain-app",
+            "version": "0.1.0",
+            "dependencies": { "chained": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "chained",
+        "1.0.0",
+        json!({ "name": "chained", "version": "1.0.0" }),
+        &[("d/index.js", "module.exports = 'chained';\n")],
+    );
+    // Each link stays inside the package on paper; followed together they land above it.
+    let pkg = cache_package_path("chained", "1.0.0");
+    std::os::unix::fs::symlink("..", pkg.join("d/s"))?;
+    std::os::unix::fs::symlink("d/s/../..", pkg.join("t"))?;
+    assert!(symlink_stays_within(&pkg, &pkg.join("d/s"), Path::new("..")));
+    assert!(!symlink_stays_within(&pkg, &pkg.join("t"), Path::new("d/s/../..")));
+    assert!(symlink_stays_within(&pkg, &pkg.join("u"), Path::new("d/s/d/index.js")));
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let err = cmd_install(Vec::new(), install_options_copy()).expect_err("chained escape");
+    assert!(format!("{err:#}").contains("points outside its package"), "{err:#}");
+    assert!(!project_root.join("node_modules").join("chained").exists());
+    Ok(())
+}
+
+#[test]
+fn doctor_reports_store_and_node_modules_drift() -> Result<()> {
+    use crate::cli::commands::doctor::{run_checks, CheckStatus};
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "doctor-app",
+            "version": "0.1.0",
+            "dependencies": { "upsilon": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "upsilon",
+        "1.0.0",
+        json!({ "name": "upsilon", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 'upsilon';\n")],
+    );
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+
+    let status_of = |label: &str| {
+        run_checks(&project_root, &Config::load())
+            .into_iter()
+            .find(|c| c.label == label)
+            .map(|c| c.status)
+    };
+    assert_eq!(status_of("lockfile"), Some(CheckStatus::Pass));
+    assert_eq!(status_of("store entries"), Some(CheckStatus::Pass));
+    assert_eq!(status_of("node_modules"), Some(CheckStatus::Pass));
+
+    let installed = project_root.join("node_modules").join(".pacm").join("upsilon");
+    // Build metadata has no bearing on which version is installed.
+    fs::write(installed.join("package.json"), r#"{"name":"upsilon","version":"1.0.0+sha.1"}"#)?;
+    assert_eq!(status_of("node_modules"), Some(CheckStatus::Pass));
+    fs::write(installed.join("package.json"), r#"{"name":"upsilon","version":"0.9.0"}"#)?;
+    assert_eq!(status_of("node_modules"), Some(CheckStatus::Fail));
+
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let store_dir = lock.packages["node_modules/upsilon"].store_path.clone().expect("store path");
+    fs::remove_dir_all(store_dir)?;
+    assert_eq!(status_of("store entries"), Some(CheckStatus::Fail));
+    Ok(())
+}
+
+#[test]
+fn remove_with_gc_deletes_orphaned_store_entries() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "gc-app",
+            "version": "0.1.0",
+            "dependencies": { "keeper": "1.0.0", "dropped": "1.0.0" }
+        }),
+    );
+    for name in ["keeper", "dropped"] {
+        seed_cached_package(
+            name,
+            "1.0.0",
+            json!({ "name": name, "version": "1.0.0" }),
+            &[("index.js", "module.exports = 1;\n")],
+        );
+    }
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let store_path = |name: &str| {
+        PathBuf::from(
+            lock.packages[&format!("node_modules/{name}")].store_path.clone().expect("store path"),
+        )
+    };
+    let (keeper_entry, dropped_entry) = (store_path("keeper"), store_path("dropped"));
+    assert!(dropped_entry.exists());
+
+    cmd_remove(vec!["dropped".into()], false, true, &Config::load())?;
+    assert!(!dropped_entry.exists(), "--gc should delete the removed package's store entry");
+    assert!(keeper_entry.exists());
+    Ok(())
+}
+
+#[test]
+fn stale_lockfile_falls_back_to_resolution() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    let manifest_with = |range: &str| {
+        json!({
+            "name": "frozen-app",
+            "version": "0.1.0",
+            "dependencies": { "drift": range }
+        })
+    };
+    write_project_manifest(&project_root, &manifest_with("^1.0.0"));
+    for version in ["1.0.0", "2.0.0"] {
+        seed_cached_package(
+            "drift",
+            version,
+            json!({ "name": "drift", "version": version }),
+            &[("index.js", "module.exports = 1;\n")],
+        );
+    }
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let options = InstallOptions { prefer_offline: true, ..install_options_copy() };
+    cmd_install(Vec::new(), options.clone())?;
+    let locked = |root: &Path| -> Result<Option<String>> {
+        let lock = Lockfile::load_or_default(lockfile_path(root))?;
+        Ok(lock.packages.get("node_modules/drift").and_then(|e| e.version.clone()))
+    };
+    assert_eq!(locked(&project_root)?.as_deref(), Some("1.0.0"));
+
+    // The old lock entry is still fully cached, so only the staleness check keeps the fast path
+    // from reinstalling 1.0.0.
+    write_project_manifest(&project_root, &manifest_with("^2.0.0"));
+    cmd_install(Vec::new(), options)?;
+    assert_eq!(locked(&project_root)?.as_deref(), Some("2.0.0"));
+    let installed = fs::read_to_string(project_root.join("node_modules/drift/package.json"))?;
+    assert!(installed.contains("2.0.0"));
+    Ok(())
+}
+
+#[test]
+fn drifted_lock_versions_are_reresolved_even_when_root_matches() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    let manifest_with = |range: &str| {
+        json!({
+            "name": "drift-app",
+            "version": "0.1.0",
+            "dependencies": { "lagging": range }
+        })
+    };
+    write_project_manifest(&project_root, &manifest_with("^1.0.0"));
+    for version in ["1.0.0", "2.0.0"] {
+        seed_cached_package(
+            "lagging",
+            version,
+            json!({ "name": "lagging", "version": version }),
+            &[("index.js", "module.exports = 1;\n")],
+        );
+    }
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let options = InstallOptions { prefer_offline: true, ..install_options_copy() };
+    cmd_install(Vec::new(), options.clone())?;
+
+    // Simulate a merge that bumped the range in both package.json and the lock root while the
+    // package entry itself still pins 1.0.0.
+    let mut lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    lock.packages.get_mut("").unwrap().dependencies.insert("lagging".into(), "^2.0.0".into());
+    crate::lockfile::write(&lock, lockfile_path(&project_root))?;
+    write_project_manifest(&project_root, &manifest_with("^2.0.0"));
+
+    cmd_install(Vec::new(), options)?;
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let locked = lock.packages.get("node_modules/lagging").and_then(|e| e.version.as_deref());
+    assert_eq!(locked, Some("2.0.0"));
+    Ok(())
+}
+
+#[test]
+fn run_if_present_skips_missing_scripts() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({ "name": "ci-app", "version": "0.1.0", "scripts": { "build": "true" } }),
+    );
+    let _cwd = CwdGuard::change_to(&project_root)?;
+
+    cmd_run(vec!["pacm-missing-script".into()], true, false)?;
+    cmd_run(vec!["pacm-missing-script".into(), "--if-present".into()], false, false)?;
+    if cfg!(unix) {
+        // Without the flag the name falls through to the shell, which cannot find it.
+        assert!(cmd_run(vec!["pacm-missing-script".into()], false, false).is_err());
+    }
+    Ok(())
+}
+
+#[test]
+fn add_latest_upgrades_outdated_declared_range() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "latest-app",
+            "version": "0.1.0",
+            "dependencies": { "omicron": "^1.0.0" }
+        }),
+    );
+    // 3.0.0 is cached but not what the registry calls latest, so the cache must not win.
+    for version in ["1.0.0", "2.1.0", "3.0.0"] {
+        seed_cached_package(
+            "omicron",
+            version,
+            json!({ "name": "omicron", "version": version }),
+            &[("index.js", "module.exports = 'omicron';\n")],
+        );
+    }
+    // Served for both the `/omicron` packument and the `/omicron/latest` version document.
+    let metadata = json!({
+        "name": "omicron",
+        "version": "2.1.0",
+        "dist": { "tarball": "http://127.0.0.1:9/omicron-2.1.0.tgz" },
+        "dist-tags": { "latest": "2.1.0" },
+        "versions": {
+            "2.1.0": {
+                "name": "omicron",
+                "version": "2.1.0",
+                "dist": { "tarball": "http://127.0.0.1:9/omicron-2.1.0.tgz" }
+            }
+        }
+    });
+
+    let prev_registry = env::var_os("PACM_REGISTRY");
+    let result = (|| -> Result<()> {
+        let _cwd = CwdGuard::change_to(&project_root)?;
+        cmd_install(
+            vec!["omicron@1.0.0".to_string()],
+            InstallOptions { offline: true, ..install_options_copy() },
+        )?;
+        env::set_var(
+            "PACM_REGISTRY",
+            spawn_slow_registry(metadata.to_string(), std::time::Duration::ZERO),
+        );
+        cmd_install(
+            vec!["omicron".to_string()],
+            InstallOptions { latest: true, ..install_options_copy() },
+        )
+    })();
+    restore_env("PACM_REGISTRY", &prev_registry);
+    result?;
+
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(project_root.join("package.json"))?)?;
+    assert_eq!(manifest["dependencies"]["omicron"], "2.1.0");
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let locked = lock.packages.get("node_modules/omicron").and_then(|e| e.version.clone());
+    assert_eq!(locked.as_deref(), Some("2.1.0"));
+    Ok(())
+}
+
+#[test]
+fn invalid_package_names_fail_before_fetching() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(&project_root, &json!({ "name": "names-app", "version": "0.1.0" }));
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    for spec in ["Express", ".bin@1.0.0", "@scope@1.0.0"] {
+        let err = cmd_install(vec![spec.to_string()], install_options_copy())
+            .expect_err("invalid spec should fail");
+        assert!(err.to_string().contains("invalid package name"), "{spec}: {err:#}");
+    }
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(project_root.join("package.json"))?)?;
+    assert!(manifest.get("dependencies").is_none());
+
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "names-app",
+            "version": "0.1.0",
+            "dependencies": { "bad name": "^1.0.0" }
+        }),
+    );
+    let err = cmd_install(Vec::new(), install_options_copy()).expect_err("invalid manifest");
+    assert!(format!("{err:#}").contains("invalid package name 'bad name'"), "{err:#}");
+    Ok(())
+}
+
+#[test]
+fn no_bin_links_is_recorded_and_skips_shims() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "no-bin-app",
+            "version": "0.1.0",
+            "dependencies": { "tau": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "tau",
+        "1.0.0",
+        json!({ "name": "tau", "version": "1.0.0", "bin": { "tau": "cli.js" } }),
+        &[("cli.js", "#!/usr/bin/env node\nconsole.log('tau');\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let bin_dir = project_root.join("node_modules").join(".bin");
+    cmd_install(Vec::new(), install_options_copy())?;
+    assert!(bin_dir.exists(), "shims are created by default");
+
+    cmd_install(Vec::new(), InstallOptions { no_bin_links: true, ..install_options_copy() })?;
+    assert!(!bin_dir.exists());
+    assert!(project_root.join("node_modules").join("tau").join("cli.js").exists());
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(lock.settings.no_bin_links);
+
+    // A later fast-path install keeps honoring the recorded preference.
+    fs::remove_dir_all(project_root.join("node_modules"))?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    assert!(project_root.join("node_modules").join("tau").exists());
+    assert!(!bin_dir.exists());
+    Ok(())
+}
+
+#[cfg(windows)]
+#[test]
+fn windows_installs_write_cmd_and_ps1_shims() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "cmd-shim-app",
+            "version": "0.1.0",
+            "dependencies": { "upsilon": "1.0.0" }
+        }),
+    );
+    seed_cached_package(
+        "upsilon",
+        "1.0.0",
+        json!({ "name": "upsilon", "version": "1.0.0", "bin": { "upsilon": "bin/cli.js" } }),
+        &[("bin/cli.js", "#!/usr/bin/env node\nconsole.log('upsilon');\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+
+    let bin_dir = project_root.join("node_modules").join(".bin");
+    let cmd = fs::read_to_string(bin_dir.join("upsilon.cmd"))?;
+    assert!(cmd.contains(r"%dp0%\..\.pacm\upsilon\bin\cli.js"), "{cmd}");
+    let ps1 = fs::read_to_string(bin_dir.join("upsilon.ps1"))?;
+    assert!(ps1.contains("$basedir/../.pacm/upsilon/bin/cli.js"), "{ps1}");
+    assert!(bin_dir.join("upsilon.exe").exists());
+    Ok(())
+}
+
+#[test]
+fn windows_script_shims_run_node_on_the_relative_target() {
+    use crate::installer::{cmd_shim, ps1_shim, WindowsShimKinds};
+
+    let target = Path::new("../.pacm/@scope/tool/bin/cli.js");
+    let cmd = cmd_shim(target);
+    assert!(cmd.starts_with("@ECHO off\r\n"));
+    assert!(cmd.contains(r#""%_prog%"  "%dp0%\..\.pacm\@scope\tool\bin\cli.js" %*"#), "{cmd}");
+    let ps1 = ps1_shim(target);
+    assert!(ps1.contains(r#"& $node "$basedir/../.pacm/@scope/tool/bin/cli.js" $args"#), "{ps1}");
+
+    let all = WindowsShimKinds { exe: true, scripts: true };
+    assert_eq!(WindowsShimKinds::parse(None), all);
+    assert_eq!(WindowsShimKinds::parse(Some("bogus")), all);
+    assert_eq!(
+        WindowsShimKinds::parse(Some("EXE")),
+        WindowsShimKinds { exe: true, scripts: false }
+    );
+    assert_eq!(
+        WindowsShimKinds::parse(Some("scripts")),
+        WindowsShimKinds { exe: false, scripts: true }
+    );
+}
+
+#[test]
+fn lockfile_diff_reports_changes_an_install_would_make() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    let manifest =
+        |deps: Value| json!({ "name": "diff-app", "version": "0.1.0", "dependencies": deps });
+    write_project_manifest(&project_root, &manifest(json!({ "phi": "1.0.0", "chi": "1.0.0" })));
+    for (name, version) in [("phi", "1.0.0"), ("phi", "2.0.0"), ("chi", "1.0.0"), ("psi", "1.0.0")]
+    {
+        seed_cached_package(
+            name,
+            version,
+            json!({ "name": name, "version": version }),
+            &[("index.js", "module.exports = 1;\n")],
+        );
+    }
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), install_options_copy())?;
+    assert!(lockfile_diff(&project_root, &Config::load())?.is_empty());
+
+    write_project_manifest(&project_root, &manifest(json!({ "phi": "2.0.0", "psi": "1.0.0" })));
+    let before = fs::read(lockfile_path(&project_root))?;
+    let diff = lockfile_diff(&project_root, &Config::load())?;
+    assert_eq!(fs::read(lockfile_path(&project_root))?, before, "diff must not write");
+
+    let json = serde_json::to_value(&diff)?;
+    assert_eq!(json["added"], json!([{ "name": "psi", "version": "1.0.0" }]));
+    assert_eq!(json["removed"], json!([{ "name": "chi", "version": "1.0.0" }]));
+    assert_eq!(json["changed"], json!([{ "name": "phi", "from": "1.0.0", "to": "2.0.0" }]));
+    Ok(())
+}
+
+#[test]
+fn cancelled_install_leaves_project_untouched() -> Result<()> {
+    struct ResetCancel;
+    impl Drop for ResetCancel {
+        fn drop(&mut self) {
+            crate::cancel::reset();
+        }
+    }
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(&project_root, &json!({ "name": "cancel-app", "version": "1.0.0" }));
+    let original = fs::read_to_string(project_root.join("package.json"))?;
+    seed_cached_package(
+        "cancel-dep",
+        "1.0.0",
+        json!({ "name": "cancel-dep", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    {
+        let _reset = ResetCancel;
+        crate::cancel::cancel();
+        let options = InstallOptions { offline: true, ..install_options_copy() };
+        let err = cmd_install(vec!["cancel-dep@1.0.0".into()], options).unwrap_err();
+        assert!(err.is::<crate::cancel::Cancelled>(), "{err:#}");
+        assert_eq!(err.to_string(), "install cancelled; no changes written");
+    }
+    assert_eq!(fs::read_to_string(project_root.join("package.json"))?, original);
+    assert!(!project_root.join("pacm.lockb").exists(), "no lockfile after a cancel");
+    assert!(!project_root.join("node_modules").join("cancel-dep").exists());
+
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    cmd_install(vec!["cancel-dep@1.0.0".into()], options)?;
+    assert!(project_root.join("pacm.lockb").exists());
+    assert!(project_root.join("node_modules").join("cancel-dep").exists());
+    Ok(())
+}
+
+#[test]
+fn install_cancelled_after_linking_reports_the_partial_install() -> Result<()> {
+    use crate::cancel::Cancelled;
+
+    struct ResetCancel;
+    impl Drop for ResetCancel {
+        fn drop(&mut self) {
+            crate::cancel::cancel_at(None);
+            crate::cancel::reset();
+        }
+    }
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(&project_root, &json!({ "name": "cancel-late", "version": "1.0.0" }));
+    let original = fs::read_to_string(project_root.join("package.json"))?;
+    seed_cached_package(
+        "late-dep",
+        "1.0.0",
+        json!({ "name": "late-dep", "version": "1.0.0" }),
+        &[("index.js", "module.exports = 1;\n")],
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    {
+        let _reset = ResetCancel;
+        crate::cancel::cancel_at(Some(Cancelled::AfterLinking));
+        let options = InstallOptions { offline: true, ..install_options_copy() };
+        let err = cmd_install(vec!["late-dep@1.0.0".into()], options).unwrap_err();
+        assert_eq!(err.downcast_ref::<Cancelled>(), Some(&Cancelled::AfterLinking), "{err:#}");
+        assert!(err.to_string().contains("node_modules was updated"), "{err}");
+    }
+    assert!(project_root.join("node_modules").join("late-dep").exists(), "linking finished");
+    assert!(!project_root.join("pacm.lockb").exists(), "no lockfile after a cancel");
+    assert_eq!(fs::read_to_string(project_root.join("package.json"))?, original);
+
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    cmd_install(vec!["late-dep@1.0.0".into()], options)?;
+    assert!(project_root.join("pacm.lockb").exists());
+    Ok(())
+}
+
+#[test]
+fn add_with_tag_saves_the_tag_and_pins_the_version() -> Result<()> {
+    use crate::cli::commands::install::manifest_updates::tagged_spec;
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    write_project_manifest(&project_root, &json!({ "name": "beta-app", "version": "0.1.0" }));
+    for version in ["2.0.0-beta.1", "2.0.0-beta.2"] {
+        seed_cached_package("kappa", version, json!({ "name": "kappa", "version": version }), &[]);
+    }
+    // The fake registry answers every path alike, so the body doubles as the `kappa/beta`
+    // version document and the full package document.
+    let metadata = |beta: &str| {
+        json!({
+            "name": "kappa",
+            "version": beta,
+            "dist": { "tarball": "http://127.0.0.1:9/kappa.tgz" },
+            "dist-tags": { "latest": "1.0.0", "beta": beta },
+            "versions": {
+                beta: {
+                    "name": "kappa",
+                    "version": beta,
+                    "dist": { "tarball": "http://127.0.0.1:9/kappa.tgz" }
+                }
+            }
+        })
+        .to_string()
+    };
+
+    assert_eq!(tagged_spec("kappa", "beta")?, "kappa@beta");
+    assert!(tagged_spec("kappa@1.0.0", "beta").is_err());
+    assert!(tagged_spec("kappa", "^1.0.0").is_err());
+
+    let prev_registry = env::var_os("PACM_REGISTRY");
+    let result = (|| -> Result<(Value, Option<String>, Option<String>)> {
+        let _cwd = CwdGuard::change_to(&project_root)?;
+        let zero = std::time::Duration::ZERO;
+        env::set_var("PACM_REGISTRY", spawn_slow_registry(metadata("2.0.0-beta.1"), zero));
+        let options = InstallOptions { save_tag: true, ..install_options_copy() };
+        cmd_install(vec![tagged_spec("kappa", "beta")?], options)?;
+        let manifest: Value =
+            serde_json::from_str(&fs::read_to_string(project_root.join("package.json"))?)?;
+        let locked = |root: &Path| -> Result<Option<String>> {
+            let lock = Lockfile::load_or_default(lockfile_path(root))?;
+            Ok(lock.packages.get("node_modules/kappa").and_then(|e| e.version.clone()))
+        };
+        let first = locked(&project_root)?;
+
+        // The tag moves upstream; a plain install keeps the locked version.
+        env::set_var("PACM_REGISTRY", spawn_slow_registry(metadata("2.0.0-beta.2"), zero));
+        cmd_install(Vec::new(), install_options_copy())?;
+        Ok((manifest, first, locked(&project_root)?))
+    })();
+    restore_env("PACM_REGISTRY", &prev_registry);
+
+    let (manifest, first, after) = result?;
+    assert_eq!(manifest["dependencies"]["kappa"], "beta");
+    assert_eq!(first.as_deref(), Some("2.0.0-beta.1"));
+    assert_eq!(after, first);
+    Ok(())
+}
+
+#[test]
+fn catalog_dependencies_lock_the_catalog_range() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    let manifest = |legacy_range: &str| {
+        json!({
+            "name": "catalog-app",
+            "version": "0.1.0",
+            "catalog": { "sigma": "^1.0.0" },
+            "catalogs": { "legacy": { "tau": legacy_range } },
+            "dependencies": { "sigma": "catalog:", "tau": "catalog:legacy" }
+        })
+    };
+    write_project_manifest(&project_root, &manifest("~1.0.0"));
+    for (name, version) in
+        [("sigma", "1.0.0"), ("sigma", "1.3.0"), ("tau", "1.0.2"), ("tau", "1.1.0")]
+    {
+        seed_cached_package(name, version, json!({ "name": name, "version": version }), &[]);
+    }
+    let locked =
+        |root: &Path| -> Result<Lockfile> { Lockfile::load_or_default(lockfile_path(root)) };
+    let version_of = |lock: &Lockfile, name: &str| {
+        lock.packages.get(&format!("node_modules/{name}")).and_then(|e| e.version.clone())
+    };
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    cmd_install(Vec::new(), options.clone())?;
+    let lock = locked(&project_root)?;
+    assert_eq!(version_of(&lock, "sigma").as_deref(), Some("1.3.0"));
+    assert_eq!(version_of(&lock, "tau").as_deref(), Some("1.0.2"));
+    let root = lock.packages.get("").expect("root entry");
+    assert_eq!(root.dependencies["sigma"], "^1.0.0");
+    assert_eq!(root.dependencies["tau"], "~1.0.0");
+    let saved: Value =
+        serde_json::from_str(&fs::read_to_string(project_root.join("package.json"))?)?;
+    assert_eq!(saved["dependencies"]["tau"], "catalog:legacy", "package.json keeps catalog:");
+
+    // Bumping the catalog entry is enough to move the dependency.
+    write_project_manifest(&project_root, &manifest("^1.1.0"));
+    cmd_install(Vec::new(), options.clone())?;
+    assert_eq!(version_of(&locked(&project_root)?, "tau").as_deref(), Some("1.1.0"));
+
+    write_project_manifest(
+        &project_root,
+        &json!({ "name": "catalog-app", "version": "0.1.0", "dependencies": { "tau": "catalog:" } }),
+    );
+    let err = cmd_install(Vec::new(), options).unwrap_err();
+    assert!(format!("{err:#}").contains("no catalog named 'default'"), "{err:#}");
+    Ok(())
+}
+
+#[test]
+fn cache_gc_keeps_store_entries_other_projects_use() -> Result<()> {
+    use crate::cache::{project_key, CasStore};
+    use crate::cli::commands::install::collect_unreferenced_store_entries;
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    for name in ["shared-ref", "only-b-ref"] {
+        seed_cached_package(name, "1.0.0", json!({ "name": name, "version": "1.0.0" }), &[]);
+    }
+    let project_a = sandbox.temp.path().join("project-a");
+    let project_b = sandbox.temp.path().join("project-b");
+    write_project_manifest(
+        &project_a,
+        &json!({ "name": "a", "version": "1.0.0", "dependencies": { "shared-ref": "1.0.0" } }),
+    );
+    write_project_manifest(
+        &project_b,
+        &json!({
+            "name": "b",
+            "version": "1.0.0",
+            "dependencies": { "shared-ref": "1.0.0", "only-b-ref": "1.0.0" }
+        }),
+    );
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    install_project(&project_a, Vec::new(), options.clone())?;
+    install_project(&project_b, Vec::new(), options)?;
+
+    let store = CasStore::open()?;
+    let entry = |name: &str| -> Result<crate::cache::StoreEntry> {
+        Ok(store.list_entries()?.into_iter().find(|e| e.name == name).expect("store entry"))
+    };
+    let (a_key, b_key) = (project_key(&project_a), project_key(&project_b));
+    assert_eq!(entry("shared-ref")?.referencing_projects, {
+        let mut both = vec![a_key.clone(), b_key.clone()];
+        both.sort();
+        both
+    });
+
+    let summary = collect_unreferenced_store_entries(&store)?;
+    assert_eq!((summary.removed, summary.kept), (0, 2), "both projects still exist");
+
+    fs::remove_dir_all(&project_b)?;
+    let summary = collect_unreferenced_store_entries(&store)?;
+    assert_eq!((summary.removed, summary.kept), (1, 1));
+    assert!(store.list_entries()?.iter().all(|e| e.name != "only-b-ref"));
+    assert_eq!(entry("shared-ref")?.referencing_projects, vec![a_key]);
+    Ok(())
+}
+
+#[test]
+fn cache_gc_during_an_install_keeps_the_entries_it_is_about_to_link() -> Result<()> {
+    use crate::cache::{CasStore, EnsureParams};
+    use crate::cli::commands::install::collect_unreferenced_store_entries;
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    for name in ["first-ref", "added-ref", "legacy-ref"] {
+        seed_cached_package(name, "1.0.0", json!({ "name": name, "version": "1.0.0" }), &[]);
+    }
+    let fresh = sandbox.temp.path().join("fresh");
+    let existing = sandbox.temp.path().join("existing");
+    write_project_manifest(&fresh, &json!({ "name": "fresh", "version": "1.0.0" }));
+    write_project_manifest(
+        &existing,
+        &json!({ "name": "existing", "version": "1.0.0", "dependencies": { "first-ref": "1.0.0" } }),
+    );
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    install_project(&existing, Vec::new(), options)?;
+
+    // The store phase of an install has run but its lockfile is not written yet: `fresh` has
+    // none at all, and `existing` does not list the package it is adding.
+    let store = CasStore::open()?;
+    let ensure = |name: &str, project: Option<&Path>| {
+        let source_dir = cache_package_path(name, "1.0.0");
+        store.ensure_entry(&EnsureParams {
+            name,
+            version: "1.0.0",
+            dependencies: &[],
+            source_dir: &source_dir,
+            integrity: None,
+            resolved: None,
+            project,
+        })
+    };
+    let first = ensure("first-ref", Some(&fresh))?;
+    let added = ensure("added-ref", Some(&existing))?;
+    // An entry written before references were recorded.
+    let legacy = ensure("legacy-ref", None)?;
+    assert!(legacy.referencing_projects.is_empty());
+
+    let summary = collect_unreferenced_store_entries(&store)?;
+    assert_eq!((summary.removed, summary.kept), (0, 3));
+    for entry in [&first, &added, &legacy] {
+        assert!(store.load_entry(&entry.store_key)?.is_some(), "{} was collected", entry.name);
+    }
+    Ok(())
+}
+
+#[test]
+fn prune_store_removes_store_entries_of_pruned_packages() -> Result<()> {
+    use crate::cache::CasStore;
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    seed_cached_package(
+        "prune-parent",
+        "1.0.0",
+        json!({ "name": "prune-parent", "version": "1.0.0", "dependencies": { "prune-child": "1.0.0" } }),
+        &[],
+    );
+    seed_cached_package(
+        "prune-child",
+        "1.0.0",
+        json!({ "name": "prune-child", "version": "1.0.0" }),
+        &[],
+    );
+    write_project_manifest(
+        &project_root,
+        &json!({ "name": "app", "version": "1.0.0", "dependencies": { "prune-parent": "1.0.0" } }),
+    );
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    cmd_install(Vec::new(), InstallOptions { offline: true, ..install_options_copy() })?;
+
+    let store = CasStore::open()?;
+    let stored = |name: &str| -> Result<bool> {
+        Ok(store.list_entries()?.iter().any(|entry| entry.name == name))
+    };
+    assert!(stored("prune-child")?);
+
+    // The parent no longer needs its dependency, so the child becomes unreachable.
+    let mut lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    lock.packages.get_mut("node_modules/prune-parent").unwrap().dependencies.clear();
+    crate::lockfile::write(&lock, lockfile_path(&project_root))?;
+
+    cmd_pm_prune(true, &Config::load())?;
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(!lock.packages.contains_key("node_modules/prune-child"));
+    assert!(!project_root.join("node_modules/prune-child").exists());
+    assert!(!stored("prune-child")?, "pruned package should leave the store");
+    assert!(stored("prune-parent")?, "reachable package keeps its store entry");
+    Ok(())
+}
+
+#[test]
+fn install_filters_platform_packages_for_the_requested_target() -> Result<()> {
+    use crate::resolver::platform::TargetPlatform;
+
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    let host_os = host_node_platform();
+    let target_os = if host_os == "win32" { "linux" } else { "win32" };
+    for (name, os) in [("native-host", host_os), ("native-target", target_os)] {
+        seed_cached_package(
+            name,
+            "1.0.0",
+            json!({ "name": name, "version": "1.0.0", "os": [os] }),
+            &[("index.js", "module.exports = 1;\n")],
+        );
+    }
+    write_project_manifest(
+        &project_root,
+        &json!({
+            "name": "app",
+            "version": "1.0.0",
+            "optionalDependencies": { "native-host": "1.0.0", "native-target": "1.0.0" }
+        }),
+    );
+    let _cwd = CwdGuard::change_to(&project_root)?;
+
+    let platform = TargetPlatform::with_overrides(Some(target_os), None, None)?;
+    assert_eq!(platform.os, target_os);
+    cmd_install(Vec::new(), InstallOptions { offline: true, platform, ..install_options_copy() })?;
+    assert!(project_root.join("node_modules/native-target/index.js").exists());
+    assert!(!project_root.join("node_modules/native-host").exists());
+    assert!(!lockfile_path(&project_root).exists(), "the lockfile describes the host install");
+
+    cmd_install(Vec::new(), InstallOptions { offline: true, ..install_options_copy() })?;
+    assert!(project_root.join("node_modules/native-host/index.js").exists());
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    let entry = |name: &str| lock.packages.get(&format!("node_modules/{name}")).cloned();
+    assert!(entry("native-host").and_then(|e| e.store_key).is_some());
+    assert!(entry("native-target").and_then(|e| e.store_key).is_none());
+
+    assert!(TargetPlatform::with_overrides(Some("plan9"), None, None).is_err());
+    assert!(TargetPlatform::with_overrides(Some("darwin"), None, Some("musl")).is_err());
+    Ok(())
+}
+
+#[test]
+fn merge_lockfile_regenerates_a_conflicted_lockfile() -> Result<()> {
+    let _guard = match TEST_MUTEX.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let sandbox = EnvSandbox::new();
+    let project_root = sandbox.project_root();
+    seed_cached_package(
+        "merge-app-dep",
+        "1.0.0",
+        json!({ "name": "merge-app-dep", "version": "1.0.0", "dependencies": { "merge-leaf": "^1.0.0" } }),
+        &[],
+    );
+    seed_cached_package(
+        "merge-leaf",
+        "1.2.0",
+        json!({ "name": "merge-leaf", "version": "1.2.0" }),
+        &[],
+    );
+    write_project_manifest(
+        &project_root,
+        &json!({ "name": "app", "version": "1.0.0", "dependencies": { "merge-app-dep": "1.0.0" } }),
+    );
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    cmd_install(Vec::new(), options.clone())?;
+    let clean = Lockfile::load_or_default(lockfile_path(&project_root))?;
+
+    // What a botched merge leaves behind: both sides glued together with conflict markers.
+    let ours = fs::read(lockfile_path(&project_root))?;
+    let mut conflicted = b"<<<<<<< HEAD\n".to_vec();
+    conflicted.extend_from_slice(&ours);
+    conflicted.extend_from_slice(b"\n=======\n");
+    conflicted.extend_from_slice(&ours[..ours.len() / 2]);
+    conflicted.extend_from_slice(b"\n>>>>>>> feature\n");
+    fs::write(lockfile_path(&project_root), &conflicted)?;
+
+    let err = cmd_install(Vec::new(), options.clone()).unwrap_err();
+    assert!(format!("{err:#}").contains("--merge-lockfile"), "{err:#}");
+    assert_eq!(fs::read(lockfile_path(&project_root))?, conflicted);
+
+    cmd_install(Vec::new(), InstallOptions { merge_lockfile: true, ..options })?;
+    let regenerated = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(regenerated.same_content(&clean));
+    Ok(())
+}
@@ -15,7 +15,31 @@ fn lockfile_sync() {
     assert!(loaded.packages.contains_key("node_modules/foo"));
 }
 
-use crate::lockfile::{decode_current_binary, encode_current_binary, PackageEntry, PeerMeta};
+#[test]
+fn identical_write_leaves_the_file_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut manifest = Manifest::new("demo".into(), "0.1.0".into());
+    manifest.dependencies.insert("foo".into(), "^1.0.0".into());
+    let mut lock = Lockfile::default();
+    lock.sync_from_manifest(&manifest);
+    let lock_path = dir.path().join("pacm.lockb");
+    // Any rewrite stamps the current metadata, so an old stamp surviving proves it was skipped.
+    let stamp =
+        crate::lockfile::LockMetadata { pacm_version: Some("0.0.1".into()), written_at: Some(1) };
+    let stamped = Lockfile { metadata: stamp.clone(), ..lock.clone() };
+    let before = crate::lockfile::encode_current_binary(&stamped).unwrap();
+    std::fs::write(&lock_path, &before).unwrap();
+    write(&lock, lock_path.clone()).unwrap();
+    assert_eq!(std::fs::read(&lock_path).unwrap(), before);
+
+    lock.packages.remove("node_modules/foo");
+    write(&lock, lock_path.clone()).unwrap();
+    assert_ne!(load(&lock_path).unwrap().metadata, stamp, "a changed lockfile is rewritten");
+}
+
+use crate::lockfile::{
+    decode_current_binary, encode_current_binary, PackageEntry, PackageSource, PeerMeta,
+};
 use std::collections::BTreeMap;
 
 #[test]
@@ -36,10 +60,12 @@ fn encode_decode_roundtrip() {
         )]),
         os: vec![String::from("linux")],
         cpu_arch: vec![String::from("x64")],
+        libc: vec![String::from("glibc")],
         store_key: None,
         content_hash: None,
         link_mode: None,
         store_path: None,
+        source: Some(PackageSource::Registry),
     };
     lf.packages.insert(String::from(""), entry.clone());
     entry.version = Some("0.0.1".into());
@@ -51,3 +77,150 @@ fn encode_decode_roundtrip() {
     let decoded = decode_current_binary(&encoded).expect("decode");
     assert_eq!(lf, decoded);
 }
+
+#[test]
+fn settings_roundtrip_through_extras_section() {
+    let mut lf = Lockfile::default();
+    let plain = encode_current_binary(&lf).unwrap();
+    lf.settings.ignore_scripts = true;
+    lf.settings.no_bin_links = true;
+    let encoded = encode_current_binary(&lf).unwrap();
+    assert!(encoded.len() > plain.len());
+    let decoded = decode_current_binary(&encoded).unwrap();
+    assert!(decoded.settings.ignore_scripts);
+    assert!(decoded.settings.no_bin_links);
+    assert!(!decode_current_binary(&plain).unwrap().settings.ignore_scripts);
+}
+
+#[test]
+fn write_records_pacm_version_and_timestamp() {
+    let dir = tempfile::tempdir().unwrap();
+    let lock_path = dir.path().join("pacm.lockb");
+    write(&Lockfile::default(), lock_path.clone()).unwrap();
+    let loaded = load(&lock_path).unwrap();
+    assert_eq!(loaded.metadata.pacm_version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+    assert!(loaded.metadata.written_at.is_some_and(|t| t > 0));
+    assert!(loaded.same_content(&Lockfile::default()));
+
+    let json = serde_json::to_value(&loaded).unwrap();
+    assert_eq!(json["metadata"]["pacmVersion"], env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn older_wire_versions_still_decode() {
+    let encoded = encode_current_binary(&Lockfile::default()).unwrap();
+    for version in 1u16..=5 {
+        let mut old = encoded.clone();
+        old[8..10].copy_from_slice(&version.to_le_bytes());
+        let decoded = decode_current_binary(&old).expect("decode older wire version");
+        assert!(decoded.same_content(&Lockfile::default()));
+        assert!(decoded.metadata.is_empty());
+    }
+}
+
+/// Rebuild a single-entry lockfile in a pre-checksum layout: no flags, no digest, and no
+/// trailing source tag on the entry.
+fn older_wire_layout(encoded: &[u8], version: u16) -> Vec<u8> {
+    let packages_len = u32::from_le_bytes(encoded[48..52].try_into().unwrap()) as usize;
+    let mut old = encoded[..8].to_vec();
+    old.extend_from_slice(&version.to_le_bytes());
+    old.extend_from_slice(&0u16.to_le_bytes());
+    old.extend_from_slice(&encoded[12..16]);
+    old.extend_from_slice(&(packages_len as u32 - 1).to_le_bytes());
+    old.extend_from_slice(&encoded[52..52 + packages_len - 1]);
+    old.extend_from_slice(&encoded[52 + packages_len..]);
+    old
+}
+
+#[test]
+fn package_sources_roundtrip() {
+    let sources = [
+        PackageSource::Registry,
+        PackageSource::Github,
+        PackageSource::Git,
+        PackageSource::Tarball,
+        PackageSource::File,
+        PackageSource::Workspace,
+    ];
+    let mut lf = Lockfile::default();
+    for (i, source) in sources.into_iter().enumerate() {
+        let entry = PackageEntry {
+            version: Some("1.0.0".into()),
+            source: Some(source),
+            ..PackageEntry::default()
+        };
+        lf.packages.insert(format!("node_modules/pkg-{i}"), entry);
+    }
+    lf.packages.insert("node_modules/unknown".into(), PackageEntry::default());
+
+    let decoded = decode_current_binary(&encode_current_binary(&lf).unwrap()).unwrap();
+    assert_eq!(lf, decoded);
+    assert_eq!(decoded.packages["node_modules/pkg-1"].source, Some(PackageSource::Github));
+    assert_eq!(decoded.packages["node_modules/unknown"].source, None);
+
+    let json = serde_json::to_value(&decoded.packages["node_modules/pkg-5"]).unwrap();
+    assert_eq!(json["source"], "workspace");
+}
+
+#[test]
+fn sourceless_wire_version_5_infers_from_resolved() {
+    let mut lf = Lockfile::default();
+    let entry = PackageEntry {
+        version: Some("1.0.0".into()),
+        resolved: Some("https://codeload.github.com/acme/widget/tar.gz/abc123".into()),
+        ..PackageEntry::default()
+    };
+    lf.packages.insert("node_modules/widget".into(), entry);
+    let encoded = encode_current_binary(&lf).unwrap();
+
+    let decoded = decode_current_binary(&older_wire_layout(&encoded, 5)).unwrap();
+    let widget = &decoded.packages["node_modules/widget"];
+    assert_eq!(widget.source, None);
+    assert_eq!(widget.source_kind(), Some(PackageSource::Github));
+    assert_eq!(
+        PackageSource::infer("https://registry.npmjs.org/a/-/a-1.0.0.tgz"),
+        PackageSource::Registry
+    );
+    assert_eq!(PackageSource::infer("workspace:packages/a"), PackageSource::Workspace);
+    assert_eq!(PackageSource::infer("file:../a.tgz"), PackageSource::File);
+    assert_eq!(PackageSource::infer("https://example.com/a.tgz"), PackageSource::Tarball);
+}
+
+#[test]
+fn lockfile_without_checksum_still_decodes() {
+    let mut lf = Lockfile::default();
+    lf.sync_from_manifest(&Manifest::new("demo".into(), "0.1.0".into()));
+    let encoded = encode_current_binary(&lf).unwrap();
+    let old = older_wire_layout(&encoded, 4);
+    assert!(decode_current_binary(&old).unwrap().same_content(&lf));
+}
+
+#[test]
+fn tampered_packages_section_is_detected() {
+    use crate::lockfile::{load_strict, LockfileChecksumMismatch};
+
+    let mut manifest = Manifest::new("demo".into(), "0.1.0".into());
+    manifest.dependencies.insert("foo".into(), "^1.0.0".into());
+    let mut lf = Lockfile::default();
+    lf.sync_from_manifest(&manifest);
+    let encoded = encode_current_binary(&lf).unwrap();
+
+    // Flip a byte inside the "^1.0.0" range recorded for foo.
+    let mut tampered = encoded.clone();
+    let at = tampered.windows(6).position(|w| w == b"^1.0.0").unwrap();
+    tampered[at + 1] = b'2';
+    let err = decode_current_binary(&tampered).unwrap_err();
+    assert!(err.is::<LockfileChecksumMismatch>(), "{err:#}");
+
+    let dir = tempfile::tempdir().unwrap();
+    let lock_path = dir.path().join("pacm.lockb");
+    std::fs::write(&lock_path, &tampered).unwrap();
+    let loaded = load(&lock_path).expect("plain load only warns");
+    assert_eq!(loaded.packages[""].dependencies["foo"], "^2.0.0");
+    let err = load_strict(&lock_path).unwrap_err();
+    assert!(err.is::<LockfileChecksumMismatch>(), "{err:#}");
+
+    // Writing the same content again repairs the checksum.
+    write(&loaded, lock_path.clone()).unwrap();
+    assert!(load_strict(&lock_path).is_ok());
+}
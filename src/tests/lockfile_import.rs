@@ -0,0 +1,121 @@
+use crate::lockfile_import::{from_package_lock, from_pnpm_lock};
+
+#[test]
+fn imports_npm_package_lock_packages_map() {
+    let text = r#"{
+        "name": "demo",
+        "lockfileVersion": 3,
+        "packages": {
+            "": {
+                "name": "demo",
+                "version": "1.0.0",
+                "dependencies": { "left-pad": "^1.3.0" },
+                "devDependencies": { "tap": "^16.0.0" }
+            },
+            "node_modules/left-pad": {
+                "version": "1.3.0",
+                "resolved": "https://registry.npmjs.org/left-pad/-/left-pad-1.3.0.tgz",
+                "integrity": "sha512-left",
+                "dependencies": { "repeat": "^2.0.0" }
+            },
+            "node_modules/repeat": { "version": "2.1.0", "integrity": "sha512-repeat" },
+            "node_modules/tap": { "version": "16.3.0", "dev": true },
+            "node_modules/tap/node_modules/repeat": { "version": "1.0.0" },
+            "node_modules/local-ws": { "resolved": "packages/local-ws", "link": true }
+        }
+    }"#;
+    let imported = from_package_lock(text).unwrap();
+    let lock = imported.lockfile;
+    let root = &lock.packages[""];
+    assert_eq!(root.dependencies["left-pad"], "^1.3.0");
+    assert_eq!(root.dev_dependencies["tap"], "^16.0.0");
+    let left_pad = &lock.packages["node_modules/left-pad"];
+    assert_eq!(left_pad.version.as_deref(), Some("1.3.0"));
+    assert_eq!(left_pad.integrity.as_deref(), Some("sha512-left"));
+    assert_eq!(left_pad.dependencies["repeat"], "^2.0.0");
+    assert_eq!(lock.packages["node_modules/repeat"].version.as_deref(), Some("2.1.0"));
+    assert!(!lock.packages.contains_key("node_modules/local-ws"));
+    assert_eq!(imported.skipped, 2);
+
+    assert!(from_package_lock(r#"{"lockfileVersion": 1, "dependencies": {}}"#).is_err());
+}
+
+#[test]
+fn imports_pnpm_lock_importers_and_packages() {
+    let text = r#"
+lockfileVersion: '6.0'
+importers:
+  .:
+    dependencies:
+      react-dom:
+        specifier: ^18.2.0
+        version: 18.2.0(react@18.2.0)
+      react:
+        specifier: ^18.2.0
+        version: 18.2.0
+    devDependencies:
+      '@types/node':
+        specifier: ~20.0.0
+        version: 20.0.1
+packages:
+  /react@18.2.0:
+    resolution: {integrity: sha512-react}
+    dependencies:
+      loose-envify: 1.4.0
+  /react@17.0.2:
+    resolution: {integrity: sha512-old-react}
+  /react-dom@18.2.0(react@18.2.0):
+    resolution: {integrity: sha512-react-dom}
+    peerDependencies:
+      react: ^18.2.0
+    dependencies:
+      loose-envify: 1.4.0
+      react: 18.2.0
+  /loose-envify@1.4.0:
+    resolution: {integrity: sha512-loose}
+  /@types/node@20.0.1:
+    resolution: {integrity: sha512-types}
+"#;
+    let imported = from_pnpm_lock(text).unwrap();
+    let lock = imported.lockfile;
+    let root = &lock.packages[""];
+    assert_eq!(root.dependencies["react"], "^18.2.0");
+    assert_eq!(root.dev_dependencies["@types/node"], "~20.0.0");
+    let react = &lock.packages["node_modules/react"];
+    assert_eq!(react.version.as_deref(), Some("18.2.0"));
+    assert_eq!(react.integrity.as_deref(), Some("sha512-react"));
+    let react_dom = &lock.packages["node_modules/react-dom"];
+    assert_eq!(react_dom.dependencies["react"], "18.2.0");
+    assert_eq!(react_dom.peer_dependencies["react"], "^18.2.0");
+    assert_eq!(lock.packages["node_modules/@types/node"].version.as_deref(), Some("20.0.1"));
+    // The 17.x copy of react is dropped in favor of the version the root uses.
+    assert_eq!(imported.skipped, 1);
+}
+
+#[test]
+fn imports_pnpm_v9_snapshots() {
+    let text = r#"
+lockfileVersion: '9.0'
+importers:
+  .:
+    dependencies:
+      debug:
+        specifier: ^4.3.0
+        version: 4.3.4
+packages:
+  debug@4.3.4:
+    resolution: {integrity: sha512-debug}
+  ms@2.1.2:
+    resolution: {integrity: sha512-ms}
+snapshots:
+  debug@4.3.4:
+    dependencies:
+      ms: 2.1.2
+  ms@2.1.2: {}
+"#;
+    let lock = from_pnpm_lock(text).unwrap().lockfile;
+    let debug = &lock.packages["node_modules/debug"];
+    assert_eq!(debug.dependencies["ms"], "2.1.2");
+    assert_eq!(debug.integrity.as_deref(), Some("sha512-debug"));
+    assert!(lock.packages.contains_key("node_modules/ms"));
+}
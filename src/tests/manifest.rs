@@ -1,4 +1,4 @@
-use crate::manifest::{load, write, Manifest};
+use crate::manifest::{load, write, Manifest, PackageManagerStatus};
 
 #[test]
 fn manifest_roundtrip() {
@@ -11,3 +11,47 @@ fn manifest_roundtrip() {
     assert_eq!(read_back.name, "demo");
     assert_eq!(read_back.dependencies.get("lodash").unwrap(), "^4.17.0");
 }
+
+#[test]
+fn package_manager_status_compares_tool_and_version() {
+    let mut m = Manifest::new("demo".into(), "1.0.0".into());
+    assert_eq!(m.package_manager_status("1.2.3"), PackageManagerStatus::Unset);
+
+    m.package_manager = Some("pacm@1.2.3".into());
+    assert_eq!(m.package_manager_status("1.2.3"), PackageManagerStatus::Matches);
+    m.package_manager = Some("pacm@1.2.3+sha512.abcdef".into());
+    assert_eq!(m.package_manager_status("1.2.3"), PackageManagerStatus::Matches);
+
+    m.package_manager = Some("pacm@2.0.0".into());
+    assert_eq!(
+        m.package_manager_status("1.2.3"),
+        PackageManagerStatus::OtherVersion("2.0.0".into())
+    );
+
+    m.package_manager = Some("pnpm@9.1.0".into());
+    assert_eq!(m.package_manager_status("1.2.3"), PackageManagerStatus::OtherTool("pnpm".into()));
+}
+
+#[test]
+fn catalog_specs_resolve_against_default_and_named_catalogs() {
+    let mut root = Manifest::new("demo".into(), "1.0.0".into());
+    root.catalog.insert("react".into(), "^18.2.0".into());
+    root.catalogs.insert("legacy".into(), [("react".to_string(), "^16.14.0".to_string())].into());
+
+    assert_eq!(root.catalog_range("react", "catalog:").unwrap().as_deref(), Some("^18.2.0"));
+    assert_eq!(root.catalog_range("react", "catalog:default").unwrap().as_deref(), Some("^18.2.0"));
+    assert_eq!(root.catalog_range("react", "catalog:legacy").unwrap().as_deref(), Some("^16.14.0"));
+    assert_eq!(root.catalog_range("react", "^17.0.0").unwrap(), None);
+
+    let missing = root.catalog_range("vue", "catalog:").unwrap_err().to_string();
+    assert!(missing.contains("vue is not in the 'default' catalog"), "{missing}");
+    let unknown = root.catalog_range("react", "catalog:next").unwrap_err().to_string();
+    assert!(unknown.contains("no catalog named 'next'"), "{unknown}");
+
+    let mut workspace = Manifest::new("app".into(), "0.1.0".into());
+    workspace.dependencies.insert("react".into(), "catalog:legacy".into());
+    workspace.dev_dependencies.insert("react-dom".into(), "^16.0.0".into());
+    root.apply_catalogs(&mut workspace).unwrap();
+    assert_eq!(workspace.dependencies["react"], "^16.14.0");
+    assert_eq!(workspace.dev_dependencies["react-dom"], "^16.0.0");
+}
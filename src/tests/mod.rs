@@ -1,11 +1,24 @@
+pub mod audit;
+#[allow(clippy::needless_as_bytes)]
 pub mod cache_integrity;
 pub mod cas_store;
+pub mod cli;
+pub mod colors;
 pub mod common;
+pub mod config;
 pub mod fast_install;
+pub mod fetch;
+#[allow(clippy::unnecessary_get_then_check)]
 pub mod install_command;
+#[allow(clippy::field_reassign_with_default)]
 pub mod lockfile;
+pub mod lockfile_import;
 pub mod manifest;
 pub mod manifest_updates;
+pub mod prune;
 pub mod resolver;
 pub mod run;
+pub mod signatures;
+pub mod view;
+#[allow(clippy::unnecessary_get_then_check)]
 pub mod workspaces;
@@ -0,0 +1,56 @@
+use crate::cli::commands::install::prune_unreachable;
+use crate::lockfile::{Lockfile, PackageEntry};
+
+fn entry(resolved: Option<&str>, deps: &[&str], dev_deps: &[&str]) -> PackageEntry {
+    PackageEntry {
+        version: Some("1.0.0".into()),
+        resolved: resolved.map(str::to_string),
+        dependencies: deps.iter().map(|d| (d.to_string(), "^1.0.0".to_string())).collect(),
+        dev_dependencies: dev_deps.iter().map(|d| (d.to_string(), "^1.0.0".to_string())).collect(),
+        ..PackageEntry::default()
+    }
+}
+
+#[test]
+fn prune_keeps_workspace_dev_deps_but_not_published_ones() {
+    let mut lock = Lockfile::default();
+    lock.packages.insert("".into(), entry(None, &["ws-app", "left-pad"], &[]));
+    lock.packages.insert(
+        "node_modules/ws-app".into(),
+        entry(Some("workspace:packages/app"), &[], &["test-runner"]),
+    );
+    lock.packages.insert("node_modules/test-runner".into(), entry(None, &[], &[]));
+    lock.packages.insert(
+        "node_modules/left-pad".into(),
+        entry(Some("https://registry.example/left-pad.tgz"), &[], &["left-pad-bench"]),
+    );
+    lock.packages.insert("node_modules/left-pad-bench".into(), entry(None, &[], &[]));
+
+    let removed = prune_unreachable(&mut lock);
+
+    assert_eq!(removed, vec!["left-pad-bench".to_string()]);
+    assert!(lock.packages.contains_key("node_modules/test-runner"));
+    assert!(lock.packages.contains_key("node_modules/ws-app"));
+    assert!(lock.packages.contains_key("node_modules/left-pad"));
+}
+
+#[test]
+fn dev_only_dependencies_are_split_from_production_ones() {
+    use crate::cli::commands::install::{dev_only_packages, production_packages};
+    let mut lock = Lockfile::default();
+    lock.packages.insert("".into(), entry(None, &["app-lib"], &["test-runner"]));
+    lock.packages.insert("node_modules/app-lib".into(), entry(None, &["shared"], &[]));
+    lock.packages
+        .insert("node_modules/test-runner".into(), entry(None, &["shared", "assert"], &[]));
+    lock.packages.insert("node_modules/shared".into(), entry(None, &[], &[]));
+    lock.packages.insert("node_modules/assert".into(), entry(None, &[], &[]));
+
+    let production = production_packages(&lock);
+    let dev_only = dev_only_packages(&lock);
+
+    assert!(dev_only.contains("test-runner") && dev_only.contains("assert"));
+    assert!(!production.contains("test-runner") && !production.contains("assert"));
+    // Shared with a production dependency, so it is not dev-only.
+    assert!(production.contains("shared") && !dev_only.contains("shared"));
+    assert!(production.contains("app-lib"));
+}
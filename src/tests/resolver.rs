@@ -1,5 +1,12 @@
+use super::common::DataHomeGuard;
+use crate::cache::cache_package_path;
+use crate::fetch::Fetcher;
+use crate::lockfile::Lockfile;
+use crate::manifest::Manifest;
 use crate::resolver::canonicalize_npm_range;
+use crate::resolver::graph::{resolve_graph, ResolveOptions};
 use semver::VersionReq;
+use serde_json::json;
 
 #[test]
 fn test_basic_wildcards() {
@@ -35,3 +42,569 @@ fn canonicalize_leaves_single_comparator() {
     assert_eq!(out, "^2.0.0");
     assert!(VersionReq::parse(&out).is_ok());
 }
+
+#[test]
+fn resolve_graph_uses_cache_without_touching_project() {
+    let _guard = DataHomeGuard::new();
+    let seed = |name: &str, version: &str, manifest: serde_json::Value| {
+        let dir = cache_package_path(name, version);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("package.json"), manifest.to_string()).unwrap();
+    };
+    seed(
+        "alpha",
+        "1.2.0",
+        json!({"name": "alpha", "version": "1.2.0", "dependencies": {"beta": "^2.0.0"}}),
+    );
+    seed("beta", "2.0.1", json!({"name": "beta", "version": "2.0.1"}));
+
+    let mut manifest = Manifest::new("app".into(), "1.0.0".into());
+    manifest.dependencies.insert("alpha".into(), "^1.0.0".into());
+    let mut lock = Lockfile::default();
+    lock.sync_from_manifest(&manifest);
+
+    let fetcher = Fetcher::new(Some("http://127.0.0.1:9".into())).unwrap();
+    let options = ResolveOptions { prefer_offline: true, ..ResolveOptions::default() };
+    let graph = resolve_graph(&manifest, lock, &fetcher, &options).unwrap();
+
+    assert_eq!(graph.instances.get("alpha").map(|i| i.version.as_str()), Some("1.2.0"));
+    assert_eq!(graph.instances.get("beta").map(|i| i.version.as_str()), Some("2.0.1"));
+    assert_eq!(graph.downloaded, 0);
+    let beta = graph.lockfile.packages.get("node_modules/beta").expect("beta locked");
+    assert_eq!(beta.version.as_deref(), Some("2.0.1"));
+}
+
+#[test]
+fn git_urls_parse_to_git_specs() {
+    use crate::resolver::spec::{GitSpec, GithubSpec, PackageSpec};
+
+    assert_eq!(
+        PackageSpec::parse("git+https://gitlab.com/acme/widgets.git#v1.2.0"),
+        PackageSpec::Git(GitSpec {
+            url: "https://gitlab.com/acme/widgets.git".into(),
+            reference: Some("v1.2.0".into()),
+        })
+    );
+    assert_eq!(
+        PackageSpec::parse("git+ssh://git@example.com/acme/widgets.git"),
+        PackageSpec::Git(GitSpec {
+            url: "ssh://git@example.com/acme/widgets.git".into(),
+            reference: None
+        })
+    );
+    assert_eq!(
+        PackageSpec::parse("git+https://github.com/acme/widgets.git#main"),
+        PackageSpec::Github(GithubSpec {
+            owner: "acme".into(),
+            repo: "widgets".into(),
+            reference: Some("main".into()),
+        })
+    );
+    assert_eq!(
+        PackageSpec::parse("git+https://example.com/widgets-1.0.0.tgz"),
+        PackageSpec::Tarball { url: "https://example.com/widgets-1.0.0.tgz".into() }
+    );
+    assert_eq!(
+        crate::resolver::spec::parse_spec("git+ssh://git@example.com:acme/widgets.git#v2").0,
+        "widgets"
+    );
+}
+
+#[test]
+fn cli_git_specs_split_into_name_and_source() {
+    use crate::resolver::spec::{parse_spec as split, GithubSpec, PackageSpec};
+
+    assert_eq!(
+        split("github:acme/widgets-js#v1.2.0"),
+        ("widgets-js".into(), "github:acme/widgets-js#v1.2.0".into())
+    );
+    assert_eq!(split("acme/widgets"), ("widgets".into(), "acme/widgets".into()));
+    assert_eq!(
+        split("git+ssh://git@example.com/acme/widgets.git#main"),
+        ("widgets".into(), "git+ssh://git@example.com/acme/widgets.git#main".into())
+    );
+    assert_eq!(
+        split("https://github.com/acme/widgets.git#main"),
+        ("widgets".into(), "https://github.com/acme/widgets.git#main".into())
+    );
+    assert_eq!(
+        PackageSpec::parse("https://github.com/acme/widgets#main"),
+        PackageSpec::Github(GithubSpec {
+            owner: "acme".into(),
+            repo: "widgets".into(),
+            reference: Some("main".into()),
+        })
+    );
+    assert!(matches!(
+        PackageSpec::parse("https://github.com/acme/widgets/archive/v1.tar.gz"),
+        PackageSpec::Tarball { .. }
+    ));
+
+    // An explicit name wins over the guess, even when the source itself contains `@`.
+    assert_eq!(
+        split("widgets@github:acme/widgets-js#v1"),
+        ("widgets".into(), "github:acme/widgets-js#v1".into())
+    );
+    assert_eq!(
+        split("@acme/widgets@git+ssh://git@example.com/acme/widgets.git"),
+        ("@acme/widgets".into(), "git+ssh://git@example.com/acme/widgets.git".into())
+    );
+    assert_eq!(split("@acme/widgets@^2"), ("@acme/widgets".into(), "^2".into()));
+}
+
+#[test]
+fn file_tarball_paths_parse_to_local_tarballs() {
+    use crate::resolver::spec::{parse_spec, PackageSpec};
+
+    assert_eq!(
+        PackageSpec::parse("file:./vendor/widgets-1.0.0.tgz"),
+        PackageSpec::LocalTarball { path: "./vendor/widgets-1.0.0.tgz".into() }
+    );
+    assert_eq!(parse_spec("file:../pkgs/widgets.tar.gz").0, "widgets");
+}
+
+#[test]
+fn workspace_file_tarballs_are_rebased_onto_the_project_root() {
+    use crate::resolver::spec::rebase_local_range;
+
+    let rebase = |range: &str| rebase_local_range(range, "packages/app");
+    assert_eq!(rebase("file:./vendor/w.tgz"), "file:packages/app/vendor/w.tgz");
+    assert_eq!(rebase("file:../shared/w.tgz"), "file:packages/app/../shared/w.tgz");
+    assert_eq!(rebase("file:/opt/w.tgz"), "file:/opt/w.tgz");
+    assert_eq!(rebase("^1.0.0"), "^1.0.0");
+    assert_eq!(rebase_local_range("file:./w.tgz", "."), "file:./w.tgz");
+}
+
+#[test]
+fn invalid_package_names_are_rejected() {
+    use crate::resolver::spec::{validate_dependency_name, validate_package_name};
+
+    for valid in ["react", "@types/node", "lodash.merge", "a-b_c", "x1"] {
+        assert!(validate_package_name(valid).is_ok(), "{valid} should be valid");
+    }
+    let long = "a".repeat(215);
+    for invalid in [
+        "",
+        "React",
+        "has space",
+        ".hidden",
+        "_private",
+        "@scope",
+        "@/pkg",
+        "a/b",
+        "caf\u{e9}",
+        &long,
+    ] {
+        let err = validate_package_name(invalid).expect_err(invalid);
+        assert!(err.to_string().contains("invalid package name"), "{err}");
+    }
+    // Mixed-case names still exist on the registry, so manifests may keep depending on them.
+    assert!(validate_dependency_name("JSONStream").is_ok());
+    assert!(validate_dependency_name(" JSONStream").is_err());
+}
+
+#[test]
+fn git_archive_checkout_packs_requested_ref() {
+    use crate::resolver::git::archive_checkout;
+    use crate::resolver::spec::GitSpec;
+    use std::process::Command;
+
+    let repo = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=pacm", "-c", "user.email=pacm@example.com"])
+            .args(args)
+            .current_dir(repo.path())
+            .output()
+            .expect("run git");
+        assert!(status.status.success(), "git {args:?} failed");
+        String::from_utf8_lossy(&status.stdout).trim().to_string()
+    };
+    git(&["init", "--quiet"]);
+    std::fs::write(repo.path().join("package.json"), r#"{"name":"widgets","version":"1.0.0"}"#)
+        .unwrap();
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "v1"]);
+    git(&["tag", "v1"]);
+    let v1 = git(&["rev-parse", "HEAD"]);
+    std::fs::write(repo.path().join("package.json"), r#"{"name":"widgets","version":"2.0.0"}"#)
+        .unwrap();
+    git(&["commit", "--quiet", "-am", "v2"]);
+
+    let spec =
+        GitSpec { url: repo.path().to_string_lossy().into_owned(), reference: Some("v1".into()) };
+    let archive = archive_checkout(&spec).expect("archive checkout");
+    assert_eq!(archive.commit, v1);
+
+    let extracted = tempfile::tempdir().unwrap();
+    crate::cache::extract_tarball_to(&archive.bytes, extracted.path(), None).unwrap();
+    let manifest = std::fs::read_to_string(extracted.path().join("package.json")).unwrap();
+    assert!(manifest.contains("1.0.0"));
+}
+
+#[test]
+fn git_refs_and_urls_are_not_passed_as_options() {
+    use crate::resolver::git::archive_checkout;
+    use crate::resolver::spec::GitSpec;
+
+    let spec = GitSpec {
+        url: "https://example.invalid/widgets.git".into(),
+        reference: Some("--upload-pack=touch pwned".into()),
+    };
+    let err = archive_checkout(&spec).unwrap_err().to_string();
+    assert!(err.contains("invalid git reference"), "{err}");
+
+    let spec = GitSpec { url: "--upload-pack=touch pwned".into(), reference: None };
+    let err = archive_checkout(&spec).unwrap_err().to_string();
+    assert!(err.contains("git failed to clone"), "{err}");
+}
+
+#[test]
+fn phase_timings_list_slow_packages_by_level() {
+    use crate::logging::{PhaseTimings, SLOW_RESOLVE_THRESHOLD};
+    use std::time::Duration;
+
+    let mut timings = PhaseTimings::new();
+    timings.record("resolve", Duration::from_millis(40));
+    timings.record("link", Duration::from_millis(5));
+    timings.record_packages([
+        ("fast@^1".to_string(), Duration::from_millis(1)),
+        ("slow@^2".to_string(), SLOW_RESOLVE_THRESHOLD * 3),
+    ]);
+
+    assert!(timings.lines(0).is_empty());
+    let summary = timings.lines(1);
+    assert_eq!(summary.len(), 3, "{summary:?}");
+    assert!(summary[0].starts_with("resolve"));
+    assert!(summary[2].contains("slow@^2"));
+    let detailed = timings.lines(2);
+    assert_eq!(detailed.len(), 4);
+    assert!(detailed[3].contains("fast@^1"), "{detailed:?}");
+}
+
+#[test]
+fn libc_restrictions_follow_host_libc() {
+    use crate::resolver::platform::libc_supported;
+    let glibc_only = vec!["glibc".to_string()];
+    assert!(!libc_supported(&glibc_only, Some("musl")));
+    assert!(libc_supported(&glibc_only, Some("glibc")));
+    assert!(!libc_supported(&["!musl".to_string()], Some("musl")));
+    assert!(libc_supported(&[], Some("musl")));
+    // Hosts without a libc (macOS, Windows) ignore the field, as npm does.
+    assert!(libc_supported(&glibc_only, None));
+}
+
+#[test]
+fn platform_lists_follow_npm_negation_rules() {
+    use crate::resolver::platform::list_allows;
+    let list = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    // Empty lists and `["any"]` allow every host.
+    assert!(list_allows(&[], "linux"));
+    assert!(list_allows(&list(&["any"]), "win32"));
+    // Negations alone block only what they name.
+    assert!(!list_allows(&list(&["!win32"]), "win32"));
+    assert!(list_allows(&list(&["!win32"]), "linux"));
+    assert!(!list_allows(&list(&["!win32", "!darwin"]), "darwin"));
+    assert!(list_allows(&list(&["!win32", "!darwin"]), "freebsd"));
+    // Any positive entry turns the list into an allow-list.
+    assert!(list_allows(&list(&["linux", "darwin"]), "darwin"));
+    assert!(!list_allows(&list(&["linux", "darwin"]), "win32"));
+    // Mixed lists: the host must be listed, and a negation always wins.
+    assert!(!list_allows(&list(&["linux", "!linux"]), "linux"));
+    assert!(!list_allows(&list(&["!linux", "linux"]), "linux"));
+    assert!(!list_allows(&list(&["linux", "!win32"]), "darwin"));
+    assert!(list_allows(&list(&["linux", "!win32"]), "linux"));
+    assert!(list_allows(&list(&["x64", "arm64", "!ia32"]), "arm64"));
+}
+
+#[test]
+fn exact_prerelease_range_picks_that_prerelease() {
+    let resolver = crate::resolver::Resolver::new();
+    let versions: std::collections::BTreeMap<semver::Version, String> =
+        ["1.2.2", "1.2.3-beta.3", "1.2.3-beta.4", "1.2.3"]
+            .into_iter()
+            .map(|v| (semver::Version::parse(v).unwrap(), String::new()))
+            .collect();
+    let (picked, _) = resolver.pick_version(&versions, "1.2.3-beta.4").unwrap();
+    assert_eq!(picked.to_string(), "1.2.3-beta.4");
+}
+
+#[test]
+fn build_metadata_ties_pick_the_same_version_every_time() {
+    let resolver = crate::resolver::Resolver::new();
+    let pick = |builds: &[&str]| {
+        let versions: std::collections::BTreeMap<semver::Version, String> =
+            builds.iter().map(|v| (semver::Version::parse(v).unwrap(), v.to_string())).collect();
+        let (picked, tarball) = resolver.pick_version(&versions, "^1.0.0").unwrap();
+        assert_eq!(picked.to_string(), tarball);
+        tarball
+    };
+
+    assert_eq!(pick(&["0.9.0", "1.0.0+git.abc", "1.0.0+git.def"]), "1.0.0+git.def");
+    assert_eq!(pick(&["1.0.0+git.def", "1.0.0+git.abc", "0.9.0"]), "1.0.0+git.def");
+    // semver orders numeric build identifiers numerically (10 > 9); the tie-break compares the
+    // full strings instead, so the result does not depend on the map's own ordering.
+    assert_eq!(pick(&["1.0.0+git.10", "1.0.0+git.9"]), "1.0.0+git.9");
+}
+
+#[test]
+fn deprecated_versions_are_reported() {
+    use super::cache_integrity::build_tarball;
+    use super::fetch::{http_response, spawn_server};
+    let _guard = DataHomeGuard::new();
+    let tarball =
+        build_tarball(&[("package/package.json", r#"{"name":"abandoned-pkg","version":"0.3.0"}"#)]);
+    let (tarball_host, _) = spawn_server(vec![http_response("200 OK", "", &tarball)]);
+    let metadata = json!({
+        "name": "abandoned-pkg",
+        "dist-tags": { "latest": "0.3.0" },
+        "versions": {
+            "0.2.0": {
+                "name": "abandoned-pkg",
+                "version": "0.2.0",
+                "deprecated": false,
+                "dist": { "tarball": format!("{tarball_host}/abandoned-pkg-0.2.0.tgz") }
+            },
+            "0.3.0": {
+                "name": "abandoned-pkg",
+                "version": "0.3.0",
+                "deprecated": "no longer maintained, use fresh-pkg",
+                "dist": { "tarball": format!("{tarball_host}/abandoned-pkg-0.3.0.tgz") }
+            }
+        }
+    });
+    let body = metadata.to_string();
+    let (registry, _) = spawn_server(vec![
+        http_response("200 OK", "", body.as_bytes()),
+        http_response("200 OK", "", body.as_bytes()),
+    ]);
+
+    let mut manifest = Manifest::new("app".into(), "1.0.0".into());
+    manifest.dependencies.insert("abandoned-pkg".into(), "^0.3.0".into());
+    let mut lock = Lockfile::default();
+    lock.sync_from_manifest(&manifest);
+    let fetcher = Fetcher::new(Some(registry)).unwrap();
+    let graph = resolve_graph(&manifest, lock, &fetcher, &ResolveOptions::default()).unwrap();
+
+    assert_eq!(
+        graph.deprecated.get("abandoned-pkg@0.3.0").map(String::as_str),
+        Some("no longer maintained, use fresh-pkg")
+    );
+    assert_eq!(graph.deprecated.len(), 1);
+}
+
+#[test]
+fn large_unpacked_size_warns_before_download() {
+    use super::cache_integrity::build_tarball;
+    use super::fetch::{http_response, spawn_server};
+    use std::sync::Mutex;
+    let _guard = DataHomeGuard::new();
+    let tarball =
+        build_tarball(&[("package/package.json", r#"{"name":"huge-pkg","version":"1.0.0"}"#)]);
+    let (tarball_host, _) = spawn_server(vec![http_response("200 OK", "", &tarball)]);
+    let metadata = json!({
+        "name": "huge-pkg",
+        "versions": {
+            "1.0.0": {
+                "name": "huge-pkg",
+                "version": "1.0.0",
+                "dist": {
+                    "tarball": format!("{tarball_host}/huge-pkg-1.0.0.tgz"),
+                    "unpackedSize": 350 * 1024 * 1024_u64,
+                    "fileCount": 12
+                }
+            }
+        }
+    });
+    let body = metadata.to_string();
+    let (registry, _) = spawn_server(vec![
+        http_response("200 OK", "", body.as_bytes()),
+        http_response("200 OK", "", body.as_bytes()),
+    ]);
+
+    let mut manifest = Manifest::new("app".into(), "1.0.0".into());
+    manifest.dependencies.insert("huge-pkg".into(), "1.0.0".into());
+    let mut lock = Lockfile::default();
+    lock.sync_from_manifest(&manifest);
+    let fetcher = Fetcher::new(Some(registry)).unwrap();
+    let events: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+    let on_status = |kind: &str, detail: &str| {
+        events.lock().unwrap().push((kind.to_string(), detail.to_string()));
+    };
+    let options = ResolveOptions { on_status: Some(&on_status), ..ResolveOptions::default() };
+    resolve_graph(&manifest, lock, &fetcher, &options).unwrap();
+
+    let events = events.into_inner().unwrap();
+    let warning = events.iter().position(|(kind, _)| kind == "warning").expect("size warning");
+    assert!(events[warning].1.contains("huge-pkg@1.0.0 is large"), "{:?}", events[warning]);
+    assert!(events[warning].1.contains("350.0 MiB"), "{:?}", events[warning]);
+    let download = events.iter().position(|(kind, _)| kind == "downloading").expect("download");
+    assert!(warning < download, "warning should come before the download: {events:?}");
+}
+
+#[test]
+fn invalid_size_warn_threshold_is_rejected() {
+    let _guard = DataHomeGuard::new();
+    let manifest = Manifest::new("app".into(), "1.0.0".into());
+    let fetcher = Fetcher::new(Some("http://127.0.0.1:9".into())).unwrap();
+    std::env::set_var("PACM_SIZE_WARN", "lots");
+    let result =
+        resolve_graph(&manifest, Lockfile::default(), &fetcher, &ResolveOptions::default());
+    std::env::remove_var("PACM_SIZE_WARN");
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("invalid PACM_SIZE_WARN 'lots'"), "{err}");
+}
+
+#[test]
+fn prefer_dedupe_picks_a_version_every_range_accepts() {
+    let _guard = DataHomeGuard::new();
+    let seed = |name: &str, version: &str, manifest: serde_json::Value| {
+        let dir = cache_package_path(name, version);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("package.json"), manifest.to_string()).unwrap();
+    };
+    seed(
+        "dedupe-consumer",
+        "1.0.0",
+        json!({"name": "dedupe-consumer", "version": "1.0.0",
+               "dependencies": {"dedupe-shared": "1.1.0 || 1.2.0"}}),
+    );
+    for version in ["1.1.0", "1.1.5", "1.2.0"] {
+        seed("dedupe-shared", version, json!({"name": "dedupe-shared", "version": version}));
+    }
+
+    let mut manifest = Manifest::new("app".into(), "1.0.0".into());
+    manifest.dependencies.insert("dedupe-consumer".into(), "1.0.0".into());
+    manifest.dependencies.insert("dedupe-shared".into(), "~1.1.0".into());
+    let fetcher = Fetcher::new(Some("http://127.0.0.1:9".into())).unwrap();
+    let resolve = |prefer_dedupe: bool| {
+        let mut lock = Lockfile::default();
+        lock.sync_from_manifest(&manifest);
+        let options =
+            ResolveOptions { prefer_offline: true, prefer_dedupe, ..ResolveOptions::default() };
+        let graph = resolve_graph(&manifest, lock, &fetcher, &options).unwrap();
+        graph.instances.get("dedupe-shared").map(|i| i.version.clone())
+    };
+
+    // By default the transitive range takes its own newest match, which ~1.1.0 rejects.
+    assert_eq!(resolve(false).as_deref(), Some("1.2.0"));
+    // With --prefer-dedupe a single version satisfies both dependents.
+    assert_eq!(resolve(true).as_deref(), Some("1.1.0"));
+}
+
+#[test]
+fn runaway_graphs_hit_the_package_and_queue_caps() {
+    let _guard = DataHomeGuard::new();
+    let seed = |name: &str, deps: serde_json::Value| {
+        let dir = cache_package_path(name, "1.0.0");
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = json!({"name": name, "version": "1.0.0", "dependencies": deps});
+        std::fs::write(dir.join("package.json"), manifest.to_string()).unwrap();
+    };
+    // A ring of packages that depend on each other, and a hub with a wide fan-out.
+    let ring: Vec<String> = (0..6).map(|i| format!("ring-{i}")).collect();
+    for (i, name) in ring.iter().enumerate() {
+        seed(name, json!({ ring[(i + 1) % ring.len()].clone(): "1.0.0" }));
+    }
+    let leaves: serde_json::Map<String, serde_json::Value> =
+        (0..30).map(|i| (format!("leaf-{i}"), json!("1.0.0"))).collect();
+    seed("hub", serde_json::Value::Object(leaves));
+
+    let fetcher = Fetcher::new(Some("http://127.0.0.1:9".into())).unwrap();
+    let resolve = |root: &str, cap: &str| {
+        let mut manifest = Manifest::new("app".into(), "1.0.0".into());
+        manifest.dependencies.insert(root.into(), "1.0.0".into());
+        let mut lock = Lockfile::default();
+        lock.sync_from_manifest(&manifest);
+        std::env::set_var("PACM_MAX_PACKAGES", cap);
+        let options = ResolveOptions { prefer_offline: true, ..ResolveOptions::default() };
+        let result = resolve_graph(&manifest, lock, &fetcher, &options);
+        std::env::remove_var("PACM_MAX_PACKAGES");
+        result
+    };
+
+    let err = resolve("ring-0", "4").unwrap_err().to_string();
+    assert!(err.contains("more than 4 packages"), "{err}");
+    let err = resolve("hub", "2").unwrap_err().to_string();
+    assert!(err.contains("queue grew past 20"), "{err}");
+    // The whole ring resolves once the cap allows it; 0 disables the cap.
+    assert_eq!(resolve("ring-0", "6").unwrap().instances.len(), 6);
+    assert_eq!(resolve("ring-0", "0").unwrap().instances.len(), 6);
+}
+
+#[test]
+fn legacy_peer_deps_leaves_peers_to_the_project() {
+    let _guard = DataHomeGuard::new();
+    for (name, manifest) in [
+        (
+            "peer-plugin",
+            json!({"name": "peer-plugin", "version": "1.0.0", "peerDependencies": {"peer-host": "^1.0.0"}}),
+        ),
+        ("peer-host", json!({"name": "peer-host", "version": "1.0.0"})),
+    ] {
+        let dir = cache_package_path(name, "1.0.0");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("package.json"), manifest.to_string()).unwrap();
+    }
+    let mut manifest = Manifest::new("app".into(), "1.0.0".into());
+    manifest.dependencies.insert("peer-plugin".into(), "1.0.0".into());
+    let fetcher = Fetcher::new(Some("http://127.0.0.1:9".into())).unwrap();
+    let resolve = |legacy_peer_deps: bool| {
+        let mut lock = Lockfile::default();
+        lock.sync_from_manifest(&manifest);
+        let options =
+            ResolveOptions { prefer_offline: true, legacy_peer_deps, ..ResolveOptions::default() };
+        let graph = resolve_graph(&manifest, lock, &fetcher, &options).unwrap();
+        graph.instances.keys().cloned().collect::<Vec<_>>()
+    };
+
+    assert_eq!(resolve(false), ["peer-host", "peer-plugin"]);
+    assert_eq!(resolve(true), ["peer-plugin"]);
+}
+
+#[test]
+fn no_verify_accepts_a_mismatched_integrity() {
+    use super::cache_integrity::build_tarball;
+    use super::fetch::{http_response, spawn_server};
+    use crate::lockfile::PackageEntry;
+
+    let _guard = DataHomeGuard::new();
+    let tgz =
+        build_tarball(&[("package/package.json", r#"{"name":"tampered","version":"1.0.0"}"#)]);
+    // A mismatch costs the download plus its one retry; the --no-verify pass downloads once.
+    let (registry, _requests) =
+        spawn_server((0..3).map(|_| http_response("200 OK", "", &tgz)).collect());
+    let fetcher = Fetcher::new(Some(registry.clone())).unwrap();
+    let mut manifest = Manifest::new("app".into(), "1.0.0".into());
+    manifest.dependencies.insert("tampered".into(), "1.0.0".into());
+    let bogus = format!("sha512-{}", "A".repeat(86) + "==");
+    let resolve = |no_verify: bool| {
+        let mut lock = Lockfile::default();
+        lock.sync_from_manifest(&manifest);
+        lock.packages.insert(
+            "node_modules/tampered".into(),
+            PackageEntry {
+                version: Some("1.0.0".into()),
+                integrity: Some(bogus.clone()),
+                resolved: Some(format!("{registry}/tampered/-/tampered-1.0.0.tgz")),
+                ..PackageEntry::default()
+            },
+        );
+        resolve_graph(
+            &manifest,
+            lock,
+            &fetcher,
+            &ResolveOptions { no_verify, ..Default::default() },
+        )
+    };
+
+    let err = format!("{:#}", resolve(false).unwrap_err());
+    assert!(err.contains("integrity mismatch"), "{err}");
+    assert!(!cache_package_path("tampered", "1.0.0").exists());
+
+    let graph = resolve(true).expect("--no-verify accepts the download");
+    let recorded = graph.lockfile.packages["node_modules/tampered"].integrity.clone();
+    assert_eq!(recorded, Some(crate::cache::verify_integrity(&tgz, None).unwrap()));
+    assert_ne!(recorded, Some(bogus));
+}
@@ -22,3 +22,116 @@ fn quote_windows() {
     let args = vec!["--watch".to_string()];
     assert_eq!(build_script_command("node build.js", &args), "node build.js \"--watch\"");
 }
+
+#[test]
+fn bare_run_lists_scripts_and_bins() {
+    use crate::cli::commands::run::{format_run_listing, list_bin_names};
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    let bin_dir = dir.path().join(".bin");
+    std::fs::create_dir_all(&bin_dir).expect("create .bin");
+    for name in ["tsc", "eslint"] {
+        std::fs::write(bin_dir.join(name), "").expect("write bin");
+    }
+    let bins = list_bin_names(&bin_dir);
+    assert_eq!(bins, vec!["eslint".to_string(), "tsc".to_string()]);
+
+    let scripts = serde_json::json!({ "test": "vitest", "build": "tsc -p ." });
+    let lines = format_run_listing(scripts.as_object(), &bins);
+    let text = lines.join("\n");
+    let build = text.find("build").expect("build listed");
+    let test = text.find("test").expect("test listed");
+    assert!(build < test, "scripts should be sorted: {text}");
+    assert!(text.contains("tsc -p ."));
+    assert!(lines.last().is_some_and(|l| l.trim() == "tsc"));
+
+    let empty = format_run_listing(None, &[]);
+    assert_eq!(empty.len(), 1);
+    assert!(empty[0].contains("no scripts"));
+}
+
+#[test]
+fn run_wraps_script_with_pre_and_post() {
+    use crate::cli::commands::run::run_script_with_hooks;
+    if cfg!(windows) {
+        return;
+    }
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    let root = dir.path();
+    let scripts = serde_json::json!({
+        "prebuild": "echo pre >> order.log",
+        "build": "echo main >> order.log; echo >> order.log",
+        "postbuild": "echo post >> order.log"
+    });
+    let scripts = scripts.as_object().expect("object");
+    let args = vec!["--flag".to_string()];
+    run_script_with_hooks(root, scripts, "build", &args, None, false).expect("build runs");
+    let order = std::fs::read_to_string(root.join("order.log")).expect("read log");
+    assert_eq!(order, "pre\nmain\n--flag\npost\n", "only the main script gets pass-through args");
+
+    std::fs::remove_file(root.join("order.log")).expect("reset log");
+    let failing = serde_json::json!({
+        "pretest": "echo pre >> order.log; exit 3",
+        "test": "echo main >> order.log"
+    });
+    let err =
+        run_script_with_hooks(root, failing.as_object().expect("object"), "test", &[], None, false)
+            .expect_err("failing pre script should abort");
+    assert!(err.to_string().contains("pretest"));
+    let order = std::fs::read_to_string(root.join("order.log")).expect("read log");
+    assert_eq!(order, "pre\n");
+}
+
+#[test]
+fn script_timeout_kills_hung_scripts() {
+    use crate::cli::commands::run::run_script_with_hooks;
+    if cfg!(windows) {
+        return;
+    }
+
+    let _env = super::common::lock_env();
+    let previous = std::env::var_os("PACM_SCRIPT_TIMEOUT");
+    std::env::set_var("PACM_SCRIPT_TIMEOUT", "1");
+    let dir = tempfile::tempdir().expect("tempdir");
+    let scripts = serde_json::json!({ "hang": "echo started; sleep 30" });
+    let started = std::time::Instant::now();
+    let result = run_script_with_hooks(
+        dir.path(),
+        scripts.as_object().expect("object"),
+        "hang",
+        &[],
+        None,
+        true,
+    );
+    match previous {
+        Some(value) => std::env::set_var("PACM_SCRIPT_TIMEOUT", value),
+        None => std::env::remove_var("PACM_SCRIPT_TIMEOUT"),
+    }
+
+    let err = result.expect_err("hung script should time out");
+    assert!(err.to_string().contains("timed out after 1s"), "{err}");
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[test]
+fn quiet_failure_replays_all_captured_output() {
+    use crate::cli::commands::run::run_script_process_to;
+    if cfg!(windows) {
+        return;
+    }
+
+    let _env = super::common::lock_env();
+    let mut cmd = std::process::Command::new("sh");
+    cmd.arg("-c").arg(
+        "i=0; while [ $i -lt 2000 ]; do echo out-$i; echo err-$i >&2; i=$((i+1)); done; \
+         echo out-done; echo err-done >&2; exit 1",
+    );
+    let mut replay = Vec::new();
+    let status = run_script_process_to(cmd, "noisy", true, &mut replay).expect("script runs");
+    assert!(!status.success());
+    let text = String::from_utf8(replay).expect("utf8 output");
+    for line in ["out-0", "out-1999", "out-done", "err-0", "err-1999", "err-done"] {
+        assert!(text.lines().any(|l| l == line), "missing {line} in replayed output");
+    }
+}
@@ -0,0 +1,187 @@
+use super::fetch::{http_response, spawn_server};
+use crate::cli::commands::install::signatures::audit_signatures;
+use crate::fetch::{
+    verify_signatures, Fetcher, InvalidSignature, NpmSignature, RegistryKey, SignatureStatus,
+};
+use crate::lockfile::{Lockfile, PackageEntry, PackageSource};
+use aws_lc_rs::encoding::{AsDer, PublicKeyX509Der};
+use aws_lc_rs::rand::SystemRandom;
+use aws_lc_rs::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+
+const KEYID: &str = "SHA256:test-key";
+const INTEGRITY: &str = "sha512-AAAA";
+
+fn registry_key() -> (EcdsaKeyPair, RegistryKey) {
+    let pair = EcdsaKeyPair::generate(&ECDSA_P256_SHA256_ASN1_SIGNING).unwrap();
+    let der = AsDer::<PublicKeyX509Der>::as_der(pair.public_key()).unwrap();
+    let key = RegistryKey {
+        keyid: KEYID.into(),
+        keytype: "ecdsa-sha2-nistp256".into(),
+        key: STANDARD.encode(der.as_ref()),
+        expires: None,
+    };
+    (pair, key)
+}
+
+fn sign(pair: &EcdsaKeyPair, message: &str) -> NpmSignature {
+    let sig = pair.sign(&SystemRandom::new(), message.as_bytes()).unwrap();
+    NpmSignature { keyid: KEYID.into(), sig: STANDARD.encode(sig.as_ref()) }
+}
+
+#[test]
+fn registry_signatures_verify_and_detect_tampering() {
+    let (pair, key) = registry_key();
+    let keys = [key];
+    let valid = sign(&pair, &format!("pkg@1.0.0:{INTEGRITY}"));
+    let check = |integrity: &str, sigs: &[NpmSignature]| {
+        verify_signatures(&keys, "pkg", "1.0.0", integrity, sigs)
+    };
+
+    assert_eq!(check(INTEGRITY, std::slice::from_ref(&valid)), SignatureStatus::Verified);
+    assert_eq!(check(INTEGRITY, &[]), SignatureStatus::Missing);
+
+    // Content other than what the registry signed.
+    assert!(matches!(
+        check("sha512-BBBB", std::slice::from_ref(&valid)),
+        SignatureStatus::Invalid(_)
+    ));
+
+    let mut sig_bytes = STANDARD.decode(&valid.sig).unwrap();
+    let last = sig_bytes.len() - 1;
+    sig_bytes[last] ^= 1;
+    let tampered = NpmSignature { keyid: KEYID.into(), sig: STANDARD.encode(sig_bytes) };
+    assert!(matches!(check(INTEGRITY, &[tampered]), SignatureStatus::Invalid(_)));
+
+    let stranger = NpmSignature { keyid: "SHA256:other".into(), ..valid };
+    let SignatureStatus::Invalid(reason) = check(INTEGRITY, &[stranger]) else {
+        panic!("a signature from an unknown key must not verify");
+    };
+    assert!(reason.contains("unknown key"), "{reason}");
+}
+
+#[test]
+fn audit_signatures_rejects_a_tampered_package() {
+    let (pair, key) = registry_key();
+    let keys = json!({ "keys": [{
+        "keyid": key.keyid,
+        "keytype": key.keytype,
+        "scheme": "ecdsa-sha2-nistp256",
+        "key": key.key,
+        "expires": null
+    }]});
+    let metadata = |name: &str, signed: &str| {
+        let sig = sign(&pair, signed);
+        json!({
+            "name": name,
+            "versions": { "1.0.0": {
+                "version": "1.0.0",
+                "dist": {
+                    "tarball": format!("https://registry.example/{name}/-/{name}-1.0.0.tgz"),
+                    "integrity": INTEGRITY,
+                    "signatures": [{ "keyid": sig.keyid, "sig": sig.sig }]
+                }
+            }}
+        })
+        .to_string()
+    };
+    let good = metadata("sig-audit-good", &format!("sig-audit-good@1.0.0:{INTEGRITY}"));
+    let bad = metadata("sig-audit-bad", "sig-audit-bad@1.0.0:sha512-BBBB");
+    let (registry, _requests) = spawn_server(vec![
+        http_response("200 OK", "", keys.to_string().as_bytes()),
+        http_response("200 OK", "", bad.as_bytes()),
+        http_response("200 OK", "", good.as_bytes()),
+    ]);
+    let fetcher = Fetcher::new(Some(registry)).unwrap();
+
+    let entry = || PackageEntry {
+        version: Some("1.0.0".into()),
+        integrity: Some(INTEGRITY.into()),
+        source: Some(PackageSource::Registry),
+        ..PackageEntry::default()
+    };
+    let mut lock = Lockfile::default();
+    lock.packages.insert("node_modules/sig-audit-bad".into(), entry());
+    lock.packages.insert("node_modules/sig-audit-good".into(), entry());
+    let err = audit_signatures(&fetcher, &lock).unwrap_err();
+    let invalid = err.downcast_ref::<InvalidSignature>().expect("typed signature error");
+    assert_eq!(invalid.package, "sig-audit-bad@1.0.0");
+
+    // Keys and metadata are cached, so this needs no further requests.
+    lock.packages.remove("node_modules/sig-audit-bad");
+    let audit = audit_signatures(&fetcher, &lock).unwrap();
+    assert_eq!(audit.verified, 1);
+}
+
+#[test]
+fn audit_signatures_requires_signatures_and_checks_the_cached_tarball() {
+    use super::cache_integrity::build_tarball;
+    use super::common::DataHomeGuard;
+    use crate::cache::{ensure_cached_package, verify_integrity};
+
+    let _sandbox = DataHomeGuard::new();
+    let (pair, key) = registry_key();
+    let keys = json!({ "keys": [{
+        "keyid": key.keyid,
+        "keytype": key.keytype,
+        "scheme": "ecdsa-sha2-nistp256",
+        "key": key.key,
+        "expires": null
+    }]});
+    let tgz = build_tarball(&[("package/package.json", r#"{"name":"sig-cached"}"#)]);
+    let actual = verify_integrity(&tgz, None).unwrap();
+    ensure_cached_package("sig-cached", "1.0.0", &tgz, None).unwrap();
+    ensure_cached_package("sig-swapped", "1.0.0", &tgz, None).unwrap();
+
+    let metadata = |name: &str, integrity: &str, signed: bool| {
+        let signatures = if signed {
+            let sig = sign(&pair, &format!("{name}@1.0.0:{integrity}"));
+            json!([{ "keyid": sig.keyid, "sig": sig.sig }])
+        } else {
+            json!([])
+        };
+        json!({
+            "name": name,
+            "versions": { "1.0.0": {
+                "version": "1.0.0",
+                "dist": {
+                    "tarball": format!("https://registry.example/{name}/-/{name}-1.0.0.tgz"),
+                    "integrity": integrity,
+                    "signatures": signatures
+                }
+            }}
+        })
+        .to_string()
+    };
+    let (registry, _requests) = spawn_server(vec![
+        http_response("200 OK", "", keys.to_string().as_bytes()),
+        http_response("200 OK", "", metadata("sig-cached", &actual, true).as_bytes()),
+        http_response("200 OK", "", metadata("sig-swapped", INTEGRITY, true).as_bytes()),
+        http_response("200 OK", "", metadata("sig-unsigned", INTEGRITY, false).as_bytes()),
+    ]);
+    let fetcher = Fetcher::new(Some(registry)).unwrap();
+    let audit_one = |name: &str, integrity: Option<&str>| {
+        let mut lock = Lockfile::default();
+        let entry = PackageEntry {
+            version: Some("1.0.0".into()),
+            integrity: integrity.map(str::to_string),
+            source: Some(PackageSource::Registry),
+            ..PackageEntry::default()
+        };
+        lock.packages.insert(format!("node_modules/{name}"), entry);
+        audit_signatures(&fetcher, &lock)
+    };
+
+    // No integrity in the lockfile: the cached tarball's hash is what must be signed.
+    assert_eq!(audit_one("sig-cached", None).unwrap().verified, 1);
+    let err = audit_one("sig-swapped", None).unwrap_err();
+    let invalid = err.downcast_ref::<InvalidSignature>().expect("typed signature error");
+    assert!(invalid.reason.contains("cached tarball"), "{}", invalid.reason);
+
+    // A registry with keys must have signed every version.
+    let err = audit_one("sig-unsigned", Some(INTEGRITY)).unwrap_err();
+    let invalid = err.downcast_ref::<InvalidSignature>().expect("typed signature error");
+    assert_eq!(invalid.package, "sig-unsigned@1.0.0");
+    assert!(invalid.reason.contains("unsigned"), "{}", invalid.reason);
+}
@@ -0,0 +1,57 @@
+use super::fetch::{http_response, spawn_server};
+use crate::cli::commands::view::{pick_view_version, view_field, view_summary};
+use crate::fetch::Fetcher;
+use serde_json::{json, Value};
+
+fn mocked_metadata() -> Value {
+    json!({
+        "name": "view-pkg",
+        "dist-tags": { "latest": "1.10.0", "next": "2.0.0-beta.1" },
+        "versions": {
+            "1.2.0": { "name": "view-pkg", "version": "1.2.0", "license": { "type": "ISC" } },
+            "1.10.0": {
+                "name": "view-pkg",
+                "version": "1.10.0",
+                "description": "A package to look at",
+                "license": "MIT",
+                "dist": { "tarball": "https://registry.example/view-pkg-1.10.0.tgz" }
+            },
+            "2.0.0-beta.1": { "name": "view-pkg", "version": "2.0.0-beta.1" }
+        }
+    })
+}
+
+#[test]
+fn view_summarizes_the_latest_version() {
+    let body = mocked_metadata().to_string();
+    let (registry, requests) = spawn_server(vec![http_response("200 OK", "", body.as_bytes())]);
+    let fetcher = Fetcher::new(Some(registry)).unwrap();
+    let doc = fetcher.package_document("view-pkg").unwrap();
+    assert!(requests.recv().unwrap().starts_with("get /view-pkg "));
+
+    let version = pick_view_version(&doc, "*").unwrap();
+    assert_eq!(version, "1.10.0");
+    let summary = view_summary(&doc, &version);
+    assert_eq!(summary["name"], "view-pkg");
+    assert_eq!(summary["description"], "A package to look at");
+    assert_eq!(summary["license"], "MIT");
+    assert_eq!(summary["distTags"]["next"], "2.0.0-beta.1");
+    assert_eq!(summary["versions"], json!(["1.2.0", "1.10.0", "2.0.0-beta.1"]));
+}
+
+#[test]
+fn view_reads_single_fields_and_tags() {
+    let doc = mocked_metadata();
+    assert_eq!(view_field(&doc, "1.10.0", "version"), Some(json!("1.10.0")));
+    assert_eq!(
+        view_field(&doc, "1.10.0", "dist.tarball"),
+        Some(json!("https://registry.example/view-pkg-1.10.0.tgz"))
+    );
+    assert_eq!(view_field(&doc, "1.10.0", "dist-tags.latest"), Some(json!("1.10.0")));
+    assert_eq!(view_field(&doc, "1.10.0", "homepage"), None);
+
+    assert_eq!(pick_view_version(&doc, "next").as_deref(), Some("2.0.0-beta.1"));
+    assert_eq!(pick_view_version(&doc, "1.2.0").as_deref(), Some("1.2.0"));
+    assert_eq!(pick_view_version(&doc, "9.9.9"), None);
+    assert_eq!(view_summary(&doc, "1.2.0")["license"], "ISC");
+}
@@ -1,6 +1,7 @@
 use crate::cache::cache_package_path;
 use crate::cli::commands::install::{cmd_install, InstallOptions};
-use crate::lockfile::Lockfile;
+use crate::config::Config;
+use crate::lockfile::{Lockfile, PackageSource};
 use crate::tests::common::DataHomeGuard;
 use anyhow::Result;
 use serde_json::{json, Value};
@@ -9,7 +10,12 @@ use std::path::{Path, PathBuf};
 use tempfile::tempdir;
 
 fn install_options_copy() -> InstallOptions {
-    InstallOptions { copy: true, no_progress: true, ..InstallOptions::default() }
+    InstallOptions {
+        copy: true,
+        no_progress: true,
+        config: Config::load(),
+        ..InstallOptions::default()
+    }
 }
 
 struct CwdGuard {
@@ -90,6 +96,7 @@ fn installs_workspace_package_and_links_it() -> Result<()> {
         lock.packages.get("node_modules/pkg-a").expect("workspace package recorded in lockfile");
     assert_eq!(entry.version.as_deref(), Some("1.2.3"));
     assert!(entry.resolved.as_deref().unwrap_or_default().starts_with("workspace:"));
+    assert_eq!(entry.source, Some(PackageSource::Workspace));
 
     let installed_pkg = project_root.join("node_modules").join("pkg-a");
     assert!(installed_pkg.join("package.json").exists());
@@ -246,3 +253,134 @@ fn installs_scoped_workspace_chain() -> Result<()> {
     assert!(nm.join("@lumix").join("logger").exists());
     Ok(())
 }
+
+#[test]
+fn dangling_workspace_reference_names_the_missing_workspace() -> Result<()> {
+    let _guard = DataHomeGuard::new();
+    let temp = tempdir()?;
+    let project_root = temp.path().join("project");
+    write_manifest(
+        &project_root.join("package.json"),
+        &json!({
+            "name": "root-app",
+            "version": "0.1.0",
+            "workspaces": ["packages/*"],
+            "dependencies": { "pkg-real": "workspace:*", "pkg-typo": "workspace:*" }
+        }),
+    );
+    write_manifest(
+        &project_root.join("packages").join("pkg-real").join("package.json"),
+        &json!({ "name": "pkg-real", "version": "1.0.0" }),
+    );
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let options = InstallOptions { offline: true, ..install_options_copy() };
+    let err = cmd_install(Vec::new(), options).expect_err("dangling workspace must fail");
+    assert!(
+        format!("{err:#}").contains("no workspace named pkg-typo found for workspace: dependency"),
+        "{err:#}"
+    );
+    assert!(!lockfile_path(&project_root).exists());
+    Ok(())
+}
+
+#[test]
+fn negated_workspace_pattern_excludes_directory() -> Result<()> {
+    use crate::workspaces::discover_workspaces;
+
+    let temp = tempdir()?;
+    let root = temp.path();
+    let root_manifest = json!({
+        "name": "negation-root",
+        "version": "0.1.0",
+        "workspaces": ["packages/*", "!packages/ignored"]
+    });
+    write_manifest(&root.join("package.json"), &root_manifest);
+    for dir in ["kept", "ignored", "also-kept"] {
+        write_manifest(
+            &root.join("packages").join(dir).join("package.json"),
+            &json!({ "name": format!("@neg/{dir}"), "version": "1.0.0" }),
+        );
+    }
+
+    let manifest = crate::manifest::load(&root.join("package.json"))?;
+    let names: Vec<String> =
+        discover_workspaces(root, &manifest)?.into_iter().map(|ws| ws.name).collect();
+    assert_eq!(names, vec!["@neg/also-kept".to_string(), "@neg/kept".to_string()]);
+    Ok(())
+}
+
+#[test]
+fn duplicate_workspace_names_report_both_paths() -> Result<()> {
+    use crate::workspaces::discover_workspaces;
+
+    let temp = tempdir()?;
+    let root = temp.path();
+    write_manifest(
+        &root.join("package.json"),
+        &json!({ "name": "dup-root", "version": "0.1.0", "workspaces": ["packages/*"] }),
+    );
+    for dir in ["first", "second"] {
+        write_manifest(
+            &root.join("packages").join(dir).join("package.json"),
+            &json!({ "name": "@dup/shared", "version": "1.0.0" }),
+        );
+    }
+
+    let manifest = crate::manifest::load(&root.join("package.json"))?;
+    let err = discover_workspaces(root, &manifest).expect_err("duplicate names must fail");
+    let message = err.to_string();
+    assert!(message.contains("'@dup/shared'"), "{message}");
+    for dir in ["first", "second"] {
+        let path = root.join("packages").join(dir);
+        assert!(message.contains(&path.display().to_string()), "{message}");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn install_links_false_symlinks_workspace_to_source() -> Result<()> {
+    let _guard = DataHomeGuard::new();
+    let temp = tempdir()?;
+    let project_root = temp.path().join("project");
+    write_manifest(
+        &project_root.join("package.json"),
+        &json!({
+            "name": "root-app",
+            "version": "0.1.0",
+            "workspaces": ["packages/*"],
+            "dependencies": { "pkg-live": "workspace:*" }
+        }),
+    );
+    let ws_dir = project_root.join("packages").join("pkg-live");
+    write_manifest(
+        &ws_dir.join("package.json"),
+        &json!({ "name": "pkg-live", "version": "1.0.0" }),
+    );
+    fs::write(ws_dir.join("index.js"), "module.exports = 1;\n")?;
+
+    let _cwd = CwdGuard::change_to(&project_root)?;
+    let symlink = InstallOptions { install_links: Some(false), ..install_options_copy() };
+    cmd_install(Vec::new(), symlink)?;
+
+    let installed = project_root.join("node_modules").join("pkg-live");
+    assert!(fs::symlink_metadata(&installed)?.file_type().is_symlink());
+    assert_eq!(fs::canonicalize(fs::read_link(&installed)?)?, fs::canonicalize(&ws_dir)?);
+    fs::write(ws_dir.join("index.js"), "module.exports = 2;\n")?;
+    assert_eq!(fs::read_to_string(installed.join("index.js"))?, "module.exports = 2;\n");
+
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(lock.settings.symlink_workspaces);
+    let entry = lock.packages.get("node_modules/pkg-live").expect("workspace entry");
+    assert_eq!(entry.link_mode.as_deref(), Some("symlink"));
+
+    let copy = InstallOptions { install_links: Some(true), ..install_options_copy() };
+    cmd_install(Vec::new(), copy)?;
+    let lock = Lockfile::load_or_default(lockfile_path(&project_root))?;
+    assert!(!lock.settings.symlink_workspaces);
+    let pacm_copy = project_root.join("node_modules").join(".pacm").join("pkg-live");
+    assert!(!fs::symlink_metadata(&pacm_copy)?.file_type().is_symlink(), "copied from the store");
+    assert!(ws_dir.join("index.js").exists(), "switching back must not touch the source");
+    Ok(())
+}
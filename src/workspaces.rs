@@ -20,28 +20,28 @@ pub fn discover_workspaces(root: &Path, manifest: &Manifest) -> Result<Vec<Works
         return Ok(Vec::new());
     }
 
+    // `!`-prefixed patterns exclude directories matched by the positive patterns, regardless of
+    // the order they appear in.
+    let mut excluded: HashSet<PathBuf> = HashSet::new();
+    for pattern in manifest.workspaces.packages() {
+        if let Some(negated) = pattern.trim().strip_prefix('!') {
+            for dir in expand_workspace_pattern(root, negated.trim())? {
+                excluded.insert(dir.canonicalize().unwrap_or(dir));
+            }
+        }
+    }
+
     let mut seen_dirs: HashSet<PathBuf> = HashSet::new();
     let mut by_name: BTreeMap<String, WorkspaceInfo> = BTreeMap::new();
 
     for pattern in manifest.workspaces.packages() {
-        if pattern.trim().is_empty() {
+        if pattern.trim().is_empty() || pattern.trim().starts_with('!') {
             continue;
         }
-        let abs_pattern = root.join(pattern);
-        let pat_str = abs_pattern.to_string_lossy().replace('\\', "/");
-        for entry in
-            glob(&pat_str).with_context(|| format!("expand workspace pattern {pat_str}"))?
-        {
-            let path = entry?;
-            let pkg_dir = if path.is_file() {
-                if path.file_name().map(|n| n == "package.json").unwrap_or(false) {
-                    path.parent().map(Path::to_path_buf).unwrap_or_else(|| path.clone())
-                } else {
-                    path.clone()
-                }
-            } else {
-                path.clone()
-            };
+        for pkg_dir in expand_workspace_pattern(root, pattern)? {
+            if excluded.contains(&pkg_dir.canonicalize().unwrap_or_else(|_| pkg_dir.clone())) {
+                continue;
+            }
             if pkg_dir == root {
                 continue;
             }
@@ -82,6 +82,27 @@ pub fn discover_workspaces(root: &Path, manifest: &Manifest) -> Result<Vec<Works
     Ok(by_name.into_values().collect())
 }
 
+/// Expand one workspace glob into candidate package directories. Matches on a `package.json`
+/// file resolve to its parent directory.
+fn expand_workspace_pattern(root: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    if pattern.is_empty() {
+        return Ok(Vec::new());
+    }
+    let abs_pattern = root.join(pattern);
+    let pat_str = abs_pattern.to_string_lossy().replace('\\', "/");
+    let mut dirs = Vec::new();
+    for entry in glob(&pat_str).with_context(|| format!("expand workspace pattern {pat_str}"))? {
+        let path = entry?;
+        let pkg_dir = if path.is_file() && path.file_name().is_some_and(|n| n == "package.json") {
+            path.parent().map(Path::to_path_buf).unwrap_or_else(|| path.clone())
+        } else {
+            path
+        };
+        dirs.push(pkg_dir);
+    }
+    Ok(dirs)
+}
+
 pub fn workspace_dep_satisfies(range: &str, version: &str) -> bool {
     let trimmed = range.trim();
     let spec = trimmed.strip_prefix("workspace:").unwrap_or(trimmed);